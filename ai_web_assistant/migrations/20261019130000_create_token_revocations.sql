@@ -0,0 +1,15 @@
+-- TOKEN REVOCATIONS
+-- scope 'token' revokes one access token (subject = jti), 'session' every token of one
+-- login session/device (subject = sid), 'user' every token issued before revoked_at
+-- (subject = user id). Rows can be dropped once expires_at passes: by then every token
+-- they cover has expired anyway.
+CREATE TABLE token_revocations (
+    id BIGSERIAL PRIMARY KEY,
+    scope TEXT NOT NULL CHECK (scope IN ('token', 'session', 'user')),
+    subject TEXT NOT NULL,
+    reason TEXT,
+    revoked_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_token_revocations_expires ON token_revocations(expires_at);
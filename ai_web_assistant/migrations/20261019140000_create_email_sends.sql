@@ -0,0 +1,11 @@
+-- EMAIL SENDS
+-- One row per account email sent (e.g. kind 'verify_email'), used to rate limit resends.
+-- Rows older than a day are no longer needed.
+CREATE TABLE email_sends (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_email_sends_user_kind ON email_sends(user_id, kind, sent_at);
@@ -0,0 +1,13 @@
+-- PASSWORD RESET TOKENS
+-- Only the SHA-256 of each emailed token is stored. A token works once (used_at) and only
+-- until expires_at; requesting a new one or changing the password invalidates the rest.
+CREATE TABLE password_reset_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_password_reset_tokens_user ON password_reset_tokens(user_id);
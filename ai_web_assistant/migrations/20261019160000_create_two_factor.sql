@@ -0,0 +1,20 @@
+-- TWO-FACTOR AUTHENTICATION
+-- One TOTP secret per user. enabled_at stays NULL until the user confirms enrolment with a
+-- code; last_used_step stops a code from being accepted twice.
+CREATE TABLE user_two_factor (
+    user_id BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    enabled_at TIMESTAMPTZ,
+    last_used_step BIGINT
+);
+
+-- Single-use recovery codes; only their SHA-256 is stored
+CREATE TABLE two_factor_recovery_codes (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_two_factor_recovery_codes_user ON two_factor_recovery_codes(user_id);
@@ -0,0 +1,27 @@
+-- LOGIN THROTTLING
+-- Every password login attempt, keyed by the normalized email as typed (whether or not an
+-- account exists) and the client IP. Rows are pruned after LOGIN_ATTEMPT_RETENTION_DAYS.
+CREATE TABLE login_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    email TEXT NOT NULL,
+    ip TEXT NOT NULL,
+    succeeded BOOLEAN NOT NULL,
+    attempted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_login_attempts_email ON login_attempts(email, attempted_at);
+CREATE INDEX idx_login_attempts_ip ON login_attempts(ip, attempted_at);
+
+-- Audit trail of lockouts; a row is also what blocks logins until locked_until
+CREATE TABLE login_lockouts (
+    id BIGSERIAL PRIMARY KEY,
+    scope TEXT NOT NULL CHECK (scope IN ('account', 'ip')),
+    subject TEXT NOT NULL,
+    user_id BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    ip TEXT NOT NULL,
+    failures INTEGER NOT NULL,
+    locked_until TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_login_lockouts_subject ON login_lockouts(scope, subject, locked_until);
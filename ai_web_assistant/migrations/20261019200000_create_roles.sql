@@ -0,0 +1,68 @@
+-- ROLES AND PERMISSIONS
+-- Permissions are fixed names checked in code; roles are named sets of them. Access tokens
+-- carry the user's roles and permissions, so changes apply on the next refresh.
+CREATE TABLE permissions (
+    name TEXT PRIMARY KEY,
+    description TEXT NOT NULL
+);
+
+CREATE TABLE roles (
+    name TEXT PRIMARY KEY,
+    description TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE role_permissions (
+    role_name TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE ON UPDATE CASCADE,
+    permission TEXT NOT NULL REFERENCES permissions(name) ON DELETE CASCADE,
+    PRIMARY KEY (role_name, permission)
+);
+
+CREATE TABLE user_roles (
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role_name TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE ON UPDATE CASCADE,
+    granted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, role_name)
+);
+
+INSERT INTO permissions (name, description) VALUES
+    ('chat.use', 'Send prompts to the assistant and start chat sessions'),
+    ('models.manage', 'Change which language models are available'),
+    ('users.read', 'View any user''s profile and export their data'),
+    ('users.manage', 'Edit or erase any user and sign them out'),
+    ('users.ban', 'Deactivate and reactivate accounts'),
+    ('roles.manage', 'Grant and remove roles'),
+    ('backups.manage', 'Take and restore backups'),
+    ('system.monitor', 'View cache and runtime metrics');
+
+INSERT INTO roles (name, description) VALUES
+    ('user', 'Every account'),
+    ('staff', 'Support and moderation'),
+    ('admin', 'Full access');
+
+INSERT INTO role_permissions (role_name, permission) VALUES
+    ('user', 'chat.use'),
+    ('staff', 'chat.use'),
+    ('staff', 'users.read'),
+    ('staff', 'users.ban'),
+    ('staff', 'system.monitor');
+
+INSERT INTO role_permissions (role_name, permission)
+SELECT 'admin', name FROM permissions;
+
+-- Existing accounts keep what the is_admin / is_staff flags gave them
+INSERT INTO user_roles (user_id, role_name) SELECT id, 'user' FROM users;
+INSERT INTO user_roles (user_id, role_name) SELECT id, 'staff' FROM users WHERE is_staff;
+INSERT INTO user_roles (user_id, role_name) SELECT id, 'admin' FROM users WHERE is_admin;
+
+-- Every new account starts with the "user" role, whichever path created it
+CREATE FUNCTION grant_default_role() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO user_roles (user_id, role_name) VALUES (NEW.id, 'user') ON CONFLICT DO NOTHING;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_default_role
+    AFTER INSERT ON users
+    FOR EACH ROW EXECUTE FUNCTION grant_default_role();
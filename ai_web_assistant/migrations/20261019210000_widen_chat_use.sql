@@ -0,0 +1,4 @@
+-- chat.use now covers every WebSocket message that changes the caller's own conversations
+UPDATE permissions
+SET description = 'Chat with the assistant and change your own conversations'
+WHERE name = 'chat.use';
@@ -0,0 +1,13 @@
+-- Logins waiting for their second step. The pending token names its row by jti, so the
+-- token stops working once it succeeds or runs out of attempts.
+CREATE TABLE two_factor_pending_logins (
+    jti UUID PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    attempts INT NOT NULL DEFAULT 0,
+    succeeded BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    consumed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_two_factor_pending_logins_user ON two_factor_pending_logins(user_id, created_at);
@@ -0,0 +1,50 @@
+use clap::{Parser, Subcommand};
+
+use crate::services::backup_service::BackupService;
+
+#[derive(Debug, Parser)]
+#[command(about = "AI web assistant server")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+// Without a subcommand the server starts as usual
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Take a backup of the chat store and user settings, then exit
+    Backup,
+    /// List the available backups, newest first
+    Backups,
+    /// Roll the chat store (or one user) back to a backup. Stop the server first.
+    Restore {
+        backup_id: String,
+        #[arg(long)]
+        user: Option<i64>,
+    },
+}
+
+// Returns the process exit code
+pub async fn run(command: Command, backups: &BackupService) -> i32 {
+    let result = match command {
+        Command::Backup => backups.create().await.map(|manifest| {
+            println!("{}", serde_json::to_string_pretty(&manifest).unwrap_or_default());
+        }),
+        Command::Backups => backups.list().await.map(|manifests| {
+            for m in manifests {
+                println!("{}  {} users  {} conversations  {} bytes", m.backup_id, m.users, m.conversations, m.bytes);
+            }
+        }),
+        Command::Restore { backup_id, user } => backups.restore(&backup_id, user).await.map(|report| {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+        }),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            1
+        }
+    }
+}
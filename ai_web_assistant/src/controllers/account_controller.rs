@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use axum::{
+    body::Body,
+    extract::{Extension, Path},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::PgPool;
+use tokio_util::io::ReaderStream;
+
+use crate::helpers::message_manager::MessageManager;
+use crate::middleware::auth::AuthUser;
+use crate::repository::user_repository::UserRepository;
+use crate::services::account_service::{AccountService, ErasureReport};
+use crate::services::revocation_service::RevocationService;
+use crate::utils::file_utils::JsonFileManager;
+
+fn account_service(db: PgPool, message_manager: Arc<MessageManager>, file_manager: Arc<JsonFileManager>) -> AccountService {
+    AccountService::new(UserRepository { db }, message_manager, file_manager)
+}
+
+async fn export_response(service: AccountService, user_id: i64) -> Response {
+    match service.export_archive(user_id).await {
+        Ok(reader) => {
+            let file_name = format!("account-data-{}-{}.zip", user_id, Utc::now().format("%Y%m%d%H%M%S"));
+            (
+                [
+                    (header::CONTENT_TYPE, "application/zip".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+                ],
+                Body::from_stream(ReaderStream::new(reader)),
+            )
+                .into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+// An incomplete report means a store failed or still holds data; running the erasure again is safe
+fn erasure_response(report: ErasureReport) -> Response {
+    let status = if report.complete { StatusCode::OK } else { StatusCode::INTERNAL_SERVER_ERROR };
+    (status, Json(json!(report))).into_response()
+}
+
+// GET /account/export streams everything stored about the caller as a zip
+pub async fn export_my_data(
+    AuthUser(claims): AuthUser,
+    Extension(db): Extension<PgPool>,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Extension(file_manager): Extension<Arc<JsonFileManager>>,
+) -> Response {
+    export_response(account_service(db, message_manager, file_manager), claims.sub).await
+}
+
+// DELETE /account erases the caller from every store and returns the deletion report
+pub async fn erase_my_account(
+    AuthUser(claims): AuthUser,
+    Extension(db): Extension<PgPool>,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Extension(file_manager): Extension<Arc<JsonFileManager>>,
+    Extension(revocations): Extension<Arc<RevocationService>>,
+) -> Response {
+    erasure_response(account_service(db, message_manager, file_manager).erase(claims.sub, &revocations).await)
+}
+
+// GET /admin/users/:id/export
+pub async fn export_user_data(
+    Extension(db): Extension<PgPool>,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Extension(file_manager): Extension<Arc<JsonFileManager>>,
+    Path(user_id): Path<i64>,
+) -> Response {
+    export_response(account_service(db, message_manager, file_manager), user_id).await
+}
+
+// DELETE /admin/users/:id/erase
+pub async fn erase_user(
+    Extension(db): Extension<PgPool>,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Extension(file_manager): Extension<Arc<JsonFileManager>>,
+    Extension(revocations): Extension<Arc<RevocationService>>,
+    Path(user_id): Path<i64>,
+) -> Response {
+    erasure_response(account_service(db, message_manager, file_manager).erase(user_id, &revocations).await)
+}
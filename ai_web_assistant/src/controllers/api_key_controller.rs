@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::middleware::auth::AuthUser;
+use crate::services::{api_key_service, revocation_service::RevocationService};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    // Defaults to 90 days, capped by API_KEY_MAX_TTL_DAYS
+    pub expires_in_days: Option<i64>,
+}
+
+// GET /api-keys lists the caller's keys, without the secrets
+pub async fn list_api_keys(
+    AuthUser(claims): AuthUser,
+    Extension(db): Extension<PgPool>,
+) -> Response {
+    match api_key_service::list_keys(&db, claims.sub).await {
+        Ok(keys) => (StatusCode::OK, Json(json!({ "api_keys": keys }))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+// POST /api-keys returns the new key once; only its hash is kept
+pub async fn create_api_key(
+    AuthUser(claims): AuthUser,
+    Extension(db): Extension<PgPool>,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Response {
+    match api_key_service::create_key(&db, claims.sub, claims.is_admin, &payload.name, &payload.scopes, payload.expires_in_days).await {
+        Ok(created) => (StatusCode::CREATED, Json(json!(created))).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+// DELETE /api-keys/:key_id
+pub async fn revoke_api_key(
+    AuthUser(claims): AuthUser,
+    Extension(db): Extension<PgPool>,
+    Extension(revocations): Extension<Arc<RevocationService>>,
+    Path(key_id): Path<String>,
+) -> Response {
+    match api_key_service::revoke_key(&db, &revocations, claims.sub, &key_id).await {
+        Ok(true) => (StatusCode::OK, Json(json!({ "status": "revoked", "id": key_id }))).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, Json(json!({ "error": "API key not found" }))).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
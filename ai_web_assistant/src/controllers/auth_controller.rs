@@ -1,14 +1,17 @@
 use axum::{
-    extract::Extension,
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{ConnectInfo, Extension, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
-use serde::{Serialize};
+use serde::{Deserialize, Serialize};
 use validator::Validate;
 use sqlx::PgPool;
 use utoipa::path;
-use crate::{payloads::{login_request::LoginRequest, register_request::RegisterRequest}, responses::login_responses::LoginResponse, services::auth_service::{self}};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use serde_json::json;
+use crate::{middleware::auth::AuthUser, payloads::{login_request::LoginRequest, logout_request::LogoutRequest, password_request::{ChangePasswordRequest, ForgotPasswordRequest, ResetPasswordRequest}, refresh_request::RefreshRequest, register_request::RegisterRequest, two_factor_request::TwoFactorLoginRequest}, responses::login_responses::{LoginResponse, TwoFactorChallenge}, services::{auth_service::{self, LoginOutcome}, login_throttle_service::LoginThrottle, password_service::PasswordService, two_factor_service::TwoFactorService, revocation_service::RevocationService, verification_service::{ResendOutcome, VerificationService}}, utils::client_ip::client_ip};
 
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -39,6 +42,7 @@ pub struct ErrorResponse {
 )]
 pub async fn register_user(
     Extension(db): Extension<PgPool>,
+    Extension(verification): Extension<Arc<VerificationService>>,
     Json(payload): Json<RegisterRequest>,
 ) -> impl IntoResponse {
     // Validate payload first
@@ -108,7 +112,13 @@ pub async fn register_user(
 
     // Proceed with registration if email is available
     match auth_service::register_user(&db, payload).await {
-        Ok(user) => (StatusCode::CREATED, Json(user)).into_response(),
+        Ok(user) => {
+            // The account exists either way; a failed send can be retried with the resend endpoint
+            if let Err(e) = verification.send_verification(user.id, &user.email).await {
+                eprintln!("❌ Failed to send verification email to user {}: {}", user.id, e);
+            }
+            (StatusCode::CREATED, Json(user)).into_response()
+        }
         Err(e) => (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e.to_string(),targe: "".to_string() })).into_response(),
     }
 }
@@ -120,42 +130,351 @@ pub async fn register_user(
     request_body = LoginRequest,
     responses(
         (status = 200, description = "Login successful", body = LoginResponse),
+        (status = 202, description = "Password accepted; finish at /auth/login/2fa", body = TwoFactorChallenge),
         (status = 400, description = "Validation error", body = ErrorResponse),
-        (status = 401, description = "Invalid credentials", body = ErrorResponse)
+        (status = 401, description = "Invalid credentials", body = ErrorResponse),
+        (status = 429, description = "Too many failed attempts for this email or IP; see Retry-After")
     ),
     tag = "Auth"
 )]
 pub async fn login_user(
     Extension(db): Extension<PgPool>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    Extension(two_factor): Extension<Arc<TwoFactorService>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
 ) -> impl IntoResponse {
     if let Err(e) = payload.validate() {
         return (
             StatusCode::BAD_REQUEST,
-            Json(AuthApiResponse::Error(ErrorResponse {
+            Json(AuthApiResponse::<LoginResponse>::Error(ErrorResponse {
                 error: format!("Validation error: {}", e),
                  targe: "error".to_string(),
             })),
+        )
+            .into_response();
+    }
+
+    let ip = client_ip(&headers, peer).to_string();
+    match auth_service::login_user(&db, &throttle, &two_factor, payload, &ip).await {
+        Ok(LoginOutcome::Session(login_response)) => (StatusCode::OK, Json(AuthApiResponse::Success(login_response))).into_response(),
+        Ok(LoginOutcome::TwoFactorRequired(challenge)) => (StatusCode::ACCEPTED, Json(challenge)).into_response(),
+        Ok(LoginOutcome::Throttled { retry_after_secs }) => throttled(retry_after_secs),
+        Err(e) => (
+            StatusCode::UNAUTHORIZED,
+            Json(AuthApiResponse::<LoginResponse>::Error(ErrorResponse {
+                error: e.to_string(),
+                targe: "error".to_string(),
+            })),
+        )
+            .into_response(),
+    }
+
+}
+
+
+#[utoipa::path(
+    post,
+    path = "/auth/login/2fa",
+    request_body = TwoFactorLoginRequest,
+    responses(
+        (status = 200, description = "Login successful", body = LoginResponse),
+        (status = 400, description = "Validation error", body = ErrorResponse),
+        (status = 401, description = "Invalid code, or the pending token expired or was used up", body = ErrorResponse),
+        (status = 429, description = "Too many failed attempts for this account or IP; see Retry-After")
+    ),
+    tag = "Auth"
+)]
+pub async fn login_two_factor(
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    Extension(two_factor): Extension<Arc<TwoFactorService>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<TwoFactorLoginRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = payload.validate() {
+        return validation_error(e);
+    }
+
+    let ip = client_ip(&headers, peer).to_string();
+    match auth_service::login_two_factor(&throttle, &two_factor, &payload.pending_token, &payload.code, &ip).await {
+        Ok(LoginOutcome::Session(login_response)) => (StatusCode::OK, Json(AuthApiResponse::Success(login_response))).into_response(),
+        Ok(LoginOutcome::Throttled { retry_after_secs }) => throttled(retry_after_secs),
+        Ok(LoginOutcome::TwoFactorRequired(challenge)) => (StatusCode::ACCEPTED, Json(challenge)).into_response(),
+        Err(e) => (
+            StatusCode::UNAUTHORIZED,
+            Json(AuthApiResponse::<LoginResponse>::Error(ErrorResponse {
+                error: e.to_string(),
+                targe: "two_factor_error".to_string(),
+            })),
+        )
+            .into_response(),
+    }
+}
+
+fn throttled(retry_after_secs: i64) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_secs.to_string())],
+        Json(json!({ "error": "Too many login attempts; try again later", "retry_after_secs": retry_after_secs })),
+    )
+        .into_response()
+}
+
+
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New access and refresh token", body = LoginResponse),
+        (status = 400, description = "Validation error", body = ErrorResponse),
+        (status = 401, description = "Refresh token invalid, expired, revoked or reused", body = ErrorResponse)
+    ),
+    tag = "Auth"
+)]
+pub async fn refresh_token(
+    Extension(db): Extension<PgPool>,
+    Json(payload): Json<RefreshRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = payload.validate() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(AuthApiResponse::Error(ErrorResponse {
+                error: format!("Validation error: {}", e),
+                targe: "error".to_string(),
+            })),
         );
     }
 
-    match auth_service::login_user(&db, payload).await {
-        Ok(auth_response) => {
-            let login_response = LoginResponse {
-                token: auth_response.token,
-                id: auth_response.id,
-                email: auth_response.email,
-                username: auth_response.username,
-            };
-            (StatusCode::OK, Json(AuthApiResponse::Success(login_response)))
-        },
+    match auth_service::refresh_session(&db, &payload.refresh_token).await {
+        Ok(login_response) => (StatusCode::OK, Json(AuthApiResponse::Success(login_response))),
         Err(e) => (
             StatusCode::UNAUTHORIZED,
             Json(AuthApiResponse::Error(ErrorResponse {
                 error: e.to_string(),
-                targe: "error".to_string(),
+                targe: "refresh_error".to_string(),
             })),
         ),
     }
+}
+
+
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    request_body(content = LogoutRequest, description = "Optional"),
+    responses(
+        (status = 200, description = "Token and session revoked"),
+        (status = 401, description = "Missing, invalid or already revoked token", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Auth"
+)]
+pub async fn logout_user(
+    AuthUser(claims): AuthUser,
+    Extension(revocations): Extension<Arc<RevocationService>>,
+    payload: Option<Json<LogoutRequest>>,
+) -> impl IntoResponse {
+    let request = payload.map(|Json(request)| request).unwrap_or_default();
+
+    // This device: the token itself, plus its session so the refresh token dies with it
+    let result = if request.all_devices {
+        revocations.revoke_user(claims.sub, "logout from all devices").await.map(|_| ())
+    } else if claims.sid.is_empty() {
+        revocations.revoke_token(&claims, "logout").await
+    } else {
+        revocations.revoke_session(claims.sub, &claims.sid, "logout").await.map(|_| ())
+    };
+
+    match result {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(json!({ "status": "logged_out", "all_devices": request.all_devices })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+                targe: "error".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/verify-email",
+    params(VerifyEmailQuery),
+    responses(
+        (status = 200, description = "Email verified"),
+        (status = 400, description = "Link invalid, expired or issued for an older address", body = ErrorResponse)
+    ),
+    tag = "Auth"
+)]
+pub async fn verify_email(
+    Extension(verification): Extension<Arc<VerificationService>>,
+    Query(query): Query<VerifyEmailQuery>,
+) -> impl IntoResponse {
+    match verification.verify(&query.token).await {
+        Ok(user_id) => (StatusCode::OK, Json(json!({ "status": "verified", "user_id": user_id }))).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+                targe: "verification_error".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
 
+
+#[utoipa::path(
+    post,
+    path = "/auth/verify-email/resend",
+    responses(
+        (status = 200, description = "Verification email sent, or the address is already verified"),
+        (status = 429, description = "Sent too recently; retry_after_secs says when to try again")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Auth"
+)]
+pub async fn resend_verification(
+    AuthUser(claims): AuthUser,
+    Extension(verification): Extension<Arc<VerificationService>>,
+) -> impl IntoResponse {
+    match verification.resend(claims.sub).await {
+        Ok(outcome @ ResendOutcome::RateLimited { .. }) => (StatusCode::TOO_MANY_REQUESTS, Json(json!(outcome))).into_response(),
+        Ok(outcome) => (StatusCode::OK, Json(json!(outcome))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+                targe: "error".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+
+fn validation_error(e: validator::ValidationErrors) -> axum::response::Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            error: format!("Validation error: {}", e),
+            targe: "error".to_string(),
+        }),
+    )
+        .into_response()
+}
+
+
+#[utoipa::path(
+    post,
+    path = "/auth/forgot-password",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 202, description = "If the email belongs to an account, a reset link is on its way"),
+        (status = 400, description = "Validation error", body = ErrorResponse)
+    ),
+    tag = "Auth"
+)]
+pub async fn forgot_password(
+    Extension(passwords): Extension<Arc<PasswordService>>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = payload.validate() {
+        return validation_error(e);
+    }
+
+    // Same answer, and about the same timing, whether or not the account exists
+    tokio::spawn(async move {
+        if let Err(e) = passwords.request_reset(&payload.email).await {
+            eprintln!("❌ Failed to send password reset email: {}", e);
+        }
+    });
+
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({ "status": "ok", "message": "If the email belongs to an account, a reset link has been sent" })),
+    )
+        .into_response()
+}
+
+
+#[utoipa::path(
+    post,
+    path = "/auth/reset-password",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Password changed; every session was signed out"),
+        (status = 400, description = "Validation error, or token invalid, expired or used", body = ErrorResponse)
+    ),
+    tag = "Auth"
+)]
+pub async fn reset_password(
+    Extension(passwords): Extension<Arc<PasswordService>>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = payload.validate() {
+        return validation_error(e);
+    }
+
+    match passwords.reset_password(&payload.token, &payload.new_password).await {
+        Ok(()) => (StatusCode::OK, Json(json!({ "status": "password_reset" }))).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+                targe: "reset_error".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+
+#[utoipa::path(
+    post,
+    path = "/auth/change-password",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed; other sessions signed out, new tokens for this one", body = LoginResponse),
+        (status = 400, description = "Validation error", body = ErrorResponse),
+        (status = 401, description = "Current password is incorrect", body = ErrorResponse)
+    ),
+    security(("bearer_auth" = [])),
+    tag = "Auth"
+)]
+pub async fn change_password(
+    AuthUser(claims): AuthUser,
+    Extension(passwords): Extension<Arc<PasswordService>>,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = payload.validate() {
+        return validation_error(e);
+    }
+
+    match passwords.change_password(claims.sub, &payload.current_password, &payload.new_password).await {
+        Ok(login_response) => (StatusCode::OK, Json(login_response)).into_response(),
+        Err(e) => (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: e.to_string(),
+                targe: "password_error".to_string(),
+            }),
+        )
+            .into_response(),
+    }
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::services::backup_service::BackupService;
+
+#[derive(Debug, Deserialize, Default)]
+pub struct RestoreRequest {
+    // Leave out to roll back the whole store
+    pub user_id: Option<i64>,
+}
+
+// GET /admin/backups, newest first
+pub async fn list_backups(Extension(backups): Extension<Arc<BackupService>>) -> Response {
+    match backups.list().await {
+        Ok(manifests) => (StatusCode::OK, Json(json!({ "backups": manifests }))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+// POST /admin/backups takes a backup now
+pub async fn create_backup(Extension(backups): Extension<Arc<BackupService>>) -> Response {
+    match backups.create().await {
+        Ok(manifest) => (StatusCode::CREATED, Json(json!(manifest))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+// POST /admin/backups/:backup_id/restore with an optional {"user_id": 42}
+pub async fn restore_backup(
+    Extension(backups): Extension<Arc<BackupService>>,
+    Path(backup_id): Path<String>,
+    payload: Option<Json<RestoreRequest>>,
+) -> Response {
+    let request = payload.map(|Json(request)| request).unwrap_or_default();
+
+    match backups.restore(&backup_id, request.user_id).await {
+        Ok(report) => (StatusCode::OK, Json(json!(report))).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+use axum::{
+    body::Body,
+    extract::{Extension, Path, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use tokio_util::io::ReaderStream;
+
+use crate::helpers::export::{self, ExportFormat};
+use crate::helpers::message_manager::MessageManager;
+use crate::middleware::auth::AuthUser;
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
+
+fn attachment(content_type: &str, file_name: &str, body: Body) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+// GET /conversations/:id/export?format=markdown|html|json
+pub async fn export_conversation(
+    AuthUser(claims): AuthUser,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Path(conversation_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let user_id = claims.sub.to_string();
+
+    let conversation = match message_manager.get_conversation(&user_id, &conversation_id).await {
+        Ok(Some(conversation)) => conversation,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, format!("Conversation not found: {}", conversation_id)),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    match export::render(&conversation_id, &conversation, query.format) {
+        Ok(content) => attachment(
+            query.format.content_type(),
+            &export::file_name(&conversation_id, &conversation, query.format),
+            Body::from(content),
+        ),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+// GET /conversations/export?format=... streams every conversation as a zip archive
+pub async fn export_all_conversations(
+    AuthUser(claims): AuthUser,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let user_id = claims.sub.to_string();
+
+    let conversations = match message_manager.get_all_conversations(&user_id).await {
+        Ok(conversations) => conversations,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let archive = export::stream_zip(conversations, query.format);
+    let file_name = format!("conversations-{}.zip", Utc::now().format("%Y%m%d-%H%M%S"));
+    attachment("application/zip", &file_name, Body::from_stream(ReaderStream::new(archive)))
+}
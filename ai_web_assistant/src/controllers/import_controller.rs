@@ -0,0 +1,41 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::helpers::import::{self, ImportFormat};
+use crate::helpers::message_manager::MessageManager;
+use crate::middleware::auth::AuthUser;
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub format: ImportFormat,
+}
+
+// POST /conversations/import?format=auto|chatgpt|markdown|native with the file as the raw body
+pub async fn import_conversations(
+    AuthUser(claims): AuthUser,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> impl IntoResponse {
+    let user_id = claims.sub.to_string();
+
+    let parsed = tokio::task::spawn_blocking(move || import::parse(&body, query.format)).await;
+    let sessions = match parsed {
+        Ok(Ok(sessions)) => sessions,
+        Ok(Err(e)) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))),
+    };
+
+    match message_manager.import_conversations(&user_id, sessions).await {
+        Ok(report) => (StatusCode::OK, Json(json!(report))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))),
+    }
+}
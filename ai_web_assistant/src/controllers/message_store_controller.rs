@@ -0,0 +1,22 @@
+use std::sync::Arc;
+use axum::{
+    extract::Extension,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use crate::helpers::message_manager::MessageManager;
+
+pub async fn cache_metrics(
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+) -> impl IntoResponse {
+    match message_manager.cache_metrics() {
+        Some(metrics) => (StatusCode::OK, Json(json!(metrics))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Message cache is not enabled (set MESSAGE_CACHE_CAPACITY)" })),
+        ),
+    }
+}
@@ -1,2 +1,15 @@
 pub mod auth_controller;
-pub mod user_controller;
\ No newline at end of file
+pub mod user_controller;
+pub mod message_store_controller;
+pub mod export_controller;
+pub mod import_controller;
+pub mod share_controller;
+pub mod account_controller;
+pub mod backup_controller;
+pub mod session_controller;
+pub mod two_factor_controller;
+pub mod api_key_controller;
+pub mod oidc_controller;
+pub mod role_controller;
+#[cfg(feature = "replication")]
+pub mod replication_controller;
\ No newline at end of file
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::services::oidc_service::OidcService;
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+fn error_response(status: StatusCode, error: impl ToString) -> Response {
+    (status, Json(json!({ "error": error.to_string() }))).into_response()
+}
+
+fn not_configured() -> Response {
+    error_response(StatusCode::NOT_FOUND, "Single sign-on is not configured")
+}
+
+// GET /auth/oidc/login redirects the browser to the identity provider
+pub async fn oidc_login(Extension(oidc): Extension<Arc<OidcService>>) -> Response {
+    if !oidc.is_enabled() {
+        return not_configured();
+    }
+
+    match oidc.authorization_url().await {
+        Ok(url) => Redirect::to(&url).into_response(),
+        Err(e) => {
+            eprintln!("❌ Failed to start OIDC login: {}", e);
+            error_response(StatusCode::BAD_GATEWAY, "Identity provider is unavailable")
+        }
+    }
+}
+
+// GET /auth/oidc/callback?code=...&state=... where the identity provider sends the browser back
+pub async fn oidc_callback(
+    Extension(oidc): Extension<Arc<OidcService>>,
+    Query(query): Query<CallbackQuery>,
+) -> Response {
+    if !oidc.is_enabled() {
+        return not_configured();
+    }
+    if let Some(error) = query.error {
+        let detail = query.error_description.unwrap_or_default();
+        return error_response(StatusCode::UNAUTHORIZED, format!("Sign-in was not completed: {} {}", error, detail).trim_end());
+    }
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return error_response(StatusCode::BAD_REQUEST, "Missing code or state");
+    };
+
+    let session = match oidc.complete_login(&code, &state).await {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("❌ OIDC login failed: {}", e);
+            return error_response(StatusCode::UNAUTHORIZED, e);
+        }
+    };
+
+    // The fragment never reaches a server, so the tokens stay out of access logs
+    match oidc.post_login_redirect() {
+        Some(target) => Redirect::to(&format!(
+            "{}#token={}&refresh_token={}&expires_in={}",
+            target, session.token, session.refresh_token, session.expires_in,
+        ))
+        .into_response(),
+        None => (StatusCode::OK, Json(session)).into_response(),
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Query},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::helpers::{
+    message_manager::MessageManager,
+    replication::{AppendAck, AppendRequest, Replicator, SECRET_HEADER},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct LogQuery {
+    pub from: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+fn authorize(message_manager: &MessageManager, headers: &HeaderMap) -> Result<Arc<Replicator>, (StatusCode, Json<Value>)> {
+    let replicator = message_manager.replicator().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Replication is not enabled on this instance" })),
+        )
+    })?;
+
+    let provided = headers.get(SECRET_HEADER).and_then(|h| h.to_str().ok());
+    if !replicator.check_secret(provided) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Invalid replication secret" })),
+        ));
+    }
+
+    if !replicator.is_leader() {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({ "error": "This instance is not the replication leader" })),
+        ));
+    }
+
+    Ok(replicator)
+}
+
+pub async fn fetch_log(
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    headers: HeaderMap,
+    Query(query): Query<LogQuery>,
+) -> impl IntoResponse {
+    let replicator = match authorize(&message_manager, &headers) {
+        Ok(replicator) => replicator,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let page = replicator
+        .entries_since(query.from.unwrap_or(0), query.limit.unwrap_or(usize::MAX))
+        .await;
+    (StatusCode::OK, Json(page)).into_response()
+}
+
+pub async fn append_entry(
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    headers: HeaderMap,
+    Json(request): Json<AppendRequest>,
+) -> impl IntoResponse {
+    if let Err(rejection) = authorize(&message_manager, &headers) {
+        return rejection.into_response();
+    }
+
+    match message_manager.apply_forwarded(request.op, &request.origin).await {
+        Ok(offset) => (StatusCode::OK, Json(AppendAck { offset })).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to append: {}", e) })),
+        )
+            .into_response(),
+    }
+}
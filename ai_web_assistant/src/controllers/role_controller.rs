@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::middleware::auth::AuthUser;
+use crate::services::{revocation_service::RevocationService, role_service};
+
+#[derive(Debug, Deserialize)]
+pub struct SetRolesRequest {
+    pub roles: Vec<String>,
+}
+
+fn error_response(status: StatusCode, error: impl ToString) -> Response {
+    (status, Json(json!({ "error": error.to_string() }))).into_response()
+}
+
+// GET /admin/roles lists every role with its permissions
+pub async fn list_roles(Extension(db): Extension<PgPool>) -> Response {
+    match role_service::list_roles(&db).await {
+        Ok(roles) => (StatusCode::OK, Json(json!({ "roles": roles }))).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+// GET /admin/users/:id/roles
+pub async fn get_user_roles(
+    Extension(db): Extension<PgPool>,
+    Path(user_id): Path<i64>,
+) -> Response {
+    match role_service::user_access(&db, user_id).await {
+        Ok(access) => (
+            StatusCode::OK,
+            Json(json!({ "user_id": user_id, "roles": access.roles, "permissions": access.permissions })),
+        )
+            .into_response(),
+        Err(e) => error_response(StatusCode::NOT_FOUND, e),
+    }
+}
+
+// PUT /admin/users/:id/roles {"roles":["user","staff"]} replaces the user's roles
+pub async fn set_user_roles(
+    AuthUser(claims): AuthUser,
+    Extension(db): Extension<PgPool>,
+    Path(user_id): Path<i64>,
+    Json(payload): Json<SetRolesRequest>,
+) -> Response {
+    match role_service::set_roles(&db, &claims, user_id, &payload.roles).await {
+        Ok(access) => (
+            StatusCode::OK,
+            Json(json!({ "user_id": user_id, "roles": access.roles, "permissions": access.permissions })),
+        )
+            .into_response(),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+// POST /admin/users/:id/ban deactivates the account and revokes all of its tokens
+pub async fn ban_user(
+    AuthUser(claims): AuthUser,
+    Extension(db): Extension<PgPool>,
+    Extension(revocations): Extension<Arc<RevocationService>>,
+    Path(user_id): Path<i64>,
+) -> Response {
+    match role_service::set_banned(&db, &revocations, &claims, user_id, true).await {
+        Ok(()) => (StatusCode::OK, Json(json!({ "status": "banned", "user_id": user_id }))).into_response(),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+// POST /admin/users/:id/unban
+pub async fn unban_user(
+    AuthUser(claims): AuthUser,
+    Extension(db): Extension<PgPool>,
+    Extension(revocations): Extension<Arc<RevocationService>>,
+    Path(user_id): Path<i64>,
+) -> Response {
+    match role_service::set_banned(&db, &revocations, &claims, user_id, false).await {
+        Ok(()) => (StatusCode::OK, Json(json!({ "status": "active", "user_id": user_id }))).into_response(),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, e),
+    }
+}
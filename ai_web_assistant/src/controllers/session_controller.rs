@@ -0,0 +1,63 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::middleware::auth::AuthUser;
+use crate::repository::refresh_token_repository::RefreshTokenRepository;
+use crate::services::revocation_service::RevocationService;
+
+// GET /auth/sessions lists the caller's devices that can still refresh, marking this one
+pub async fn list_sessions(
+    AuthUser(claims): AuthUser,
+    Extension(db): Extension<PgPool>,
+) -> Response {
+    match (RefreshTokenRepository { db }).active_sessions(claims.sub).await {
+        Ok(sessions) => {
+            let sessions: Vec<_> = sessions
+                .into_iter()
+                .map(|session| {
+                    let current = session.session_id == claims.sid;
+                    let mut value = json!(session);
+                    value["current"] = json!(current);
+                    value
+                })
+                .collect();
+            (StatusCode::OK, Json(json!({ "sessions": sessions }))).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+// DELETE /auth/sessions/:session_id logs one device out
+pub async fn revoke_session(
+    AuthUser(claims): AuthUser,
+    Extension(revocations): Extension<Arc<RevocationService>>,
+    Path(session_id): Path<String>,
+) -> Response {
+    match revocations.revoke_session(claims.sub, &session_id, "revoked by user").await {
+        Ok(true) => (StatusCode::OK, Json(json!({ "status": "revoked", "session_id": session_id }))).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, Json(json!({ "error": "Session not found" }))).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+// POST /admin/users/:id/revoke-tokens signs the user out everywhere
+pub async fn revoke_user_tokens(
+    Extension(revocations): Extension<Arc<RevocationService>>,
+    Path(user_id): Path<i64>,
+) -> Response {
+    match revocations.revoke_user(user_id, "revoked by admin").await {
+        Ok(sessions) => (
+            StatusCode::OK,
+            Json(json!({ "status": "revoked", "user_id": user_id, "sessions_revoked": sessions })),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
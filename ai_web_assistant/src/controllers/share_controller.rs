@@ -0,0 +1,118 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Extension, Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::helpers::export::{self, ExportFormat};
+use crate::helpers::message_manager::MessageManager;
+use crate::helpers::share;
+use crate::middleware::auth::AuthUser;
+
+#[derive(Debug, Deserialize, Default)]
+pub struct CreateShareRequest {
+    pub expires_in_hours: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareQuery {
+    pub format: Option<ExportFormat>,
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
+
+// GET /share/:token — public, no authentication. HTML unless ?format=json or Accept asks for JSON.
+pub async fn view_shared(
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Path(token): Path<String>,
+    Query(query): Query<ShareQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let snapshot = match message_manager.get_shared(&token).await {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Share link not found or expired".to_string()),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let wants_json = headers.get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+    let format = query.format.unwrap_or(if wants_json { ExportFormat::Json } else { ExportFormat::Html });
+
+    // The share id stands in for the owner's conversation id, which viewers have no use for
+    match export::render(&snapshot.share_id, &snapshot.conversation, format) {
+        Ok(content) => (
+            [
+                (header::CONTENT_TYPE, format.content_type()),
+                (header::CACHE_CONTROL, "no-store"),
+                (header::HeaderName::from_static("x-robots-tag"), "noindex"),
+            ],
+            content,
+        )
+            .into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+// POST /share/:token/fork copies the shared conversation into the caller's account
+pub async fn fork_shared(
+    AuthUser(claims): AuthUser,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Path(token): Path<String>,
+) -> Response {
+    match message_manager.fork_shared(&token, &claims.sub.to_string()).await {
+        Ok(conversation) => (StatusCode::CREATED, Json(json!({ "conversation": conversation }))).into_response(),
+        Err(e) => error_response(StatusCode::NOT_FOUND, e.to_string()),
+    }
+}
+
+// POST /conversations/:id/share with an optional {"expires_in_hours": 24}
+pub async fn create_share(
+    AuthUser(claims): AuthUser,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Path(conversation_id): Path<String>,
+    payload: Option<Json<CreateShareRequest>>,
+) -> Response {
+    let request = payload.map(|Json(request)| request).unwrap_or_default();
+    let expires_in = request.expires_in_hours.map(|hours| Duration::hours(hours.into()));
+
+    match message_manager.create_share(&claims.sub.to_string(), &conversation_id, expires_in).await {
+        Ok(link) => (StatusCode::CREATED, Json(json!(share::view(link, Utc::now())))).into_response(),
+        Err(e) => error_response(StatusCode::NOT_FOUND, e.to_string()),
+    }
+}
+
+// GET /shares lists the caller's share links, newest first
+pub async fn list_shares(
+    AuthUser(claims): AuthUser,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+) -> Response {
+    match message_manager.list_shares(&claims.sub.to_string()).await {
+        Ok(links) => {
+            let now = Utc::now();
+            let links: Vec<_> = links.into_iter().map(|link| share::view(link, now)).collect();
+            (StatusCode::OK, Json(json!({ "shares": links }))).into_response()
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+// DELETE /shares/:share_id revokes a link; the snapshot is deleted right away
+pub async fn revoke_share(
+    AuthUser(claims): AuthUser,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Path(share_id): Path<String>,
+) -> Response {
+    match message_manager.revoke_share(&claims.sub.to_string(), &share_id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => error_response(StatusCode::NOT_FOUND, format!("Share link not found: {}", share_id)),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
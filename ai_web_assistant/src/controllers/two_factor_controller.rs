@@ -0,0 +1,87 @@
+use std::sync::Arc;
+use axum::{
+    extract::Extension,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use validator::Validate;
+
+use crate::middleware::auth::AuthUser;
+use crate::payloads::two_factor_request::{DisableTwoFactorRequest, TwoFactorCodeRequest};
+use crate::services::two_factor_service::TwoFactorService;
+
+fn error_response(status: StatusCode, error: impl ToString) -> Response {
+    (status, Json(json!({ "error": error.to_string() }))).into_response()
+}
+
+// GET /auth/2fa
+pub async fn two_factor_status(
+    AuthUser(claims): AuthUser,
+    Extension(two_factor): Extension<Arc<TwoFactorService>>,
+) -> Response {
+    match two_factor.status(claims.sub).await {
+        Ok(status) => (StatusCode::OK, Json(json!(status))).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+// POST /auth/2fa/setup returns the secret and otpauth:// URI to scan
+pub async fn setup_two_factor(
+    AuthUser(claims): AuthUser,
+    Extension(two_factor): Extension<Arc<TwoFactorService>>,
+) -> Response {
+    match two_factor.setup(claims.sub, &claims.email).await {
+        Ok(setup) => (StatusCode::OK, Json(json!(setup))).into_response(),
+        Err(e) => error_response(StatusCode::CONFLICT, e),
+    }
+}
+
+// POST /auth/2fa/confirm {"code":"123456"} turns 2FA on and returns the recovery codes once
+pub async fn confirm_two_factor(
+    AuthUser(claims): AuthUser,
+    Extension(two_factor): Extension<Arc<TwoFactorService>>,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> Response {
+    if let Err(e) = payload.validate() {
+        return error_response(StatusCode::BAD_REQUEST, format!("Validation error: {}", e));
+    }
+
+    match two_factor.confirm(claims.sub, &payload.code).await {
+        Ok(codes) => (StatusCode::OK, Json(json!({ "enabled": true, "recovery_codes": codes }))).into_response(),
+        Err(e) => error_response(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+// POST /auth/2fa/recovery-codes {"code":"123456"} replaces every recovery code
+pub async fn regenerate_recovery_codes(
+    AuthUser(claims): AuthUser,
+    Extension(two_factor): Extension<Arc<TwoFactorService>>,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> Response {
+    if let Err(e) = payload.validate() {
+        return error_response(StatusCode::BAD_REQUEST, format!("Validation error: {}", e));
+    }
+
+    match two_factor.regenerate_recovery_codes(claims.sub, &payload.code).await {
+        Ok(codes) => (StatusCode::OK, Json(json!({ "recovery_codes": codes }))).into_response(),
+        Err(e) => error_response(StatusCode::UNAUTHORIZED, e),
+    }
+}
+
+// POST /auth/2fa/disable {"password":"...","code":"123456"}
+pub async fn disable_two_factor(
+    AuthUser(claims): AuthUser,
+    Extension(two_factor): Extension<Arc<TwoFactorService>>,
+    Json(payload): Json<DisableTwoFactorRequest>,
+) -> Response {
+    if let Err(e) = payload.validate() {
+        return error_response(StatusCode::BAD_REQUEST, format!("Validation error: {}", e));
+    }
+
+    match two_factor.disable(claims.sub, &payload.password, &payload.code).await {
+        Ok(()) => (StatusCode::OK, Json(json!({ "enabled": false }))).into_response(),
+        Err(e) => error_response(StatusCode::UNAUTHORIZED, e),
+    }
+}
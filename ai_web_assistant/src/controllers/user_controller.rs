@@ -1,13 +1,21 @@
 use axum::{
-    extract::{Path, Extension},
+    extract::Extension,
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
 
-use crate::{repository::user_repository::UserRepository, responses::responses::SafeUser, services::user_service::UserService};
+use crate::{
+    helpers::message_manager::MessageManager,
+    middleware::{ownership::OwnerOrPermission, permission::{UsersManage, UsersRead}},
+    repository::user_repository::UserRepository,
+    responses::responses::SafeUser,
+    services::{account_service::AccountService, revocation_service::RevocationService, user_service::UserService},
+    utils::file_utils::JsonFileManager,
+};
 
 // Make sure ApiResponse derives Serialize
 #[derive(Debug, Serialize)]
@@ -37,10 +45,14 @@ fn api_response<T: Serialize>(
     (status, Json(ApiResponse { data, error }))
 }
 
+// Your own profile, or anyone's with users.read
 pub async fn get_user_by_id(
+    OwnerOrPermission(_, user_id, _): OwnerOrPermission<UsersRead>,
     Extension(db): Extension<PgPool>,
-    Path(user_id): Path<i32>,
 ) -> impl IntoResponse {
+    let Ok(user_id) = i32::try_from(user_id) else {
+        return api_response(StatusCode::NOT_FOUND, None, Some("User not found".to_string()));
+    };
     let repository = UserRepository { db };
     let service = UserService::new(repository);
 
@@ -59,11 +71,15 @@ pub async fn get_user_by_id(
 }
 
 
+// Your own profile, or anyone's with users.manage
 pub async fn update_user(
+    OwnerOrPermission(_, user_id, _): OwnerOrPermission<UsersManage>,
     Extension(db): Extension<PgPool>,
-    Path(user_id): Path<i32>,
     Json(payload): Json<UpdateUserRequest>,
 ) -> impl IntoResponse {
+    let Ok(user_id) = i32::try_from(user_id) else {
+        return api_response(StatusCode::NOT_FOUND, None, Some("User not found".to_string()));
+    };
     let repository = UserRepository { db };
     let service = UserService::new(repository);
     
@@ -73,15 +89,91 @@ pub async fn update_user(
     }
 }
 
+// Erases the user from every store, not just Postgres, and returns the deletion report.
+// Your own account, or anyone's with users.manage.
 pub async fn delete_user(
+    OwnerOrPermission(_, user_id, _): OwnerOrPermission<UsersManage>,
     Extension(db): Extension<PgPool>,
-    Path(user_id): Path<i32>,
+    Extension(message_manager): Extension<Arc<MessageManager>>,
+    Extension(file_manager): Extension<Arc<JsonFileManager>>,
+    Extension(revocations): Extension<Arc<RevocationService>>,
 ) -> impl IntoResponse {
-    let repository = UserRepository { db };
-    let service = UserService::new(repository);
-    
-    match service.remove_user(user_id).await {
-        Ok(_) => api_response(StatusCode::NO_CONTENT, None::<()>, None),
-        Err(e) => api_response(StatusCode::BAD_REQUEST, None::<()>, Some(e.to_string())),
+    let service = AccountService::new(UserRepository { db }, message_manager, file_manager);
+    let report = service.erase(user_id, &revocations).await;
+
+    if report.complete {
+        api_response(StatusCode::OK, Some(report), None)
+    } else {
+        api_response(StatusCode::INTERNAL_SERVER_ERROR, Some(report), Some("Erasure incomplete, retry to finish".to_string()))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::{header, Method, Request}, routing::get, Router};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use crate::repository::role_repository::UserAccess;
+    use crate::utils::jwt::generate_token;
+
+    // Ownership is decided before the database is touched, so a pool that never connects is enough
+    fn app() -> Router {
+        let pool = PgPool::connect_lazy("postgres://localhost:1/unused").unwrap();
+        Router::new()
+            .route("/users/:id", get(get_user_by_id).put(update_user).delete(delete_user))
+            .layer(Extension(Arc::new(RevocationService::new(pool.clone()))))
+            .layer(Extension(pool))
+    }
+
+    fn token(user_id: i64, permissions: &[&str]) -> String {
+        let access = UserAccess {
+            roles: vec!["user".to_string()],
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        };
+        generate_token(user_id, "a@example.com".to_string(), &access, "session").unwrap()
+    }
+
+    async fn call(method: Method, path: &str, token: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"username":"someone"}"#))
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn other_users_are_forbidden_without_an_override() {
+        let token = token(1, &["chat.use"]);
+        for (method, permission) in [(Method::GET, "users.read"), (Method::PUT, "users.manage"), (Method::DELETE, "users.manage")] {
+            let (status, body) = call(method.clone(), "/users/2", &token).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", method);
+            assert_eq!(body["code"], "not_owner");
+            assert_eq!(body["permission"], permission);
+        }
+    }
+
+    #[tokio::test]
+    async fn read_permission_does_not_allow_changes() {
+        let token = token(1, &["chat.use", "users.read"]);
+        for method in [Method::PUT, Method::DELETE] {
+            let (status, body) = call(method.clone(), "/users/2", &token).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", method);
+            assert_eq!(body["permission"], "users.manage");
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_token_is_unauthorized() {
+        let request = Request::builder().uri("/users/2").body(Body::empty()).unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,438 @@
+// export.rs
+use std::collections::{HashMap, HashSet};
+use anyhow::Result;
+use async_zip::{Compression, ZipDateTime, ZipEntryBuilder};
+use async_zip::tokio::write::ZipFileWriter;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::DuplexStream;
+
+use crate::utils::file_models::{ChatMessage, ChatSession, MessageRevision};
+
+// Bumped whenever a field of ExportedConversation / ExportedMessage changes meaning
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+const ZIP_BUFFER_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    #[serde(alias = "md")]
+    Markdown,
+    Html,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+}
+
+// The documented JSON export format; also accepted back by the importer
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedConversation {
+    pub schema_version: u32,
+    pub conversation_id: String,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+    pub exported_at: DateTime<Utc>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub folder: Option<String>,
+    pub messages: Vec<ExportedMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedMessage {
+    pub message_id: String,
+    pub role: String,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub edited: bool,
+    #[serde(default)]
+    pub edit_timestamp: Option<DateTime<Utc>>,
+    // The message this one answers or follows up on; None for top-level turns
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    // Set when several messages share the same parent (e.g. regenerated answers)
+    #[serde(default)]
+    pub branch: Option<Branch>,
+    #[serde(default)]
+    pub revisions: Vec<MessageRevision>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Branch {
+    pub index: usize,
+    pub count: usize,
+}
+
+pub fn to_document(conversation_id: &str, conversation: &ChatSession, exported_at: DateTime<Utc>) -> ExportedConversation {
+    let parents = thread_parents(&conversation.messages);
+    let branches = branches(&conversation.messages, &parents);
+
+    ExportedConversation {
+        schema_version: EXPORT_SCHEMA_VERSION,
+        conversation_id: conversation_id.to_string(),
+        title: conversation.title.clone(),
+        created_at: conversation.created_at,
+        exported_at,
+        pinned: conversation.pinned,
+        archived: conversation.archived,
+        tags: conversation.tags.clone(),
+        folder: conversation.folder.clone(),
+        messages: conversation.messages.iter()
+            .map(|message| ExportedMessage {
+                message_id: message.message_id.clone(),
+                role: message.role.clone(),
+                content: message.content.clone(),
+                timestamp: message.timestamp,
+                edited: message.edited,
+                edit_timestamp: message.edit_timestamp,
+                parent_id: parents.get(message.message_id.as_str()).map(|p| p.to_string()),
+                branch: branches.get(message.message_id.as_str()).copied(),
+                revisions: message.revisions.clone(),
+            })
+            .collect(),
+    }
+}
+
+pub fn render(conversation_id: &str, conversation: &ChatSession, format: ExportFormat) -> Result<String> {
+    let document = to_document(conversation_id, conversation, Utc::now());
+
+    Ok(match format {
+        ExportFormat::Markdown => render_markdown(&document),
+        ExportFormat::Html => render_html(&document),
+        ExportFormat::Json => serde_json::to_string_pretty(&document)?,
+    })
+}
+
+// "Rust lifetimes, explained!" -> "rust-lifetimes-explained-1a2b3c4d.md"
+pub fn file_name(conversation_id: &str, conversation: &ChatSession, format: ExportFormat) -> String {
+    let mut slug = String::new();
+    for c in conversation.title.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= 50 {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    let slug = if slug.is_empty() { "conversation" } else { slug };
+
+    let short_id: String = conversation_id.chars().filter(|c| c.is_ascii_alphanumeric()).take(8).collect();
+    format!("{}-{}.{}", slug, short_id, format.extension())
+}
+
+// Writes one file per conversation into a zip; see write_zip
+pub fn stream_zip(conversations: Vec<(String, ChatSession)>, format: ExportFormat) -> DuplexStream {
+    write_zip(conversations.into_iter().map(move |(conversation_id, conversation)| {
+        let content = render(&conversation_id, &conversation, format)?;
+        Ok(ZipFile {
+            name: file_name(&conversation_id, &conversation, format),
+            modified: conversation.created_at,
+            content: content.into_bytes(),
+        })
+    }))
+}
+
+pub struct ZipFile {
+    pub name: String,
+    pub modified: DateTime<Utc>,
+    pub content: Vec<u8>,
+}
+
+// Produces the archive on a background task, pulling one file at a time from `files`;
+// the returned reader yields the zip as it is written so nothing is buffered in full
+pub fn write_zip<I>(files: I) -> DuplexStream
+where
+    I: Iterator<Item = Result<ZipFile>> + Send + 'static,
+{
+    let (writer, reader) = tokio::io::duplex(ZIP_BUFFER_BYTES);
+
+    tokio::spawn(async move {
+        let mut zip = ZipFileWriter::with_tokio(writer);
+
+        for file in files {
+            let result = async {
+                let file = file?;
+                let entry = ZipEntryBuilder::new(file.name.into(), Compression::Deflate)
+                    .last_modification_date(ZipDateTime::from_chrono(&file.modified));
+                zip.write_entry_whole(entry, &file.content).await?;
+                anyhow::Ok(())
+            }.await;
+
+            // The client is gone or the archive is broken; either way stop writing
+            if let Err(e) = result {
+                eprintln!("Failed to write export archive: {}", e);
+                return;
+            }
+        }
+
+        if let Err(e) = zip.close().await {
+            eprintln!("Failed to finish export archive: {}", e);
+        }
+    });
+
+    reader
+}
+
+// An AI message hangs off the user message it answers; any other message off its
+// parent_id, unless that is "root" or points outside the conversation
+fn thread_parents(messages: &[ChatMessage]) -> HashMap<&str, &str> {
+    let ids: HashSet<&str> = messages.iter().map(|m| m.message_id.as_str()).collect();
+
+    messages.iter()
+        .filter_map(|message| {
+            let parent = match (message.role.as_str(), message.reply_id.as_deref()) {
+                ("ai", Some(reply_id)) => reply_id,
+                _ => message.parent_id.as_str(),
+            };
+            (parent != "root" && ids.contains(parent)).then_some((message.message_id.as_str(), parent))
+        })
+        .collect()
+}
+
+fn branches<'a>(messages: &'a [ChatMessage], parents: &HashMap<&'a str, &'a str>) -> HashMap<&'a str, Branch> {
+    let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+    for message in messages {
+        if let Some(parent) = parents.get(message.message_id.as_str()) {
+            children.entry(parent).or_default().push(&message.message_id);
+        }
+    }
+
+    children.into_values()
+        .filter(|siblings| siblings.len() > 1)
+        .flat_map(|siblings| {
+            let count = siblings.len();
+            siblings.into_iter()
+                .enumerate()
+                .map(move |(i, id)| (id, Branch { index: i + 1, count }))
+        })
+        .collect()
+}
+
+fn role_label(role: &str) -> String {
+    match role {
+        "ai" => "Assistant".to_string(),
+        "user" => "User".to_string(),
+        other => {
+            let mut chars = other.chars();
+            chars.next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_else(|| "Unknown".to_string())
+        }
+    }
+}
+
+fn format_time(timestamp: &DateTime<Utc>) -> String {
+    timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+// Edit marker, branch position and a reply reference when the parent is not the previous message
+fn message_notes(document: &ExportedConversation, index: usize) -> Vec<String> {
+    let message = &document.messages[index];
+    let mut notes = Vec::new();
+
+    if message.edited {
+        match &message.edit_timestamp {
+            Some(at) => notes.push(format!("edited {}", format_time(at))),
+            None => notes.push("edited".to_string()),
+        }
+    }
+    if let Some(branch) = message.branch {
+        notes.push(format!("branch {} of {}", branch.index, branch.count));
+    }
+    if let Some(parent_id) = &message.parent_id {
+        let previous = index.checked_sub(1).map(|i| document.messages[i].message_id.as_str());
+        if previous != Some(parent_id.as_str()) {
+            notes.push(format!("in reply to {}", parent_id));
+        }
+    }
+    notes
+}
+
+fn render_markdown(document: &ExportedConversation) -> String {
+    let mut out = format!("# {}\n\n", document.title);
+    out.push_str(&format!("- Conversation: `{}`\n", document.conversation_id));
+    out.push_str(&format!("- Created: {}\n", format_time(&document.created_at)));
+    out.push_str(&format!("- Exported: {}\n", format_time(&document.exported_at)));
+    if let Some(folder) = &document.folder {
+        out.push_str(&format!("- Folder: {}\n", folder));
+    }
+    if !document.tags.is_empty() {
+        out.push_str(&format!("- Tags: {}\n", document.tags.join(", ")));
+    }
+    if document.archived {
+        out.push_str("- Archived\n");
+    }
+
+    for (index, message) in document.messages.iter().enumerate() {
+        out.push_str(&format!("\n---\n\n### {} · {}\n", role_label(&message.role), format_time(&message.timestamp)));
+
+        let notes = message_notes(document, index);
+        if !notes.is_empty() {
+            out.push_str(&format!("_{}_\n", notes.join(" · ")));
+        }
+        out.push_str(&format!("<a id=\"{}\"></a>\n\n", message.message_id));
+        out.push_str(message.content.trim_end());
+        out.push('\n');
+    }
+
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:860px;margin:2rem auto;padding:0 1rem;color:#1f2328}\
+header p{margin:.2rem 0;color:#59636e}\
+article{border:1px solid #d1d9e0;border-radius:8px;padding:.8rem 1rem;margin:1rem 0}\
+article.ai{background:#f6f8fa}\
+article.branch{margin-left:2rem}\
+h3{margin:0 0 .3rem;font-size:1rem}\
+.notes{font-size:.85rem;color:#59636e;margin-bottom:.5rem}\
+.content{white-space:pre-wrap;word-wrap:break-word;margin:0;font-family:inherit}";
+
+fn render_html(document: &ExportedConversation) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<header>\n", escape_html(&document.title), HTML_STYLE));
+    out.push_str(&format!("<h1>{}</h1>\n", escape_html(&document.title)));
+    out.push_str(&format!("<p>Conversation <code>{}</code></p>\n", escape_html(&document.conversation_id)));
+    out.push_str(&format!("<p>Created {} · Exported {}</p>\n", format_time(&document.created_at), format_time(&document.exported_at)));
+    if let Some(folder) = &document.folder {
+        out.push_str(&format!("<p>Folder: {}</p>\n", escape_html(folder)));
+    }
+    if !document.tags.is_empty() {
+        out.push_str(&format!("<p>Tags: {}</p>\n", escape_html(&document.tags.join(", "))));
+    }
+    if document.archived {
+        out.push_str("<p>Archived</p>\n");
+    }
+    out.push_str("</header>\n<main>\n");
+
+    for (index, message) in document.messages.iter().enumerate() {
+        let mut classes = vec![escape_html(&message.role)];
+        if message.branch.is_some() {
+            classes.push("branch".to_string());
+        }
+
+        out.push_str(&format!("<article id=\"{}\" class=\"{}\">\n", escape_html(&message.message_id), classes.join(" ")));
+        out.push_str(&format!("<h3>{} · {}</h3>\n", role_label(&message.role), format_time(&message.timestamp)));
+
+        let notes = message_notes(document, index);
+        if !notes.is_empty() {
+            out.push_str(&format!("<div class=\"notes\">{}</div>\n", escape_html(&notes.join(" · "))));
+        }
+        out.push_str(&format!("<pre class=\"content\">{}</pre>\n</article>\n", escape_html(&message.content)));
+    }
+
+    out.push_str("</main>\n</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn message(id: &str, role: &str, reply_id: Option<&str>, content: &str) -> ChatMessage {
+        ChatMessage {
+            message_id: id.to_string(),
+            parent_id: "root".to_string(),
+            reply_id: reply_id.map(str::to_string),
+            role: role.to_string(),
+            content: content.to_string(),
+            edited: false,
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap(),
+            edit_timestamp: None,
+            revisions: Vec::new(),
+        }
+    }
+
+    // A question with a regenerated answer, so the second answer is a branch
+    fn fixture() -> ChatSession {
+        serde_json::from_value(serde_json::json!({
+            "title": "Tags & <b>bold</b>",
+            "created_at": Utc.with_ymd_and_hms(2024, 1, 2, 3, 0, 0).unwrap(),
+            "messages": [
+                message("m1", "user", None, "What is <b>?"),
+                message("a1", "ai", Some("m1"), "A bold tag."),
+                message("a2", "ai", Some("m1"), "Bold text."),
+            ],
+            "tags": ["html"],
+        })).unwrap()
+    }
+
+    #[test]
+    fn markdown_lists_each_message_with_its_notes() {
+        let markdown = render("c1", &fixture(), ExportFormat::Markdown).unwrap();
+
+        assert!(markdown.starts_with("# Tags & <b>bold</b>\n\n- Conversation: `c1`\n- Created: 2024-01-02 03:00:00 UTC\n"));
+        assert!(markdown.contains("- Tags: html\n"));
+        assert!(markdown.contains("\n---\n\n### User · 2024-01-02 03:04:05 UTC\n<a id=\"m1\"></a>\n\nWhat is <b>?\n"));
+        assert!(markdown.contains("### Assistant · 2024-01-02 03:04:05 UTC\n_branch 1 of 2_\n<a id=\"a1\"></a>\n\nA bold tag.\n"));
+        assert!(markdown.contains("_branch 2 of 2 · in reply to m1_\n<a id=\"a2\"></a>\n\nBold text.\n"));
+    }
+
+    #[test]
+    fn html_escapes_user_content_and_marks_branches() {
+        let html = render("c1", &fixture(), ExportFormat::Html).unwrap();
+
+        assert!(html.contains("<title>Tags &amp; &lt;b&gt;bold&lt;/b&gt;</title>"));
+        assert!(html.contains("<article id=\"m1\" class=\"user\">\n<h3>User · 2024-01-02 03:04:05 UTC</h3>\n<pre class=\"content\">What is &lt;b&gt;?</pre>"));
+        assert!(html.contains("<article id=\"a2\" class=\"ai branch\">"));
+        assert!(html.contains("<div class=\"notes\">branch 2 of 2 · in reply to m1</div>"));
+        assert!(!html.contains("<b>"));
+    }
+
+    #[test]
+    fn json_round_trips_the_documented_schema() {
+        let json = render("c1", &fixture(), ExportFormat::Json).unwrap();
+        let document: ExportedConversation = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(document.schema_version, EXPORT_SCHEMA_VERSION);
+        assert_eq!(document.conversation_id, "c1");
+        assert_eq!(document.tags, vec!["html"]);
+        let ids: Vec<&str> = document.messages.iter().map(|m| m.message_id.as_str()).collect();
+        assert_eq!(ids, vec!["m1", "a1", "a2"]);
+        assert_eq!(document.messages[0].parent_id, None);
+        assert_eq!(document.messages[2].parent_id.as_deref(), Some("m1"));
+        let branch = document.messages[2].branch.unwrap();
+        assert_eq!((branch.index, branch.count), (2, 2));
+    }
+}
@@ -0,0 +1,611 @@
+// import.rs
+use std::collections::HashMap;
+use std::env;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::helpers::export::ExportedConversation;
+use crate::utils::file_models::{ChatMessage, ChatSession, ImportOrigin, UserConversations};
+
+const DEFAULT_MAX_IMPORT_BYTES: usize = 50 * 1024 * 1024;
+const DEFAULT_TITLE: &str = "Imported Chat";
+
+// IMPORT_MAX_BYTES caps the size of a single upload
+pub fn max_import_bytes() -> usize {
+    env::var("IMPORT_MAX_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|bytes| *bytes > 0)
+        .unwrap_or(DEFAULT_MAX_IMPORT_BYTES)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    #[default]
+    Auto,
+    Chatgpt,
+    #[serde(alias = "md")]
+    Markdown,
+    // Our own JSON export
+    Native,
+}
+
+impl ImportFormat {
+    fn source(self) -> &'static str {
+        match self {
+            ImportFormat::Auto => "auto",
+            ImportFormat::Chatgpt => "chatgpt",
+            ImportFormat::Markdown => "markdown",
+            ImportFormat::Native => "native",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportOutcome {
+    pub conversation_id: String,
+    pub title: String,
+    pub status: &'static str,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub conversations: Vec<ImportOutcome>,
+}
+
+// Turns an upload into conversations ready to merge; every one carries its ImportOrigin
+pub fn parse(content: &str, format: ImportFormat) -> Result<Vec<ChatSession>> {
+    let format = match format {
+        ImportFormat::Auto => detect(content)?,
+        other => other,
+    };
+    let now = Utc::now();
+
+    let sessions = match format {
+        ImportFormat::Chatgpt => parse_chatgpt(serde_json::from_str(content)?, now),
+        ImportFormat::Native => parse_native(content, now)?,
+        ImportFormat::Markdown | ImportFormat::Auto => parse_markdown(content, now).into_iter().collect(),
+    };
+
+    if sessions.is_empty() {
+        return Err(anyhow!("No conversations found in {} import", format.source()));
+    }
+    Ok(sessions)
+}
+
+fn detect(content: &str) -> Result<ImportFormat> {
+    let trimmed = content.trim_start();
+    if !trimmed.starts_with('{') && !trimmed.starts_with('[') {
+        return Ok(ImportFormat::Markdown);
+    }
+
+    let value: Value = serde_json::from_str(trimmed)?;
+    let first = match &value {
+        Value::Array(items) => items.first().cloned().unwrap_or(Value::Null),
+        other => other.clone(),
+    };
+
+    if first.get("mapping").is_some() {
+        Ok(ImportFormat::Chatgpt)
+    } else if first.get("schema_version").is_some() {
+        Ok(ImportFormat::Native)
+    } else {
+        Err(anyhow!("Unrecognised JSON import; expected ChatGPT conversations.json or an export from this app"))
+    }
+}
+
+// Adds everything that is not already there. A conversation seen before (same source and
+// external id) is skipped when unchanged and refreshed when the source has grown since;
+// conversations that were created here are never overwritten by an import.
+pub fn merge(user_conv: &mut UserConversations, sessions: Vec<ChatSession>) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    for mut session in sessions {
+        let Some(origin) = session.imported.clone() else {
+            continue;
+        };
+
+        let same_origin = |conversation: &ChatSession| conversation.imported.as_ref()
+            .is_some_and(|existing| existing.source == origin.source && existing.external_id == origin.external_id);
+        let existing = user_conv.conversations.iter_mut().find(|(id, conversation)| {
+            same_origin(conversation) || (origin.source == "native" && **id == origin.external_id)
+        });
+
+        if let Some((id, conversation)) = existing {
+            // The stored hash is what was imported last time, so local edits since then are kept
+            let existing_hash = match &conversation.imported {
+                Some(existing) if same_origin(conversation) => existing.content_hash.clone(),
+                _ => content_hash(conversation),
+            };
+
+            let status = if existing_hash == origin.content_hash || !same_origin(conversation) {
+                report.skipped += 1;
+                "skipped"
+            } else {
+                conversation.title = session.title;
+                conversation.messages = session.messages;
+                conversation.imported = Some(origin);
+                report.updated += 1;
+                "updated"
+            };
+            report.conversations.push(ImportOutcome { conversation_id: id.clone(), title: conversation.title.clone(), status });
+            continue;
+        }
+
+        // Our own exports keep their conversation id when it is free
+        let conversation_id = if origin.source == "native" && !user_conv.conversations.contains_key(&origin.external_id) {
+            origin.external_id.clone()
+        } else {
+            Uuid::new_v4().to_string()
+        };
+
+        session.imported = Some(origin);
+        report.conversations.push(ImportOutcome {
+            conversation_id: conversation_id.clone(),
+            title: session.title.clone(),
+            status: "imported",
+        });
+        report.imported += 1;
+        user_conv.conversations.insert(conversation_id, session);
+    }
+
+    report
+}
+
+// Title plus every message's role, content and timestamp; ids are not stable across imports
+pub fn content_hash(session: &ChatSession) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(session.title.as_bytes());
+    for message in &session.messages {
+        hasher.update([0u8]);
+        hasher.update(message.role.as_bytes());
+        hasher.update([0u8]);
+        hasher.update(message.content.as_bytes());
+        hasher.update([0u8]);
+        hasher.update(message.timestamp.to_rfc3339().as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn session(
+    title: String,
+    created_at: DateTime<Utc>,
+    messages: Vec<ChatMessage>,
+    source: ImportFormat,
+    external_id: Option<String>,
+    now: DateTime<Utc>,
+) -> ChatSession {
+    let mut session = ChatSession {
+        title,
+        created_at,
+        messages,
+        expires_at: None,
+        pinned: false,
+        tags: Vec::new(),
+        folder: None,
+        archived: false,
+        imported: None,
+    };
+
+    let content_hash = content_hash(&session);
+    session.imported = Some(ImportOrigin {
+        source: source.source().to_string(),
+        external_id: external_id.unwrap_or_else(|| content_hash.clone()),
+        content_hash,
+        imported_at: now,
+    });
+    session
+}
+
+// Messages are linked the way ws_handler links them: user turns hang off "root",
+// AI answers point at the user message they reply to
+fn push_message(messages: &mut Vec<ChatMessage>, role: &str, content: String, timestamp: DateTime<Utc>) {
+    let reply_id = (role == "ai")
+        .then(|| messages.iter().rev().find(|m| m.role == "user").map(|m| m.message_id.clone()))
+        .flatten();
+
+    messages.push(ChatMessage {
+        message_id: Uuid::new_v4().to_string(),
+        parent_id: "root".to_string(),
+        reply_id,
+        role: role.to_string(),
+        content,
+        edited: false,
+        timestamp,
+        edit_timestamp: None,
+        revisions: Vec::new(),
+    });
+}
+
+fn from_unix_seconds(value: &Value) -> Option<DateTime<Utc>> {
+    value.as_f64().and_then(|secs| DateTime::from_timestamp_millis((secs * 1000.0) as i64))
+}
+
+// ChatGPT keeps every branch in `mapping`; only the branch that was on screen
+// (current_node back up to the root) is imported
+fn parse_chatgpt(value: Value, now: DateTime<Utc>) -> Vec<ChatSession> {
+    let items = match value {
+        Value::Array(items) => items,
+        other => vec![other],
+    };
+
+    items.iter()
+        .filter_map(|item| {
+            let mapping = item.get("mapping")?.as_object()?;
+            let created_at = item.get("create_time").and_then(from_unix_seconds).unwrap_or(now);
+            let title = item.get("title")
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .unwrap_or(DEFAULT_TITLE)
+                .to_string();
+            let external_id = item.get("conversation_id")
+                .or_else(|| item.get("id"))
+                .and_then(Value::as_str)
+                .map(str::to_string);
+
+            let leaf = item.get("current_node")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .or_else(|| {
+                    mapping.iter()
+                        .filter(|(_, node)| node.get("children").and_then(Value::as_array).is_none_or(|c| c.is_empty()))
+                        .max_by(|a, b| {
+                            let time = |node: &Value| node.pointer("/message/create_time").and_then(Value::as_f64).unwrap_or(0.0);
+                            time(a.1).total_cmp(&time(b.1))
+                        })
+                        .map(|(id, _)| id.clone())
+                })?;
+
+            let mut path = Vec::new();
+            let mut cursor = Some(leaf);
+            while let Some(node_id) = cursor {
+                let Some(node) = mapping.get(&node_id) else { break };
+                path.push(node);
+                // A malformed export could loop; a path can never be longer than the mapping
+                if path.len() > mapping.len() {
+                    break;
+                }
+                cursor = node.get("parent").and_then(Value::as_str).map(str::to_string);
+            }
+            path.reverse();
+
+            let mut messages = Vec::new();
+            let mut last_timestamp = created_at;
+            for node in path {
+                let Some(message) = node.get("message").filter(|m| !m.is_null()) else { continue };
+                if message.pointer("/metadata/is_visually_hidden_from_conversation").and_then(Value::as_bool) == Some(true) {
+                    continue;
+                }
+
+                let role = match message.pointer("/author/role").and_then(Value::as_str) {
+                    Some("user") => "user",
+                    Some("assistant") => "ai",
+                    _ => continue,
+                };
+                let content = message.pointer("/content/parts")
+                    .and_then(Value::as_array)
+                    .map(|parts| parts.iter().filter_map(Value::as_str).collect::<Vec<_>>().join("\n"))
+                    .unwrap_or_default();
+                if content.trim().is_empty() {
+                    continue;
+                }
+
+                let timestamp = message.get("create_time").and_then(from_unix_seconds).unwrap_or(last_timestamp);
+                last_timestamp = timestamp;
+                push_message(&mut messages, role, content, timestamp);
+            }
+
+            (!messages.is_empty()).then(|| session(title, created_at, messages, ImportFormat::Chatgpt, external_id, now))
+        })
+        .collect()
+}
+
+// Accepts a single exported conversation or an array of them
+fn parse_native(content: &str, now: DateTime<Utc>) -> Result<Vec<ChatSession>> {
+    let documents: Vec<ExportedConversation> = match serde_json::from_str::<Value>(content)? {
+        Value::Array(items) => items.into_iter().map(serde_json::from_value).collect::<Result<_, _>>()?,
+        other => vec![serde_json::from_value(other)?],
+    };
+
+    Ok(documents.into_iter()
+        .filter(|document| !document.messages.is_empty())
+        .map(|document| {
+            // Fresh message ids so a re-import never collides with the originals
+            let ids: HashMap<String, String> = document.messages.iter()
+                .map(|m| (m.message_id.clone(), Uuid::new_v4().to_string()))
+                .collect();
+
+            let messages = document.messages.into_iter()
+                .map(|m| {
+                    let parent = m.parent_id.as_ref().and_then(|p| ids.get(p)).cloned();
+                    let (parent_id, reply_id) = match (m.role.as_str(), parent) {
+                        ("ai", parent) => ("root".to_string(), parent),
+                        (_, parent) => (parent.unwrap_or_else(|| "root".to_string()), None),
+                    };
+                    ChatMessage {
+                        message_id: ids[&m.message_id].clone(),
+                        parent_id,
+                        reply_id,
+                        role: m.role,
+                        content: m.content,
+                        edited: m.edited,
+                        timestamp: m.timestamp,
+                        edit_timestamp: m.edit_timestamp,
+                        revisions: m.revisions,
+                    }
+                })
+                .collect();
+
+            let mut session = session(
+                document.title,
+                document.created_at,
+                messages,
+                ImportFormat::Native,
+                Some(document.conversation_id),
+                now,
+            );
+            session.pinned = document.pinned;
+            session.archived = document.archived;
+            session.tags = document.tags;
+            session.folder = document.folder;
+            session
+        })
+        .collect())
+}
+
+fn speaker_role(name: &str) -> Option<&'static str> {
+    match name.trim().to_lowercase().as_str() {
+        "user" | "you" | "me" | "human" => Some("user"),
+        "assistant" | "ai" | "chatgpt" | "gpt" | "claude" | "gemini" | "bot" | "model" => Some("ai"),
+        _ => None,
+    }
+}
+
+// Recognises "### User · 2024-01-01 10:00:00 UTC" (our export), "## Assistant",
+// "**You:** text" and "ChatGPT: text"; returns the role, an optional timestamp and inline text
+fn speaker_line(line: &str) -> Option<(&'static str, Option<DateTime<Utc>>, String)> {
+    let trimmed = line.trim();
+
+    if let Some(heading) = trimmed.strip_prefix("##") {
+        let heading = heading.trim_start_matches('#').trim();
+        let (name, rest) = heading.split_once(['·', ':', '(', '—'])
+            .map(|(name, rest)| (name, rest.trim_end_matches(')')))
+            .unwrap_or((heading, ""));
+        let role = speaker_role(name)?;
+        return Some((role, parse_timestamp(rest.trim()), String::new()));
+    }
+
+    let unbolded = trimmed.strip_prefix("**").unwrap_or(trimmed);
+    let (name, rest) = unbolded.split_once(':')?;
+    let name = name.trim_end_matches('*');
+    let role = speaker_role(name)?;
+    let rest = rest.strip_prefix("**").unwrap_or(rest);
+    Some((role, None, rest.trim().to_string()))
+}
+
+fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    if raw.is_empty() {
+        return None;
+    }
+    DateTime::parse_from_rfc3339(raw)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| NaiveDateTime::parse_from_str(raw.trim_end_matches(" UTC"), "%Y-%m-%d %H:%M:%S").ok().map(|dt| dt.and_utc()))
+        .or_else(|| NaiveDateTime::parse_from_str(raw.trim_end_matches(" UTC"), "%Y-%m-%d %H:%M").ok().map(|dt| dt.and_utc()))
+}
+
+// Speaker role, optional timestamp from the header and the body lines under it
+type Turn = (&'static str, Option<DateTime<Utc>>, Vec<String>);
+
+// One transcript per file. Messages without a timestamp are placed a second after the previous one.
+fn parse_markdown(content: &str, now: DateTime<Utc>) -> Option<ChatSession> {
+    let mut title: Option<String> = None;
+    let mut created_at: Option<DateTime<Utc>> = None;
+    let mut turns: Vec<Turn> = Vec::new();
+
+    for line in content.lines() {
+        if let Some((role, timestamp, inline)) = speaker_line(line) {
+            let body = if inline.is_empty() { Vec::new() } else { vec![inline] };
+            turns.push((role, timestamp, body));
+            continue;
+        }
+
+        match turns.last_mut() {
+            Some((_, _, body)) => {
+                let trimmed = line.trim();
+                // Our own export adds an anchor and an italic notes line under each header
+                let is_anchor = trimmed.starts_with("<a id=") && trimmed.ends_with("</a>");
+                let is_notes = body.is_empty() && trimmed.len() > 1 && trimmed.starts_with('_') && trimmed.ends_with('_');
+                if !is_anchor && !is_notes {
+                    body.push(line.to_string());
+                }
+            }
+            None => {
+                if let Some(heading) = line.strip_prefix("# ") {
+                    title.get_or_insert_with(|| heading.trim().to_string());
+                } else if let Some(created) = line.trim().strip_prefix("- Created:") {
+                    created_at = parse_timestamp(created.trim());
+                }
+            }
+        }
+    }
+
+    let mut messages = Vec::new();
+    let mut last_timestamp = created_at.unwrap_or(now);
+    for (role, timestamp, mut body) in turns {
+        while body.last().is_some_and(|l| l.trim().is_empty() || l.trim() == "---") {
+            body.pop();
+        }
+        let text = body.join("\n").trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+
+        let timestamp = timestamp.unwrap_or_else(|| {
+            if messages.is_empty() { last_timestamp } else { last_timestamp + chrono::Duration::seconds(1) }
+        });
+        last_timestamp = timestamp;
+        push_message(&mut messages, role, text, timestamp);
+    }
+
+    if messages.is_empty() {
+        return None;
+    }
+
+    let created_at = created_at.unwrap_or(messages[0].timestamp);
+    let title = title.filter(|t| !t.is_empty()).unwrap_or_else(|| DEFAULT_TITLE.to_string());
+    Some(session(title, created_at, messages, ImportFormat::Markdown, None, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::helpers::export::{self, ExportFormat};
+
+    fn chatgpt_node(parent: Option<&str>, children: &[&str], role: &str, text: &str, time: f64) -> Value {
+        json!({
+            "parent": parent,
+            "children": children,
+            "message": {
+                "author": { "role": role },
+                "content": { "parts": [text] },
+                "create_time": time,
+            },
+        })
+    }
+
+    // A root, a system prompt, one question and two regenerated answers
+    fn chatgpt_export(current_node: Option<&str>) -> Value {
+        json!([{
+            "title": "  Borrow checker  ",
+            "conversation_id": "gpt-1",
+            "create_time": 1_700_000_000.0,
+            "current_node": current_node,
+            "mapping": {
+                "root": { "parent": null, "children": ["sys"], "message": null },
+                "sys": chatgpt_node(Some("root"), &["q"], "system", "You are helpful", 1_700_000_001.0),
+                "q": chatgpt_node(Some("sys"), &["a1", "a2"], "user", "Why?", 1_700_000_002.0),
+                "a1": chatgpt_node(Some("q"), &[], "assistant", "First answer", 1_700_000_003.0),
+                "a2": chatgpt_node(Some("q"), &[], "assistant", "Second answer", 1_700_000_004.0),
+            },
+        }])
+    }
+
+    fn contents(session: &ChatSession) -> Vec<(&str, &str)> {
+        session.messages.iter().map(|m| (m.role.as_str(), m.content.as_str())).collect()
+    }
+
+    #[test]
+    fn chatgpt_imports_the_branch_on_screen() {
+        let sessions = parse(&chatgpt_export(Some("a1")).to_string(), ImportFormat::Auto).unwrap();
+        assert_eq!(sessions.len(), 1);
+        let session = &sessions[0];
+
+        assert_eq!(session.title, "Borrow checker");
+        assert_eq!(contents(session), [("user", "Why?"), ("ai", "First answer")]);
+        assert_eq!(session.messages[1].reply_id.as_ref(), Some(&session.messages[0].message_id));
+        assert_eq!(session.messages[0].timestamp.timestamp(), 1_700_000_002);
+
+        let origin = session.imported.as_ref().unwrap();
+        assert_eq!((origin.source.as_str(), origin.external_id.as_str()), ("chatgpt", "gpt-1"));
+    }
+
+    #[test]
+    fn chatgpt_without_current_node_takes_the_newest_leaf() {
+        let sessions = parse(&chatgpt_export(None).to_string(), ImportFormat::Chatgpt).unwrap();
+        assert_eq!(contents(&sessions[0]), [("user", "Why?"), ("ai", "Second answer")]);
+    }
+
+    #[test]
+    fn markdown_accepts_common_speaker_styles() {
+        let transcript = "# Trip plans\n\n- Created: 2024-05-01 09:00:00 UTC\n\n**You:** Where to?\n\nChatGPT: Lisbon.\nIt is sunny.\n\n## User\n\nThanks\n\n---\n";
+        let session = &parse(transcript, ImportFormat::Auto).unwrap()[0];
+
+        assert_eq!(session.title, "Trip plans");
+        assert_eq!(contents(session), [("user", "Where to?"), ("ai", "Lisbon.\nIt is sunny."), ("user", "Thanks")]);
+        let times: Vec<_> = session.messages.iter().map(|m| m.timestamp.to_rfc3339()).collect();
+        assert_eq!(times, ["2024-05-01T09:00:00+00:00", "2024-05-01T09:00:01+00:00", "2024-05-01T09:00:02+00:00"]);
+    }
+
+    fn local_conversation() -> ChatSession {
+        let mut messages = Vec::new();
+        let at = "2024-05-01T09:00:00Z".parse().unwrap();
+        push_message(&mut messages, "user", "Hello there".to_string(), at);
+        push_message(&mut messages, "ai", "Hi! How can I help?".to_string(), at + chrono::Duration::seconds(5));
+        let mut conversation = session("Greetings".to_string(), at, messages, ImportFormat::Native, None, at);
+        conversation.imported = None;
+        conversation.tags = vec!["demo".to_string()];
+        conversation
+    }
+
+    #[test]
+    fn own_exports_import_back() {
+        let original = local_conversation();
+
+        for format in [ExportFormat::Json, ExportFormat::Markdown] {
+            let rendered = export::render("conv-1", &original, format).unwrap();
+            let session = &parse(&rendered, ImportFormat::Auto).unwrap()[0];
+            assert_eq!(session.title, "Greetings", "{:?}", format);
+            assert_eq!(contents(session), contents(&original), "{:?}", format);
+            let times: Vec<_> = session.messages.iter().map(|m| m.timestamp).collect();
+            assert_eq!(times, original.messages.iter().map(|m| m.timestamp).collect::<Vec<_>>(), "{:?}", format);
+        }
+
+        let native = &parse(&export::render("conv-1", &original, ExportFormat::Json).unwrap(), ImportFormat::Native).unwrap()[0];
+        assert_eq!(native.tags, ["demo"]);
+        assert_eq!(native.messages[1].reply_id.as_ref(), Some(&native.messages[0].message_id));
+        assert_ne!(native.messages[0].message_id, original.messages[0].message_id);
+    }
+
+    #[test]
+    fn unrecognised_or_empty_uploads_are_errors() {
+        assert!(parse(r#"{"hello": "world"}"#, ImportFormat::Auto).unwrap_err().to_string().starts_with("Unrecognised JSON import"));
+        assert_eq!(parse("just some notes", ImportFormat::Auto).unwrap_err().to_string(), "No conversations found in markdown import");
+        assert!(parse("[]", ImportFormat::Chatgpt).is_err());
+    }
+
+    #[test]
+    fn reimports_are_skipped_until_the_source_grows() {
+        let mut user_conv = UserConversations::default();
+        let upload = chatgpt_export(Some("a1")).to_string();
+
+        let report = merge(&mut user_conv, parse(&upload, ImportFormat::Auto).unwrap());
+        assert_eq!((report.imported, report.updated, report.skipped), (1, 0, 0));
+        let report = merge(&mut user_conv, parse(&upload, ImportFormat::Auto).unwrap());
+        assert_eq!((report.imported, report.updated, report.skipped), (0, 0, 1));
+
+        let grown = parse(&chatgpt_export(Some("a2")).to_string(), ImportFormat::Auto).unwrap();
+        let report = merge(&mut user_conv, grown);
+        assert_eq!((report.imported, report.updated, report.skipped), (0, 1, 0));
+        assert_eq!(user_conv.conversations.len(), 1);
+        assert_eq!(contents(user_conv.conversations.values().next().unwrap())[1], ("ai", "Second answer"));
+    }
+
+    #[test]
+    fn native_imports_keep_a_free_id_and_never_overwrite_local_conversations() {
+        let rendered = export::render("conv-1", &local_conversation(), ExportFormat::Json).unwrap();
+
+        let mut fresh = UserConversations::default();
+        merge(&mut fresh, parse(&rendered, ImportFormat::Auto).unwrap());
+        assert!(fresh.conversations.contains_key("conv-1"));
+
+        let mut existing = UserConversations::default();
+        let mut edited = local_conversation();
+        edited.messages[0].content = "Edited locally".to_string();
+        existing.conversations.insert("conv-1".to_string(), edited);
+        let report = merge(&mut existing, parse(&rendered, ImportFormat::Auto).unwrap());
+        assert_eq!((report.imported, report.skipped), (0, 1));
+        assert_eq!(existing.conversations["conv-1"].messages[0].content, "Edited locally");
+    }
+}
@@ -0,0 +1,418 @@
+// message_cache.rs
+use std::env;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use dashmap::DashMap;
+use lru::LruCache;
+use serde::Serialize;
+
+use crate::helpers::message_manager::MessageManager;
+use crate::utils::file_models::UserConversations;
+
+const DEFAULT_FLUSH_DELAY_MS: u64 = 250;
+const DEFAULT_MAX_FLUSH_DELAY_MS: u64 = 2000;
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub capacity: NonZeroUsize,
+    pub flush_delay: Duration,
+    pub max_flush_delay: Duration,
+}
+
+impl CacheConfig {
+    // MESSAGE_CACHE_CAPACITY is the number of users kept in memory; unset or 0 disables the cache
+    pub fn from_env() -> Option<Self> {
+        let capacity = env::var("MESSAGE_CACHE_CAPACITY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .and_then(NonZeroUsize::new)?;
+
+        let millis = |key: &str, default: u64| {
+            env::var(key).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+        let flush_delay = Duration::from_millis(millis("MESSAGE_CACHE_FLUSH_DELAY_MS", DEFAULT_FLUSH_DELAY_MS));
+        let max_flush_delay = Duration::from_millis(millis("MESSAGE_CACHE_MAX_FLUSH_DELAY_MS", DEFAULT_MAX_FLUSH_DELAY_MS))
+            .max(flush_delay);
+
+        Some(Self { capacity, flush_delay, max_flush_delay })
+    }
+}
+
+struct CacheEntry {
+    data: UserConversations,
+    dirty: Option<DirtyState>,
+}
+
+// A dirty shard is flushed once writes have been quiet for flush_delay,
+// or once it has been dirty for max_flush_delay, whichever comes first
+#[derive(Clone, Copy)]
+struct DirtyState {
+    first_write: Instant,
+    last_write: Instant,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheMetrics {
+    pub capacity: usize,
+    pub entries: usize,
+    pub dirty_entries: usize,
+    pub connected_users: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    pub evictions: u64,
+    pub writes: u64,
+    pub flushes: u64,
+    pub flush_errors: u64,
+}
+
+// Per-user LRU of decoded shards. Dirty shards and shards of users with an open
+// connection are never evicted; all connections of a user share the same entry.
+pub struct MessageCache {
+    config: CacheConfig,
+    entries: Mutex<LruCache<String, CacheEntry>>,
+    connections: DashMap<String, usize>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    writes: AtomicU64,
+    flushes: AtomicU64,
+    flush_errors: AtomicU64,
+}
+
+impl MessageCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(LruCache::unbounded()),
+            connections: DashMap::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            flushes: AtomicU64::new(0),
+            flush_errors: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, user_id: &str) -> Option<UserConversations> {
+        let data = self.entries.lock().unwrap().get(user_id).map(|entry| entry.data.clone());
+        if data.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        data
+    }
+
+    // Counted when a shard actually has to be read from disk
+    pub fn record_miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn contains(&self, user_id: &str) -> bool {
+        self.entries.lock().unwrap().contains(user_id)
+    }
+
+    // Caches a shard that matches what is on disk. Callers hold the shard lock.
+    pub fn insert_clean(&self, user_id: &str, data: UserConversations) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.put(user_id.to_string(), CacheEntry { data, dirty: None });
+        self.evict_over_capacity(&mut entries);
+    }
+
+    // Records a write that has not reached disk yet. Callers hold the shard lock.
+    pub fn insert_dirty(&self, user_id: &str, data: UserConversations) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+
+        let first_write = entries.peek(user_id)
+            .and_then(|entry| entry.dirty)
+            .map(|dirty| dirty.first_write)
+            .unwrap_or(now);
+
+        entries.put(user_id.to_string(), CacheEntry {
+            data,
+            dirty: Some(DirtyState { first_write, last_write: now }),
+        });
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        self.evict_over_capacity(&mut entries);
+    }
+
+    pub fn invalidate(&self, user_id: &str) {
+        self.entries.lock().unwrap().pop(user_id);
+    }
+
+    // Users whose pending writes should be flushed now (all of them when forced)
+    pub fn due_for_flush(&self, force: bool) -> Vec<String> {
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap();
+
+        entries.iter()
+            .filter_map(|(user_id, entry)| {
+                let dirty = entry.dirty?;
+                let due = force
+                    || now.duration_since(dirty.last_write) >= self.config.flush_delay
+                    || now.duration_since(dirty.first_write) >= self.config.max_flush_delay;
+                due.then(|| user_id.clone())
+            })
+            .collect()
+    }
+
+    // Hands out the pending state of a shard and marks it clean. Callers hold the shard lock
+    // and must call flush_failed if the write does not go through.
+    pub fn take_dirty(&self, user_id: &str) -> Option<UserConversations> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.peek_mut(user_id)?;
+        entry.dirty.take()?;
+        Some(entry.data.clone())
+    }
+
+    pub fn flush_succeeded(&self) {
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn flush_failed(&self, user_id: &str) {
+        self.flush_errors.fetch_add(1, Ordering::Relaxed);
+
+        let now = Instant::now();
+        if let Some(entry) = self.entries.lock().unwrap().peek_mut(user_id) {
+            entry.dirty.get_or_insert(DirtyState { first_write: now, last_write: now });
+        }
+    }
+
+    pub fn user_ids(&self) -> Vec<String> {
+        self.entries.lock().unwrap().iter().map(|(user_id, _)| user_id.clone()).collect()
+    }
+
+    pub fn connected(&self, user_id: &str) {
+        *self.connections.entry(user_id.to_string()).or_insert(0) += 1;
+    }
+
+    // Returns true when this was the user's last open connection
+    pub fn disconnected(&self, user_id: &str) -> bool {
+        let remaining = match self.connections.get_mut(user_id) {
+            Some(mut count) => {
+                *count = count.saturating_sub(1);
+                *count
+            }
+            None => return true,
+        };
+
+        if remaining == 0 {
+            self.connections.remove_if(user_id, |_, count| *count == 0);
+            return true;
+        }
+        false
+    }
+
+    pub fn metrics(&self) -> CacheMetrics {
+        let (entries, dirty_entries) = {
+            let entries = self.entries.lock().unwrap();
+            (entries.len(), entries.iter().filter(|(_, entry)| entry.dirty.is_some()).count())
+        };
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+
+        CacheMetrics {
+            capacity: self.config.capacity.get(),
+            entries,
+            dirty_entries,
+            connected_users: self.connections.len(),
+            hits,
+            misses,
+            hit_rate: if hits + misses == 0 { 0.0 } else { hits as f64 / (hits + misses) as f64 },
+            evictions: self.evictions.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            flushes: self.flushes.load(Ordering::Relaxed),
+            flush_errors: self.flush_errors.load(Ordering::Relaxed),
+        }
+    }
+
+    // Drops least recently used clean entries until back under capacity. If everything left is
+    // dirty or pinned by a connection the cache runs over capacity until the flusher catches up.
+    fn evict_over_capacity(&self, entries: &mut LruCache<String, CacheEntry>) {
+        while entries.len() > self.config.capacity.get() {
+            let victim = entries.iter()
+                .rev()
+                .find(|(user_id, entry)| entry.dirty.is_none() && !self.connections.contains_key(*user_id))
+                .map(|(user_id, _)| user_id.clone());
+
+            match victim {
+                Some(user_id) => {
+                    entries.pop(&user_id);
+                    self.evictions.fetch_add(1, Ordering::Relaxed);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+pub fn spawn_flusher(message_manager: Arc<MessageManager>, config: CacheConfig) {
+    println!(
+        "🗄️ Message cache for {} users (flush after {}ms idle, at most {}ms)",
+        config.capacity,
+        config.flush_delay.as_millis(),
+        config.max_flush_delay.as_millis(),
+    );
+
+    let tick = (config.flush_delay / 2).max(Duration::from_millis(10));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tick);
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = message_manager.flush_cache(false).await {
+                eprintln!("Message cache flush failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::file_models::ChatSession;
+
+    fn config(capacity: usize, flush_delay: Duration) -> CacheConfig {
+        CacheConfig {
+            capacity: NonZeroUsize::new(capacity).unwrap(),
+            flush_delay,
+            max_flush_delay: flush_delay,
+        }
+    }
+
+    fn shard(title: &str) -> UserConversations {
+        let mut data = UserConversations::default();
+        data.conversations.insert("c1".to_string(), session(title));
+        data
+    }
+
+    // Imported, which is the simplest way to write a whole conversation through the manager
+    fn session(title: &str) -> ChatSession {
+        serde_json::from_value(serde_json::json!({
+            "title": title,
+            "created_at": chrono::Utc::now(),
+            "messages": [],
+            "imported": {
+                "source": "test",
+                "external_id": title,
+                "content_hash": title,
+                "imported_at": chrono::Utc::now(),
+            },
+        })).unwrap()
+    }
+
+    fn temp_root() -> String {
+        std::env::temp_dir().join(format!("aiwa-cache-{}", uuid::Uuid::new_v4())).to_string_lossy().into_owned()
+    }
+
+    // What a fresh instance without a cache finds on disk
+    async fn on_disk(root: &str, user_id: &str) -> usize {
+        MessageManager::new(root).get_user_data(user_id).await.unwrap().conversations.len()
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_clean_entry() {
+        let cache = MessageCache::new(config(2, Duration::ZERO));
+        cache.insert_clean("a", shard("a"));
+        cache.insert_clean("b", shard("b"));
+        assert!(cache.get("a").is_some());
+
+        cache.insert_clean("c", shard("c"));
+        assert!(cache.contains("a"));
+        assert!(!cache.contains("b"));
+        assert!(cache.contains("c"));
+        assert_eq!(cache.metrics().evictions, 1);
+    }
+
+    #[test]
+    fn dirty_entries_are_only_evicted_once_flushed() {
+        let cache = MessageCache::new(config(1, Duration::ZERO));
+        cache.insert_dirty("a", shard("a"));
+        cache.insert_clean("b", shard("b"));
+        assert!(cache.contains("a"));
+        assert!(!cache.contains("b"));
+
+        assert!(cache.take_dirty("a").is_some());
+        cache.insert_clean("b", shard("b"));
+        assert!(!cache.contains("a"));
+        assert!(cache.contains("b"));
+    }
+
+    #[test]
+    fn a_failed_flush_keeps_the_entry_dirty() {
+        let cache = MessageCache::new(config(1, Duration::ZERO));
+        cache.insert_dirty("a", shard("a"));
+        assert!(cache.take_dirty("a").is_some());
+        cache.flush_failed("a");
+
+        cache.insert_clean("b", shard("b"));
+        assert!(cache.contains("a"));
+        assert_eq!(cache.due_for_flush(false), vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn connected_users_are_pinned_until_their_last_connection_closes() {
+        let cache = MessageCache::new(config(1, Duration::ZERO));
+        cache.connected("a");
+        cache.connected("a");
+        cache.insert_clean("a", shard("a"));
+        cache.insert_clean("b", shard("b"));
+        assert!(cache.contains("a"));
+
+        assert!(!cache.disconnected("a"));
+        cache.insert_clean("b", shard("b"));
+        assert!(cache.contains("a"));
+
+        assert!(cache.disconnected("a"));
+        cache.insert_clean("b", shard("b"));
+        assert!(!cache.contains("a"));
+    }
+
+    #[tokio::test]
+    async fn dirty_shards_reach_disk_before_they_are_evicted() {
+        let root = temp_root();
+        let manager = MessageManager::new(&root).with_cache(config(1, Duration::ZERO));
+        manager.import_conversations("1", vec![session("first")]).await.unwrap();
+        manager.import_conversations("2", vec![session("second")]).await.unwrap();
+        assert_eq!(manager.cache_metrics().unwrap().entries, 2);
+
+        assert_eq!(manager.flush_cache(false).await.unwrap(), 2);
+        manager.get_user_data("3").await.unwrap();
+        assert_eq!(manager.cache_metrics().unwrap().entries, 1);
+        assert_eq!(on_disk(&root, "1").await, 1);
+        assert_eq!(on_disk(&root, "2").await, 1);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn pending_writes_are_flushed_on_shutdown() {
+        let root = temp_root();
+        let manager = MessageManager::new(&root).with_cache(config(10, Duration::from_secs(3600)));
+        manager.import_conversations("1", vec![session("first")]).await.unwrap();
+
+        assert_eq!(manager.flush_cache(false).await.unwrap(), 0);
+        assert_eq!(on_disk(&root, "1").await, 0);
+
+        // What main does once the server has stopped
+        assert_eq!(manager.flush_cache(true).await.unwrap(), 1);
+        assert_eq!(on_disk(&root, "1").await, 1);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn the_last_disconnect_flushes_and_releases_the_user() {
+        let root = temp_root();
+        let manager = MessageManager::new(&root).with_cache(config(10, Duration::from_secs(3600)));
+        manager.user_connected("1");
+        manager.import_conversations("1", vec![session("first")]).await.unwrap();
+
+        manager.user_disconnected("1").await.unwrap();
+        assert_eq!(on_disk(&root, "1").await, 1);
+        assert_eq!(manager.cache_metrics().unwrap().entries, 0);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
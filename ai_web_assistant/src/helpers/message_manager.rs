@@ -1,50 +1,94 @@
 // message_manager.rs
 use chrono::{DateTime, Utc};
-use serde_json::Value;
+use dashmap::DashMap;
+use std::cmp::Reverse;
 use std::collections::HashMap;
-use tokio::sync::Mutex;
+use std::path::{Path, PathBuf};
+use tokio::sync::{Mutex, OwnedMutexGuard};
 use anyhow::{Result, anyhow};
 use serde::Serialize;
 use std::sync::Arc;
 use std::error::Error;
+use uuid::Uuid;
 
-use crate::utils::file_models::{ChatMessage, ChatSession, ConversationMetadata, MessageData, UserConversations};
+use crate::helpers::message_cache::{CacheConfig, CacheMetrics, MessageCache};
+use crate::helpers::{import::{self, ImportReport}, organize::{self, OrganizeChange, Organization}, revisions::{self, DiffSegment}, share::{self, ShareSnapshot}, trash};
+use crate::payloads::communication_response::MessageWithContext;
+use crate::utils::file_models::{ChatMessage, ChatSession, ConversationMetadata, MessageRevision, ShareLink, TrashItem, UserConversations};
+#[cfg(feature = "ttl")]
+use crate::{helpers::retention::{self, PurgeRecord}, utils::file_models::RetentionPolicy};
+
+#[cfg(feature = "replication")]
+use crate::helpers::replication::{ReplicationOp, Replicator};
+
+// One shard per user under <root>/users/<user_id>.json, plus a sidebar index under
+// <root>/index/<user_id>.json. Every shard has its own lock, so users never wait on each other.
+const SHARD_DIR: &str = "users";
+const INDEX_DIR: &str = "index";
+// Shared snapshots under <root>/shares/<sha256(token)>.json, readable without knowing the owner
+const SHARE_DIR: &str = "shares";
+
+#[derive(Debug, Default, Serialize)]
+pub struct ErasedUserData {
+    pub conversations: usize,
+    pub messages: usize,
+    pub trash_items: usize,
+    pub share_links: usize,
+}
 
 #[derive(Clone)]
 pub struct MessageManager {
-    file_path: String,
-    file_lock: Arc<Mutex<()>>,
+    root_dir: PathBuf,
+    shard_locks: Arc<DashMap<String, Arc<Mutex<()>>>>,
+    cache: Option<Arc<MessageCache>>,
+    #[cfg(feature = "replication")]
+    replicator: Option<Arc<Replicator>>,
 }
 
-    impl MessageManager {
-        pub fn new(file_path: &str) -> Self {
-            Self {
-                file_path: file_path.to_string(),
-                file_lock: Arc::new(Mutex::new(())),
-            }
+impl MessageManager {
+    pub fn new(root_dir: &str) -> Self {
+        Self {
+            root_dir: PathBuf::from(root_dir),
+            shard_locks: Arc::new(DashMap::new()),
+            cache: None,
+            #[cfg(feature = "replication")]
+            replicator: None,
         }
+    }
+
+    // Serve reads from memory and batch writes into delayed flushes (see helpers::message_cache)
+    pub fn with_cache(mut self, config: CacheConfig) -> Self {
+        self.cache = Some(Arc::new(MessageCache::new(config)));
+        self
+    }
+
+    pub fn cache_metrics(&self) -> Option<CacheMetrics> {
+        self.cache.as_ref().map(|cache| cache.metrics())
+    }
+
+    #[cfg(feature = "replication")]
+    pub fn with_replicator(mut self, replicator: Arc<Replicator>) -> Self {
+        self.replicator = Some(replicator);
+        self
+    }
+
+    #[cfg(feature = "replication")]
+    pub fn replicator(&self) -> Option<Arc<Replicator>> {
+        self.replicator.clone()
+    }
 
-        // Main save_message function matching Python
-        pub async fn save_message(
+    // Main save_message function matching Python
+    pub async fn save_message(
         &self,
         user_id: &u64,
         chat_id: &str,
         message_data: ChatMessage,
     ) -> Result<ChatMessage> {
-        let _lock = self.file_lock.lock().await;
-
-        let mut messages_db = self.load_messages().await?;
-
         let user_key = user_id.to_string(); // Convert u64 → String
 
-        // Initialize user structure if not exists
-        if !messages_db.users.contains_key(&user_key) {
-            messages_db.users.insert(user_key.clone(), UserConversations {
-                conversations: HashMap::new(),
-            });
-        }
+        let _lock = self.lock_shard(&user_key).await;
 
-        let user_conversations = messages_db.users.get_mut(&user_key).unwrap();
+        let mut user_conversations = self.load_user(&user_key).await?;
 
         // Initialize chat structure if not exists
         if !user_conversations.conversations.contains_key(chat_id) {
@@ -52,11 +96,20 @@ pub struct MessageManager {
                 title: "New Chat".to_string(),
                 created_at: Utc::now(),
                 messages: Vec::new(),
+                expires_at: None,
+                pinned: false,
+                tags: Vec::new(),
+                folder: None,
+                archived: false,
+                imported: None,
             });
         }
 
         let chat_session = user_conversations.conversations.get_mut(chat_id).unwrap();
 
+        // Continuing an archived conversation brings it back to the sidebar
+        chat_session.archived = false;
+
         // If this is the first user message, set it as the chat title
         if message_data.role == "user" &&
         chat_session.messages.is_empty() &&
@@ -73,7 +126,7 @@ pub struct MessageManager {
         // Add message to the chat
         chat_session.messages.push(message_data.clone());
 
-        self.save_messages(&messages_db).await?;
+        self.commit(&user_key, &user_conversations).await?;
 
         Ok(message_data)
     }
@@ -86,33 +139,29 @@ pub struct MessageManager {
         message_id: &str,
         new_content: &str,
     ) -> Result<Option<ChatMessage>> {
-        let _lock = self.file_lock.lock().await;
-        
-        let mut messages_db = self.load_messages().await?;
-        
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+
         let mut result = None;
-        
-        if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            if let Some(chat) = user_conv.conversations.get_mut(chat_id) {
-                // Find and update the message
-                for message in &mut chat.messages {
-                    if message.message_id == message_id {
-                        message.content = new_content.to_string();
-                        message.edited = true;
-                        message.edit_timestamp = Some(Utc::now());
-                        
-                        result = Some(message.clone());
-                        break;
-                    }
+
+        if let Some(chat) = user_conv.conversations.get_mut(chat_id) {
+            // Find and update the message
+            for message in &mut chat.messages {
+                if message.message_id == message_id {
+                    revisions::record_edit(message, new_content, Utc::now(), None);
+
+                    result = Some(message.clone());
+                    break;
                 }
             }
         }
-        
+
         // Save only if we found and updated a message
         if result.is_some() {
-            self.save_messages(&messages_db).await?;
+            self.commit(user_id, &user_conv).await?;
         }
-        
+
         Ok(result)
     }
 
@@ -122,26 +171,17 @@ pub struct MessageManager {
         chat_id: &str,
         message_id: &str,
     ) -> Result<bool> {
-        let _lock = self.file_lock.lock().await;
-        
-        let mut messages_db = self.load_messages().await?;
-        
-        let mut deleted = false;
-        
-        if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            if let Some(chat) = user_conv.conversations.get_mut(chat_id) {
-                let original_len = chat.messages.len();
-                chat.messages.retain(|msg| msg.message_id != message_id);
-                
-                deleted = chat.messages.len() < original_len;
-            }
-        }
-        
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+
+        let deleted = trash::trash_message(&mut user_conv, chat_id, message_id, Utc::now()).is_some();
+
         // Save only if we deleted a message
         if deleted {
-            self.save_messages(&messages_db).await?;
+            self.commit(user_id, &user_conv).await?;
         }
-        
+
         Ok(deleted)
     }
 
@@ -151,29 +191,29 @@ pub struct MessageManager {
         chat_id: &str,
         message_id: &str,
     ) -> Result<usize> {
-        let _lock = self.file_lock.lock().await;
-        
-        let mut messages_db = self.load_messages().await?;
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
         let mut deleted_count = 0;
-        
-        if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            if let Some(chat) = user_conv.conversations.get_mut(chat_id) {
-                let original_messages = std::mem::take(&mut chat.messages);
-                
-                for message in original_messages {
-                    if message.message_id != message_id && 
-                    !(message.role == "ai" && message.reply_id.as_ref() == Some(&message_id.to_string())) {
-                        chat.messages.push(message);
-                    } else {
-                        deleted_count += 1;
-                    }
-                }
+
+        let doomed: Vec<String> = user_conv.conversations.get(chat_id)
+            .map(|chat| chat.messages.iter()
+                .filter(|message| message.message_id == message_id ||
+                    (message.role == "ai" && message.reply_id.as_deref() == Some(message_id)))
+                .map(|message| message.message_id.clone())
+                .collect())
+            .unwrap_or_default();
+
+        let now = Utc::now();
+        for doomed_id in doomed {
+            if trash::trash_message(&mut user_conv, chat_id, &doomed_id, now).is_some() {
+                deleted_count += 1;
             }
         }
         if deleted_count > 0 {
-            self.save_messages(&messages_db).await?;
+            self.commit(user_id, &user_conv).await?;
         }
-        
+
         Ok(deleted_count)
     }
 
@@ -182,44 +222,37 @@ pub struct MessageManager {
         user_id: &str,
         chat_id: Option<&str>,
     ) -> Result<Vec<ChatMessage>> {
-        let messages_db = self.load_messages().await?;
-        
-        if let Some(user_conv) = messages_db.users.get(user_id) {
-            let mut all_messages = Vec::new();
-            
-            for (cid, chat_data) in &user_conv.conversations {
-                if chat_id.is_none() || chat_id == Some(cid) {
-                    all_messages.extend(chat_data.messages.clone());
-                }
+        let user_conv = self.read_user(user_id).await?;
+        let mut all_messages = Vec::new();
+
+        for (cid, chat_data) in &user_conv.conversations {
+            if chat_id.is_none() || chat_id == Some(cid) {
+                all_messages.extend(chat_data.messages.clone());
             }
-            
-            all_messages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-            return Ok(all_messages);
         }
-        
-        Ok(Vec::new())
+
+        all_messages.sort_by_key(|m| m.timestamp);
+        Ok(all_messages)
     }
 
     pub async fn get_user_chats(&self, user_id: &str) -> Result<Vec<ChatSummary>> {
-        let messages_db = self.load_messages().await?;
+        let user_conv = self.read_user(user_id).await?;
         let mut chats = Vec::new();
-        
-        if let Some(user_conv) = messages_db.users.get(user_id) {
-            for (chat_id, chat_data) in &user_conv.conversations {
-                let last_message = chat_data.messages.iter()
-                    .max_by_key(|msg| msg.timestamp)
-                    .map(|msg| msg.timestamp);
-                
-                chats.push(ChatSummary {
-                    id: chat_id.clone(),
-                    title: chat_data.title.clone(),
-                    last_message,
-                    message_count: chat_data.messages.len(),
-                });
-            }
+
+        for (chat_id, chat_data) in &user_conv.conversations {
+            let last_message = chat_data.messages.iter()
+                .max_by_key(|msg| msg.timestamp)
+                .map(|msg| msg.timestamp);
+
+            chats.push(ChatSummary {
+                id: chat_id.clone(),
+                title: chat_data.title.clone(),
+                last_message,
+                message_count: chat_data.messages.len(),
+            });
         }
-        
-        chats.sort_by(|a, b| b.last_message.cmp(&a.last_message));
+
+        chats.sort_by_key(|chat| Reverse(chat.last_message));
         Ok(chats)
     }
 
@@ -230,111 +263,352 @@ pub struct MessageManager {
         user_message_id: &str,
         new_ai_content: &str,
     ) -> Result<Option<ChatMessage>> {
-        let _lock = self.file_lock.lock().await;
-        
-        let mut messages_db = self.load_messages().await?;
-        
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+
         let mut result = None;
-        
-        if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            if let Some(chat) = user_conv.conversations.get_mut(chat_id) {
-                // Find the AI message that replies to this user message
-                for message in &mut chat.messages {
-                    if message.role == "ai" && message.reply_id.as_ref() == Some(&user_message_id.to_string()) {
-                        message.content = new_ai_content.to_string();
-                        message.edited = true;
-                        message.edit_timestamp = Some(Utc::now());
-                        
-                        result = Some(message.clone());
-                        break;
-                    }
+
+        if let Some(chat) = user_conv.conversations.get_mut(chat_id) {
+            // Find the AI message that replies to this user message
+            for message in &mut chat.messages {
+                if message.role == "ai" && message.reply_id.as_ref() == Some(&user_message_id.to_string()) {
+                    revisions::record_edit(message, new_ai_content, Utc::now(), None);
+
+                    result = Some(message.clone());
+                    break;
                 }
             }
         }
         if result.is_some() {
-            self.save_messages(&messages_db).await?;
+            self.commit(user_id, &user_conv).await?;
         }
-        
+
         Ok(result)
     }
-    
+
     pub async fn delete_chat(&self, user_id: &str, chat_id: &str) -> Result<bool> {
-        let _lock = self.file_lock.lock().await;
-        
-        let mut messages_db = self.load_messages().await?;
-        
-        let mut deleted = false;
-        
-        if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            deleted = user_conv.conversations.remove(chat_id).is_some();
-        }
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+
+        let deleted = trash::trash_conversation(&mut user_conv, chat_id, Utc::now()).is_some();
         if deleted {
-            self.save_messages(&messages_db).await?;
+            self.commit(user_id, &user_conv).await?;
         }
-        
+
         Ok(deleted)
-}
+    }
 
     pub async fn update_chat_title(&self, user_id: &str, chat_id: &str, new_title: &str) -> Result<bool> {
-        let _lock = self.file_lock.lock().await;
-        
-        let mut messages_db = self.load_messages().await?;
-        
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+
         let mut updated = false;
-        
-        if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            if let Some(chat) = user_conv.conversations.get_mut(chat_id) {
-                chat.title = new_title.to_string();
-                updated = true;
-            }
+
+        if let Some(chat) = user_conv.conversations.get_mut(chat_id) {
+            chat.title = new_title.to_string();
+            updated = true;
         }
         if updated {
-            self.save_messages(&messages_db).await?;
+            self.commit(user_id, &user_conv).await?;
         }
-        
+
         Ok(updated)
     }
 
-    pub async fn delete_all_chats(&self, user_id: &str) -> Result<usize> {
-        let _lock = self.file_lock.lock().await;
-        
-        let mut messages_db = self.load_messages().await?;
-        
-        let chat_count = if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            let count = user_conv.conversations.len();
-            user_conv.conversations.clear();
-            count
-        } else {
-            0
+    // Pinning, tagging, filing and archiving all go through here; returns the updated sidebar entry
+    pub async fn organize_conversation(
+        &self,
+        user_id: &str,
+        chat_id: &str,
+        change: OrganizeChange,
+    ) -> Result<ConversationMetadata> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        let chat = user_conv.conversations.get_mut(chat_id)
+            .ok_or_else(|| anyhow!("Conversation not found: {}", chat_id))?;
+
+        change.apply(chat);
+        let updated = metadata(chat_id, chat);
+
+        self.commit(user_id, &user_conv).await?;
+        Ok(updated)
+    }
+
+    pub async fn rename_folder(&self, user_id: &str, from: &str, to: &str) -> Result<usize> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        let moved = organize::rename_folder(&mut user_conv, from, to);
+
+        if moved > 0 {
+            self.commit(user_id, &user_conv).await?;
+        }
+        Ok(moved)
+    }
+
+    pub async fn get_organization(&self, user_id: &str) -> Result<Organization> {
+        Ok(organize::organization(&self.read_user(user_id).await?))
+    }
+
+    pub async fn get_conversation(&self, user_id: &str, chat_id: &str) -> Result<Option<ChatSession>> {
+        Ok(self.read_user(user_id).await?.conversations.remove(chat_id))
+    }
+
+    // Every conversation including archived ones, oldest first
+    pub async fn get_all_conversations(&self, user_id: &str) -> Result<Vec<(String, ChatSession)>> {
+        let mut conversations: Vec<(String, ChatSession)> = self.read_user(user_id).await?
+            .conversations
+            .into_iter()
+            .collect();
+
+        conversations.sort_by_key(|(_, conversation)| conversation.created_at);
+        Ok(conversations)
+    }
+
+    pub async fn import_conversations(&self, user_id: &str, sessions: Vec<ChatSession>) -> Result<ImportReport> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        let report = import::merge(&mut user_conv, sessions);
+
+        if report.imported + report.updated > 0 {
+            self.commit(user_id, &user_conv).await?;
+        }
+        Ok(report)
+    }
+
+    // Publishes a snapshot of the conversation as it is now; later messages are not shared
+    pub async fn create_share(
+        &self,
+        user_id: &str,
+        chat_id: &str,
+        expires_in: Option<chrono::Duration>,
+    ) -> Result<ShareLink> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        let conversation = user_conv.conversations.get(chat_id)
+            .ok_or_else(|| anyhow!("Conversation not found: {}", chat_id))?;
+
+        let now = Utc::now();
+        let link = ShareLink {
+            share_id: Uuid::new_v4().to_string(),
+            token: share::new_token(),
+            conversation_id: chat_id.to_string(),
+            title: conversation.title.clone(),
+            created_at: now,
+            expires_at: expires_in.map(|duration| now + duration),
+        };
+        let snapshot = ShareSnapshot {
+            share_id: link.share_id.clone(),
+            owner_user_id: user_id.to_string(),
+            conversation_id: chat_id.to_string(),
+            created_at: now,
+            expires_at: link.expires_at,
+            conversation: share::snapshot(conversation),
+        };
+
+        self.store_share_snapshot(user_id, &link.token, &snapshot).await?;
+        user_conv.shares.push(link.clone());
+        self.commit(user_id, &user_conv).await?;
+        Ok(link)
+    }
+
+    pub async fn list_shares(&self, user_id: &str) -> Result<Vec<ShareLink>> {
+        let mut shares = self.read_user(user_id).await?.shares;
+        shares.sort_by_key(|share| Reverse(share.created_at));
+        Ok(shares)
+    }
+
+    // Returns false when the user has no share with that id
+    pub async fn revoke_share(&self, user_id: &str, share_id: &str) -> Result<bool> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        let Some(position) = user_conv.shares.iter().position(|link| link.share_id == share_id) else {
+            return Ok(false);
+        };
+
+        let link = user_conv.shares.remove(position);
+        self.drop_share_snapshot(user_id, &link.token).await?;
+        self.commit(user_id, &user_conv).await?;
+        Ok(true)
+    }
+
+    // Unauthenticated lookup; unknown, revoked and expired tokens all come back as None
+    pub async fn get_shared(&self, token: &str) -> Result<Option<ShareSnapshot>> {
+        if !share::is_well_formed(token) {
+            return Ok(None);
+        }
+
+        let snapshot: ShareSnapshot = match tokio::fs::read(self.share_path(token)?).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        if snapshot.is_expired(Utc::now()) {
+            self.remove_share_snapshot(token).await?;
+            return Ok(None);
+        }
+        Ok(Some(snapshot))
+    }
+
+    // "Continue this chat": copies a shared conversation into the viewer's own account
+    pub async fn fork_shared(&self, token: &str, user_id: &str) -> Result<ConversationMetadata> {
+        let snapshot = self.get_shared(token).await?
+            .ok_or_else(|| anyhow!("Share link not found or expired"))?;
+
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        let conversation_id = Uuid::new_v4().to_string();
+        let conversation = share::fork(&snapshot.conversation, Utc::now());
+        let forked = metadata(&conversation_id, &conversation);
+
+        user_conv.conversations.insert(conversation_id, conversation);
+        self.commit(user_id, &user_conv).await?;
+        Ok(forked)
+    }
+
+    // Raw copy of everything stored for the user, for the account data archive
+    pub async fn get_user_data(&self, user_id: &str) -> Result<UserConversations> {
+        self.read_user(user_id).await
+    }
+
+    // Account erasure: drops the shard, sidebar index, cached copy and share snapshots
+    pub async fn erase_user(&self, user_id: &str) -> Result<ErasedUserData> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let user_conv = self.load_user(user_id).await?;
+        let erased = ErasedUserData {
+            conversations: user_conv.conversations.len(),
+            messages: user_conv.conversations.values().map(|c| c.messages.len()).sum(),
+            trash_items: user_conv.trash.len(),
+            share_links: user_conv.shares.len(),
         };
+
+        for link in &user_conv.shares {
+            self.remove_share_snapshot(&link.token).await?;
+        }
+        if let Some(cache) = &self.cache {
+            cache.invalidate(user_id);
+        }
+        self.remove_shard(user_id).await?;
+
+        #[cfg(feature = "replication")]
+        if let Some(replicator) = &self.replicator {
+            replicator.record(ReplicationOp::DeleteUser { user_id: user_id.to_string() }).await?;
+        }
+
+        Ok(erased)
+    }
+
+    // Used to verify an erasure: true while any trace of the user is left in the store
+    pub async fn has_user_data(&self, user_id: &str) -> Result<bool> {
+        let cached = self.cache.as_ref().is_some_and(|cache| cache.contains(user_id));
+        Ok(cached || self.shard_path(user_id)?.exists() || self.index_path(user_id)?.exists())
+    }
+
+    // Every user with a shard on disk or pending in the cache
+    pub async fn user_ids(&self) -> Result<Vec<String>> {
+        self.list_user_ids().await
+    }
+
+    // A user's shard (including unflushed writes) and their share snapshots, read under the shard lock
+    pub async fn backup_user(&self, user_id: &str) -> Result<(UserConversations, Vec<ShareSnapshot>)> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let user_conv = self.load_user(user_id).await?;
+        let mut shares = Vec::new();
+        for link in &user_conv.shares {
+            match tokio::fs::read(self.share_path(&link.token)?).await {
+                Ok(bytes) => shares.push(serde_json::from_slice(&bytes)?),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok((user_conv, shares))
+    }
+
+    // Replaces everything stored for the user with a backed-up copy, or removes it for None.
+    // Share snapshots follow the restored links, so revoked-since links come back and newer ones go.
+    pub async fn restore_user(&self, user_id: &str, backup: Option<(UserConversations, Vec<ShareSnapshot>)>) -> Result<()> {
+        let _lock = self.lock_shard(user_id).await;
+
+        // A corrupt shard is exactly what a restore is for, so it must not stop one
+        let current_links = self.load_user(user_id).await.map(|c| c.shares).unwrap_or_default();
+        for link in &current_links {
+            self.drop_share_snapshot(user_id, &link.token).await?;
+        }
+        if let Some(cache) = &self.cache {
+            cache.invalidate(user_id);
+        }
+
+        let Some((user_conv, shares)) = backup else {
+            self.remove_shard(user_id).await?;
+
+            #[cfg(feature = "replication")]
+            if let Some(replicator) = &self.replicator {
+                replicator.record(ReplicationOp::DeleteUser { user_id: user_id.to_string() }).await?;
+            }
+            return Ok(());
+        };
+
+        self.write_shard(user_id, &user_conv).await?;
+        for snapshot in &shares {
+            if let Some(link) = user_conv.shares.iter().find(|link| link.share_id == snapshot.share_id) {
+                self.store_share_snapshot(user_id, &link.token, snapshot).await?;
+            }
+        }
+
+        #[cfg(feature = "replication")]
+        if let Some(replicator) = &self.replicator {
+            replicator.record(ReplicationOp::PutUser { user_id: user_id.to_string(), data: user_conv }).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_all_chats(&self, user_id: &str) -> Result<usize> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+
+        let now = Utc::now();
+        let chat_ids: Vec<String> = user_conv.conversations.keys().cloned().collect();
+        let chat_count = chat_ids.len();
+        for chat_id in chat_ids {
+            trash::trash_conversation(&mut user_conv, &chat_id, now);
+        }
         if chat_count > 0 {
-            self.save_messages(&messages_db).await?;
+            self.commit(user_id, &user_conv).await?;
         }
-        
+
         Ok(chat_count)
     }
 
     pub async fn delete_multiple_chats(&self, user_id: &str, chat_ids: &[String]) -> Result<usize> {
-        let _lock = self.file_lock.lock().await;
-        
-        let mut messages_db = self.load_messages().await?;
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
         let mut deleted_count = 0;
-        
-        if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            let chat_set: std::collections::HashSet<_> = chat_ids.iter().collect();
-            let original_keys: Vec<String> = user_conv.conversations.keys().cloned().collect();
-            
-            for chat_id in original_keys {
-                if chat_set.contains(&chat_id) {
-                    user_conv.conversations.remove(&chat_id);
-                    deleted_count += 1;
-                }
+
+        let now = Utc::now();
+        for chat_id in chat_ids {
+            if trash::trash_conversation(&mut user_conv, chat_id, now).is_some() {
+                deleted_count += 1;
             }
         }
         if deleted_count > 0 {
-            self.save_messages(&messages_db).await?;
+            self.commit(user_id, &user_conv).await?;
         }
-        
+
         Ok(deleted_count)
     }
 
@@ -342,128 +616,466 @@ pub struct MessageManager {
         self.delete_chat(user_id, session_id).await
     }
 
-    // Private helper methods
-    async fn load_messages(&self) -> Result<MessageData> {
-        let path = std::path::Path::new(&self.file_path);
-        
+    #[cfg(feature = "ttl")]
+    pub async fn set_conversation_ttl(
+        &self,
+        user_id: &str,
+        chat_id: &str,
+        ttl_seconds: Option<u64>,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+
+        let chat = user_conv.conversations.get_mut(chat_id)
+            .ok_or_else(|| anyhow!("Conversation not found: {}", chat_id))?;
+
+        chat.expires_at = ttl_seconds.map(|secs| Utc::now() + chrono::Duration::seconds(secs as i64));
+        let expires_at = chat.expires_at;
+
+        self.commit(user_id, &user_conv).await?;
+        Ok(expires_at)
+    }
+
+    #[cfg(feature = "ttl")]
+    pub async fn get_retention_policy(&self, user_id: &str) -> Result<Option<RetentionPolicy>> {
+        Ok(self.read_user(user_id).await?.retention)
+    }
+
+    #[cfg(feature = "ttl")]
+    pub async fn set_retention_policy(&self, user_id: &str, policy: Option<RetentionPolicy>) -> Result<()> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        user_conv.retention = policy;
+
+        self.commit(user_id, &user_conv).await
+    }
+
+    // Removes every conversation the effective policy marks as expired and reports what went.
+    // Shards are swept one at a time so the sweep never blocks the whole store.
+    #[cfg(feature = "ttl")]
+    pub async fn purge_expired(
+        &self,
+        default_policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<PurgeRecord>> {
+        let mut purged = Vec::new();
+
+        for user_id in self.list_user_ids().await? {
+            let _lock = self.lock_shard(&user_id).await;
+
+            let mut user_conv = self.load_user(&user_id).await?;
+            let policy = retention::effective_policy(default_policy, user_conv.retention.as_ref());
+            let before = purged.len();
+
+            for (conversation_id, reason) in retention::select_expired(&policy, &user_conv.conversations, now) {
+                if let Some(conversation) = user_conv.conversations.remove(&conversation_id) {
+                    purged.push(PurgeRecord {
+                        user_id: user_id.clone(),
+                        conversation_id,
+                        message_count: conversation.messages.len(),
+                        reason,
+                        last_activity: retention::last_activity(&conversation),
+                        purged_at: now,
+                    });
+                }
+            }
+
+            if purged.len() > before {
+                self.commit(&user_id, &user_conv).await?;
+            }
+        }
+
+        Ok(purged)
+    }
+
+    // Applies a change that originated on another instance; it is not fed back to the replicator
+    #[cfg(feature = "replication")]
+    pub async fn apply_replicated(&self, op: &ReplicationOp) -> Result<()> {
+        let _lock = self.lock_shard(op.user_id()).await;
+        self.apply_op(op).await
+    }
+
+    // Leader side of a follower write: apply and append to the log under the same lock
+    #[cfg(feature = "replication")]
+    pub async fn apply_forwarded(&self, op: ReplicationOp, origin: &str) -> Result<u64> {
+        let replicator = self.replicator.clone()
+            .ok_or_else(|| anyhow!("Replication is not configured"))?;
+
+        let _lock = self.lock_shard(op.user_id()).await;
+
+        self.apply_op(&op).await?;
+        replicator.append(origin, op).await
+    }
+
+    #[cfg(feature = "replication")]
+    pub async fn snapshot_users(&self) -> Result<Vec<ReplicationOp>> {
+        let mut ops = Vec::new();
+
+        for user_id in self.list_user_ids().await? {
+            let _lock = self.lock_shard(&user_id).await;
+            let data = self.load_user(&user_id).await?;
+            for link in &data.shares {
+                match tokio::fs::read(self.share_path(&link.token)?).await {
+                    Ok(bytes) => ops.push(ReplicationOp::PutShare {
+                        user_id: user_id.clone(),
+                        token_hash: share::token_hash(&link.token),
+                        snapshot: serde_json::from_slice(&bytes)?,
+                    }),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            ops.push(ReplicationOp::PutUser { user_id, data });
+        }
+
+        Ok(ops)
+    }
+
+    // Replicated state supersedes anything still pending locally for that user
+    #[cfg(feature = "replication")]
+    async fn apply_op(&self, op: &ReplicationOp) -> Result<()> {
+        match op {
+            ReplicationOp::PutUser { user_id, data } => {
+                if let Some(cache) = &self.cache {
+                    cache.invalidate(user_id);
+                }
+                self.write_shard(user_id, data).await
+            }
+            ReplicationOp::DeleteUser { user_id } => {
+                if let Some(cache) = &self.cache {
+                    cache.invalidate(user_id);
+                }
+                // The erasure on the origin took the snapshots of these links with it
+                let links = self.load_user(user_id).await.map(|c| c.shares).unwrap_or_default();
+                for link in &links {
+                    self.remove_share_snapshot(&link.token).await?;
+                }
+                if let Some(cache) = &self.cache {
+                    cache.invalidate(user_id);
+                }
+                self.remove_shard(user_id).await
+            }
+            ReplicationOp::PutShare { token_hash, snapshot, .. } => {
+                write_atomic(&self.shard_file(SHARE_DIR, token_hash)?, &serde_json::to_vec_pretty(snapshot)?).await
+            }
+            ReplicationOp::DeleteShare { token_hash, .. } => self.remove_share_file(token_hash).await,
+        }
+    }
+
+    pub fn user_connected(&self, user_id: &str) {
+        if let Some(cache) = &self.cache {
+            cache.connected(user_id);
+        }
+    }
+
+    // Once a user's last connection closes, their pending writes are flushed and the entry dropped
+    pub async fn user_disconnected(&self, user_id: &str) -> Result<()> {
+        let Some(cache) = &self.cache else {
+            return Ok(());
+        };
+        if !cache.disconnected(user_id) {
+            return Ok(());
+        }
+
+        let _lock = self.lock_shard(user_id).await;
+        self.flush_user(cache, user_id).await?;
+        cache.invalidate(user_id);
+        Ok(())
+    }
+
+    // Writes out cached shards whose flush delay has passed; `force` flushes everything pending
+    pub async fn flush_cache(&self, force: bool) -> Result<usize> {
+        let Some(cache) = &self.cache else {
+            return Ok(0);
+        };
+
+        let mut flushed = 0;
+        let mut last_error = None;
+
+        for user_id in cache.due_for_flush(force) {
+            let _lock = self.lock_shard(&user_id).await;
+            match self.flush_user(cache, &user_id).await {
+                Ok(true) => flushed += 1,
+                Ok(false) => {}
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        match last_error {
+            Some(e) => Err(e),
+            None => Ok(flushed),
+        }
+    }
+
+    // Splits a pre-sharding messages.json into per-user shards. Users that already have a
+    // shard are left alone, and the old file is renamed so this only ever runs once.
+    pub async fn migrate_legacy_file(&self, legacy_path: &str) -> Result<usize> {
+        let path = Path::new(legacy_path);
         if !path.exists() {
-            return Ok(MessageData {
-                users: HashMap::new(),
-            });
+            return Ok(0);
         }
-        
+
         let content = tokio::fs::read_to_string(path).await?;
-        
-        // Try to parse as the new structure first
-        match serde_json::from_str::<MessageData>(&content) {
-            Ok(message_data) => Ok(message_data),
-            Err(_) => {
-                Ok(MessageData {
-                    users: HashMap::new(),
-                })
+        let legacy: serde_json::Value = if content.trim().is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::from_str(&content)
+                .map_err(|e| anyhow!("Failed to parse {}: {}", legacy_path, e))?
+        };
+
+        let users: HashMap<String, UserConversations> = match legacy.get("users") {
+            Some(users) => serde_json::from_value(users.clone())
+                .map_err(|e| anyhow!("Unexpected user data in {}: {}", legacy_path, e))?,
+            None => HashMap::new(),
+        };
+
+        let mut migrated = 0;
+        for (user_id, data) in users {
+            let _lock = self.lock_shard(&user_id).await;
+            if self.shard_path(&user_id)?.exists() {
+                continue;
             }
+            self.write_shard(&user_id, &data).await?;
+            migrated += 1;
         }
+
+        tokio::fs::rename(path, format!("{}.migrated", legacy_path)).await?;
+        Ok(migrated)
     }
 
-    async fn save_messages(&self, message_data: &MessageData) -> Result<()> {
-        let path = std::path::Path::new(&self.file_path);
-        
-        // Create parent directories if they don't exist
-        if let Some(parent) = path.parent() {
-            tokio::fs::create_dir_all(parent).await?;
+    // Private helper methods
+    async fn flush_user(&self, cache: &MessageCache, user_id: &str) -> Result<bool> {
+        let Some(data) = cache.take_dirty(user_id) else {
+            return Ok(false);
+        };
+
+        match self.write_shard(user_id, &data).await {
+            Ok(()) => {
+                cache.flush_succeeded();
+                Ok(true)
+            }
+            Err(e) => {
+                cache.flush_failed(user_id);
+                Err(anyhow!("Failed to flush shard for user {}: {}", user_id, e))
+            }
         }
-        
-        let file = std::fs::File::create(path)?;
-        let writer = std::io::BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, message_data)?;
+    }
+
+    async fn lock_shard(&self, user_id: &str) -> OwnedMutexGuard<()> {
+        let lock = self.shard_locks
+            .entry(user_id.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(())))
+            .clone();
+        lock.lock_owned().await
+    }
+
+    // User ids become file names, so anything that could escape the store is rejected
+    fn shard_file(&self, dir: &str, user_id: &str) -> Result<PathBuf> {
+        let valid = !user_id.is_empty()
+            && user_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(anyhow!("Invalid user id for message store: {:?}", user_id));
+        }
+        Ok(self.root_dir.join(dir).join(format!("{}.json", user_id)))
+    }
+
+    fn shard_path(&self, user_id: &str) -> Result<PathBuf> {
+        self.shard_file(SHARD_DIR, user_id)
+    }
+
+    fn index_path(&self, user_id: &str) -> Result<PathBuf> {
+        self.shard_file(INDEX_DIR, user_id)
+    }
+
+    fn share_path(&self, token: &str) -> Result<PathBuf> {
+        self.shard_file(SHARE_DIR, &share::token_hash(token))
+    }
+
+    async fn remove_share_snapshot(&self, token: &str) -> Result<()> {
+        self.remove_share_file(&share::token_hash(token)).await
+    }
+
+    async fn remove_share_file(&self, token_hash: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.shard_file(SHARE_DIR, token_hash)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Share snapshots live outside the shard, so they are replicated as ops of their own
+    async fn store_share_snapshot(&self, user_id: &str, token: &str, snapshot: &ShareSnapshot) -> Result<()> {
+        write_atomic(&self.share_path(token)?, &serde_json::to_vec_pretty(snapshot)?).await?;
+
+        #[cfg(feature = "replication")]
+        if let Some(replicator) = &self.replicator {
+            replicator.record(ReplicationOp::PutShare {
+                user_id: user_id.to_string(),
+                token_hash: share::token_hash(token),
+                snapshot: Box::new(snapshot.clone()),
+            }).await?;
+        }
+
         Ok(())
     }
 
-    pub async fn get_all_conversation_titles(&self, user_id: u64) -> anyhow::Result<Vec<ConversationMetadata>> {
-        const MAX_RETRIES: usize = 5;
-        const RETRY_DELAY_MS: u64 = 100;
-        
-        for attempt in 0..MAX_RETRIES {
-            // Check if file exists and has content
-            if let Ok(metadata) = tokio::fs::metadata("messages.json").await {
-                if metadata.len() == 0 {
-                    if attempt == MAX_RETRIES - 1 {
-                        return Ok(Vec::new()); // Return empty list instead of error
-                    }
-                    tokio::time::sleep(tokio::time::Duration::from_millis(RETRY_DELAY_MS)).await;
-                    continue;
-                }
+    async fn drop_share_snapshot(&self, user_id: &str, token: &str) -> Result<()> {
+        self.remove_share_snapshot(token).await?;
+
+        #[cfg(feature = "replication")]
+        if let Some(replicator) = &self.replicator {
+            replicator.record(ReplicationOp::DeleteShare {
+                user_id: user_id.to_string(),
+                token_hash: share::token_hash(token),
+            }).await?;
+        }
+
+        Ok(())
+    }
+
+    // Read-only access; only a cache miss takes the shard lock
+    async fn read_user(&self, user_id: &str) -> Result<UserConversations> {
+        if let Some(data) = self.cache.as_ref().and_then(|cache| cache.get(user_id)) {
+            return Ok(data);
+        }
+
+        let _lock = self.lock_shard(user_id).await;
+        self.load_user(user_id).await
+    }
+
+    // Callers hold the shard lock, so a shard loaded from disk can safely be cached
+    async fn load_user(&self, user_id: &str) -> Result<UserConversations> {
+        if let Some(data) = self.cache.as_ref().and_then(|cache| cache.get(user_id)) {
+            return Ok(data);
+        }
+
+        if let Some(cache) = &self.cache {
+            cache.record_miss();
+        }
+
+        let path = self.shard_path(user_id)?;
+
+        let data = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| anyhow!("Failed to parse shard for user {}: {}", user_id, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => UserConversations::default(),
+            Err(e) => return Err(anyhow!("Failed to read shard for user {}: {}", user_id, e)),
+        };
+
+        if let Some(cache) = &self.cache {
+            cache.insert_clean(user_id, data.clone());
+        }
+        Ok(data)
+    }
+
+    async fn list_user_ids(&self) -> Result<Vec<String>> {
+        let mut user_ids = Vec::new();
+
+        let mut entries = match tokio::fs::read_dir(self.root_dir.join(SHARD_DIR)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(user_ids),
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
             }
-            
-            match tokio::fs::read_to_string("messages.json").await {
-                Ok(data_str) if data_str.trim().is_empty() => {
-                    if attempt == MAX_RETRIES - 1 {
-                        return Ok(Vec::new()); // Empty file, return empty list
-                    }
-                    tokio::time::sleep(tokio::time::Duration::from_millis(RETRY_DELAY_MS)).await;
-                    continue;
-                }
-                Ok(data_str) => {
-                    // Try to parse the JSON
-                    match serde_json::from_str::<Value>(&data_str) {
-                        Ok(data) => {
-                            let mut conversations = Vec::new();
-                            
-                            if let Some(users) = data.get("users") {
-                                let user_key = user_id.to_string();
-                                if let Some(user_data) = users.get(&user_key) {
-                                    if let Some(conversations_obj) = user_data.get("conversations") {
-                                        if let Some(conversations_map) = conversations_obj.as_object() {
-                                            for (conversation_id, conversation_data) in conversations_map {
-                                                let title = conversation_data.get("title")
-                                                    .and_then(|t| t.as_str())
-                                                    .ok_or_else(|| anyhow::anyhow!("Missing title for conversation {}", conversation_id))?;
-                                                
-                                                let created_at_str = conversation_data.get("created_at")
-                                                    .and_then(|c| c.as_str())
-                                                    .ok_or_else(|| anyhow::anyhow!("Missing created_at for conversation {}", conversation_id))?;
-                                                
-                                                let created_at = DateTime::parse_from_rfc3339(created_at_str)?
-                                                    .with_timezone(&Utc);
-                                                
-                                                conversations.push(ConversationMetadata {
-                                                    id: conversation_id.clone(),
-                                                    title: title.to_string(),
-                                                    created_at,
-                                                });
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            
-                            conversations.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-                            return Ok(conversations);
-                        }
-                        Err(e) if e.is_eof() => {
-                            // EOF error, retry
-                            if attempt == MAX_RETRIES - 1 {
-                                return Err(anyhow::anyhow!("Failed to parse messages.json after {} attempts: {}", MAX_RETRIES, e));
-                            }
-                            tokio::time::sleep(tokio::time::Duration::from_millis(RETRY_DELAY_MS)).await;
-                            continue;
-                        }
-                        Err(e) => {
-                            return Err(anyhow::anyhow!("Failed to parse messages.json: {}", e));
-                        }
-                    }
+            if let Some(user_id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                user_ids.push(user_id.to_string());
+            }
+        }
+
+        // Users created since the last flush only exist in the cache so far
+        if let Some(cache) = &self.cache {
+            for user_id in cache.user_ids() {
+                if !user_ids.contains(&user_id) {
+                    user_ids.push(user_id);
                 }
-                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                    // File doesn't exist, return empty list
+            }
+        }
+
+        Ok(user_ids)
+    }
+
+    // Persists the shard (or queues it for the flusher) and hands it to the replicator, if one is attached
+    async fn commit(&self, user_id: &str, user_conv: &UserConversations) -> Result<()> {
+        match &self.cache {
+            Some(cache) => cache.insert_dirty(user_id, user_conv.clone()),
+            None => self.write_shard(user_id, user_conv).await?,
+        }
+
+        #[cfg(feature = "replication")]
+        if let Some(replicator) = &self.replicator {
+            replicator.record(ReplicationOp::PutUser {
+                user_id: user_id.to_string(),
+                data: user_conv.clone(),
+            }).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn write_shard(&self, user_id: &str, user_conv: &UserConversations) -> Result<()> {
+        write_atomic(&self.shard_path(user_id)?, &serde_json::to_vec_pretty(user_conv)?).await?;
+
+        let index = build_index(user_conv);
+        write_atomic(&self.index_path(user_id)?, &serde_json::to_vec_pretty(&index)?).await
+    }
+
+    async fn remove_shard(&self, user_id: &str) -> Result<()> {
+        for path in [self.shard_path(user_id)?, self.index_path(user_id)?] {
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    // Sidebar listing; archived conversations are left out
+    pub async fn get_all_conversation_titles(&self, user_id: u64) -> Result<Vec<ConversationMetadata>> {
+        let mut index = self.conversation_index(user_id).await?;
+        index.retain(|conv| !conv.archived);
+        Ok(index)
+    }
+
+    pub async fn get_archived_conversation_titles(&self, user_id: u64) -> Result<Vec<ConversationMetadata>> {
+        let mut index = self.conversation_index(user_id).await?;
+        index.retain(|conv| conv.archived);
+        Ok(index)
+    }
+
+    // Every conversation straight from the index file; falls back to the shard if the index is missing.
+    // A cached user may have writes the index does not reflect yet, so it is built from memory.
+    async fn conversation_index(&self, user_id: u64) -> Result<Vec<ConversationMetadata>> {
+        let user_key = user_id.to_string();
+
+        if let Some(user_conv) = self.cache.as_ref().and_then(|cache| cache.get(&user_key)) {
+            return Ok(build_index(&user_conv));
+        }
+
+        match tokio::fs::read_to_string(self.index_path(&user_key)?).await {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| anyhow!("Failed to parse sidebar index for user {}: {}", user_id, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let _lock = self.lock_shard(&user_key).await;
+                let cached = self.cache.as_ref().is_some_and(|cache| cache.contains(&user_key));
+                if !cached && !self.shard_path(&user_key)?.exists() {
                     return Ok(Vec::new());
                 }
-                Err(e) => {
-                    return Err(anyhow::anyhow!("Failed to read messages.json: {}", e));
-                }
+
+                let user_conv = self.load_user(&user_key).await?;
+                let index = build_index(&user_conv);
+                write_atomic(&self.index_path(&user_key)?, &serde_json::to_vec_pretty(&index)?).await?;
+                Ok(index)
             }
+            Err(e) => Err(anyhow!("Failed to read sidebar index for user {}: {}", user_id, e)),
         }
-        
-        Ok(Vec::new()) // Fallback: return empty list
     }
 
     pub async fn edit_message_content(
@@ -472,21 +1084,19 @@ pub struct MessageManager {
         message_id: &str,
         new_content: &str,
     ) -> Result<(), Box<dyn Error + Send>> {
-        let mut messages_db = self.load_messages().await?;
-        
-        if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            for conversation in user_conv.conversations.values_mut() {
-                if let Some(message) = conversation.messages.iter_mut().find(|m| m.message_id == message_id) {
-                    message.content = new_content.to_string();
-                    message.edited = true;
-                    message.edit_timestamp = Some(Utc::now());
-                    
-                    self.save_messages(&messages_db).await?;
-                    return Ok(());
-                }
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+
+        for conversation in user_conv.conversations.values_mut() {
+            if let Some(message) = conversation.messages.iter_mut().find(|m| m.message_id == message_id) {
+                revisions::record_edit(message, new_content, Utc::now(), None);
+
+                self.commit(user_id, &user_conv).await?;
+                return Ok(());
             }
         }
-        
+
         Err(anyhow::anyhow!("Message not found: {}", message_id).into())
     }
 
@@ -496,131 +1106,278 @@ pub struct MessageManager {
         target_id: &str,
         new_title: &str,
     ) -> Result<String, Box<dyn Error + Send>> {
-        let mut messages_db = self.load_messages().await?;
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
         let mut result: Option<String> = None;
 
-        if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            // Direct conversation ID match
-            if let Some(conversation) = user_conv.conversations.get_mut(target_id) {
-                let title = if new_title.len() > 30 {
-                    format!("{}...", &new_title[..30])
-                } else {
-                    new_title.to_string()
-                };
-                conversation.title = title.clone();
-                result = Some(title);
+        // Direct conversation ID match
+        if let Some(conversation) = user_conv.conversations.get_mut(target_id) {
+            let title = if new_title.len() > 30 {
+                format!("{}...", &new_title[..30])
             } else {
-                // If target_id is a message ID, find its conversation
-                for (_conv_id, conversation) in user_conv.conversations.iter_mut() {
-                    if conversation.messages.iter().any(|m| m.message_id == target_id) {
-                        let title = if new_title.len() > 30 {
-                            format!("{}...", &new_title[..30])
-                        } else {
-                            new_title.to_string()
-                        };
-                        conversation.title = title.clone();
-                        result = Some(title);
-                        break;
-                    }
+                new_title.to_string()
+            };
+            conversation.title = title.clone();
+            result = Some(title);
+        } else {
+            // If target_id is a message ID, find its conversation
+            for (_conv_id, conversation) in user_conv.conversations.iter_mut() {
+                if conversation.messages.iter().any(|m| m.message_id == target_id) {
+                    let title = if new_title.len() > 30 {
+                        format!("{}...", &new_title[..30])
+                    } else {
+                        new_title.to_string()
+                    };
+                    conversation.title = title.clone();
+                    result = Some(title);
+                    break;
                 }
             }
         }
 
         if let Some(conversation_title) = result {
-            self.save_messages(&messages_db).await?;
+            self.commit(user_id, &user_conv).await?;
             return Ok(conversation_title);
         }
 
         Err(anyhow::anyhow!("Target not found: {}", target_id).into())
     }
 
+    // Deletes are soft: conversations and messages go to the user's trash and can be restored
     pub async fn delete_by_id(
         &self,
         user_id: &str,
         target_id: &str,
     ) -> Result<DeleteResult, Box<dyn Error + Send>> {
-        let mut messages_db = self.load_messages().await?;
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        let now = Utc::now();
         let mut result: Option<DeleteResult> = None;
-        
-        if let Some(user_conv) = messages_db.users.get_mut(user_id) {
-            // First, check if it's a conversation ID
-            if let Some(conversation) = user_conv.conversations.get(target_id) {
-                let title = conversation.title.clone();
-                user_conv.conversations.remove(target_id);
-                
-                result = Some(DeleteResult {
-                    deleted_type: "conversation".to_string(),
-                    target_id: target_id.to_string(),
-                    title: Some(title.clone()),
-                    message: format!("Deleted conversation: {}", title),
-                });
-            } else {
-                // Search for message ID across all conversations
-                let mut found_conv_id: Option<String> = None;
-                let mut found_title: Option<String> = None;
-                let mut should_remove_conversation = false;
-                
-                for (conv_id, conversation) in user_conv.conversations.iter_mut() {
-                    if let Some(index) = conversation.messages.iter().position(|m| m.message_id == target_id) {
-                        found_title = Some(conversation.title.clone());
-                        conversation.messages.remove(index);
-                        
-                        // Check if conversation is now empty
-                        should_remove_conversation = conversation.messages.is_empty();
-                        found_conv_id = Some(conv_id.clone());
-                        break;
-                    }
-                }
-                
-                if let (Some(conv_id), Some(title)) = (found_conv_id, found_title) {
-                    if should_remove_conversation {
-                        user_conv.conversations.remove(&conv_id);
-                        result = Some(DeleteResult {
-                            deleted_type: "message_and_conversation".to_string(),
-                            target_id: target_id.to_string(),
-                            title: Some(title.clone()),
-                            message: format!("Deleted message and empty conversation: {}", title),
-                        });
-                    } else {
-                        result = Some(DeleteResult {
-                            deleted_type: "message".to_string(),
-                            target_id: target_id.to_string(),
-                            title: Some(title.clone()),
-                            message: format!("Deleted message from conversation: {}", title),
-                        });
-                    }
+
+        // First, check if it's a conversation ID
+        if let Some(title) = user_conv.conversations.get(target_id).map(|c| c.title.clone()) {
+            let trash_id = trash::trash_conversation(&mut user_conv, target_id, now);
+
+            result = Some(DeleteResult {
+                deleted_type: "conversation".to_string(),
+                target_id: target_id.to_string(),
+                title: Some(title.clone()),
+                message: format!("Moved conversation to trash: {}", title),
+                trash_id,
+            });
+        } else {
+            // Search for message ID across all conversations
+            let found = user_conv.conversations.iter()
+                .find(|(_, conversation)| conversation.messages.iter().any(|m| m.message_id == target_id))
+                .map(|(conv_id, conversation)| (conv_id.clone(), conversation.title.clone(), conversation.messages.len() == 1));
+
+            if let Some((conv_id, title, is_last_message)) = found {
+                if is_last_message {
+                    // The conversation goes to the trash as a whole so restoring it brings the message back too
+                    let trash_id = trash::trash_conversation(&mut user_conv, &conv_id, now);
+                    result = Some(DeleteResult {
+                        deleted_type: "message_and_conversation".to_string(),
+                        target_id: target_id.to_string(),
+                        title: Some(title.clone()),
+                        message: format!("Moved last message and its conversation to trash: {}", title),
+                        trash_id,
+                    });
+                } else {
+                    let trash_id = trash::trash_message(&mut user_conv, &conv_id, target_id, now);
+                    result = Some(DeleteResult {
+                        deleted_type: "message".to_string(),
+                        target_id: target_id.to_string(),
+                        title: Some(title.clone()),
+                        message: format!("Moved message to trash from conversation: {}", title),
+                        trash_id,
+                    });
                 }
             }
         }
-        
+
         if let Some(delete_result) = result {
-            self.save_messages(&messages_db).await?;
+            self.commit(user_id, &user_conv).await?;
             return Ok(delete_result);
         }
-        
+
         Err(anyhow::anyhow!("Target not found: {}", target_id).into())
     }
 
+    // Newest deletions first
+    pub async fn list_trash(&self, user_id: &str) -> Result<Vec<TrashItem>> {
+        let mut items = self.read_user(user_id).await?.trash;
+        items.sort_by_key(|item| Reverse(item.deleted_at));
+        Ok(items)
+    }
+
+    pub async fn restore_from_trash(&self, user_id: &str, trash_id: &str) -> Result<TrashItem> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        let item = trash::restore(&mut user_conv, trash_id)?;
+
+        self.commit(user_id, &user_conv).await?;
+        Ok(item)
+    }
+
+    pub async fn empty_trash(&self, user_id: &str) -> Result<usize> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        let purged = user_conv.trash.len();
+        user_conv.trash.clear();
+
+        if purged > 0 {
+            self.commit(user_id, &user_conv).await?;
+        }
+        Ok(purged)
+    }
+
+    // Permanently removes trash items deleted before the cutoff, one shard at a time
+    pub async fn purge_trash(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        let mut purged = 0;
+
+        for user_id in self.list_user_ids().await? {
+            let _lock = self.lock_shard(&user_id).await;
+
+            let mut user_conv = self.load_user(&user_id).await?;
+            let count = trash::purge_before(&mut user_conv, cutoff);
+            if count > 0 {
+                self.commit(&user_id, &user_conv).await?;
+                purged += count;
+            }
+        }
+
+        Ok(purged)
+    }
+
+    pub async fn list_message_revisions(&self, user_id: &str, message_id: &str) -> Result<Vec<MessageRevision>> {
+        let user_conv = self.read_user(user_id).await?;
+        let message = find_message(&user_conv, message_id)
+            .ok_or_else(|| anyhow!("Message not found: {}", message_id))?;
+
+        Ok(revisions::history(message))
+    }
+
+    pub async fn diff_message_revisions(
+        &self,
+        user_id: &str,
+        message_id: &str,
+        from_revision: u32,
+        to_revision: u32,
+    ) -> Result<Vec<DiffSegment>> {
+        let user_conv = self.read_user(user_id).await?;
+        let message = find_message(&user_conv, message_id)
+            .ok_or_else(|| anyhow!("Message not found: {}", message_id))?;
+
+        let from = revisions::find(message, from_revision)?;
+        let to = revisions::find(message, to_revision)?;
+        Ok(revisions::diff_words(&from.content, &to.content))
+    }
+
+    // Reverting never rewrites history: the old content comes back as a new revision
+    pub async fn revert_message(&self, user_id: &str, message_id: &str, revision: u32) -> Result<(ChatMessage, u32)> {
+        let _lock = self.lock_shard(user_id).await;
+
+        let mut user_conv = self.load_user(user_id).await?;
+        let message = user_conv.conversations.values_mut()
+            .flat_map(|conversation| conversation.messages.iter_mut())
+            .find(|m| m.message_id == message_id)
+            .ok_or_else(|| anyhow!("Message not found: {}", message_id))?;
+
+        let target = revisions::find(message, revision)?;
+        let new_revision = revisions::record_edit(message, &target.content, Utc::now(), Some(revision));
+        let message = message.clone();
+
+        self.commit(user_id, &user_conv).await?;
+        Ok((message, new_revision))
+    }
+
+    // Case-insensitive match on message content across the user's conversations
+    pub async fn search_messages(
+        &self,
+        user_id: &str,
+        query: &str,
+        include_archived: bool,
+    ) -> Result<Vec<MessageWithContext>> {
+        let user_conv = self.read_user(user_id).await?;
+        let needle = query.to_lowercase();
+
+        Ok(user_conv.conversations.iter()
+            .filter(|(_, conversation)| include_archived || !conversation.archived)
+            .flat_map(|(conversation_id, conversation)| {
+                conversation.messages.iter()
+                    .filter(|message| message.content.to_lowercase().contains(&needle))
+                    .map(move |message| MessageWithContext {
+                        message: message.clone(),
+                        conversation_id: conversation_id.clone(),
+                        conversation_title: conversation.title.clone(),
+                        created_at: conversation.created_at,
+                        archived: conversation.archived,
+                    })
+            })
+            .collect())
+    }
+
     pub async fn get_all_user_messages(
         &self,
         user_id: &str,
     ) -> Result<Vec<ChatMessage>, Box<dyn Error + Send>> {
-        let messages_db = self.load_messages().await?;
+        let user_conv = self.read_user(user_id).await?;
         let mut all_messages = Vec::new();
-        
-        if let Some(user_conv) = messages_db.users.get(user_id) {
-            for conversation in user_conv.conversations.values() {
-                all_messages.extend(conversation.messages.clone());
-            }
+
+        for conversation in user_conv.conversations.values() {
+            all_messages.extend(conversation.messages.clone());
         }
-        
-        all_messages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-        
+
+        all_messages.sort_by_key(|m| m.timestamp);
+
         Ok(all_messages)
     }
 
 }
 
+fn find_message<'a>(user_conv: &'a UserConversations, message_id: &str) -> Option<&'a ChatMessage> {
+    user_conv.conversations.values()
+        .flat_map(|conversation| conversation.messages.iter())
+        .find(|m| m.message_id == message_id)
+}
+
+fn metadata(id: &str, conversation: &ChatSession) -> ConversationMetadata {
+    ConversationMetadata {
+        id: id.to_string(),
+        title: conversation.title.clone(),
+        created_at: conversation.created_at,
+        pinned: conversation.pinned,
+        tags: conversation.tags.clone(),
+        folder: conversation.folder.clone(),
+        archived: conversation.archived,
+    }
+}
+
+fn build_index(user_conv: &UserConversations) -> Vec<ConversationMetadata> {
+    let mut index: Vec<ConversationMetadata> = user_conv.conversations.iter()
+        .map(|(id, conversation)| metadata(id, conversation))
+        .collect();
+
+    index.sort_by_key(|entry| Reverse(entry.created_at));
+    index
+}
+
+// Write to a sibling temp file and rename over the target so readers never see a half-written shard
+async fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let tmp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp_path, bytes).await?;
+    tokio::fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
 #[derive(Debug, Serialize)]
 pub struct ChatSummary {
     pub id: String,
@@ -631,8 +1388,9 @@ pub struct ChatSummary {
 
 #[derive(Debug, Serialize)]
 pub struct DeleteResult {
-    pub deleted_type: String, 
+    pub deleted_type: String,
     pub target_id: String,
     pub title: Option<String>,
     pub message: String,
-}
\ No newline at end of file
+    pub trash_id: Option<String>,
+}
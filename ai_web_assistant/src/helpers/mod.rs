@@ -1 +1,13 @@
-pub mod message_manager;
\ No newline at end of file
+pub mod message_manager;
+pub mod message_cache;
+pub mod trash;
+pub mod revisions;
+pub mod pagination;
+pub mod organize;
+pub mod export;
+pub mod import;
+pub mod share;
+#[cfg(feature = "ttl")]
+pub mod retention;
+#[cfg(feature = "replication")]
+pub mod replication;
\ No newline at end of file
@@ -0,0 +1,141 @@
+// organize.rs
+use std::collections::BTreeMap;
+use serde::Serialize;
+
+use crate::utils::file_models::{ChatSession, ConversationMetadata, UserConversations};
+
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LEN: usize = 40;
+
+// "/Work//Clients/ " -> "Work/Clients"; an empty path means no folder
+pub fn normalize_folder(raw: &str) -> Option<String> {
+    let path = raw.split('/')
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    (!path.is_empty()).then_some(path)
+}
+
+// Trimmed, de-duplicated (case-insensitively) and bounded, in the order given
+pub fn normalize_tags(raw: Vec<String>) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+
+    for tag in raw {
+        let tag: String = tag.trim().chars().take(MAX_TAG_LEN).collect();
+        if tag.is_empty() || tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            continue;
+        }
+        tags.push(tag);
+        if tags.len() == MAX_TAGS {
+            break;
+        }
+    }
+
+    tags
+}
+
+// A folder filter matches the folder itself and everything nested below it
+pub fn in_folder(folder: Option<&str>, filter: &str) -> bool {
+    match (folder, normalize_folder(filter)) {
+        (Some(folder), Some(filter)) => folder == filter || folder.starts_with(&format!("{}/", filter)),
+        _ => false,
+    }
+}
+
+#[derive(Debug)]
+pub enum OrganizeChange {
+    Pin(bool),
+    Tags(Vec<String>),
+    Folder(Option<String>),
+    Archive(bool),
+}
+
+impl OrganizeChange {
+    pub fn apply(self, conversation: &mut ChatSession) {
+        match self {
+            OrganizeChange::Pin(pinned) => conversation.pinned = pinned,
+            OrganizeChange::Tags(tags) => conversation.tags = normalize_tags(tags),
+            OrganizeChange::Folder(folder) => conversation.folder = folder.as_deref().and_then(normalize_folder),
+            OrganizeChange::Archive(archived) => conversation.archived = archived,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SidebarFilter {
+    pub tag: Option<String>,
+    pub folder: Option<String>,
+}
+
+impl SidebarFilter {
+    pub fn matches(&self, conversation: &ConversationMetadata) -> bool {
+        let tag_ok = self.tag.as_deref().is_none_or(|tag| {
+            conversation.tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim()))
+        });
+        let folder_ok = self.folder.as_deref().is_none_or(|folder| in_folder(conversation.folder.as_deref(), folder));
+        tag_ok && folder_ok
+    }
+}
+
+// Moves `from` and its subfolders under the new name; returns the number of conversations moved
+pub fn rename_folder(user_conv: &mut UserConversations, from: &str, to: &str) -> usize {
+    let (Some(from), Some(to)) = (normalize_folder(from), normalize_folder(to)) else {
+        return 0;
+    };
+
+    let mut moved = 0;
+    for conversation in user_conv.conversations.values_mut() {
+        let Some(folder) = conversation.folder.as_deref() else {
+            continue;
+        };
+        if in_folder(Some(folder), &from) {
+            conversation.folder = Some(format!("{}{}", to, &folder[from.len()..]));
+            moved += 1;
+        }
+    }
+    moved
+}
+
+#[derive(Debug, Serialize)]
+pub struct Organization {
+    // Every folder path in use, including parents that only contain subfolders
+    pub folders: BTreeMap<String, usize>,
+    pub tags: BTreeMap<String, usize>,
+    pub pinned: usize,
+    pub archived: usize,
+}
+
+pub fn organization(user_conv: &UserConversations) -> Organization {
+    let mut folders = BTreeMap::new();
+    let mut tags = BTreeMap::new();
+    let mut pinned = 0;
+    let mut archived = 0;
+
+    // Archived conversations are not in the sidebar, so they only add to the archived count
+    for conversation in user_conv.conversations.values() {
+        if conversation.archived {
+            archived += 1;
+            continue;
+        }
+        if let Some(folder) = &conversation.folder {
+            let mut path = String::new();
+            for segment in folder.split('/') {
+                if !path.is_empty() {
+                    path.push('/');
+                }
+                path.push_str(segment);
+                *folders.entry(path.clone()).or_insert(0) += usize::from(path == *folder);
+            }
+        }
+        for tag in &conversation.tags {
+            *tags.entry(tag.clone()).or_insert(0) += 1;
+        }
+        if conversation.pinned {
+            pinned += 1;
+        }
+    }
+
+    Organization { folders, tags, pinned, archived }
+}
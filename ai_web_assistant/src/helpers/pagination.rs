@@ -0,0 +1,197 @@
+// pagination.rs
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+
+use crate::payloads::page_request::{Cursor, PageRequest};
+
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+pub const MAX_PAGE_LIMIT: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+    OldestFirst,
+    NewestFirst,
+}
+
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub has_more: bool,
+}
+
+// Items are ordered by (timestamp, id) so ties never reorder between pages. `before` and
+// `after` are chronological regardless of the order the page is returned in; `has_more`
+// says whether further items exist in the direction being paged (older, unless only `after` is set).
+pub fn paginate<T>(
+    mut items: Vec<T>,
+    page: &PageRequest,
+    order: Order,
+    key: impl Fn(&T) -> (DateTime<Utc>, String),
+) -> Result<Page<T>> {
+    let limit = page.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+
+    items.sort_by_key(|item| key(item));
+
+    let before = page.before.as_deref().map(|raw| resolve(&items, raw, &key)).transpose()?;
+    let after = page.after.as_deref().map(|raw| resolve(&items, raw, &key)).transpose()?;
+
+    items.retain(|item| {
+        let item_key = key(item);
+        before.as_ref().is_none_or(|bound| bound.has_before(&item_key))
+            && after.as_ref().is_none_or(|bound| bound.has_after(&item_key))
+    });
+
+    let has_more = items.len() > limit;
+    if page.after.is_some() && page.before.is_none() {
+        items.truncate(limit);
+    } else {
+        items.drain(..items.len().saturating_sub(limit));
+    }
+
+    if order == Order::NewestFirst {
+        items.reverse();
+    }
+
+    Ok(Page { items, has_more })
+}
+
+// For requests whose clients predate paging: with no cursor and no limit everything is
+// returned, as before; anything else pages like `paginate`.
+pub fn paginate_or_all<T>(
+    mut items: Vec<T>,
+    page: &PageRequest,
+    order: Order,
+    key: impl Fn(&T) -> (DateTime<Utc>, String),
+) -> Result<Page<T>> {
+    if page.before.is_some() || page.after.is_some() || page.limit.is_some() {
+        return paginate(items, page, order, key);
+    }
+
+    items.sort_by_key(|item| key(item));
+    if order == Order::NewestFirst {
+        items.reverse();
+    }
+    Ok(Page { items, has_more: false })
+}
+
+enum Bound {
+    Key((DateTime<Utc>, String)),
+    Timestamp(DateTime<Utc>),
+}
+
+impl Bound {
+    // True when `item` sorts strictly before this bound
+    fn has_before(&self, item: &(DateTime<Utc>, String)) -> bool {
+        match self {
+            Bound::Key(bound) => item < bound,
+            Bound::Timestamp(ts) => item.0 < *ts,
+        }
+    }
+
+    // True when `item` sorts strictly after this bound
+    fn has_after(&self, item: &(DateTime<Utc>, String)) -> bool {
+        match self {
+            Bound::Key(bound) => item > bound,
+            Bound::Timestamp(ts) => item.0 > *ts,
+        }
+    }
+}
+
+fn resolve<T>(items: &[T], raw: &str, key: &impl Fn(&T) -> (DateTime<Utc>, String)) -> Result<Bound> {
+    match Cursor::parse(raw) {
+        Cursor::Timestamp(ts) => Ok(Bound::Timestamp(ts)),
+        Cursor::Id(id) => items.iter()
+            .map(key)
+            .find(|(_, item_id)| *item_id == id)
+            .map(Bound::Key)
+            .ok_or_else(|| anyhow!("Unknown cursor: {}", id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Items are (minute, id); "b" and "c" share a timestamp
+    fn items() -> Vec<(i64, &'static str)> {
+        vec![(3, "d"), (1, "b"), (0, "a"), (1, "c"), (4, "e")]
+    }
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        "2026-10-19T12:00:00Z".parse::<DateTime<Utc>>().unwrap() + chrono::Duration::minutes(minutes)
+    }
+
+    fn key(item: &(i64, &'static str)) -> (DateTime<Utc>, String) {
+        (at(item.0), item.1.to_string())
+    }
+
+    fn request(before: Option<&str>, after: Option<&str>, limit: Option<usize>) -> PageRequest {
+        PageRequest { before: before.map(str::to_string), after: after.map(str::to_string), limit }
+    }
+
+    fn ids(page: &PageRequest, order: Order) -> (Vec<&'static str>, bool) {
+        let page = paginate(items(), page, order, key).unwrap();
+        (page.items.into_iter().map(|(_, id)| id).collect(), page.has_more)
+    }
+
+    #[test]
+    fn first_page_is_the_newest_items() {
+        assert_eq!(ids(&request(None, None, Some(2)), Order::OldestFirst), (vec!["d", "e"], true));
+        assert_eq!(ids(&request(None, None, Some(2)), Order::NewestFirst), (vec!["e", "d"], true));
+        assert_eq!(ids(&request(None, None, None), Order::OldestFirst), (vec!["a", "b", "c", "d", "e"], false));
+    }
+
+    #[test]
+    fn paging_back_by_id_walks_ties_without_gaps() {
+        assert_eq!(ids(&request(Some("d"), None, Some(1)), Order::OldestFirst), (vec!["c"], true));
+        assert_eq!(ids(&request(Some("c"), None, Some(1)), Order::OldestFirst), (vec!["b"], true));
+        assert_eq!(ids(&request(Some("b"), None, Some(1)), Order::OldestFirst), (vec!["a"], false));
+    }
+
+    #[test]
+    fn after_alone_pages_forward_from_the_cursor() {
+        assert_eq!(ids(&request(None, Some("b"), Some(2)), Order::OldestFirst), (vec!["c", "d"], true));
+        assert_eq!(ids(&request(None, Some("b"), Some(2)), Order::NewestFirst), (vec!["d", "c"], true));
+        assert_eq!(ids(&request(None, Some("d"), Some(2)), Order::OldestFirst), (vec!["e"], false));
+    }
+
+    #[test]
+    fn both_bounds_are_exclusive() {
+        assert_eq!(ids(&request(Some("e"), Some("a"), None), Order::OldestFirst), (vec!["b", "c", "d"], false));
+    }
+
+    #[test]
+    fn timestamp_cursors_compare_on_time_only() {
+        let one = at(1).to_rfc3339();
+        assert_eq!(ids(&request(Some(&one), None, None), Order::OldestFirst), (vec!["a"], false));
+        assert_eq!(ids(&request(None, Some(&one), None), Order::OldestFirst), (vec!["d", "e"], false));
+    }
+
+    #[test]
+    fn limits_are_clamped() {
+        assert_eq!(ids(&request(None, None, Some(0)), Order::OldestFirst), (vec!["e"], true));
+        let many: Vec<_> = (0..300).map(|i| (i, "x")).collect();
+        let page = paginate(many, &request(None, None, Some(1000)), Order::OldestFirst, key).unwrap();
+        assert_eq!(page.items.len(), MAX_PAGE_LIMIT);
+        assert!(page.has_more);
+    }
+
+    #[test]
+    fn paginate_or_all_returns_everything_without_paging_params() {
+        let many: Vec<_> = (0..300).map(|i| (i, "x")).collect();
+        let page = paginate_or_all(many, &request(None, None, None), Order::NewestFirst, key).unwrap();
+        assert_eq!(page.items.len(), 300);
+        assert_eq!(page.items[0].0, 299);
+        assert!(!page.has_more);
+
+        let page = paginate_or_all(items(), &request(None, None, Some(2)), Order::OldestFirst, key).unwrap();
+        assert_eq!(page.items.into_iter().map(|(_, id)| id).collect::<Vec<_>>(), vec!["d", "e"]);
+        assert!(page.has_more);
+    }
+
+    #[test]
+    fn unknown_id_cursor_is_an_error() {
+        let error = paginate(items(), &request(Some("zz"), None, None), Order::OldestFirst, key).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown cursor: zz");
+    }
+}
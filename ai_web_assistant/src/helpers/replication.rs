@@ -0,0 +1,647 @@
+// replication.rs
+//
+// Leader/follower replication of the conversation store. Every local write is
+// turned into a whole-user snapshot op; share snapshots, which live outside the
+// user shard, travel as ops of their own. The leader appends ops to an ordered log;
+// followers forward their own writes to the leader and pull the log from their
+// last applied offset.
+//
+// Followers apply their own writes locally before forwarding them, and skip
+// older log entries for a user (or share) until the leader has acknowledged
+// the pending writes to it. The originating user therefore always reads their own writes,
+// whichever instance they are connected to.
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::helpers::message_manager::MessageManager;
+use crate::helpers::share::ShareSnapshot;
+use crate::utils::file_models::UserConversations;
+
+pub const SECRET_HEADER: &str = "x-replication-secret";
+
+const DEFAULT_LOG_FILE: &str = "replication.log";
+const DEFAULT_STATE_FILE: &str = "replication_state.json";
+const DEFAULT_POLL_INTERVAL_MS: u64 = 500;
+const PAGE_SIZE: usize = 500;
+const COMPACT_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ReplicationOp {
+    PutUser {
+        user_id: String,
+        data: UserConversations,
+    },
+    DeleteUser {
+        user_id: String,
+    },
+    // Shares are addressed by the hash of their token, like the snapshot files
+    PutShare {
+        user_id: String,
+        token_hash: String,
+        snapshot: Box<ShareSnapshot>,
+    },
+    DeleteShare {
+        user_id: String,
+        token_hash: String,
+    },
+}
+
+impl ReplicationOp {
+    // The owner; their shard lock orders every op that touches their data
+    pub fn user_id(&self) -> &str {
+        match self {
+            ReplicationOp::PutUser { user_id, .. } => user_id,
+            ReplicationOp::DeleteUser { user_id } => user_id,
+            ReplicationOp::PutShare { user_id, .. } => user_id,
+            ReplicationOp::DeleteShare { user_id, .. } => user_id,
+        }
+    }
+
+    // What a later op with the same key replaces
+    fn compaction_key(&self) -> String {
+        match self {
+            ReplicationOp::PutUser { user_id, .. } | ReplicationOp::DeleteUser { user_id } => format!("user:{}", user_id),
+            ReplicationOp::PutShare { token_hash, .. } | ReplicationOp::DeleteShare { token_hash, .. } => {
+                format!("share:{}", token_hash)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LogEntry {
+    pub offset: u64,
+    pub origin: String,
+    pub timestamp: DateTime<Utc>,
+    pub op: ReplicationOp,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogPage {
+    pub entries: Vec<LogEntry>,
+    pub head: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppendRequest {
+    pub origin: String,
+    pub op: ReplicationOp,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppendAck {
+    pub offset: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct FollowerState {
+    applied_offset: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplicationRole {
+    Leader,
+    Follower { leader_url: String },
+}
+
+// Append-only log, mirrored to a JSON-lines file. Offsets start at 1.
+struct ReplicationLog {
+    path: String,
+    entries: Vec<LogEntry>,
+    next_offset: u64,
+}
+
+impl ReplicationLog {
+    fn load(path: &str) -> Result<Self> {
+        let mut entries: Vec<LogEntry> = Vec::new();
+
+        if let Ok(file) = std::fs::File::open(path) {
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<LogEntry>(&line) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => eprintln!("[Replication] Skipping unreadable log line in {}: {}", path, e),
+                }
+            }
+        }
+
+        let next_offset = entries.last().map_or(1, |entry| entry.offset + 1);
+        Ok(Self { path: path.to_string(), entries, next_offset })
+    }
+
+    async fn append(&mut self, origin: &str, op: ReplicationOp) -> Result<u64> {
+        // An erased user's older snapshots must not linger in the log, so deletes compact right away
+        let erases_user = matches!(op, ReplicationOp::DeleteUser { .. });
+        let entry = LogEntry {
+            offset: self.next_offset,
+            origin: origin.to_string(),
+            timestamp: Utc::now(),
+            op,
+        };
+
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+
+        self.next_offset += 1;
+        self.entries.push(entry);
+
+        if erases_user || self.entries.len() > COMPACT_THRESHOLD {
+            self.compact().await?;
+        }
+
+        Ok(self.next_offset - 1)
+    }
+
+    fn head(&self) -> u64 {
+        self.next_offset - 1
+    }
+
+    fn since(&self, from: u64, limit: usize) -> Vec<LogEntry> {
+        let start = self.entries.partition_point(|entry| entry.offset <= from);
+        self.entries[start..].iter().take(limit).cloned().collect()
+    }
+
+    // Ops are whole snapshots, so only the newest entry per user shard or share is needed
+    // to rebuild state, and nothing of a user from before their erasure. Offsets are kept,
+    // which keeps followers' positions valid.
+    async fn compact(&mut self) -> Result<()> {
+        let mut latest: HashMap<String, u64> = HashMap::new();
+        let mut erased: HashMap<&str, u64> = HashMap::new();
+        for entry in &self.entries {
+            latest.insert(entry.op.compaction_key(), entry.offset);
+            if let ReplicationOp::DeleteUser { user_id } = &entry.op {
+                erased.insert(user_id, entry.offset);
+            }
+        }
+        let keep: Vec<bool> = self.entries.iter()
+            .map(|entry| {
+                latest.get(&entry.op.compaction_key()) == Some(&entry.offset)
+                    && erased.get(entry.op.user_id()).is_none_or(|erased_at| *erased_at <= entry.offset)
+            })
+            .collect();
+
+        let before = self.entries.len();
+        let mut keep = keep.into_iter();
+        self.entries.retain(|_| keep.next().unwrap_or(true));
+
+        let mut content = String::new();
+        for entry in &self.entries {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+        let tmp_path = format!("{}.tmp", self.path);
+        tokio::fs::write(&tmp_path, content).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+
+        println!("🔁 Compacted replication log: {} -> {} entries", before, self.entries.len());
+        Ok(())
+    }
+}
+
+pub struct Replicator {
+    role: ReplicationRole,
+    instance_id: String,
+    secret: Option<String>,
+    client: reqwest::Client,
+    log: Mutex<ReplicationLog>,
+    pending: Mutex<VecDeque<ReplicationOp>>,
+    // compaction key -> newest leader offset that already reflects this instance's writes
+    watermarks: DashMap<String, u64>,
+    applied_offset: AtomicU64,
+    state_path: String,
+    poll_interval: Duration,
+}
+
+impl Replicator {
+    // REPLICATION_ROLE=leader|follower, REPLICATION_LEADER_URL, REPLICATION_SECRET
+    pub fn from_env() -> Result<Self> {
+        let role = match env::var("REPLICATION_ROLE").unwrap_or_else(|_| "leader".to_string()).to_lowercase().as_str() {
+            "leader" => ReplicationRole::Leader,
+            "follower" => {
+                let leader_url = env::var("REPLICATION_LEADER_URL")
+                    .map_err(|_| anyhow!("REPLICATION_LEADER_URL must be set for a follower"))?;
+                ReplicationRole::Follower { leader_url: leader_url.trim_end_matches('/').to_string() }
+            }
+            other => return Err(anyhow!("Unknown REPLICATION_ROLE: {}", other)),
+        };
+
+        let instance_id = env::var("INSTANCE_ID").unwrap_or_else(|_| Uuid::new_v4().to_string());
+        let secret = env::var("REPLICATION_SECRET").ok().filter(|s| !s.is_empty());
+        let log_path = env::var("REPLICATION_LOG_FILE").unwrap_or_else(|_| DEFAULT_LOG_FILE.to_string());
+        let state_path = env::var("REPLICATION_STATE_FILE").unwrap_or_else(|_| DEFAULT_STATE_FILE.to_string());
+        let poll_ms = env::var("REPLICATION_POLL_INTERVAL_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_POLL_INTERVAL_MS);
+
+        let applied_offset = std::fs::read_to_string(&state_path)
+            .ok()
+            .and_then(|data| serde_json::from_str::<FollowerState>(&data).ok())
+            .unwrap_or_default()
+            .applied_offset;
+
+        Ok(Self {
+            role,
+            instance_id,
+            secret,
+            client: reqwest::Client::new(),
+            log: Mutex::new(ReplicationLog::load(&log_path)?),
+            pending: Mutex::new(VecDeque::new()),
+            watermarks: DashMap::new(),
+            applied_offset: AtomicU64::new(applied_offset),
+            state_path,
+            poll_interval: Duration::from_millis(poll_ms),
+        })
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.role == ReplicationRole::Leader
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    // Replication endpoints stay closed unless a shared secret is configured.
+    // Digests are compared in constant time, so neither the secret nor its length leaks through timing.
+    pub fn check_secret(&self, provided: Option<&str>) -> bool {
+        match (&self.secret, provided) {
+            (Some(expected), Some(provided)) => {
+                Sha256::digest(expected.as_bytes()).ct_eq(&Sha256::digest(provided.as_bytes())).into()
+            }
+            _ => false,
+        }
+    }
+
+    // Called by the MessageManager after every local write, while it still holds its lock
+    pub async fn record(&self, op: ReplicationOp) -> Result<()> {
+        match &self.role {
+            ReplicationRole::Leader => {
+                self.append(&self.instance_id, op).await?;
+            }
+            ReplicationRole::Follower { .. } => {
+                // Shadow older log entries for the same key until the leader acknowledges the write
+                self.watermarks.insert(op.compaction_key(), u64::MAX);
+                self.pending.lock().await.push_back(op);
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn append(&self, origin: &str, op: ReplicationOp) -> Result<u64> {
+        if !self.is_leader() {
+            return Err(anyhow!("Only the leader accepts log appends"));
+        }
+        self.log.lock().await.append(origin, op).await
+    }
+
+    pub async fn entries_since(&self, from: u64, limit: usize) -> LogPage {
+        let log = self.log.lock().await;
+        LogPage {
+            entries: log.since(from, limit.min(PAGE_SIZE)),
+            head: log.head(),
+        }
+    }
+
+    pub async fn head(&self) -> u64 {
+        self.log.lock().await.head()
+    }
+
+    pub fn applied_offset(&self) -> u64 {
+        self.applied_offset.load(Ordering::SeqCst)
+    }
+
+    // Leader: seed an empty log with the existing store so new followers can catch up from 0.
+    // Follower: start the background sync loop.
+    pub async fn start(self: Arc<Self>, message_manager: Arc<MessageManager>) -> Result<()> {
+        match &self.role {
+            ReplicationRole::Leader => {
+                if self.head().await == 0 {
+                    let ops = message_manager.snapshot_users().await?;
+                    let count = ops.len();
+                    for op in ops {
+                        self.append(&self.instance_id, op).await?;
+                    }
+                    println!("🔁 Replication leader {} seeded log with {} user(s)", self.instance_id, count);
+                } else {
+                    println!("🔁 Replication leader {} at offset {}", self.instance_id, self.head().await);
+                }
+            }
+            ReplicationRole::Follower { leader_url } => {
+                println!(
+                    "🔁 Replication follower {} following {} from offset {}",
+                    self.instance_id, leader_url, self.applied_offset()
+                );
+                let replicator = self.clone();
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(replicator.poll_interval);
+                    loop {
+                        interval.tick().await;
+                        if let Err(e) = replicator.sync_once(&message_manager).await {
+                            eprintln!("[Replication] Sync with leader failed: {}", e);
+                        }
+                    }
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn sync_once(&self, message_manager: &MessageManager) -> Result<()> {
+        self.flush_pending().await?;
+
+        loop {
+            let page = self.fetch_page(self.applied_offset()).await?;
+            if page.entries.is_empty() {
+                break;
+            }
+
+            for entry in &page.entries {
+                if entry.origin != self.instance_id && !self.is_shadowed(entry) {
+                    message_manager.apply_replicated(&entry.op).await?;
+                }
+                self.applied_offset.store(entry.offset, Ordering::SeqCst);
+            }
+            self.save_state().await?;
+
+            if self.applied_offset() >= page.head {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn flush_pending(&self) -> Result<()> {
+        let ReplicationRole::Follower { leader_url } = &self.role else {
+            return Ok(());
+        };
+
+        loop {
+            let op = match self.pending.lock().await.front().cloned() {
+                Some(op) => op,
+                None => return Ok(()),
+            };
+
+            let ack: AppendAck = self.client
+                .post(format!("{}/replication/append", leader_url))
+                .header(SECRET_HEADER, self.secret.clone().unwrap_or_default())
+                .json(&AppendRequest { origin: self.instance_id.clone(), op: op.clone() })
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            let mut pending = self.pending.lock().await;
+            pending.pop_front();
+            if !pending.iter().any(|queued| queued.compaction_key() == op.compaction_key()) {
+                self.watermarks.insert(op.compaction_key(), ack.offset);
+            }
+        }
+    }
+
+    async fn fetch_page(&self, from: u64) -> Result<LogPage> {
+        let ReplicationRole::Follower { leader_url } = &self.role else {
+            return Err(anyhow!("Only followers pull from the leader"));
+        };
+
+        let page = self.client
+            .get(format!("{}/replication/log", leader_url))
+            .query(&[("from", from.to_string()), ("limit", PAGE_SIZE.to_string())])
+            .header(SECRET_HEADER, self.secret.clone().unwrap_or_default())
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<LogPage>()
+            .await?;
+
+        Ok(page)
+    }
+
+    // An entry older than this instance's own acknowledged (or still pending) write
+    // for the same user shard or share would roll it back, so it is skipped.
+    fn is_shadowed(&self, entry: &LogEntry) -> bool {
+        self.watermarks
+            .get(&entry.op.compaction_key())
+            .is_some_and(|watermark| *watermark > entry.offset)
+    }
+
+    async fn save_state(&self) -> Result<()> {
+        let state = FollowerState { applied_offset: self.applied_offset() };
+        tokio::fs::write(&self.state_path, serde_json::to_string_pretty(&state)?).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replicator(secret: Option<&str>) -> Replicator {
+        Replicator {
+            role: ReplicationRole::Leader,
+            instance_id: "test".to_string(),
+            secret: secret.map(str::to_string),
+            client: reqwest::Client::new(),
+            log: Mutex::new(ReplicationLog::load("/nonexistent/replication.log").unwrap()),
+            pending: Mutex::new(VecDeque::new()),
+            watermarks: DashMap::new(),
+            applied_offset: AtomicU64::new(0),
+            state_path: "/nonexistent/replication_state.json".to_string(),
+            poll_interval: Duration::from_millis(10),
+        }
+    }
+
+    fn temp_log() -> ReplicationLog {
+        let path = std::env::temp_dir().join(format!("aiwa-replication-{}.log", uuid::Uuid::new_v4()));
+        ReplicationLog::load(path.to_str().unwrap()).unwrap()
+    }
+
+    fn put_user(user_id: &str, title: &str) -> ReplicationOp {
+        let mut data = UserConversations::default();
+        data.conversations.insert("c1".to_string(), session(title));
+        ReplicationOp::PutUser { user_id: user_id.to_string(), data }
+    }
+
+    fn put_share(user_id: &str, token_hash: &str) -> ReplicationOp {
+        ReplicationOp::PutShare {
+            user_id: user_id.to_string(),
+            token_hash: token_hash.to_string(),
+            snapshot: Box::new(ShareSnapshot {
+                share_id: format!("share-{}", token_hash),
+                owner_user_id: user_id.to_string(),
+                conversation_id: "c1".to_string(),
+                created_at: Utc::now(),
+                expires_at: None,
+                conversation: session("Shared"),
+            }),
+        }
+    }
+
+    fn session(title: &str) -> crate::utils::file_models::ChatSession {
+        serde_json::from_value(serde_json::json!({
+            "title": title,
+            "created_at": Utc::now(),
+            "messages": [],
+        })).unwrap()
+    }
+
+    fn entry(offset: u64, op: ReplicationOp) -> LogEntry {
+        LogEntry { offset, origin: "leader".to_string(), timestamp: Utc::now(), op }
+    }
+
+    fn kinds(log: &ReplicationLog) -> Vec<(u64, String)> {
+        log.entries.iter().map(|entry| (entry.offset, entry.op.compaction_key())).collect()
+    }
+
+    #[test]
+    fn check_secret_accepts_only_the_exact_secret() {
+        let replicator = replicator(Some("s3cret"));
+        assert!(replicator.check_secret(Some("s3cret")));
+        assert!(!replicator.check_secret(Some("s3cre")));
+        assert!(!replicator.check_secret(Some("s3cret ")));
+        assert!(!replicator.check_secret(Some("")));
+        assert!(!replicator.check_secret(None));
+    }
+
+    #[test]
+    fn check_secret_rejects_everything_without_a_configured_secret() {
+        let replicator = replicator(None);
+        assert!(!replicator.check_secret(Some("")));
+        assert!(!replicator.check_secret(None));
+    }
+
+    #[tokio::test]
+    async fn compaction_keeps_the_latest_op_per_share_and_drops_an_erased_users_shares() {
+        let mut log = temp_log();
+        log.append("leader", put_share("1", "aaa")).await.unwrap();
+        log.append("leader", put_share("1", "bbb")).await.unwrap();
+        log.append("leader", put_share("2", "ccc")).await.unwrap();
+        log.append("leader", ReplicationOp::DeleteShare { user_id: "1".to_string(), token_hash: "aaa".to_string() }).await.unwrap();
+        log.compact().await.unwrap();
+        assert_eq!(kinds(&log), vec![
+            (2, "share:bbb".to_string()),
+            (3, "share:ccc".to_string()),
+            (4, "share:aaa".to_string()),
+        ]);
+
+        log.append("leader", ReplicationOp::DeleteUser { user_id: "1".to_string() }).await.unwrap();
+        assert_eq!(kinds(&log), vec![(3, "share:ccc".to_string()), (5, "user:1".to_string())]);
+        let _ = std::fs::remove_file(&log.path);
+    }
+
+    #[test]
+    fn a_pending_share_write_only_shadows_entries_for_that_share() {
+        let replicator = replicator(None);
+        replicator.watermarks.insert(put_share("1", "aaa").compaction_key(), 10);
+
+        assert!(replicator.is_shadowed(&entry(9, put_share("1", "aaa"))));
+        assert!(!replicator.is_shadowed(&entry(9, put_share("1", "bbb"))));
+        assert!(!replicator.is_shadowed(&entry(9, put_user("1", "Leader's edit"))));
+        assert!(!replicator.is_shadowed(&entry(11, put_share("1", "aaa"))));
+    }
+
+    fn offsets(entries: &[LogEntry]) -> Vec<u64> {
+        entries.iter().map(|entry| entry.offset).collect()
+    }
+
+    fn title(entry: &LogEntry) -> String {
+        match &entry.op {
+            ReplicationOp::PutUser { data, .. } => data.conversations["c1"].title.clone(),
+            op => panic!("not a user snapshot: {:?}", op),
+        }
+    }
+
+    #[tokio::test]
+    async fn since_pages_through_the_entries_after_an_offset() {
+        let mut log = temp_log();
+        for user_id in ["1", "2", "3", "4", "5"] {
+            log.append("leader", put_user(user_id, "t")).await.unwrap();
+        }
+
+        assert_eq!(offsets(&log.since(0, 2)), vec![1, 2]);
+        assert_eq!(offsets(&log.since(2, 2)), vec![3, 4]);
+        assert_eq!(offsets(&log.since(4, 2)), vec![5]);
+        assert!(log.since(5, 2).is_empty());
+        assert_eq!(log.head(), 5);
+        let _ = std::fs::remove_file(&log.path);
+    }
+
+    #[tokio::test]
+    async fn erasing_a_user_compacts_their_earlier_snapshots_away() {
+        let mut log = temp_log();
+        log.append("leader", put_user("1", "first")).await.unwrap();
+        log.append("leader", put_user("2", "other")).await.unwrap();
+        log.append("leader", put_user("1", "second")).await.unwrap();
+        log.append("leader", ReplicationOp::DeleteUser { user_id: "1".to_string() }).await.unwrap();
+
+        assert_eq!(kinds(&log), vec![(2, "user:2".to_string()), (4, "user:1".to_string())]);
+        // Offsets survive compaction, so a follower at offset 1 carries on from the gap
+        assert_eq!(offsets(&log.since(1, 10)), vec![2, 4]);
+        assert_eq!(log.head(), 4);
+
+        // And the file holds the same as memory
+        let reloaded = ReplicationLog::load(&log.path).unwrap();
+        assert_eq!(kinds(&reloaded), kinds(&log));
+        let _ = std::fs::remove_file(&log.path);
+    }
+
+    #[tokio::test]
+    async fn the_log_compacts_to_the_latest_snapshot_per_user_past_the_threshold() {
+        let mut log = temp_log();
+        for i in 0..COMPACT_THRESHOLD {
+            log.append("leader", put_user(&(i % 2).to_string(), &i.to_string())).await.unwrap();
+        }
+        assert_eq!(log.entries.len(), COMPACT_THRESHOLD);
+
+        log.append("leader", put_user("0", "latest")).await.unwrap();
+        let last = COMPACT_THRESHOLD as u64 + 1;
+        assert_eq!(offsets(&log.entries), vec![last - 1, last]);
+        assert_eq!(title(&log.entries[0]), (COMPACT_THRESHOLD - 1).to_string());
+        assert_eq!(title(&log.entries[1]), "latest");
+        let _ = std::fs::remove_file(&log.path);
+    }
+
+    #[tokio::test]
+    async fn a_followers_own_writes_shadow_older_entries_for_that_user() {
+        let mut follower = replicator(None);
+        follower.role = ReplicationRole::Follower { leader_url: "http://leader.invalid".to_string() };
+
+        follower.record(put_user("1", "mine")).await.unwrap();
+        // Not acknowledged yet: everything the leader has for the user is older
+        assert!(follower.is_shadowed(&entry(1_000, put_user("1", "theirs"))));
+        assert!(!follower.is_shadowed(&entry(1_000, put_user("2", "theirs"))));
+
+        // Once the leader acknowledges it at offset 7, only later snapshots are applied
+        follower.watermarks.insert(put_user("1", "mine").compaction_key(), 7);
+        assert!(follower.is_shadowed(&entry(6, put_user("1", "theirs"))));
+        assert!(!follower.is_shadowed(&entry(7, put_user("1", "mine"))));
+        assert!(!follower.is_shadowed(&entry(8, put_user("1", "newer"))));
+    }
+}
@@ -0,0 +1,256 @@
+// retention.rs
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::helpers::message_manager::MessageManager;
+use crate::utils::file_models::{ChatSession, RetentionPolicy};
+
+const DEFAULT_SWEEP_INTERVAL_SECS: u64 = 3600;
+const DEFAULT_AUDIT_FILE: &str = "retention_audit.jsonl";
+
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    pub default_policy: RetentionPolicy,
+    pub sweep_interval: std::time::Duration,
+    pub audit_path: String,
+}
+
+impl RetentionConfig {
+    // Deployment-wide rules, e.g. RETENTION_MAX_AGE_DAYS=90 RETENTION_KEEP_LAST=200
+    pub fn from_env() -> Self {
+        let sweep_secs = env::var("RETENTION_SWEEP_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_SWEEP_INTERVAL_SECS);
+
+        Self {
+            default_policy: RetentionPolicy {
+                max_age_days: env::var("RETENTION_MAX_AGE_DAYS").ok().and_then(|v| v.parse().ok()),
+                // 0 would purge every conversation, so it counts as unset
+                keep_last: env::var("RETENTION_KEEP_LAST").ok().and_then(|v| v.parse().ok()).filter(|n| *n > 0),
+            },
+            sweep_interval: std::time::Duration::from_secs(sweep_secs),
+            audit_path: env::var("RETENTION_AUDIT_FILE").unwrap_or_else(|_| DEFAULT_AUDIT_FILE.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PurgeReason {
+    Expired,
+    MaxAge,
+    KeepLast,
+}
+
+// One line of the audit log. Content and titles are never written here, only ids, counts and why;
+// the log outlives account erasure.
+#[derive(Debug, Serialize, Clone)]
+pub struct PurgeRecord {
+    pub user_id: String,
+    pub conversation_id: String,
+    pub message_count: usize,
+    pub reason: PurgeReason,
+    pub last_activity: DateTime<Utc>,
+    pub purged_at: DateTime<Utc>,
+}
+
+// A user's own rules can only tighten the deployment rules, never loosen them.
+pub fn effective_policy(default: &RetentionPolicy, user: Option<&RetentionPolicy>) -> RetentionPolicy {
+    match user {
+        Some(user) => RetentionPolicy {
+            max_age_days: stricter(default.max_age_days, user.max_age_days),
+            keep_last: stricter(default.keep_last, user.keep_last),
+        },
+        None => default.clone(),
+    }
+}
+
+fn stricter<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+pub fn last_activity(session: &ChatSession) -> DateTime<Utc> {
+    session.messages.iter()
+        .map(|m| m.edit_timestamp.unwrap_or(m.timestamp))
+        .max()
+        .map_or(session.created_at, |latest| latest.max(session.created_at))
+}
+
+pub fn select_expired(
+    policy: &RetentionPolicy,
+    conversations: &HashMap<String, ChatSession>,
+    now: DateTime<Utc>,
+) -> Vec<(String, PurgeReason)> {
+    let mut expired = Vec::new();
+    let mut survivors = Vec::new();
+
+    for (conversation_id, session) in conversations {
+        let activity = last_activity(session);
+
+        if session.expires_at.is_some_and(|at| at <= now) {
+            expired.push((conversation_id.clone(), PurgeReason::Expired));
+        } else if policy.max_age_days.is_some_and(|days| activity < now - Duration::days(days as i64)) {
+            expired.push((conversation_id.clone(), PurgeReason::MaxAge));
+        } else {
+            survivors.push((conversation_id, activity));
+        }
+    }
+
+    if let Some(keep_last) = policy.keep_last {
+        // Newest first, ties broken by id so repeated sweeps agree on what to keep.
+        // The newest conversation always stays, whatever a stored policy says.
+        survivors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        for (conversation_id, _) in survivors.into_iter().skip(keep_last.max(1)) {
+            expired.push((conversation_id.clone(), PurgeReason::KeepLast));
+        }
+    }
+
+    expired
+}
+
+pub async fn append_audit(audit_path: &str, records: &[PurgeRecord]) -> Result<()> {
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(audit_path)
+        .await?;
+    file.write_all(lines.as_bytes()).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+pub fn spawn_sweeper(message_manager: Arc<MessageManager>, config: RetentionConfig) {
+    println!(
+        "🧹 Retention sweeper every {}s (max_age_days={:?}, keep_last={:?})",
+        config.sweep_interval.as_secs(),
+        config.default_policy.max_age_days,
+        config.default_policy.keep_last,
+    );
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.sweep_interval);
+        loop {
+            interval.tick().await;
+
+            match message_manager.purge_expired(&config.default_policy, Utc::now()).await {
+                Ok(records) if records.is_empty() => {}
+                Ok(records) => {
+                    if let Err(e) = append_audit(&config.audit_path, &records).await {
+                        eprintln!("Failed to write retention audit ({} records): {}", records.len(), e);
+                    }
+                    println!("🧹 Retention sweep purged {} conversation(s)", records.len());
+                }
+                Err(e) => eprintln!("Retention sweep failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn now() -> DateTime<Utc> {
+        "2026-10-19T12:00:00Z".parse().unwrap()
+    }
+
+    // Conversation created `age_days` ago with one message at creation time
+    fn session(age_days: i64) -> ChatSession {
+        let created_at = now() - Duration::days(age_days);
+        serde_json::from_value(json!({
+            "title": "t",
+            "created_at": created_at,
+            "messages": [{
+                "message_id": "m1", "parent_id": "", "reply_id": null, "role": "user",
+                "content": "hi", "edited": false, "timestamp": created_at
+            }],
+        }))
+        .unwrap()
+    }
+
+    fn conversations(sessions: Vec<(&str, ChatSession)>) -> HashMap<String, ChatSession> {
+        sessions.into_iter().map(|(id, session)| (id.to_string(), session)).collect()
+    }
+
+    fn sorted(mut expired: Vec<(String, PurgeReason)>) -> Vec<(String, PurgeReason)> {
+        expired.sort_by(|a, b| a.0.cmp(&b.0));
+        expired
+    }
+
+    fn policy(max_age_days: Option<u32>, keep_last: Option<usize>) -> RetentionPolicy {
+        RetentionPolicy { max_age_days, keep_last }
+    }
+
+    #[test]
+    fn nothing_expires_without_rules() {
+        let all = conversations(vec![("a", session(1000)), ("b", session(0))]);
+        assert!(select_expired(&policy(None, None), &all, now()).is_empty());
+    }
+
+    #[test]
+    fn explicit_expiry_wins_over_other_rules() {
+        let mut expiring = session(0);
+        expiring.expires_at = Some(now());
+        let mut later = session(0);
+        later.expires_at = Some(now() + Duration::seconds(1));
+        let all = conversations(vec![("a", expiring), ("b", later)]);
+
+        assert_eq!(select_expired(&policy(Some(30), Some(0)), &all, now())[0], ("a".to_string(), PurgeReason::Expired));
+        assert_eq!(select_expired(&policy(None, None), &all, now()), vec![("a".to_string(), PurgeReason::Expired)]);
+    }
+
+    #[test]
+    fn max_age_counts_from_the_latest_activity() {
+        let mut revived = session(40);
+        revived.messages[0].edit_timestamp = Some(now() - Duration::days(1));
+        let all = conversations(vec![("old", session(31)), ("edge", session(30)), ("revived", revived)]);
+
+        assert_eq!(select_expired(&policy(Some(30), None), &all, now()), vec![("old".to_string(), PurgeReason::MaxAge)]);
+    }
+
+    #[test]
+    fn keep_last_drops_the_oldest_survivors_with_stable_ties() {
+        let all = conversations(vec![("a", session(1)), ("b", session(5)), ("c", session(5)), ("d", session(100))]);
+
+        assert_eq!(
+            sorted(select_expired(&policy(Some(30), Some(2)), &all, now())),
+            vec![("c".to_string(), PurgeReason::KeepLast), ("d".to_string(), PurgeReason::MaxAge)],
+        );
+        assert_eq!(
+            sorted(select_expired(&policy(None, Some(2)), &all, now())),
+            vec![("c".to_string(), PurgeReason::KeepLast), ("d".to_string(), PurgeReason::KeepLast)],
+        );
+    }
+
+    #[test]
+    fn keep_last_zero_still_keeps_the_newest_conversation() {
+        let all = conversations(vec![("a", session(1)), ("b", session(5))]);
+        assert_eq!(select_expired(&policy(None, Some(0)), &all, now()), vec![("b".to_string(), PurgeReason::KeepLast)]);
+    }
+
+    #[test]
+    fn users_can_only_tighten_the_deployment_policy() {
+        let default = policy(Some(90), None);
+        assert_eq!(effective_policy(&default, Some(&policy(Some(365), Some(10)))).max_age_days, Some(90));
+        assert_eq!(effective_policy(&default, Some(&policy(Some(7), None))).max_age_days, Some(7));
+        assert_eq!(effective_policy(&default, Some(&policy(None, Some(10)))).keep_last, Some(10));
+        assert_eq!(effective_policy(&default, None).keep_last, None);
+    }
+}
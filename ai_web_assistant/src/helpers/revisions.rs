@@ -0,0 +1,149 @@
+// revisions.rs
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+use crate::utils::file_models::{ChatMessage, MessageRevision};
+
+#[derive(Debug, Serialize)]
+pub struct DiffSegment {
+    pub op: &'static str,
+    pub text: String,
+}
+
+// Full history of a message. Messages that were never edited report their content as revision 1.
+pub fn history(message: &ChatMessage) -> Vec<MessageRevision> {
+    if message.revisions.is_empty() {
+        return vec![original(message)];
+    }
+    message.revisions.clone()
+}
+
+// Applies an edit and appends it as a new revision, returning its number
+pub fn record_edit(
+    message: &mut ChatMessage,
+    new_content: &str,
+    now: DateTime<Utc>,
+    reverted_from: Option<u32>,
+) -> u32 {
+    if message.revisions.is_empty() {
+        let first = original(message);
+        message.revisions.push(first);
+    }
+
+    let revision = message.revisions.last().map(|r| r.revision).unwrap_or(0) + 1;
+    message.revisions.push(MessageRevision {
+        revision,
+        content: new_content.to_string(),
+        created_at: now,
+        reverted_from,
+    });
+
+    message.content = new_content.to_string();
+    message.edited = true;
+    message.edit_timestamp = Some(now);
+    revision
+}
+
+pub fn find(message: &ChatMessage, revision: u32) -> Result<MessageRevision> {
+    history(message)
+        .into_iter()
+        .find(|r| r.revision == revision)
+        .ok_or_else(|| anyhow!("Revision {} not found for message {}", revision, message.message_id))
+}
+
+// Word-level diff; consecutive words with the same outcome are merged into one segment
+pub fn diff_words(old: &str, new: &str) -> Vec<DiffSegment> {
+    let diff = TextDiff::from_words(old, new);
+    let mut segments: Vec<DiffSegment> = Vec::new();
+
+    for change in diff.iter_all_changes() {
+        let op = match change.tag() {
+            ChangeTag::Equal => "equal",
+            ChangeTag::Delete => "delete",
+            ChangeTag::Insert => "insert",
+        };
+
+        match segments.last_mut() {
+            Some(last) if last.op == op => last.text.push_str(change.value()),
+            _ => segments.push(DiffSegment { op, text: change.value().to_string() }),
+        }
+    }
+
+    segments
+}
+
+// Before the first recorded edit the current content is the oldest text we have
+// (messages edited before revisions existed have lost their original)
+fn original(message: &ChatMessage) -> MessageRevision {
+    MessageRevision {
+        revision: 1,
+        content: message.content.clone(),
+        created_at: message.edit_timestamp.unwrap_or(message.timestamp),
+        reverted_from: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        "2026-10-19T12:00:00Z".parse::<DateTime<Utc>>().unwrap() + chrono::Duration::minutes(minutes)
+    }
+
+    fn message(content: &str) -> ChatMessage {
+        serde_json::from_value(json!({
+            "message_id": "m1", "parent_id": "", "reply_id": null, "role": "user",
+            "content": content, "edited": false, "timestamp": at(0)
+        }))
+        .unwrap()
+    }
+
+    fn ops(segments: &[DiffSegment]) -> Vec<(&str, &str)> {
+        segments.iter().map(|s| (s.op, s.text.as_str())).collect()
+    }
+
+    #[test]
+    fn unedited_message_is_its_own_first_revision() {
+        let history = history(&message("hello"));
+        assert_eq!(history.len(), 1);
+        assert_eq!((history[0].revision, history[0].content.as_str()), (1, "hello"));
+        assert!(find(&message("hello"), 2).is_err());
+    }
+
+    #[test]
+    fn edits_append_numbered_revisions_and_keep_the_original() {
+        let mut message = message("first");
+        assert_eq!(record_edit(&mut message, "second", at(1), None), 2);
+        assert_eq!(record_edit(&mut message, "first", at(2), Some(1)), 3);
+
+        let contents: Vec<_> = history(&message).into_iter().map(|r| (r.revision, r.content, r.reverted_from)).collect();
+        assert_eq!(contents, [(1, "first".into(), None), (2, "second".into(), None), (3, "first".into(), Some(1))]);
+        assert_eq!(history(&message)[0].created_at, at(0));
+        assert!(message.edited);
+        assert_eq!(message.edit_timestamp, Some(at(2)));
+        assert_eq!(find(&message, 2).unwrap().content, "second");
+    }
+
+    #[test]
+    fn diff_merges_runs_of_words() {
+        let segments = diff_words("the quick brown fox", "the slow brown dog jumps");
+        assert_eq!(ops(&segments), [
+            ("equal", "the "),
+            ("delete", "quick"),
+            ("insert", "slow"),
+            ("equal", " brown "),
+            ("delete", "fox"),
+            ("insert", "dog jumps"),
+        ]);
+    }
+
+    #[test]
+    fn diff_of_identical_text_is_one_equal_segment() {
+        assert_eq!(ops(&diff_words("same words here", "same words here")), [("equal", "same words here")]);
+        assert!(diff_words("", "").is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+// share.rs
+use std::collections::HashMap;
+use std::env;
+use chrono::{DateTime, Utc};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::utils::file_models::{ChatMessage, ChatSession, ShareLink};
+
+const TOKEN_BYTES: usize = 32;
+
+// 256 random bits, hex encoded
+pub fn new_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn is_well_formed(token: &str) -> bool {
+    token.len() == TOKEN_BYTES * 2 && token.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// Snapshots are stored under the token's hash, so the share directory holds no usable links
+pub fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// SHARE_BASE_URL (e.g. "https://chat.example.com") makes share URLs absolute
+pub fn share_url(token: &str) -> String {
+    let base = env::var("SHARE_BASE_URL").unwrap_or_default();
+    format!("{}/share/{}", base.trim_end_matches('/'), token)
+}
+
+// What is written to disk when a conversation is shared
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShareSnapshot {
+    pub share_id: String,
+    pub owner_user_id: String,
+    pub conversation_id: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    pub conversation: ChatSession,
+}
+
+impl ShareSnapshot {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShareLinkView {
+    #[serde(flatten)]
+    pub link: ShareLink,
+    pub url: String,
+    pub expired: bool,
+}
+
+pub fn view(link: ShareLink, now: DateTime<Utc>) -> ShareLinkView {
+    ShareLinkView {
+        url: share_url(&link.token),
+        expired: link.expires_at.is_some_and(|expires_at| expires_at <= now),
+        link,
+    }
+}
+
+// Viewers only get the messages: no tags, folder, pin state or edit history
+pub fn snapshot(conversation: &ChatSession) -> ChatSession {
+    ChatSession {
+        title: conversation.title.clone(),
+        created_at: conversation.created_at,
+        messages: conversation.messages.iter()
+            .map(|message| ChatMessage { revisions: Vec::new(), ..message.clone() })
+            .collect(),
+        expires_at: None,
+        pinned: false,
+        tags: Vec::new(),
+        folder: None,
+        archived: false,
+        imported: None,
+    }
+}
+
+// A private copy of a shared conversation with fresh message ids, so edits and
+// deletes in the copy never touch the owner's messages
+pub fn fork(conversation: &ChatSession, now: DateTime<Utc>) -> ChatSession {
+    let ids: HashMap<&str, String> = conversation.messages.iter()
+        .map(|m| (m.message_id.as_str(), Uuid::new_v4().to_string()))
+        .collect();
+    let remap = |id: &str| ids.get(id).cloned().unwrap_or_else(|| id.to_string());
+
+    ChatSession {
+        created_at: now,
+        messages: conversation.messages.iter()
+            .map(|message| ChatMessage {
+                message_id: remap(&message.message_id),
+                parent_id: remap(&message.parent_id),
+                reply_id: message.reply_id.as_deref().map(remap),
+                ..message.clone()
+            })
+            .collect(),
+        ..snapshot(conversation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn conversation() -> ChatSession {
+        let at: DateTime<Utc> = "2026-10-19T12:00:00Z".parse().unwrap();
+        serde_json::from_value(json!({
+            "title": "Shared",
+            "created_at": at,
+            "pinned": true,
+            "tags": ["private"],
+            "folder": "Work",
+            "messages": [
+                { "message_id": "q", "parent_id": "root", "reply_id": null, "role": "user", "content": "Question",
+                  "edited": true, "timestamp": at, "edit_timestamp": at,
+                  "revisions": [{ "revision": 1, "content": "Old question", "created_at": at }] },
+                { "message_id": "a", "parent_id": "root", "reply_id": "q", "role": "ai", "content": "Answer",
+                  "edited": false, "timestamp": at },
+            ],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn only_full_length_hex_tokens_are_well_formed() {
+        assert!(is_well_formed(&new_token()));
+        assert!(is_well_formed(&"AbCd".repeat(16)));
+        assert!(!is_well_formed(&"a".repeat(63)));
+        assert!(!is_well_formed(&"a".repeat(65)));
+        assert!(!is_well_formed(&format!("{}g", "a".repeat(63))));
+        assert!(!is_well_formed("../../etc/passwd"));
+        assert!(!is_well_formed(""));
+    }
+
+    #[test]
+    fn tokens_are_random_and_stored_hashed() {
+        let token = new_token();
+        assert_ne!(token, new_token());
+        assert_eq!(token_hash(&token), token_hash(&token));
+        assert_ne!(token_hash(&token), token);
+    }
+
+    #[test]
+    fn snapshot_strips_private_state() {
+        let shared = snapshot(&conversation());
+        assert!(!shared.pinned && shared.tags.is_empty() && shared.folder.is_none());
+        assert!(shared.messages.iter().all(|m| m.revisions.is_empty()));
+        assert_eq!(shared.messages[0].content, "Question");
+    }
+
+    #[test]
+    fn fork_remaps_ids_and_keeps_the_links() {
+        let original = conversation();
+        let now = original.created_at + chrono::Duration::days(1);
+        let copy = fork(&original, now);
+
+        assert_eq!(copy.created_at, now);
+        assert_ne!(copy.messages[0].message_id, "q");
+        assert_eq!(copy.messages[0].parent_id, "root");
+        assert_eq!(copy.messages[1].reply_id.as_ref(), Some(&copy.messages[0].message_id));
+        assert!(copy.tags.is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+// trash.rs
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::helpers::message_manager::MessageManager;
+use crate::utils::file_models::{ChatSession, TrashItem, TrashedEntry, UserConversations};
+
+const DEFAULT_GRACE_PERIOD_DAYS: i64 = 30;
+const DEFAULT_PURGE_INTERVAL_SECS: u64 = 3600;
+
+#[derive(Debug, Clone)]
+pub struct TrashConfig {
+    pub grace_period: chrono::Duration,
+    pub purge_interval: Duration,
+}
+
+impl TrashConfig {
+    pub fn from_env() -> Self {
+        let grace_days = env::var("TRASH_GRACE_PERIOD_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|days| *days >= 0)
+            .unwrap_or(DEFAULT_GRACE_PERIOD_DAYS);
+
+        let purge_secs = env::var("TRASH_PURGE_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_PURGE_INTERVAL_SECS);
+
+        Self {
+            grace_period: chrono::Duration::days(grace_days),
+            purge_interval: Duration::from_secs(purge_secs),
+        }
+    }
+}
+
+// What list_trash returns; message content is reduced to a short preview
+#[derive(Debug, Serialize)]
+pub struct TrashSummary {
+    pub trash_id: String,
+    pub kind: &'static str,
+    pub conversation_id: String,
+    pub title: String,
+    pub message_count: usize,
+    pub preview: Option<String>,
+    pub deleted_at: DateTime<Utc>,
+    pub purge_at: DateTime<Utc>,
+}
+
+pub fn summarize(item: &TrashItem, grace_period: chrono::Duration) -> TrashSummary {
+    let (kind, title, message_count, preview) = match &item.entry {
+        TrashedEntry::Conversation { conversation } => (
+            "conversation",
+            conversation.title.clone(),
+            conversation.messages.len(),
+            None,
+        ),
+        TrashedEntry::Message { conversation_title, message } => (
+            "message",
+            conversation_title.clone(),
+            1,
+            Some(message.content.chars().take(80).collect()),
+        ),
+    };
+
+    TrashSummary {
+        trash_id: item.trash_id.clone(),
+        kind,
+        conversation_id: item.conversation_id.clone(),
+        title,
+        message_count,
+        preview,
+        deleted_at: item.deleted_at,
+        purge_at: item.deleted_at + grace_period,
+    }
+}
+
+pub fn trash_conversation(user_conv: &mut UserConversations, conversation_id: &str, now: DateTime<Utc>) -> Option<String> {
+    let conversation = user_conv.conversations.remove(conversation_id)?;
+    Some(push(user_conv, conversation_id, now, TrashedEntry::Conversation { conversation }))
+}
+
+pub fn trash_message(
+    user_conv: &mut UserConversations,
+    conversation_id: &str,
+    message_id: &str,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let conversation = user_conv.conversations.get_mut(conversation_id)?;
+    let index = conversation.messages.iter().position(|m| m.message_id == message_id)?;
+    let message = conversation.messages.remove(index);
+    let conversation_title = conversation.title.clone();
+
+    Some(push(user_conv, conversation_id, now, TrashedEntry::Message { conversation_title, message }))
+}
+
+// Puts a trashed item back where it came from. A message whose conversation is gone
+// gets a fresh conversation, unless that conversation is itself waiting in the trash.
+pub fn restore(user_conv: &mut UserConversations, trash_id: &str) -> Result<TrashItem> {
+    let position = user_conv.trash.iter()
+        .position(|item| item.trash_id == trash_id)
+        .ok_or_else(|| anyhow!("Trash item not found: {}", trash_id))?;
+
+    let item = &user_conv.trash[position];
+    let conversation_id = item.conversation_id.clone();
+
+    match &item.entry {
+        TrashedEntry::Conversation { .. } => {
+            if user_conv.conversations.contains_key(&conversation_id) {
+                return Err(anyhow!("Conversation {} already exists", conversation_id));
+            }
+        }
+        TrashedEntry::Message { .. } => {
+            let conversation_in_trash = user_conv.trash.iter().any(|other| {
+                other.conversation_id == conversation_id
+                    && matches!(other.entry, TrashedEntry::Conversation { .. })
+            });
+            if !user_conv.conversations.contains_key(&conversation_id) && conversation_in_trash {
+                return Err(anyhow!("Conversation {} is in the trash; restore it first", conversation_id));
+            }
+        }
+    }
+
+    let item = user_conv.trash.remove(position);
+    match &item.entry {
+        TrashedEntry::Conversation { conversation } => {
+            user_conv.conversations.insert(conversation_id, conversation.clone());
+        }
+        TrashedEntry::Message { conversation_title, message } => {
+            let conversation = user_conv.conversations.entry(conversation_id).or_insert_with(|| ChatSession {
+                title: conversation_title.clone(),
+                created_at: message.timestamp,
+                messages: Vec::new(),
+                expires_at: None,
+                pinned: false,
+                tags: Vec::new(),
+                folder: None,
+                archived: false,
+                imported: None,
+            });
+            let index = conversation.messages.partition_point(|m| m.timestamp <= message.timestamp);
+            conversation.messages.insert(index, message.clone());
+        }
+    }
+
+    Ok(item)
+}
+
+// Drops everything deleted before the cutoff and returns how many items went
+pub fn purge_before(user_conv: &mut UserConversations, cutoff: DateTime<Utc>) -> usize {
+    let before = user_conv.trash.len();
+    user_conv.trash.retain(|item| item.deleted_at > cutoff);
+    before - user_conv.trash.len()
+}
+
+fn push(user_conv: &mut UserConversations, conversation_id: &str, now: DateTime<Utc>, entry: TrashedEntry) -> String {
+    let trash_id = Uuid::new_v4().to_string();
+    user_conv.trash.push(TrashItem {
+        trash_id: trash_id.clone(),
+        deleted_at: now,
+        conversation_id: conversation_id.to_string(),
+        entry,
+    });
+    trash_id
+}
+
+pub fn spawn_purger(message_manager: Arc<MessageManager>, config: TrashConfig) {
+    println!(
+        "🗑️ Trash purge every {}s (grace period {} days)",
+        config.purge_interval.as_secs(),
+        config.grace_period.num_days(),
+    );
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.purge_interval);
+        loop {
+            interval.tick().await;
+
+            match message_manager.purge_trash(Utc::now() - config.grace_period).await {
+                Ok(0) => {}
+                Ok(count) => println!("🗑️ Purged {} item(s) from the trash", count),
+                Err(e) => eprintln!("Trash purge failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration as ChronoDuration;
+    use serde_json::json;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        "2026-10-19T12:00:00Z".parse::<DateTime<Utc>>().unwrap() + ChronoDuration::minutes(minutes)
+    }
+
+    fn user_with(conversation_id: &str, message_ids: &[&str]) -> UserConversations {
+        let messages: Vec<_> = message_ids.iter().enumerate().map(|(i, id)| json!({
+            "message_id": id, "parent_id": "", "reply_id": null, "role": "user",
+            "content": format!("message {}", id), "edited": false, "timestamp": at(i as i64)
+        })).collect();
+        let conversation = serde_json::from_value(json!({ "title": "Chat", "created_at": at(0), "messages": messages })).unwrap();
+
+        let mut user_conv = UserConversations::default();
+        user_conv.conversations.insert(conversation_id.to_string(), conversation);
+        user_conv
+    }
+
+    fn message_ids(user_conv: &UserConversations, conversation_id: &str) -> Vec<String> {
+        user_conv.conversations[conversation_id].messages.iter().map(|m| m.message_id.clone()).collect()
+    }
+
+    #[test]
+    fn purge_drops_only_items_deleted_before_the_cutoff() {
+        let mut user_conv = user_with("c1", &["m1", "m2", "m3"]);
+        trash_message(&mut user_conv, "c1", "m1", at(10)).unwrap();
+        trash_message(&mut user_conv, "c1", "m2", at(20)).unwrap();
+        let kept = trash_message(&mut user_conv, "c1", "m3", at(30)).unwrap();
+
+        assert_eq!(purge_before(&mut user_conv, at(20)), 2);
+        assert_eq!(user_conv.trash.len(), 1);
+        assert_eq!(user_conv.trash[0].trash_id, kept);
+        assert_eq!(purge_before(&mut user_conv, at(20)), 0);
+    }
+
+    #[test]
+    fn restored_message_goes_back_in_timestamp_order() {
+        let mut user_conv = user_with("c1", &["m1", "m2", "m3"]);
+        let trash_id = trash_message(&mut user_conv, "c1", "m2", at(5)).unwrap();
+        assert_eq!(message_ids(&user_conv, "c1"), ["m1", "m3"]);
+
+        restore(&mut user_conv, &trash_id).unwrap();
+        assert_eq!(message_ids(&user_conv, "c1"), ["m1", "m2", "m3"]);
+        assert!(user_conv.trash.is_empty());
+    }
+
+    #[test]
+    fn message_waits_for_its_trashed_conversation() {
+        let mut user_conv = user_with("c1", &["m1", "m2"]);
+        let message = trash_message(&mut user_conv, "c1", "m1", at(5)).unwrap();
+        let conversation = trash_conversation(&mut user_conv, "c1", at(6)).unwrap();
+
+        assert!(restore(&mut user_conv, &message).unwrap_err().to_string().contains("restore it first"));
+        restore(&mut user_conv, &conversation).unwrap();
+        restore(&mut user_conv, &message).unwrap();
+        assert_eq!(message_ids(&user_conv, "c1"), ["m1", "m2"]);
+    }
+
+    #[test]
+    fn message_of_a_deleted_conversation_gets_a_fresh_one() {
+        let mut user_conv = user_with("c1", &["m1"]);
+        let message = trash_message(&mut user_conv, "c1", "m1", at(5)).unwrap();
+        user_conv.conversations.clear();
+
+        restore(&mut user_conv, &message).unwrap();
+        assert_eq!(user_conv.conversations["c1"].title, "Chat");
+        assert_eq!(message_ids(&user_conv, "c1"), ["m1"]);
+    }
+
+    #[test]
+    fn conversation_is_not_restored_over_a_live_one() {
+        let mut user_conv = user_with("c1", &["m1"]);
+        let trash_id = trash_conversation(&mut user_conv, "c1", at(5)).unwrap();
+        user_conv.conversations = user_with("c1", &["other"]).conversations;
+
+        assert!(restore(&mut user_conv, &trash_id).is_err());
+        assert_eq!(user_conv.trash.len(), 1);
+        assert!(restore(&mut user_conv, "missing").is_err());
+    }
+
+    #[test]
+    fn summary_previews_messages_and_schedules_the_purge() {
+        let mut user_conv = user_with("c1", &["m1"]);
+        user_conv.conversations.get_mut("c1").unwrap().messages[0].content = "x".repeat(200);
+        trash_message(&mut user_conv, "c1", "m1", at(0)).unwrap();
+
+        let summary = summarize(&user_conv.trash[0], ChronoDuration::days(30));
+        assert_eq!(summary.kind, "message");
+        assert_eq!(summary.preview.unwrap().len(), 80);
+        assert_eq!(summary.purge_at, at(0) + ChronoDuration::days(30));
+    }
+}
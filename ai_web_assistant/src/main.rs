@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tower_http::cors::{CorsLayer, Any};
 use tokio::net::TcpListener;
@@ -16,16 +17,32 @@ mod ws;
 mod payloads;
 mod swagger_doc;
 mod helpers;
+mod cli;
+#[cfg(test)]
+mod test_support;
 
 
+use clap::Parser;
 use config::settings::Settings;
 use connection::db::establish_connection;
+#[cfg(feature = "replication")]
+use crate::helpers::replication::Replicator;
 use crate::{
-    services::user_service, ws::{ws_channel::WsBroadcaster, ws_server::start_ws_server}
+    helpers::message_manager::MessageManager,
+    services::backup_service::{self, BackupConfig, BackupService},
+    services::revocation_service::{self, RevocationService},
+    services::user_service,
+    services::password_service::{PasswordResetConfig, PasswordService},
+    services::two_factor_service::{self, TwoFactorService},
+    services::oidc_service::{OidcConfig, OidcService},
+    services::login_throttle_service::{self, LoginThrottle, LoginThrottleConfig},
+    services::verification_service::{VerificationConfig, VerificationService},
+    ws::{ws_channel::WsBroadcaster, ws_server::start_ws_server}
 };
 
 #[tokio::main]
 async fn main() {
+    let cli = cli::Cli::parse();
     let _settings = Settings::new();
 
     let pool = match establish_connection().await {
@@ -39,6 +56,54 @@ async fn main() {
 
     let broadcaster = Arc::new(WsBroadcaster::new());
     let user_service = Arc::new(user_service::UserService::new(repository::user_repository::UserRepository { db: pool.clone() }));
+
+    let message_store_dir = std::env::var("MESSAGE_STORE_DIR").unwrap_or_else(|_| "data/messages".to_string());
+    match MessageManager::new(&message_store_dir).migrate_legacy_file("messages.json").await {
+        Ok(0) => {}
+        Ok(count) => println!("📦 Migrated {} users from messages.json into {}", count, message_store_dir),
+        Err(e) => {
+            eprintln!("❌ Failed to migrate messages.json: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let cache_config = helpers::message_cache::CacheConfig::from_env();
+    let message_manager = match &cache_config {
+        Some(config) => MessageManager::new(&message_store_dir).with_cache(config.clone()),
+        None => MessageManager::new(&message_store_dir),
+    };
+
+    #[cfg(feature = "replication")]
+    let replicator = match Replicator::from_env() {
+        Ok(replicator) => Arc::new(replicator),
+        Err(e) => {
+            eprintln!("❌ Failed to initialize replication: {}", e);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(feature = "replication")]
+    let message_manager = message_manager.with_replicator(replicator.clone());
+
+    let message_manager = Arc::new(message_manager);
+
+    #[cfg(feature = "replication")]
+    if let Err(e) = replicator.start(message_manager.clone()).await {
+        eprintln!("❌ Failed to start replication: {}", e);
+        std::process::exit(1);
+    }
+
+    let backup_config = BackupConfig::from_env();
+    let backup_service = Arc::new(BackupService::new(
+        backup_config.clone(),
+        repository::user_repository::UserRepository { db: pool.clone() },
+        message_manager.clone(),
+    ));
+
+    // `backup`, `backups` and `restore` run against the store and exit without starting the server
+    if let Some(command) = cli.command {
+        std::process::exit(cli::run(command, &backup_service).await);
+    }
+
     let llm_service = match services::llm_service::LlmService::new() {
         Ok(service) => Arc::new(service),
         Err(e) => {
@@ -47,21 +112,84 @@ async fn main() {
         }
     };
 
+    if let Some(config) = cache_config.clone() {
+        helpers::message_cache::spawn_flusher(message_manager.clone(), config);
+
+        // Pending writes only live in memory, so flush them before exiting on Ctrl+C
+        tokio::spawn({
+            let message_manager = message_manager.clone();
+            async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    match message_manager.flush_cache(true).await {
+                        Ok(count) => println!("🗄️ Flushed {} cached shard(s) before shutdown", count),
+                        Err(e) => eprintln!("❌ Failed to flush message cache on shutdown: {}", e),
+                    }
+                    std::process::exit(0);
+                }
+            }
+        });
+    }
+
+    // Shared by the WebSocket server and the account export/erasure endpoints
+    let file_manager = Arc::new(utils::file_utils::JsonFileManager::new());
+
+    let revocations = Arc::new(RevocationService::new(pool.clone()));
+    match revocations.sync().await {
+        Ok(count) => println!("🔒 Loaded {} active token revocation(s)", count),
+        Err(e) => {
+            eprintln!("❌ Failed to load token revocations: {}", e);
+            std::process::exit(1);
+        }
+    }
+    revocation_service::spawn_sync(revocations.clone());
+
+    let mailer = match services::mailer::from_env() {
+        Ok(mailer) => mailer,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize mailer: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let verification = Arc::new(VerificationService::new(pool.clone(), mailer.clone(), VerificationConfig::from_env()));
+    let passwords = Arc::new(PasswordService::new(pool.clone(), mailer, revocations.clone(), PasswordResetConfig::from_env()));
+    let two_factor = Arc::new(TwoFactorService::new(pool.clone()));
+    two_factor_service::spawn_pruner(two_factor.clone());
+    let oidc = Arc::new(OidcService::new(pool.clone(), OidcConfig::from_env()));
+    let login_throttle = Arc::new(LoginThrottle::new(pool.clone(), LoginThrottleConfig::from_env()));
+    login_throttle_service::spawn_pruner(login_throttle.clone());
+    // Hash the dummy password now rather than on the first login for an unknown email
+    services::auth_service::dummy_password_hash();
+
+    backup_service::spawn_scheduler(backup_service.clone(), backup_config);
+
+    helpers::trash::spawn_purger(message_manager.clone(), helpers::trash::TrashConfig::from_env());
+
+    #[cfg(feature = "ttl")]
+    helpers::retention::spawn_sweeper(message_manager.clone(), helpers::retention::RetentionConfig::from_env());
+
     tokio::spawn({
         let broadcaster = broadcaster.clone();
+        let message_manager = message_manager.clone();
         let user_service = user_service.clone();
         let llm_service = llm_service.clone();
+        let file_manager = file_manager.clone();
+        let revocations = revocations.clone();
+        let verification = verification.clone();
         async move {
             start_ws_server(
                 "0.0.0.0:9001",
                 broadcaster,
                 user_service,
                 llm_service,
+                message_manager,
+                file_manager,
+                revocations,
+                verification,
             ).await;
         }
     });
 
-    let app = router::url::create_routes(pool, broadcaster.clone())
+    let app = router::url::create_routes(pool, broadcaster.clone(), message_manager.clone(), file_manager, backup_service, revocations, verification, passwords, two_factor, oidc, login_throttle)
     .layer(CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
@@ -69,5 +197,5 @@ async fn main() {
 
     let listener = TcpListener::bind("0.0.0.0:8022").await.unwrap();
     println!("🚀 Server started on http://{}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
@@ -6,9 +6,42 @@ use axum::{
     Json,
 };
 use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
 
+use crate::services::api_key_service;
+use crate::services::revocation_service::RevocationService;
 use crate::utils::jwt::{decode_token, Claims};
 
+fn auth_error(status: StatusCode, error: &str) -> Response {
+    (
+        status,
+        Json(json!(
+            { 
+                "title": "Authentication Error",
+                "details":"Something went wrong with authentication.",
+                "code": "generic_authentication_error",
+                "error": error 
+            }
+        )),
+    )
+        .into_response()
+}
+
+// Personal API keys go through the same Bearer header; their scopes decide which routes they reach
+async fn authenticate_api_key(parts: &Parts, key: &str) -> Result<Claims, Response> {
+    let Some(scope) = api_key_service::required_scope(&parts.method, parts.uri.path()) else {
+        return Err(auth_error(StatusCode::FORBIDDEN, "API keys can't be used for this endpoint"));
+    };
+    let Some(db) = parts.extensions.get::<PgPool>() else {
+        return Err(auth_error(StatusCode::INTERNAL_SERVER_ERROR, "Internal auth error"));
+    };
+
+    api_key_service::authenticate(db, key, scope)
+        .await
+        .map_err(|e| auth_error(StatusCode::UNAUTHORIZED, &e.to_string()))
+}
+
 #[derive(Debug, Clone)]
 pub struct AuthUser(pub Claims);
 
@@ -40,8 +73,33 @@ where
                     .into_response()
             })?;
 
-        match decode_token(token) {
-            Ok(claims) => Ok(AuthUser(claims)),
+        let decoded = if api_key_service::is_api_key(token) {
+            Ok(authenticate_api_key(parts, token).await?)
+        } else {
+            decode_token(token)
+        };
+
+        match decoded {
+            Ok(claims) => {
+                // Fails closed: a router without the revocation store cannot authenticate anyone
+                let revoked = parts.extensions.get::<Arc<RevocationService>>()
+                    .is_none_or(|revocations| revocations.is_revoked(&claims));
+                if revoked {
+                    return Err((
+                        StatusCode::UNAUTHORIZED,
+                        Json(json!(
+                            { 
+                                "title": "Authentication Error",
+                                "details":"Something went wrong with authentication.",
+                                "code": "generic_authentication_error",
+                                "error": "Token has been revoked" 
+                            }
+                        )),
+                    )
+                        .into_response());
+                }
+                Ok(AuthUser(claims))
+            }
             Err(e) => {
                 let err_msg = e.to_string();
                 if err_msg.contains("ExpiredSignature") {
@@ -75,36 +133,3 @@ where
         }
     }
 }
-
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
-pub struct AdminUser(pub Claims);
-
-#[async_trait]
-impl<S> FromRequestParts<S> for AdminUser
-where
-    S: Send + Sync,
-{
-    type Rejection = Response;
-
-    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let AuthUser(claims) = AuthUser::from_request_parts(parts, state).await?;
-
-        if claims.is_admin {
-            Ok(AdminUser(claims))
-        } else {
-            Err((
-                StatusCode::FORBIDDEN,
-                Json(json!(
-                    { 
-                        "title": "Authentication Error",
-                        "details":"Something went wrong with authentication.",
-                        "code": "generic_authentication_error",
-                        "error": "Access denied: Admins only" 
-                    }
-                )),
-            )
-                .into_response())
-        }
-    }
-}
@@ -1 +1,3 @@
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod permission;
+pub mod ownership;
\ No newline at end of file
@@ -0,0 +1,84 @@
+use std::marker::PhantomData;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permission::Permission;
+use crate::utils::jwt::Claims;
+
+// The one ownership rule: callers act on their own user id, and on anyone else's only with
+// the permission named for that action
+pub fn may_act_for(claims: &Claims, user_id: i64, override_permission: &str) -> bool {
+    claims.sub == user_id || claims.has_permission(override_permission)
+}
+
+pub fn forbidden(override_permission: &str) -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!(
+            {
+                "title": "Authorization Error",
+                "details": "You can only act on your own account.",
+                "code": "not_owner",
+                "error": format!("Access denied: requires ownership or {}", override_permission),
+                "permission": override_permission
+            }
+        )),
+    )
+        .into_response()
+}
+
+// Caller and the `:id` user of the path, when the caller is that user or holds P
+pub struct OwnerOrPermission<P: Permission>(pub Claims, pub i64, pub PhantomData<P>);
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for OwnerOrPermission<P>
+where
+    S: Send + Sync,
+    P: Permission + Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let AuthUser(claims) = AuthUser::from_request_parts(parts, state).await?;
+        let Path(user_id) = Path::<i64>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        if !may_act_for(&claims, user_id, P::NAME) {
+            return Err(forbidden(P::NAME));
+        }
+        Ok(OwnerOrPermission(claims, user_id, PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(sub: i64, permissions: &[&str]) -> Claims {
+        serde_json::from_value(json!({
+            "sub": sub, "email": "a@example.com", "roles": [], "is_admin": false,
+            "is_user": true, "exp": 0, "permissions": permissions
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn owners_act_for_themselves() {
+        assert!(may_act_for(&claims(1, &[]), 1, "users.manage"));
+        assert!(!may_act_for(&claims(1, &[]), 2, "users.manage"));
+    }
+
+    #[test]
+    fn only_the_named_permission_overrides_ownership() {
+        assert!(may_act_for(&claims(1, &["users.manage"]), 2, "users.manage"));
+        assert!(!may_act_for(&claims(1, &["users.read"]), 2, "users.manage"));
+    }
+}
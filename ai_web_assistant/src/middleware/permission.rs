@@ -0,0 +1,74 @@
+use std::marker::PhantomData;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::middleware::auth::AuthUser;
+use crate::utils::jwt::Claims;
+
+// A permission as a type, so routes can name what they need:
+// `.route_layer(middleware::from_extractor::<RequirePermission<BackupsManage>>())`
+pub trait Permission {
+    const NAME: &'static str;
+}
+
+macro_rules! permissions {
+    ($($marker:ident => $name:literal),* $(,)?) => {
+        $(
+            pub struct $marker;
+
+            impl Permission for $marker {
+                const NAME: &'static str = $name;
+            }
+        )*
+    };
+}
+
+permissions! {
+    ChatUse => "chat.use",
+    ModelsManage => "models.manage",
+    UsersRead => "users.read",
+    UsersManage => "users.manage",
+    UsersBan => "users.ban",
+    RolesManage => "roles.manage",
+    BackupsManage => "backups.manage",
+    SystemMonitor => "system.monitor",
+}
+
+// Authenticated caller holding permission P; 403 otherwise
+pub struct RequirePermission<P: Permission>(pub Claims, pub PhantomData<P>);
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    S: Send + Sync,
+    P: Permission + Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let AuthUser(claims) = AuthUser::from_request_parts(parts, state).await?;
+
+        if claims.has_permission(P::NAME) {
+            return Ok(RequirePermission(claims, PhantomData));
+        }
+        Err((
+            StatusCode::FORBIDDEN,
+            Json(json!(
+                {
+                    "title": "Authorization Error",
+                    "details": "Your account lacks a permission this action needs.",
+                    "code": "missing_permission",
+                    "error": format!("Access denied: requires {}", P::NAME),
+                    "permission": P::NAME
+                }
+            )),
+        )
+            .into_response())
+    }
+}
@@ -13,7 +13,15 @@ pub struct User {
     pub is_verified: bool,
     pub is_staff: bool,
     pub last_login: Option<NaiveDateTime>,
-    pub email_verified_at: Option<NaiveDateTime>,
+    pub email_verified_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+}
+
+// A row of user_settings; included in chat data backups
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct UserSettings {
+    pub user_id: i64,
+    pub preferred_language: Option<String>,
+    pub theme: Option<String>,
 }
\ No newline at end of file
@@ -0,0 +1,598 @@
+// binary_protocol.rs
+//
+// Compact alternative to the JSON WebSocket framing.
+//
+// Frame layout: [version: u8][tag: u8][body]. The body is the bincode encoding
+// (standard config) of the message fields in declaration order. Tags are stable
+// within a version: new messages get new tags, and changing the fields of an
+// existing tag means bumping PROTOCOL_VERSION. Anything without a dedicated tag
+// travels under the JSON tag as UTF-8 JSON text, so both framings carry the same
+// messages.
+use anyhow::{Result, anyhow};
+use bincode::{Decode, Encode};
+use chrono::{DateTime, Utc};
+
+use crate::payloads::{
+    communication_request::CommunicationRequest,
+    communication_response::{CommunicationResponse, ConversationSummary},
+    connection_request::ConnectionRequest,
+    page_request::PageRequest,
+};
+use crate::utils::file_models::ChatMessage;
+
+pub const PROTOCOL_NAME: &str = "binary";
+pub const PROTOCOL_VERSION: u8 = 3;
+
+pub mod tags {
+    // client -> server
+    pub const AI_REQUEST: u8 = 0x01;
+    pub const FETCH_SIDEBAR_HISTORY: u8 = 0x02;
+    pub const FETCH_CONVERSATION: u8 = 0x03;
+    pub const START_NEW_SESSION: u8 = 0x04;
+    pub const EDIT_MESSAGE_CONTENT: u8 = 0x05;
+    pub const EDIT_CONTENT_TITLE: u8 = 0x06;
+    pub const DELETE_CONTENT: u8 = 0x07;
+    pub const FETCH_ALL_MESSAGES: u8 = 0x08;
+    pub const SET_CONVERSATION_TTL: u8 = 0x20;
+    pub const SET_RETENTION_POLICY: u8 = 0x21;
+    pub const FETCH_RETENTION_POLICY: u8 = 0x22;
+    pub const START_CONNECTION: u8 = 0x40;
+    pub const DISCONNECT: u8 = 0x41;
+
+    // server -> client
+    pub const STREAM_CHUNK: u8 = 0x81;
+    pub const STREAM_END: u8 = 0x82;
+    pub const SESSION_CREATED: u8 = 0x83;
+    pub const DISCONNECTED: u8 = 0x84;
+    pub const ERROR: u8 = 0x85;
+    pub const AI_RESPONSE: u8 = 0x86;
+    pub const SIDEBAR_HISTORY: u8 = 0x87;
+    pub const CONVERSATION_HISTORY: u8 = 0x88;
+    pub const MESSAGE_CREATED: u8 = 0x89;
+
+    // either direction: UTF-8 JSON text in the JSON-mode shape
+    pub const JSON: u8 = 0xFF;
+}
+
+#[derive(Debug)]
+pub enum ClientFrame {
+    Connection(ConnectionRequest),
+    Communication(CommunicationRequest),
+}
+
+#[derive(Debug)]
+pub enum ServerFrame {
+    Response(CommunicationResponse),
+    Json(serde_json::Value),
+}
+
+// Edit history is not part of the binary message; clients fetch it with list_message_revisions
+#[derive(Encode, Decode)]
+struct WireMessage {
+    message_id: String,
+    parent_id: String,
+    reply_id: Option<String>,
+    role: String,
+    content: String,
+    edited: bool,
+    timestamp: WireTimestamp,
+    edit_timestamp: Option<WireTimestamp>,
+}
+
+#[derive(Encode, Decode)]
+struct WireConversation {
+    conversation_id: String,
+    title: String,
+    created_at: WireTimestamp,
+    pinned: bool,
+    tags: Vec<String>,
+    folder: Option<String>,
+}
+
+// (seconds, nanoseconds) since the Unix epoch, lossless for DateTime<Utc>
+type WireTimestamp = (i64, u32);
+
+impl From<&ChatMessage> for WireMessage {
+    fn from(message: &ChatMessage) -> Self {
+        Self {
+            message_id: message.message_id.clone(),
+            parent_id: message.parent_id.clone(),
+            reply_id: message.reply_id.clone(),
+            role: message.role.clone(),
+            content: message.content.clone(),
+            edited: message.edited,
+            timestamp: to_wire_timestamp(&message.timestamp),
+            edit_timestamp: message.edit_timestamp.as_ref().map(to_wire_timestamp),
+        }
+    }
+}
+
+impl TryFrom<WireMessage> for ChatMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(wire: WireMessage) -> Result<Self> {
+        Ok(Self {
+            message_id: wire.message_id,
+            parent_id: wire.parent_id,
+            reply_id: wire.reply_id,
+            role: wire.role,
+            content: wire.content,
+            edited: wire.edited,
+            timestamp: from_wire_timestamp(wire.timestamp)?,
+            edit_timestamp: wire.edit_timestamp.map(from_wire_timestamp).transpose()?,
+            revisions: Vec::new(),
+        })
+    }
+}
+
+impl From<&ConversationSummary> for WireConversation {
+    fn from(summary: &ConversationSummary) -> Self {
+        Self {
+            conversation_id: summary.conversation_id.clone(),
+            title: summary.title.clone(),
+            created_at: to_wire_timestamp(&summary.created_at),
+            pinned: summary.pinned,
+            tags: summary.tags.clone(),
+            folder: summary.folder.clone(),
+        }
+    }
+}
+
+impl TryFrom<WireConversation> for ConversationSummary {
+    type Error = anyhow::Error;
+
+    fn try_from(wire: WireConversation) -> Result<Self> {
+        Ok(Self {
+            conversation_id: wire.conversation_id,
+            title: wire.title,
+            created_at: from_wire_timestamp(wire.created_at)?,
+            pinned: wire.pinned,
+            tags: wire.tags,
+            folder: wire.folder,
+        })
+    }
+}
+
+fn to_wire_timestamp(ts: &DateTime<Utc>) -> WireTimestamp {
+    (ts.timestamp(), ts.timestamp_subsec_nanos())
+}
+
+fn from_wire_timestamp((secs, nanos): WireTimestamp) -> Result<DateTime<Utc>> {
+    DateTime::from_timestamp(secs, nanos).ok_or_else(|| anyhow!("Timestamp out of range: {}.{}", secs, nanos))
+}
+
+fn frame<T: Encode>(tag: u8, body: T) -> Result<Vec<u8>> {
+    let mut frame = vec![PROTOCOL_VERSION, tag];
+    frame.extend(bincode::encode_to_vec(body, bincode::config::standard())?);
+    Ok(frame)
+}
+
+fn split_frame(frame: &[u8]) -> Result<(u8, &[u8])> {
+    match frame {
+        [version, tag, body @ ..] if *version == PROTOCOL_VERSION => Ok((*tag, body)),
+        [version, ..] => Err(anyhow!("Unsupported binary protocol version: {}", version)),
+        [] => Err(anyhow!("Empty binary frame")),
+    }
+}
+
+fn body<T: Decode<()>>(body: &[u8]) -> Result<T> {
+    let (value, read) = bincode::decode_from_slice::<T, _>(body, bincode::config::standard())?;
+    if read != body.len() {
+        return Err(anyhow!("Trailing bytes in binary frame: {} unread", body.len() - read));
+    }
+    Ok(value)
+}
+
+pub fn json_frame(text: &str) -> Vec<u8> {
+    let mut frame = vec![PROTOCOL_VERSION, tags::JSON];
+    frame.extend_from_slice(text.as_bytes());
+    frame
+}
+
+fn json_body(body: &[u8]) -> Result<serde_json::Value> {
+    Ok(serde_json::from_slice(body)?)
+}
+
+pub fn encode_client_frame(request: &ClientFrame) -> Result<Vec<u8>> {
+    match request {
+        ClientFrame::Connection(ConnectionRequest::StartConnection { token, protocol }) => {
+            frame(tags::START_CONNECTION, (token, protocol))
+        }
+        ClientFrame::Connection(ConnectionRequest::Disconnect { session_id, user_id }) => {
+            frame(tags::DISCONNECT, (session_id, user_id))
+        }
+        ClientFrame::Connection(request @ ConnectionRequest::Reauthenticate { .. }) => {
+            Ok(json_frame(&serde_json::to_string(request)?))
+        }
+        ClientFrame::Communication(request) => match request {
+            CommunicationRequest::AIRequest { prompt, session_id } => {
+                frame(tags::AI_REQUEST, (prompt, session_id))
+            }
+            CommunicationRequest::FetchSidebarHistory { user_id, tag, folder, page } => {
+                frame(tags::FETCH_SIDEBAR_HISTORY, (user_id, tag, folder, (&page.before, &page.after, &page.limit)))
+            }
+            CommunicationRequest::FetchConversation { conversation_id, page } => {
+                frame(tags::FETCH_CONVERSATION, (conversation_id, &page.before, &page.after, &page.limit))
+            }
+            CommunicationRequest::StartNewSession { user_id } => {
+                frame(tags::START_NEW_SESSION, user_id)
+            }
+            CommunicationRequest::EditMessageContentById { content_id, content } => {
+                frame(tags::EDIT_MESSAGE_CONTENT, (content_id, content))
+            }
+            CommunicationRequest::EditContentTitleById { message_id, content } => {
+                frame(tags::EDIT_CONTENT_TITLE, (message_id, content))
+            }
+            CommunicationRequest::DeleteContentTById { target_id } => {
+                frame(tags::DELETE_CONTENT, target_id)
+            }
+            CommunicationRequest::FetchAllMessages { page } => {
+                frame(tags::FETCH_ALL_MESSAGES, (&page.before, &page.after, &page.limit))
+            }
+            #[cfg(feature = "ttl")]
+            CommunicationRequest::SetConversationTtl { conversation_id, ttl_seconds } => {
+                frame(tags::SET_CONVERSATION_TTL, (conversation_id, ttl_seconds))
+            }
+            #[cfg(feature = "ttl")]
+            CommunicationRequest::SetRetentionPolicy { max_age_days, keep_last } => {
+                frame(tags::SET_RETENTION_POLICY, (max_age_days, keep_last))
+            }
+            #[cfg(feature = "ttl")]
+            CommunicationRequest::FetchRetentionPolicy => frame(tags::FETCH_RETENTION_POLICY, ()),
+            #[allow(unreachable_patterns)]
+            other => Ok(json_frame(&serde_json::to_string(other)?)),
+        },
+    }
+}
+
+pub fn decode_client_frame(frame: &[u8]) -> Result<ClientFrame> {
+    let (tag, payload) = split_frame(frame)?;
+
+    let request = match tag {
+        tags::START_CONNECTION => {
+            let (token, protocol) = body(payload)?;
+            return Ok(ClientFrame::Connection(ConnectionRequest::StartConnection { token, protocol }));
+        }
+        tags::DISCONNECT => {
+            let (session_id, user_id) = body(payload)?;
+            return Ok(ClientFrame::Connection(ConnectionRequest::Disconnect { session_id, user_id }));
+        }
+        tags::AI_REQUEST => {
+            let (prompt, session_id) = body(payload)?;
+            CommunicationRequest::AIRequest { prompt, session_id }
+        }
+        tags::FETCH_SIDEBAR_HISTORY => {
+            let (user_id, tag, folder, (before, after, limit)) = body(payload)?;
+            CommunicationRequest::FetchSidebarHistory { user_id, tag, folder, page: PageRequest { before, after, limit } }
+        }
+        tags::FETCH_CONVERSATION => {
+            let (conversation_id, before, after, limit) = body(payload)?;
+            CommunicationRequest::FetchConversation { conversation_id, page: PageRequest { before, after, limit } }
+        }
+        tags::START_NEW_SESSION => CommunicationRequest::StartNewSession { user_id: body(payload)? },
+        tags::EDIT_MESSAGE_CONTENT => {
+            let (content_id, content) = body(payload)?;
+            CommunicationRequest::EditMessageContentById { content_id, content }
+        }
+        tags::EDIT_CONTENT_TITLE => {
+            let (message_id, content) = body(payload)?;
+            CommunicationRequest::EditContentTitleById { message_id, content }
+        }
+        tags::DELETE_CONTENT => CommunicationRequest::DeleteContentTById { target_id: body(payload)? },
+        tags::FETCH_ALL_MESSAGES => {
+            let (before, after, limit) = body(payload)?;
+            CommunicationRequest::FetchAllMessages { page: PageRequest { before, after, limit } }
+        }
+        #[cfg(feature = "ttl")]
+        tags::SET_CONVERSATION_TTL => {
+            let (conversation_id, ttl_seconds) = body(payload)?;
+            CommunicationRequest::SetConversationTtl { conversation_id, ttl_seconds }
+        }
+        #[cfg(feature = "ttl")]
+        tags::SET_RETENTION_POLICY => {
+            let (max_age_days, keep_last) = body(payload)?;
+            CommunicationRequest::SetRetentionPolicy { max_age_days, keep_last }
+        }
+        #[cfg(feature = "ttl")]
+        tags::FETCH_RETENTION_POLICY => {
+            body::<()>(payload)?;
+            CommunicationRequest::FetchRetentionPolicy
+        }
+        tags::JSON => {
+            let value = json_body(payload)?;
+            return match serde_json::from_value::<ConnectionRequest>(value.clone()) {
+                Ok(connection) => Ok(ClientFrame::Connection(connection)),
+                Err(_) => Ok(ClientFrame::Communication(serde_json::from_value(value)?)),
+            };
+        }
+        other => return Err(anyhow!("Unknown client message tag: 0x{:02x}", other)),
+    };
+
+    Ok(ClientFrame::Communication(request))
+}
+
+// The request handler speaks JSON text, so binary client frames are lowered to it
+pub fn client_frame_to_json(frame: &[u8]) -> Result<String> {
+    match decode_client_frame(frame)? {
+        ClientFrame::Connection(request) => Ok(serde_json::to_string(&request)?),
+        ClientFrame::Communication(request) => Ok(serde_json::to_string(&request)?),
+    }
+}
+
+pub fn encode_response(response: &CommunicationResponse) -> Result<Vec<u8>> {
+    match response {
+        CommunicationResponse::StreamChunk { chunk } => frame(tags::STREAM_CHUNK, chunk),
+        CommunicationResponse::StreamEnd { status } => frame(tags::STREAM_END, status),
+        CommunicationResponse::SessionCreated { status, session_id, user_id } => {
+            frame(tags::SESSION_CREATED, (status, session_id, user_id))
+        }
+        CommunicationResponse::Disconnected { status } => frame(tags::DISCONNECTED, status),
+        CommunicationResponse::Error { status, error } => frame(tags::ERROR, (status, error)),
+        CommunicationResponse::AIResponse { status, response } => {
+            frame(tags::AI_RESPONSE, (status, response))
+        }
+        CommunicationResponse::SidebarHistory { status, conversations, has_more } => {
+            let conversations: Vec<WireConversation> = conversations.iter().map(WireConversation::from).collect();
+            frame(tags::SIDEBAR_HISTORY, (status, conversations, has_more))
+        }
+        CommunicationResponse::ConversationHistory { status, conversation_id, messages, has_more } => {
+            let messages: Vec<WireMessage> = messages.iter().map(WireMessage::from).collect();
+            frame(tags::CONVERSATION_HISTORY, (status, conversation_id, messages, has_more))
+        }
+        CommunicationResponse::MessageCreated { status, message_id, message } => {
+            frame(tags::MESSAGE_CREATED, (status, message_id, WireMessage::from(message)))
+        }
+        other => Ok(json_frame(&serde_json::to_string(other)?)),
+    }
+}
+
+pub fn decode_response(frame: &[u8]) -> Result<ServerFrame> {
+    let (tag, payload) = split_frame(frame)?;
+
+    let response = match tag {
+        tags::STREAM_CHUNK => CommunicationResponse::StreamChunk { chunk: body(payload)? },
+        tags::STREAM_END => CommunicationResponse::StreamEnd { status: body(payload)? },
+        tags::SESSION_CREATED => {
+            let (status, session_id, user_id) = body(payload)?;
+            CommunicationResponse::SessionCreated { status, session_id, user_id }
+        }
+        tags::DISCONNECTED => CommunicationResponse::Disconnected { status: body(payload)? },
+        tags::ERROR => {
+            let (status, error) = body(payload)?;
+            CommunicationResponse::Error { status, error }
+        }
+        tags::AI_RESPONSE => {
+            let (status, response) = body(payload)?;
+            CommunicationResponse::AIResponse { status, response }
+        }
+        tags::SIDEBAR_HISTORY => {
+            let (status, conversations, has_more): (String, Vec<WireConversation>, bool) = body(payload)?;
+            CommunicationResponse::SidebarHistory {
+                status,
+                conversations: conversations.into_iter()
+                    .map(ConversationSummary::try_from)
+                    .collect::<Result<_>>()?,
+                has_more,
+            }
+        }
+        tags::CONVERSATION_HISTORY => {
+            let (status, conversation_id, messages, has_more): (String, String, Vec<WireMessage>, bool) = body(payload)?;
+            CommunicationResponse::ConversationHistory {
+                status,
+                conversation_id,
+                messages: messages.into_iter()
+                    .map(ChatMessage::try_from)
+                    .collect::<Result<_>>()?,
+                has_more,
+            }
+        }
+        tags::MESSAGE_CREATED => {
+            let (status, message_id, message): (String, String, WireMessage) = body(payload)?;
+            CommunicationResponse::MessageCreated {
+                status,
+                message_id,
+                message: ChatMessage::try_from(message)?,
+            }
+        }
+        tags::JSON => return Ok(ServerFrame::Json(json_body(payload)?)),
+        other => return Err(anyhow!("Unknown server message tag: 0x{:02x}", other)),
+    };
+
+    Ok(ServerFrame::Response(response))
+}
+
+// Typed responses without a compact tag, or that fail to encode, go out as JSON
+pub fn encode_outgoing(response: &CommunicationResponse) -> Vec<u8> {
+    encode_response(response)
+        .unwrap_or_else(|_| json_frame(&serde_json::to_string(response).unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn as_json<T: serde::Serialize>(value: &T) -> serde_json::Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    fn page() -> PageRequest {
+        PageRequest { before: Some("m-9".into()), after: None, limit: Some(25) }
+    }
+
+    fn message() -> ChatMessage {
+        ChatMessage {
+            message_id: "m-1".into(),
+            parent_id: "c-1".into(),
+            reply_id: Some("m-2".into()),
+            role: "user".into(),
+            content: "héllo ✨".into(),
+            edited: true,
+            timestamp: Utc.timestamp_opt(1_700_000_000, 123_456_789).unwrap(),
+            edit_timestamp: Some(Utc.timestamp_opt(1_700_000_100, 1).unwrap()),
+            revisions: Vec::new(),
+        }
+    }
+
+    fn client_frames() -> Vec<(u8, ClientFrame)> {
+        use CommunicationRequest as C;
+        let mut frames = vec![
+            (tags::START_CONNECTION, ClientFrame::Connection(ConnectionRequest::StartConnection {
+                token: "jwt".into(),
+                protocol: Some(PROTOCOL_NAME.into()),
+            })),
+            (tags::DISCONNECT, ClientFrame::Connection(ConnectionRequest::Disconnect { session_id: "s-1".into(), user_id: 7 })),
+            (tags::AI_REQUEST, ClientFrame::Communication(C::AIRequest { prompt: "hi".into(), session_id: "s-1".into() })),
+            (tags::FETCH_SIDEBAR_HISTORY, ClientFrame::Communication(C::FetchSidebarHistory {
+                user_id: 7,
+                tag: Some("work".into()),
+                folder: None,
+                page: page(),
+            })),
+            (tags::FETCH_CONVERSATION, ClientFrame::Communication(C::FetchConversation { conversation_id: "c-1".into(), page: page() })),
+            (tags::START_NEW_SESSION, ClientFrame::Communication(C::StartNewSession { user_id: 7 })),
+            (tags::EDIT_MESSAGE_CONTENT, ClientFrame::Communication(C::EditMessageContentById { content_id: "m-1".into(), content: "new".into() })),
+            (tags::EDIT_CONTENT_TITLE, ClientFrame::Communication(C::EditContentTitleById { message_id: "c-1".into(), content: "title".into() })),
+            (tags::DELETE_CONTENT, ClientFrame::Communication(C::DeleteContentTById { target_id: "c-1".into() })),
+            (tags::FETCH_ALL_MESSAGES, ClientFrame::Communication(C::FetchAllMessages { page: PageRequest::default() })),
+        ];
+        #[cfg(feature = "ttl")]
+        frames.extend([
+            (tags::SET_CONVERSATION_TTL, ClientFrame::Communication(C::SetConversationTtl { conversation_id: "c-1".into(), ttl_seconds: Some(60) })),
+            (tags::SET_RETENTION_POLICY, ClientFrame::Communication(C::SetRetentionPolicy { max_age_days: Some(30), keep_last: None })),
+            (tags::FETCH_RETENTION_POLICY, ClientFrame::Communication(C::FetchRetentionPolicy)),
+        ]);
+        frames
+    }
+
+    fn client_json(frame: &ClientFrame) -> serde_json::Value {
+        match frame {
+            ClientFrame::Connection(request) => as_json(request),
+            ClientFrame::Communication(request) => as_json(request),
+        }
+    }
+
+    fn responses() -> Vec<(u8, CommunicationResponse)> {
+        use CommunicationResponse as R;
+        vec![
+            (tags::STREAM_CHUNK, R::StreamChunk { chunk: "tok".into() }),
+            (tags::STREAM_END, R::StreamEnd { status: "success".into() }),
+            (tags::SESSION_CREATED, R::SessionCreated { status: "ok".into(), session_id: "s-1".into(), user_id: 7 }),
+            (tags::DISCONNECTED, R::Disconnected { status: "disconnected".into() }),
+            (tags::ERROR, R::Error { status: "error".into(), error: "nope".into() }),
+            (tags::AI_RESPONSE, R::AIResponse { status: "ok".into(), response: "answer".into() }),
+            (tags::SIDEBAR_HISTORY, R::SidebarHistory {
+                status: "ok".into(),
+                conversations: vec![ConversationSummary {
+                    conversation_id: "c-1".into(),
+                    title: "Title".into(),
+                    created_at: Utc.timestamp_opt(1_700_000_000, 5).unwrap(),
+                    pinned: true,
+                    tags: vec!["work".into()],
+                    folder: Some("a/b".into()),
+                }],
+                has_more: true,
+            }),
+            (tags::CONVERSATION_HISTORY, R::ConversationHistory {
+                status: "ok".into(),
+                conversation_id: "c-1".into(),
+                messages: vec![message()],
+                has_more: false,
+            }),
+            (tags::MESSAGE_CREATED, R::MessageCreated { status: "message_created".into(), message_id: "m-1".into(), message: message() }),
+        ]
+    }
+
+    #[test]
+    fn client_frames_round_trip_under_their_tag() {
+        for (tag, request) in client_frames() {
+            let encoded = encode_client_frame(&request).unwrap();
+            assert_eq!(encoded[..2], [PROTOCOL_VERSION, tag]);
+            let decoded = decode_client_frame(&encoded).unwrap();
+            assert_eq!(client_json(&decoded), client_json(&request), "tag 0x{:02x}", tag);
+        }
+    }
+
+    #[test]
+    fn responses_round_trip_under_their_tag() {
+        for (tag, response) in responses() {
+            let encoded = encode_response(&response).unwrap();
+            assert_eq!(encoded[..2], [PROTOCOL_VERSION, tag]);
+            assert_eq!(encode_outgoing(&response), encoded);
+            match decode_response(&encoded).unwrap() {
+                ServerFrame::Response(decoded) => assert_eq!(as_json(&decoded), as_json(&response), "tag 0x{:02x}", tag),
+                ServerFrame::Json(value) => panic!("tag 0x{:02x} decoded as JSON: {}", tag, value),
+            }
+        }
+    }
+
+    #[test]
+    fn untagged_messages_fall_back_to_json() {
+        let requests = [
+            ClientFrame::Connection(ConnectionRequest::Reauthenticate { token: "jwt".into() }),
+            ClientFrame::Communication(CommunicationRequest::ListFolders),
+            ClientFrame::Communication(CommunicationRequest::PinConversation { conversation_id: "c-1".into(), pinned: true }),
+        ];
+        for request in requests {
+            let encoded = encode_client_frame(&request).unwrap();
+            assert_eq!(encoded[..2], [PROTOCOL_VERSION, tags::JSON]);
+            assert_eq!(client_json(&decode_client_frame(&encoded).unwrap()), client_json(&request));
+        }
+
+        let response = CommunicationResponse::SearchResults {
+            status: "ok".into(),
+            query: "q".into(),
+            results: Vec::new(),
+            has_more: false,
+        };
+        let encoded = encode_outgoing(&response);
+        assert_eq!(encoded[..2], [PROTOCOL_VERSION, tags::JSON]);
+        match decode_response(&encoded).unwrap() {
+            ServerFrame::Json(value) => assert_eq!(value, as_json(&response)),
+            ServerFrame::Response(other) => panic!("expected JSON, got {:?}", other),
+        }
+
+        let ad_hoc = json_frame(r#"{"type":"sidebar_history","pinned":[]}"#);
+        assert!(matches!(decode_response(&ad_hoc).unwrap(), ServerFrame::Json(_)));
+    }
+
+    #[test]
+    fn client_frames_lower_to_the_json_request_text() {
+        let request = ClientFrame::Communication(CommunicationRequest::AIRequest { prompt: "hi".into(), session_id: "s-1".into() });
+        let text = client_frame_to_json(&encode_client_frame(&request).unwrap()).unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&text).unwrap(), client_json(&request));
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut request = encode_client_frame(&client_frames().remove(2).1).unwrap();
+        request[0] = PROTOCOL_VERSION + 1;
+        let error = decode_client_frame(&request).unwrap_err().to_string();
+        assert!(error.contains("Unsupported binary protocol version"), "{}", error);
+
+        let mut response = encode_response(&CommunicationResponse::StreamChunk { chunk: "tok".into() }).unwrap();
+        response[0] = PROTOCOL_VERSION - 1;
+        assert!(decode_response(&response).is_err());
+
+        assert!(decode_client_frame(&[]).is_err());
+        assert!(decode_response(&[PROTOCOL_VERSION]).is_err());
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let mut request = encode_client_frame(&client_frames().remove(2).1).unwrap();
+        request.push(0);
+        let error = decode_client_frame(&request).unwrap_err().to_string();
+        assert!(error.contains("Trailing bytes"), "{}", error);
+
+        let mut response = encode_response(&CommunicationResponse::StreamEnd { status: "success".into() }).unwrap();
+        response.extend([1, 2]);
+        assert!(decode_response(&response).unwrap_err().to_string().contains("Trailing bytes"));
+    }
+
+    #[test]
+    fn unknown_tags_are_rejected() {
+        assert!(decode_client_frame(&[PROTOCOL_VERSION, 0x7e]).is_err());
+        assert!(decode_response(&[PROTOCOL_VERSION, 0xfe]).is_err());
+    }
+}
@@ -1,6 +1,10 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+use crate::helpers::{export::ExportFormat, import::ImportFormat};
+use crate::middleware::permission::{ChatUse, Permission, UsersManage, UsersRead};
+use crate::payloads::page_request::PageRequest;
+
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum CommunicationRequest {
     #[serde(rename = "ai_request")]
@@ -10,10 +14,22 @@ pub enum CommunicationRequest {
     },
 
     #[serde(rename = "fetch_sidebar_history")]
-    FetchSidebarHistory { user_id: u64 },
+    FetchSidebarHistory {
+        user_id: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tag: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        folder: Option<String>,
+        #[serde(flatten)]
+        page: PageRequest,
+    },
 
     #[serde(rename = "fetch_conversation")]
-    FetchConversation { conversation_id: String },
+    FetchConversation {
+        conversation_id: String,
+        #[serde(flatten)]
+        page: PageRequest,
+    },
 
     #[serde(rename = "start_new_session")]
     StartNewSession { user_id: u64 },
@@ -36,7 +52,259 @@ pub enum CommunicationRequest {
     },
 
     #[serde(rename = "fetch_all_messages")]
-    FetchAllMessages
+    FetchAllMessages {
+        #[serde(flatten)]
+        page: PageRequest,
+    },
+
+    #[serde(rename = "search_messages")]
+    SearchMessages {
+        query: String,
+        #[serde(default)]
+        include_archived: bool,
+        #[serde(flatten)]
+        page: PageRequest,
+    },
+
+    #[serde(rename = "pin_conversation")]
+    PinConversation {
+        conversation_id: String,
+        pinned: bool,
+    },
+
+    #[serde(rename = "set_conversation_tags")]
+    SetConversationTags {
+        conversation_id: String,
+        tags: Vec<String>,
+    },
+
+    // `null` (or an empty path) takes the conversation out of its folder
+    #[serde(rename = "move_to_folder")]
+    MoveToFolder {
+        conversation_id: String,
+        folder: Option<String>,
+    },
+
+    #[serde(rename = "archive_conversation")]
+    ArchiveConversation {
+        conversation_id: String,
+    },
+
+    #[serde(rename = "unarchive_conversation")]
+    UnarchiveConversation {
+        conversation_id: String,
+    },
+
+    #[serde(rename = "fetch_archived_conversations")]
+    FetchArchivedConversations {
+        #[serde(flatten)]
+        page: PageRequest,
+    },
+
+    #[serde(rename = "rename_folder")]
+    RenameFolder {
+        from: String,
+        to: String,
+    },
+
+    #[serde(rename = "list_folders")]
+    ListFolders,
+
+    // Without a conversation_id every conversation is exported, one frame each
+    #[serde(rename = "export_conversation")]
+    ExportConversation {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        conversation_id: Option<String>,
+        #[serde(default)]
+        format: ExportFormat,
+    },
+
+    // `content` is the uploaded file as text
+    #[serde(rename = "import_conversations")]
+    ImportConversations {
+        #[serde(default)]
+        format: ImportFormat,
+        content: String,
+    },
+
+    #[serde(rename = "create_share_link")]
+    CreateShareLink {
+        conversation_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires_in_hours: Option<u32>,
+    },
+
+    #[serde(rename = "list_share_links")]
+    ListShareLinks,
+
+    #[serde(rename = "revoke_share_link")]
+    RevokeShareLink {
+        share_id: String,
+    },
+
+    #[serde(rename = "fork_shared_conversation")]
+    ForkSharedConversation {
+        token: String,
+    },
+
+    #[serde(rename = "list_message_revisions")]
+    ListMessageRevisions {
+        message_id: String,
+    },
+
+    #[serde(rename = "diff_message_revisions")]
+    DiffMessageRevisions {
+        message_id: String,
+        from_revision: u32,
+        to_revision: u32,
+    },
+
+    #[serde(rename = "revert_message")]
+    RevertMessage {
+        message_id: String,
+        revision: u32,
+    },
+
+    #[serde(rename = "list_trash")]
+    ListTrash,
+
+    #[serde(rename = "restore")]
+    Restore {
+        trash_id: String,
+    },
+
+    #[serde(rename = "empty_trash")]
+    EmptyTrash,
+
+    #[cfg(feature = "ttl")]
+    #[serde(rename = "set_conversation_ttl")]
+    SetConversationTtl {
+        conversation_id: String,
+        ttl_seconds: Option<u64>,
+    },
+
+    #[cfg(feature = "ttl")]
+    #[serde(rename = "set_retention_policy")]
+    SetRetentionPolicy {
+        max_age_days: Option<u32>,
+        keep_last: Option<usize>,
+    },
+
+    #[cfg(feature = "ttl")]
+    #[serde(rename = "fetch_retention_policy")]
+    FetchRetentionPolicy,
+
+}
+
+impl CommunicationRequest {
+    // Permission the caller's token must carry for this message type, if any. Everything that
+    // changes the caller's conversations needs chat.use; reads need none. No wildcard arm, so a
+    // new message type has to pick one.
+    pub fn required_permission(&self) -> Option<&'static str> {
+        use CommunicationRequest::*;
+        match self {
+            AIRequest { .. }
+            | StartNewSession { .. }
+            | EditMessageContentById { .. }
+            | EditContentTitleById { .. }
+            | DeleteContentTById { .. }
+            | PinConversation { .. }
+            | SetConversationTags { .. }
+            | MoveToFolder { .. }
+            | ArchiveConversation { .. }
+            | UnarchiveConversation { .. }
+            | RenameFolder { .. }
+            | ImportConversations { .. }
+            | CreateShareLink { .. }
+            | RevokeShareLink { .. }
+            | ForkSharedConversation { .. }
+            | RevertMessage { .. }
+            | Restore { .. }
+            | EmptyTrash => Some(ChatUse::NAME),
+            #[cfg(feature = "ttl")]
+            SetConversationTtl { .. } | SetRetentionPolicy { .. } => Some(ChatUse::NAME),
+
+            FetchSidebarHistory { .. }
+            | FetchConversation { .. }
+            | FetchAllMessages { .. }
+            | SearchMessages { .. }
+            | FetchArchivedConversations { .. }
+            | ListFolders
+            | ExportConversation { .. }
+            | ListShareLinks
+            | ListMessageRevisions { .. }
+            | DiffMessageRevisions { .. }
+            | ListTrash => None,
+            #[cfg(feature = "ttl")]
+            FetchRetentionPolicy => None,
+        }
+    }
+
+    // User id the client names in the message, with the permission needed when it isn't
+    // the connected user's own
+    pub fn target_user(&self) -> Option<(i64, &'static str)> {
+        let target = |user_id: &u64| i64::try_from(*user_id).unwrap_or(-1);
+        match self {
+            CommunicationRequest::FetchSidebarHistory { user_id, .. } => Some((target(user_id), UsersRead::NAME)),
+            CommunicationRequest::StartNewSession { user_id } => Some((target(user_id), UsersManage::NAME)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(value: serde_json::Value) -> CommunicationRequest {
+        serde_json::from_value(value).unwrap()
+    }
 
+    #[test]
+    fn state_changing_messages_need_chat_use() {
+        let messages = [
+            json!({"type": "ai_request", "prompt": "hi", "session_id": "s"}),
+            json!({"type": "start_new_session", "user_id": 1}),
+            json!({"type": "edit_content_title", "content_id": "m", "content": "x"}),
+            json!({"type": "edit_content", "message_id": "c", "content": "x"}),
+            json!({"type": "delete_content", "target_id": "c"}),
+            json!({"type": "pin_conversation", "conversation_id": "c", "pinned": true}),
+            json!({"type": "set_conversation_tags", "conversation_id": "c", "tags": []}),
+            json!({"type": "move_to_folder", "conversation_id": "c", "folder": null}),
+            json!({"type": "archive_conversation", "conversation_id": "c"}),
+            json!({"type": "unarchive_conversation", "conversation_id": "c"}),
+            json!({"type": "rename_folder", "from": "a", "to": "b"}),
+            json!({"type": "import_conversations", "content": "[]"}),
+            json!({"type": "create_share_link", "conversation_id": "c"}),
+            json!({"type": "revoke_share_link", "share_id": "s"}),
+            json!({"type": "fork_shared_conversation", "token": "t"}),
+            json!({"type": "revert_message", "message_id": "m", "revision": 1}),
+            json!({"type": "restore", "trash_id": "t"}),
+            json!({"type": "empty_trash"}),
+        ];
+        for message in messages {
+            assert_eq!(request(message.clone()).required_permission(), Some(ChatUse::NAME), "{}", message);
+        }
+    }
 
+    #[test]
+    fn reads_need_no_permission() {
+        let messages = [
+            json!({"type": "fetch_sidebar_history", "user_id": 1}),
+            json!({"type": "fetch_conversation", "conversation_id": "c"}),
+            json!({"type": "fetch_all_messages"}),
+            json!({"type": "search_messages", "query": "q"}),
+            json!({"type": "fetch_archived_conversations"}),
+            json!({"type": "list_folders"}),
+            json!({"type": "export_conversation"}),
+            json!({"type": "list_share_links"}),
+            json!({"type": "list_message_revisions", "message_id": "m"}),
+            json!({"type": "diff_message_revisions", "message_id": "m", "from_revision": 1, "to_revision": 2}),
+            json!({"type": "list_trash"}),
+        ];
+        for message in messages {
+            assert_eq!(request(message.clone()).required_permission(), None, "{}", message);
+        }
+    }
 }
@@ -1,9 +1,9 @@
 use chrono::{DateTime, Utc};
 // communication_response.rs
-use serde::{Serialize};
+use serde::{Deserialize, Serialize};
 
 use crate::utils::file_models::ChatMessage;
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum CommunicationResponse {
     #[serde(rename = "stream_chunk")]
@@ -44,6 +44,8 @@ pub enum CommunicationResponse {
     SidebarHistory {
         status: String,
         conversations: Vec<ConversationSummary>,
+        #[serde(default)]
+        has_more: bool,
     },
 
     #[serde(rename = "conversation_history")]
@@ -51,6 +53,16 @@ pub enum CommunicationResponse {
         status: String,
         conversation_id: String,
         messages: Vec<ChatMessage>,
+        #[serde(default)]
+        has_more: bool,
+    },
+
+    #[serde(rename = "search_results")]
+    SearchResults {
+        status: String,
+        query: String,
+        results: Vec<MessageWithContext>,
+        has_more: bool,
     },
 
     MessageCreated  {
@@ -60,17 +72,25 @@ pub enum CommunicationResponse {
     },
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversationSummary {
     pub conversation_id: String,
     pub title: String,
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub folder: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageWithContext {
     pub message: ChatMessage,
     pub conversation_id: String,
     pub conversation_title: String,
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub archived: bool,
 }
\ No newline at end of file
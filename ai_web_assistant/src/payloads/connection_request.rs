@@ -1,11 +1,20 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ConnectionRequest {
     #[serde(rename = "start_connection")]
-    StartConnection { token: String },
+    StartConnection {
+        token: String,
+        // "json" (default) or "binary" when the server is built with binary-protocol
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        protocol: Option<String>,
+    },
 
     #[serde(rename = "disconnect")]
     Disconnect { session_id: String, user_id: u64 },
+
+    // Swaps in a fresh access token (from /auth/refresh) without reconnecting
+    #[serde(rename = "reauthenticate")]
+    Reauthenticate { token: String },
 }
@@ -0,0 +1,9 @@
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct LogoutRequest {
+    /// Also log out every other device and close their WebSocket connections
+    #[serde(default)]
+    pub all_devices: bool,
+}
@@ -1,5 +1,12 @@
 pub mod register_request;
 pub mod login_request;
+pub mod refresh_request;
+pub mod logout_request;
+pub mod password_request;
+pub mod two_factor_request;
 pub mod communication_request;
 pub mod communication_response;
-pub mod connection_request;
\ No newline at end of file
+pub mod connection_request;
+pub mod page_request;
+#[cfg(feature = "binary-protocol")]
+pub mod binary_protocol;
\ No newline at end of file
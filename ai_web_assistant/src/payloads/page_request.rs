@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Cursor paging shared by list requests. `before` / `after` take either an item id
+// or an RFC 3339 timestamp; with neither, the newest `limit` items are returned.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PageRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cursor {
+    Id(String),
+    Timestamp(DateTime<Utc>),
+}
+
+impl Cursor {
+    pub fn parse(raw: &str) -> Self {
+        match DateTime::parse_from_rfc3339(raw) {
+            Ok(ts) => Cursor::Timestamp(ts.with_timezone(&Utc)),
+            Err(_) => Cursor::Id(raw.to_string()),
+        }
+    }
+}
@@ -0,0 +1,10 @@
+use serde::Deserialize;
+use validator::Validate;
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct RefreshRequest {
+    /// Refresh token from the last login or refresh; it can only be used once
+    #[validate(length(min = 1, message = "Refresh token is required"))]
+    pub refresh_token: String,
+}
@@ -0,0 +1,29 @@
+use serde::Deserialize;
+use validator::Validate;
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct TwoFactorLoginRequest {
+    /// `pending_token` from /auth/login
+    #[validate(length(min = 1, message = "Pending token is required"))]
+    pub pending_token: String,
+
+    /// Authenticator code or a recovery code
+    #[validate(length(min = 1, message = "Code is required"))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct TwoFactorCodeRequest {
+    #[validate(length(min = 1, message = "Code is required"))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct DisableTwoFactorRequest {
+    #[validate(length(min = 1, message = "Password is required"))]
+    pub password: String,
+
+    #[validate(length(min = 1, message = "Code is required"))]
+    pub code: String,
+}
@@ -0,0 +1,158 @@
+use sqlx::{PgPool, Row, postgres::PgRow};
+use uuid::Uuid;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyRecord {
+    pub id: String,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+// A usable key together with what authentication needs to know about its owner
+#[derive(Debug, Clone)]
+pub struct ApiKeyOwner {
+    pub key_id: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub user_id: i64,
+    pub email: String,
+    pub is_admin: bool,
+}
+
+pub struct ApiKeyRepository {
+    pub db: PgPool,
+}
+
+impl ApiKeyRepository {
+    pub async fn create(
+        &self,
+        user_id: i64,
+        name: &str,
+        key_prefix: &str,
+        key_hash: &str,
+        scopes: &[String],
+        expires_at: DateTime<Utc>,
+    ) -> Result<ApiKeyRecord> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO api_keys (user_id, name, key_prefix, key_hash, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, name, key_prefix, scopes, created_at, expires_at, last_used_at, revoked_at
+            "#
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(key_prefix)
+        .bind(key_hash)
+        .bind(scopes)
+        .bind(expires_at)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(map_api_key(row))
+    }
+
+    pub async fn list_for_user(&self, user_id: i64) -> Result<Vec<ApiKeyRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, name, key_prefix, scopes, created_at, expires_at, last_used_at, revoked_at
+            FROM api_keys
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows.into_iter().map(map_api_key).collect())
+    }
+
+    // Unrevoked, unexpired key of an active account
+    pub async fn find_active_by_hash(&self, key_hash: &str) -> Result<Option<ApiKeyOwner>> {
+        let row = sqlx::query(
+            r#"
+            SELECT k.id, k.scopes, k.created_at, k.expires_at, u.id AS user_id, u.email, u.is_admin
+            FROM api_keys k
+            JOIN users u ON u.id = k.user_id
+            WHERE k.key_hash = $1
+              AND k.revoked_at IS NULL
+              AND k.expires_at > NOW()
+              AND u.is_active
+            "#
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(row.map(|row| ApiKeyOwner {
+            key_id: row.get::<Uuid, _>("id").to_string(),
+            scopes: row.get("scopes"),
+            created_at: row.get("created_at"),
+            expires_at: row.get("expires_at"),
+            user_id: row.get("user_id"),
+            email: row.get("email"),
+            is_admin: row.get("is_admin"),
+        }))
+    }
+
+    // At most one write a minute per key, however busy it is
+    pub async fn touch(&self, key_id: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE api_keys SET last_used_at = NOW()
+            WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+            "#
+        )
+        .bind(Uuid::parse_str(key_id)?)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    // False when the user has no such unrevoked key
+    pub async fn revoke(&self, user_id: i64, key_id: Uuid) -> Result<bool> {
+        let rows = sqlx::query("UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL")
+            .bind(key_id)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    pub async fn revoke_all_for_user(&self, user_id: i64) -> Result<Vec<String>> {
+        let ids: Vec<Uuid> = sqlx::query_scalar(
+            "UPDATE api_keys SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL RETURNING id"
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(ids.into_iter().map(|id| id.to_string()).collect())
+    }
+}
+
+fn map_api_key(row: PgRow) -> ApiKeyRecord {
+    ApiKeyRecord {
+        id: row.get::<Uuid, _>("id").to_string(),
+        name: row.get("name"),
+        key_prefix: row.get("key_prefix"),
+        scopes: row.get("scopes"),
+        created_at: row.get("created_at"),
+        expires_at: row.get("expires_at"),
+        last_used_at: row.get("last_used_at"),
+        revoked_at: row.get("revoked_at"),
+    }
+}
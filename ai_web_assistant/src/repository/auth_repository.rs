@@ -44,7 +44,41 @@ impl AuthenticationRepository {
         Ok(SafeUser::from(user))
     }
 
-    pub async fn login_user(&self, email: &str) -> Result<User> {
+    // Accounts created through single sign-on: the identity provider vouched for the email,
+    // and the random password is never shown to anyone
+    pub async fn create_sso_user(&self, email: &str, username: &str, password: &str) -> Result<User> {
+        let uuid = Uuid::new_v4().to_string();
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO users (uuid, email, username, password, is_verified, email_verified_at)
+            VALUES ($1, $2, $3, $4, TRUE, NOW())
+            RETURNING 
+                id, 
+                email, 
+                username, 
+                password, 
+                is_active, 
+                is_admin, 
+                is_verified, 
+                is_staff,
+                last_login,
+                email_verified_at,
+                created_at,
+                updated_at
+            "#
+        )
+        .bind(&uuid)
+        .bind(email)
+        .bind(username)
+        .bind(password)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(map_user(row))
+    }
+
+    pub async fn login_user(&self, email: &str) -> Result<Option<User>> {
         let row = sqlx::query(
             r#"
             SELECT 
@@ -68,10 +102,7 @@ impl AuthenticationRepository {
         .fetch_optional(&self.db)
         .await?;
 
-        match row {
-            Some(row) => Ok(map_user(row)),
-            None => Err(anyhow!("Invalid credentials")),
-        }
+        Ok(row.map(map_user))
     }
 }
 
@@ -86,7 +117,7 @@ fn map_user(row: PgRow) -> User {
         is_verified: row.get("is_verified"),
         is_staff: row.get("is_staff"),
         last_login: row.get::<Option<NaiveDateTime>, _>("last_login"),
-        email_verified_at: row.get::<Option<NaiveDateTime>, _>("email_verified_at"),
+        email_verified_at: row.get("email_verified_at"),
         created_at: row.get("created_at"),
         updated_at: row.get("updated_at"),
     }
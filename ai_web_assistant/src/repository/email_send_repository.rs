@@ -0,0 +1,47 @@
+use sqlx::{PgPool, Row};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+pub struct RecentSends {
+    pub count: i64,
+    pub first_sent_at: Option<DateTime<Utc>>,
+    pub last_sent_at: Option<DateTime<Utc>>,
+}
+
+pub struct EmailSendRepository {
+    pub db: PgPool,
+}
+
+impl EmailSendRepository {
+    pub async fn record(&self, user_id: i64, kind: &str) -> Result<()> {
+        sqlx::query("INSERT INTO email_sends (user_id, kind) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(kind)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    // How many emails of this kind went out since `since`, and when the first and last did
+    pub async fn recent(&self, user_id: i64, kind: &str, since: DateTime<Utc>) -> Result<RecentSends> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) AS sent, MIN(sent_at) AS first_sent_at, MAX(sent_at) AS last_sent_at
+            FROM email_sends
+            WHERE user_id = $1 AND kind = $2 AND sent_at > $3
+            "#
+        )
+        .bind(user_id)
+        .bind(kind)
+        .bind(since)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(RecentSends {
+            count: row.get("sent"),
+            first_sent_at: row.get("first_sent_at"),
+            last_sent_at: row.get("last_sent_at"),
+        })
+    }
+}
@@ -0,0 +1,118 @@
+use sqlx::{PgPool, Row};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+pub const ACCOUNT: &str = "account";
+pub const IP: &str = "ip";
+
+pub struct FailureStats {
+    pub count: i64,
+    pub last_failed_at: Option<DateTime<Utc>>,
+}
+
+pub struct LoginAttemptRepository {
+    pub db: PgPool,
+}
+
+impl LoginAttemptRepository {
+    pub async fn record(&self, email: &str, ip: &str, succeeded: bool) -> Result<()> {
+        sqlx::query("INSERT INTO login_attempts (email, ip, succeeded) VALUES ($1, $2, $3)")
+            .bind(email)
+            .bind(ip)
+            .bind(succeeded)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    // A successful login wipes the account's failures; the lockouts table keeps the audit trail
+    pub async fn clear_failures(&self, email: &str) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM login_attempts WHERE email = $1 AND NOT succeeded")
+            .bind(email)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Failures for an email or IP since `since`, counting only those after the subject's last lockout
+    pub async fn failures(&self, scope: &str, subject: &str, since: DateTime<Utc>) -> Result<FailureStats> {
+        let column = if scope == ACCOUNT { "email" } else { "ip" };
+        let query = format!(
+            r#"
+            SELECT COUNT(*) AS failures, MAX(attempted_at) AS last_failed_at
+            FROM login_attempts
+            WHERE {column} = $1 AND NOT succeeded AND attempted_at > GREATEST(
+                $2,
+                COALESCE((SELECT MAX(created_at) FROM login_lockouts WHERE scope = $3 AND subject = $1), $2)
+            )
+            "#
+        );
+
+        let row = sqlx::query(&query)
+            .bind(subject)
+            .bind(since)
+            .bind(scope)
+            .fetch_one(&self.db)
+            .await?;
+
+        Ok(FailureStats {
+            count: row.get("failures"),
+            last_failed_at: row.get("last_failed_at"),
+        })
+    }
+
+    // Latest end of any lockout still in force for the email or the IP
+    pub async fn locked_until(&self, email: &str, ip: &str) -> Result<Option<DateTime<Utc>>> {
+        let locked_until: Option<DateTime<Utc>> = sqlx::query_scalar(
+            r#"
+            SELECT MAX(locked_until) FROM login_lockouts
+            WHERE ((scope = 'account' AND subject = $1) OR (scope = 'ip' AND subject = $2))
+              AND locked_until > NOW()
+            "#
+        )
+        .bind(email)
+        .bind(ip)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(locked_until)
+    }
+
+    pub async fn record_lockout(
+        &self,
+        scope: &str,
+        subject: &str,
+        user_id: Option<i64>,
+        ip: &str,
+        failures: i64,
+        locked_until: DateTime<Utc>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO login_lockouts (scope, subject, user_id, ip, failures, locked_until)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#
+        )
+        .bind(scope)
+        .bind(subject)
+        .bind(user_id)
+        .bind(ip)
+        .bind(i32::try_from(failures).unwrap_or(i32::MAX))
+        .bind(locked_until)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn prune(&self, before: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM login_attempts WHERE attempted_at < $1")
+            .bind(before)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -1,3 +1,11 @@
-
 pub mod user_repository;
-pub mod auth_repository;
\ No newline at end of file
+pub mod auth_repository;
+pub mod refresh_token_repository;
+pub mod revocation_repository;
+pub mod email_send_repository;
+pub mod password_reset_repository;
+pub mod two_factor_repository;
+pub mod api_key_repository;
+pub mod oidc_repository;
+pub mod login_attempt_repository;
+pub mod role_repository;
\ No newline at end of file
@@ -0,0 +1,72 @@
+use sqlx::{PgPool, Row};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+pub struct OidcRepository {
+    pub db: PgPool,
+}
+
+impl OidcRepository {
+    pub async fn save_state(&self, state: &str, code_verifier: &str, nonce: &str, expires_at: DateTime<Utc>) -> Result<()> {
+        // Abandoned logins are cleared as new ones start
+        sqlx::query("DELETE FROM oidc_login_states WHERE expires_at < NOW()")
+            .execute(&self.db)
+            .await?;
+
+        sqlx::query("INSERT INTO oidc_login_states (state, code_verifier, nonce, expires_at) VALUES ($1, $2, $3, $4)")
+            .bind(state)
+            .bind(code_verifier)
+            .bind(nonce)
+            .bind(expires_at)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    // Single use: returns (code_verifier, nonce) and forgets the state
+    pub async fn take_state(&self, state: &str) -> Result<Option<(String, String)>> {
+        let row = sqlx::query(
+            "DELETE FROM oidc_login_states WHERE state = $1 AND expires_at > NOW() RETURNING code_verifier, nonce"
+        )
+        .bind(state)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(row.map(|row| (row.get("code_verifier"), row.get("nonce"))))
+    }
+
+    pub async fn find_linked_user(&self, issuer: &str, subject: &str) -> Result<Option<i64>> {
+        let user_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE user_identities SET last_login_at = NOW()
+            WHERE issuer = $1 AND subject = $2
+            RETURNING user_id
+            "#
+        )
+        .bind(issuer)
+        .bind(subject)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    pub async fn link(&self, user_id: i64, issuer: &str, subject: &str, email: Option<&str>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_identities (user_id, issuer, subject, email)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (issuer, subject) DO NOTHING
+            "#
+        )
+        .bind(user_id)
+        .bind(issuer)
+        .bind(subject)
+        .bind(email)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use sqlx::PgPool;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+pub struct PasswordResetRepository {
+    pub db: PgPool,
+}
+
+impl PasswordResetRepository {
+    // Stores a new token and invalidates the user's older ones
+    pub async fn create(&self, user_id: i64, token_hash: &str, expires_at: DateTime<Utc>) -> Result<()> {
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query("UPDATE password_reset_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)")
+            .bind(user_id)
+            .bind(token_hash)
+            .bind(expires_at)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Marks the token used and returns its user; None if it is unknown, used or expired
+    pub async fn consume(&self, token_hash: &str) -> Result<Option<i64>> {
+        let user_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE password_reset_tokens
+            SET used_at = NOW()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+            RETURNING user_id
+            "#
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    pub async fn invalidate_for_user(&self, user_id: i64) -> Result<u64> {
+        let rows = sqlx::query("UPDATE password_reset_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL")
+            .bind(user_id)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        Ok(rows)
+    }
+}
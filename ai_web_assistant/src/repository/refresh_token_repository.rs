@@ -0,0 +1,173 @@
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+pub struct RefreshTokenRecord {
+    pub id: Uuid,
+    pub user_id: i64,
+    pub family_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+// A login session (refresh token family) that can still be refreshed
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveSession {
+    pub session_id: String,
+    pub started_at: DateTime<Utc>,
+    pub last_refreshed_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub struct RefreshTokenRepository {
+    pub db: PgPool,
+}
+
+impl RefreshTokenRepository {
+    pub async fn create(&self, user_id: i64, family_id: Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<Uuid> {
+        let id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#
+        )
+        .bind(user_id)
+        .bind(family_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(id)
+    }
+
+    pub async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshTokenRecord>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, user_id, family_id, expires_at, used_at, revoked_at
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            "#
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(row.map(|row| RefreshTokenRecord {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            family_id: row.get("family_id"),
+            expires_at: row.get("expires_at"),
+            used_at: row.get("used_at"),
+            revoked_at: row.get("revoked_at"),
+        }))
+    }
+
+    // Marks the token used and stores its replacement in one transaction.
+    // Returns false when the token was already used or revoked by a concurrent request.
+    pub async fn rotate(&self, current: &RefreshTokenRecord, token_hash: &str, expires_at: DateTime<Utc>) -> Result<bool> {
+        let mut tx = self.db.begin().await?;
+
+        let claimed = sqlx::query(
+            "UPDATE refresh_tokens SET used_at = NOW() WHERE id = $1 AND used_at IS NULL AND revoked_at IS NULL"
+        )
+        .bind(current.id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        if claimed == 0 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        let replacement: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#
+        )
+        .bind(current.user_id)
+        .bind(current.family_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE refresh_tokens SET replaced_by = $2 WHERE id = $1")
+            .bind(current.id)
+            .bind(replacement)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn revoke_family(&self, family_id: Uuid) -> Result<u64> {
+        let rows = sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL")
+            .bind(family_id)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        Ok(rows)
+    }
+
+    // Only revokes a family that belongs to the user; 0 means no such active session
+    pub async fn revoke_user_family(&self, user_id: i64, family_id: Uuid) -> Result<u64> {
+        let rows = sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = NOW() WHERE user_id = $1 AND family_id = $2 AND revoked_at IS NULL"
+        )
+        .bind(user_id)
+        .bind(family_id)
+        .execute(&self.db)
+        .await?
+        .rows_affected();
+
+        Ok(rows)
+    }
+
+    pub async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64> {
+        let rows = sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+            .bind(user_id)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        Ok(rows)
+    }
+
+    pub async fn active_sessions(&self, user_id: i64) -> Result<Vec<ActiveSession>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                family_id,
+                MIN(created_at) AS started_at,
+                MAX(created_at) AS last_refreshed_at,
+                MAX(expires_at) AS expires_at
+            FROM refresh_tokens
+            WHERE user_id = $1
+            GROUP BY family_id
+            HAVING BOOL_OR(used_at IS NULL AND revoked_at IS NULL AND expires_at > NOW())
+            ORDER BY MAX(created_at) DESC
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| ActiveSession {
+            session_id: row.get::<Uuid, _>("family_id").to_string(),
+            started_at: row.get("started_at"),
+            last_refreshed_at: row.get("last_refreshed_at"),
+            expires_at: row.get("expires_at"),
+        }).collect())
+    }
+}
@@ -0,0 +1,80 @@
+use sqlx::{PgPool, Row, postgres::PgRow};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone)]
+pub struct RevocationRecord {
+    pub id: i64,
+    pub scope: String,
+    pub subject: String,
+    pub revoked_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub struct RevocationRepository {
+    pub db: PgPool,
+}
+
+impl RevocationRepository {
+    pub async fn insert(
+        &self,
+        scope: &str,
+        subject: &str,
+        reason: &str,
+        revoked_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<RevocationRecord> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO token_revocations (scope, subject, reason, revoked_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, scope, subject, revoked_at, expires_at
+            "#
+        )
+        .bind(scope)
+        .bind(subject)
+        .bind(reason)
+        .bind(revoked_at)
+        .bind(expires_at)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(map_revocation(row))
+    }
+
+    // Unexpired revocations recorded after `after_id`, oldest first
+    pub async fn since(&self, after_id: i64) -> Result<Vec<RevocationRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, scope, subject, revoked_at, expires_at
+            FROM token_revocations
+            WHERE id > $1 AND expires_at > NOW()
+            ORDER BY id
+            "#
+        )
+        .bind(after_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows.into_iter().map(map_revocation).collect())
+    }
+
+    pub async fn delete_expired(&self) -> Result<u64> {
+        let rows = sqlx::query("DELETE FROM token_revocations WHERE expires_at <= NOW()")
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        Ok(rows)
+    }
+}
+
+fn map_revocation(row: PgRow) -> RevocationRecord {
+    RevocationRecord {
+        id: row.get("id"),
+        scope: row.get("scope"),
+        subject: row.get("subject"),
+        revoked_at: row.get("revoked_at"),
+        expires_at: row.get("expires_at"),
+    }
+}
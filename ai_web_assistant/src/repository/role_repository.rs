@@ -0,0 +1,103 @@
+use sqlx::{PgPool, Row};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+// What a user may do; embedded in their access tokens
+#[derive(Debug, Clone, Default)]
+pub struct UserAccess {
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoleRecord {
+    pub name: String,
+    pub description: String,
+    pub permissions: Vec<String>,
+}
+
+pub struct RoleRepository {
+    pub db: PgPool,
+}
+
+impl RoleRepository {
+    pub async fn access_for_user(&self, user_id: i64) -> Result<UserAccess> {
+        let roles: Vec<String> = sqlx::query_scalar(
+            "SELECT role_name FROM user_roles WHERE user_id = $1 ORDER BY role_name"
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        let permissions: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT DISTINCT rp.permission
+            FROM user_roles ur
+            JOIN role_permissions rp ON rp.role_name = ur.role_name
+            WHERE ur.user_id = $1
+            ORDER BY rp.permission
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(UserAccess { roles, permissions })
+    }
+
+    pub async fn list_roles(&self) -> Result<Vec<RoleRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.name, r.description,
+                   COALESCE(ARRAY_AGG(rp.permission ORDER BY rp.permission) FILTER (WHERE rp.permission IS NOT NULL), '{}') AS permissions
+            FROM roles r
+            LEFT JOIN role_permissions rp ON rp.role_name = r.name
+            GROUP BY r.name, r.description
+            ORDER BY r.name
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| RoleRecord {
+                name: row.get("name"),
+                description: row.get("description"),
+                permissions: row.get("permissions"),
+            })
+            .collect())
+    }
+
+    // Replaces the user's roles. The is_admin / is_staff columns follow, so profiles stay accurate.
+    pub async fn set_user_roles(&self, user_id: i64, roles: &[String]) -> Result<()> {
+        let known: Vec<String> = sqlx::query_scalar("SELECT name FROM roles WHERE name = ANY($1)")
+            .bind(roles)
+            .fetch_all(&self.db)
+            .await?;
+        if let Some(unknown) = roles.iter().find(|role| !known.contains(role)) {
+            return Err(anyhow!("Unknown role {:?}", unknown));
+        }
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query("DELETE FROM user_roles WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("INSERT INTO user_roles (user_id, role_name) SELECT $1, UNNEST($2::TEXT[])")
+            .bind(user_id)
+            .bind(roles)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "UPDATE users SET is_admin = ('admin' = ANY($2)), is_staff = ('staff' = ANY($2)), updated_at = NOW() WHERE id = $1"
+        )
+        .bind(user_id)
+        .bind(roles)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use sqlx::{PgPool, Row};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct TwoFactorRecord {
+    pub user_id: i64,
+    pub secret: String,
+    pub enabled_at: Option<DateTime<Utc>>,
+}
+
+pub struct TwoFactorRepository {
+    pub db: PgPool,
+}
+
+impl TwoFactorRepository {
+    pub async fn find(&self, user_id: i64) -> Result<Option<TwoFactorRecord>> {
+        let row = sqlx::query("SELECT user_id, secret, enabled_at FROM user_two_factor WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.db)
+            .await?;
+
+        Ok(row.map(|row| TwoFactorRecord {
+            user_id: row.get("user_id"),
+            secret: row.get("secret"),
+            enabled_at: row.get("enabled_at"),
+        }))
+    }
+
+    pub async fn is_enabled(&self, user_id: i64) -> Result<bool> {
+        let enabled: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM user_two_factor WHERE user_id = $1 AND enabled_at IS NOT NULL)"
+        )
+        .bind(user_id)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(enabled)
+    }
+
+    // Starts (or restarts) enrolment; an already enabled secret is never replaced
+    pub async fn start_enrolment(&self, user_id: i64, secret: &str) -> Result<bool> {
+        let rows = sqlx::query(
+            r#"
+            INSERT INTO user_two_factor (user_id, secret)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+                SET secret = EXCLUDED.secret, created_at = NOW(), last_used_step = NULL
+                WHERE user_two_factor.enabled_at IS NULL
+            "#
+        )
+        .bind(user_id)
+        .bind(secret)
+        .execute(&self.db)
+        .await?
+        .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    // Records the time step of an accepted code. False means it (or a later one) was used already.
+    pub async fn claim_step(&self, user_id: i64, step: i64) -> Result<bool> {
+        let rows = sqlx::query(
+            r#"
+            UPDATE user_two_factor
+            SET last_used_step = $2
+            WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(&self.db)
+        .await?
+        .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    // Turns 2FA on and stores the first set of recovery codes
+    pub async fn enable(&self, user_id: i64, code_hashes: &[String]) -> Result<()> {
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query("UPDATE user_two_factor SET enabled_at = NOW() WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        replace_codes(&mut tx, user_id, code_hashes).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn replace_recovery_codes(&self, user_id: i64, code_hashes: &[String]) -> Result<()> {
+        let mut tx = self.db.begin().await?;
+        replace_codes(&mut tx, user_id, code_hashes).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn use_recovery_code(&self, user_id: i64, code_hash: &str) -> Result<bool> {
+        let rows = sqlx::query(
+            "UPDATE two_factor_recovery_codes SET used_at = NOW() WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL"
+        )
+        .bind(user_id)
+        .bind(code_hash)
+        .execute(&self.db)
+        .await?
+        .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    pub async fn remaining_recovery_codes(&self, user_id: i64) -> Result<i64> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM two_factor_recovery_codes WHERE user_id = $1 AND used_at IS NULL"
+        )
+        .bind(user_id)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(count)
+    }
+
+    pub async fn create_pending_login(&self, jti: Uuid, user_id: i64, expires_at: DateTime<Utc>) -> Result<()> {
+        sqlx::query("INSERT INTO two_factor_pending_logins (jti, user_id, expires_at) VALUES ($1, $2, $3)")
+            .bind(jti)
+            .bind(user_id)
+            .bind(expires_at)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    // Reserves one code attempt on a pending login before the code is checked; the attempt
+    // that reaches `max_attempts` uses the login up. None when it is unknown, expired or used up.
+    pub async fn take_attempt(&self, jti: Uuid, user_id: i64, max_attempts: i32) -> Result<Option<i32>> {
+        let attempts = sqlx::query_scalar(
+            r#"
+            UPDATE two_factor_pending_logins
+            SET attempts = attempts + 1,
+                consumed_at = CASE WHEN attempts + 1 >= $3 THEN NOW() END
+            WHERE jti = $1 AND user_id = $2 AND consumed_at IS NULL AND expires_at > NOW()
+            RETURNING attempts
+            "#
+        )
+        .bind(jti)
+        .bind(user_id)
+        .bind(max_attempts)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(attempts)
+    }
+
+    pub async fn complete_pending_login(&self, jti: Uuid) -> Result<()> {
+        sqlx::query("UPDATE two_factor_pending_logins SET succeeded = TRUE, consumed_at = NOW() WHERE jti = $1")
+            .bind(jti)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    // Wrong codes entered since `since`, across all of the user's pending logins. A completed
+    // login starts the count over.
+    pub async fn recent_failed_attempts(&self, user_id: i64, since: DateTime<Utc>) -> Result<i64> {
+        let failures: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COALESCE(SUM(attempts - succeeded::INT), 0)::BIGINT
+            FROM two_factor_pending_logins
+            WHERE user_id = $1
+              AND created_at > GREATEST($2, COALESCE(
+                  (SELECT MAX(consumed_at) FROM two_factor_pending_logins WHERE user_id = $1 AND succeeded),
+                  '-infinity'
+              ))
+            "#
+        )
+        .bind(user_id)
+        .bind(since)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(failures)
+    }
+
+    // Ends every open pending login of the user, so each needs the password again
+    pub async fn consume_pending_logins(&self, user_id: i64) -> Result<u64> {
+        let rows = sqlx::query(
+            "UPDATE two_factor_pending_logins SET consumed_at = NOW() WHERE user_id = $1 AND consumed_at IS NULL"
+        )
+        .bind(user_id)
+        .execute(&self.db)
+        .await?
+        .rows_affected();
+
+        Ok(rows)
+    }
+
+    pub async fn prune_pending_logins(&self, before: DateTime<Utc>) -> Result<u64> {
+        let rows = sqlx::query("DELETE FROM two_factor_pending_logins WHERE expires_at < $1")
+            .bind(before)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        Ok(rows)
+    }
+
+    pub async fn disable(&self, user_id: i64) -> Result<()> {
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query("DELETE FROM two_factor_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM user_two_factor WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+async fn replace_codes(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>, user_id: i64, code_hashes: &[String]) -> Result<()> {
+    sqlx::query("DELETE FROM two_factor_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut **tx)
+        .await?;
+
+    for code_hash in code_hashes {
+        sqlx::query("INSERT INTO two_factor_recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(code_hash)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
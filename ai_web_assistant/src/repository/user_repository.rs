@@ -1,5 +1,5 @@
 use sqlx::{PgPool, Row, postgres::PgRow};
-use crate::models::users::User;
+use crate::models::users::{User, UserSettings};
 use crate::controllers::user_controller::UpdateUserRequest;
 use anyhow::{Result, anyhow};
 use chrono::NaiveDateTime;
@@ -39,6 +39,36 @@ impl UserRepository {
         }
     }
 
+    // Email addresses from identity providers don't always match the stored case
+    pub async fn find_by_email_ignore_case(&self, email: &str) -> Result<Option<User>> {
+        let row = sqlx::query(
+            r#"
+            SELECT 
+                id, 
+                email, 
+                username, 
+                password, 
+                is_active, 
+                is_admin, 
+                is_verified, 
+                is_staff,
+                last_login,
+                email_verified_at,
+                created_at,
+                updated_at
+            FROM users 
+            WHERE LOWER(email) = LOWER($1)
+            ORDER BY id
+            LIMIT 1
+            "#
+        )
+        .bind(email)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(row.map(map_user))
+    }
+
     pub async fn find_by_id(&self, user_id: i32) -> Result<User> {
         let row = sqlx::query(
             r#"
@@ -75,8 +105,17 @@ impl UserRepository {
         let new_username = payload.username.unwrap_or(current.username);
         let new_email = payload.email.unwrap_or(current.email);
 
+        // A new address has to be verified again
         sqlx::query(
-            "UPDATE users SET username = $1, email = $2 WHERE id = $3"
+            r#"
+            UPDATE users
+            SET username = $1,
+                email = $2,
+                is_verified = is_verified AND email = $2,
+                email_verified_at = CASE WHEN email = $2 THEN email_verified_at END,
+                updated_at = NOW()
+            WHERE id = $3
+            "#
         )
         .bind(new_username)
         .bind(new_email)
@@ -102,6 +141,145 @@ impl UserRepository {
     }
 
 
+    // Unlike delete_user, a missing row is not an error; returns the number of rows removed.
+    // chat_sessions, messages and user_settings rows go with it (ON DELETE CASCADE).
+    pub async fn erase_user(&self, user_id: i64) -> Result<u64> {
+        let rows = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        Ok(rows)
+    }
+
+    pub async fn exists(&self, user_id: i64) -> Result<bool> {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+            .bind(user_id)
+            .fetch_one(&self.db)
+            .await?;
+
+        Ok(exists)
+    }
+
+    // Ids of every account that still exists, among the given ones
+    pub async fn existing_ids(&self, user_ids: &[i64]) -> Result<Vec<i64>> {
+        let ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM users WHERE id = ANY($1)")
+            .bind(user_ids)
+            .fetch_all(&self.db)
+            .await?;
+
+        Ok(ids)
+    }
+
+    pub async fn all_settings(&self) -> Result<Vec<UserSettings>> {
+        let rows = sqlx::query_as::<_, UserSettings>(
+            "SELECT user_id, preferred_language, theme FROM user_settings ORDER BY user_id"
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows)
+    }
+
+    // Swaps the settings of one user (or of everyone, for None) for `rows` in one transaction.
+    // Rows for accounts that no longer exist are dropped rather than failing the foreign key.
+    pub async fn replace_settings(&self, user_id: Option<i64>, rows: &[UserSettings]) -> Result<u64> {
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query("DELETE FROM user_settings WHERE $1::BIGINT IS NULL OR user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut restored = 0;
+        for row in rows {
+            restored += sqlx::query(
+                r#"
+                INSERT INTO user_settings (user_id, preferred_language, theme)
+                SELECT $1, $2, $3
+                WHERE EXISTS (SELECT 1 FROM users WHERE id = $1)
+                "#
+            )
+            .bind(row.user_id)
+            .bind(&row.preferred_language)
+            .bind(&row.theme)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(restored)
+    }
+
+    // Only verifies the address the link was issued for. Returns false when the account is gone
+    // or its email has changed since.
+    pub async fn mark_email_verified(&self, user_id: i64, email: &str) -> Result<bool> {
+        let rows = sqlx::query(
+            r#"
+            UPDATE users
+            SET is_verified = TRUE,
+                email_verified_at = COALESCE(email_verified_at, NOW()),
+                updated_at = NOW()
+            WHERE id = $1 AND email = $2
+            "#
+        )
+        .bind(user_id)
+        .bind(email)
+        .execute(&self.db)
+        .await?
+        .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    pub async fn update_password(&self, user_id: i64, password_hash: &str) -> Result<()> {
+        let rows = sqlx::query("UPDATE users SET password = $1, updated_at = NOW() WHERE id = $2")
+            .bind(password_hash)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        if rows == 0 {
+            return Err(anyhow!("User not found"));
+        }
+        Ok(())
+    }
+
+    pub async fn set_active(&self, user_id: i64, active: bool) -> Result<()> {
+        let rows = sqlx::query("UPDATE users SET is_active = $1, updated_at = NOW() WHERE id = $2")
+            .bind(active)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        if rows == 0 {
+            return Err(anyhow!("User not found"));
+        }
+        Ok(())
+    }
+
+    pub async fn username_taken(&self, username: &str) -> Result<bool> {
+        let taken: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE username = $1)")
+            .bind(username)
+            .fetch_one(&self.db)
+            .await?;
+
+        Ok(taken)
+    }
+
+    pub async fn is_verified(&self, user_id: i64) -> Result<bool> {
+        let verified: Option<bool> = sqlx::query_scalar("SELECT is_verified FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.db)
+            .await?;
+
+        Ok(verified.unwrap_or(false))
+    }
+
     pub async fn find_by_username(&self, username: &str) -> Result<User> {
         let row = sqlx::query(
             r#"
@@ -146,7 +324,7 @@ fn map_user(row: PgRow) -> User {
         is_verified: row.get("is_verified"),
         is_staff: row.get("is_staff"),
         last_login: row.get::<Option<NaiveDateTime>, _>("last_login"),
-        email_verified_at: row.get::<Option<NaiveDateTime>, _>("email_verified_at"),
+        email_verified_at: row.get("email_verified_at"),
         created_at: row.get("created_at"),
         updated_at: row.get("updated_at"),
     }
@@ -4,7 +4,21 @@ use utoipa::ToSchema;
 #[derive(Serialize, ToSchema)]
 pub struct LoginResponse {
     pub token: String,
+    /// Seconds until `token` expires
+    pub expires_in: i64,
+    /// Opaque, single-use; exchange it at /auth/refresh for a new pair
+    pub refresh_token: String,
     pub id: i64,
     pub email: String,
     pub username: String,
 }
+
+// Returned by /auth/login instead of tokens when the account has 2FA enabled
+#[derive(Serialize, ToSchema)]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    /// Short-lived; send it with a code to /auth/login/2fa
+    pub pending_token: String,
+    /// Seconds until `pending_token` expires
+    pub expires_in: i64,
+}
@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod responses;
 pub mod login_responses;
\ No newline at end of file
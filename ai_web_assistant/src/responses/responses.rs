@@ -13,7 +13,7 @@ pub struct SafeUser {
     pub is_verified: bool,
     pub is_staff: bool,
     pub last_login: Option<NaiveDateTime>,
-    pub email_verified_at: Option<NaiveDateTime>,
+    pub email_verified_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -2,6 +2,7 @@ use std::sync::Arc;
 use utoipa_swagger_ui::SwaggerUi;
 use utoipa::OpenApi;
 use axum::{
+    extract::DefaultBodyLimit,
     middleware::{self},
     response::IntoResponse,
     routing::{delete, get, post, put},
@@ -11,12 +12,33 @@ use axum::{
 use serde_json::json;
 use sqlx::PgPool;
 use crate::ws::{ws_channel::WsBroadcaster};
+use crate::helpers::message_manager::MessageManager;
+use crate::utils::file_utils::JsonFileManager;
+use crate::services::{backup_service::BackupService, login_throttle_service::LoginThrottle, oidc_service::OidcService, password_service::PasswordService, revocation_service::RevocationService, two_factor_service::TwoFactorService, verification_service::VerificationService};
 use crate::controllers::{
-    auth_controller::{login_user, register_user},
+    api_key_controller::{create_api_key, list_api_keys, revoke_api_key},
+    account_controller::{erase_my_account, erase_user, export_my_data, export_user_data},
+    backup_controller::{create_backup, list_backups, restore_backup},
+    auth_controller::{change_password, forgot_password, login_two_factor, login_user, logout_user, refresh_token, register_user, resend_verification, reset_password, verify_email},
+    export_controller::{export_all_conversations, export_conversation},
+    import_controller::import_conversations,
+    message_store_controller::cache_metrics,
+    oidc_controller::{oidc_callback, oidc_login},
+    role_controller::{ban_user, get_user_roles, list_roles, set_user_roles, unban_user},
+    session_controller::{list_sessions, revoke_session, revoke_user_tokens},
+    two_factor_controller::{confirm_two_factor, disable_two_factor, regenerate_recovery_codes, setup_two_factor, two_factor_status},
+    share_controller::{create_share, fork_shared, list_shares, revoke_share, view_shared},
     user_controller::{delete_user, get_user_by_id, update_user},
 };
 
-use crate::middleware::auth::{AuthUser, AdminUser};
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permission::{
+    BackupsManage, Permission, RequirePermission, RolesManage, SystemMonitor, UsersBan, UsersManage, UsersRead,
+};
+
+fn require<P: Permission + Send + Sync + 'static>() -> middleware::FromExtractorLayer<RequirePermission<P>, ()> {
+    middleware::from_extractor::<RequirePermission<P>>()
+}
 
 async fn default_handler() -> impl IntoResponse {
     "Backend is up!"
@@ -32,13 +54,21 @@ async fn handler_404() -> impl IntoResponse {
     )
 }
 
-pub fn create_routes(pool: PgPool, broadcaster: Arc<WsBroadcaster>) -> Router {
+#[allow(clippy::too_many_arguments)]
+pub fn create_routes(pool: PgPool, broadcaster: Arc<WsBroadcaster>, message_manager: Arc<MessageManager>, file_manager: Arc<JsonFileManager>, backup_service: Arc<BackupService>, revocations: Arc<RevocationService>, verification: Arc<VerificationService>, passwords: Arc<PasswordService>, two_factor: Arc<TwoFactorService>, oidc: Arc<OidcService>, login_throttle: Arc<LoginThrottle>) -> Router {
     let swagger_handler = SwaggerUi::new("/swagger-ui")
     .url("/api-docs/openapi.json", crate::swagger_doc::doc::ApiDoc::openapi());
     let _ = broadcaster;
     let auth_routes = Router::new()
         .route("/auth/register", post(register_user))
         .route("/auth/login", post(login_user))
+        .route("/auth/login/2fa", post(login_two_factor))
+        .route("/auth/refresh", post(refresh_token))
+        .route("/auth/verify-email", get(verify_email))
+        .route("/auth/forgot-password", post(forgot_password))
+        .route("/auth/reset-password", post(reset_password))
+        .route("/auth/oidc/login", get(oidc_login))
+        .route("/auth/oidc/callback", get(oidc_callback))
         .layer(Extension(pool.clone()));
 
     let user_routes = Router::new()
@@ -48,18 +78,88 @@ pub fn create_routes(pool: PgPool, broadcaster: Arc<WsBroadcaster>) -> Router {
         .layer(middleware::from_extractor::<AuthUser>())
         .layer(Extension(pool.clone()));
 
+    let session_routes = Router::new()
+        .route("/auth/logout", post(logout_user))
+        .route("/auth/verify-email/resend", post(resend_verification))
+        .route("/auth/change-password", post(change_password))
+        .route("/auth/2fa", get(two_factor_status))
+        .route("/auth/2fa/setup", post(setup_two_factor))
+        .route("/auth/2fa/confirm", post(confirm_two_factor))
+        .route("/auth/2fa/recovery-codes", post(regenerate_recovery_codes))
+        .route("/auth/2fa/disable", post(disable_two_factor))
+        .route("/auth/sessions", get(list_sessions))
+        .route("/auth/sessions/:session_id", delete(revoke_session))
+        .route("/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/api-keys/:key_id", delete(revoke_api_key));
+
+    let account_routes = Router::new()
+        .route("/account/export", get(export_my_data))
+        .route("/account", delete(erase_my_account));
+
+    let conversation_routes = Router::new()
+        .route("/conversations/export", get(export_all_conversations))
+        .route("/conversations/:id/export", get(export_conversation))
+        .route(
+            "/conversations/import",
+            post(import_conversations).layer(DefaultBodyLimit::max(crate::helpers::import::max_import_bytes())),
+        )
+        .route("/conversations/:id/share", post(create_share))
+        .route("/shares", get(list_shares))
+        .route("/shares/:share_id", delete(revoke_share))
+        .route("/share/:token/fork", post(fork_shared));
+
+    // Public: anyone holding the token can read the snapshot
+    let share_routes = Router::new()
+        .route("/share/:token", get(view_shared));
+
+    // Each admin route names the permission it needs
     let admin_routes = Router::new()
-        .route("/admin/secret", get(|| async { "Admin Only" }))
-        .layer(middleware::from_extractor::<AdminUser>())
+        .route("/admin/secret", get(|| async { "Admin Only" }).route_layer(require::<SystemMonitor>()))
+        .route("/admin/message-cache", get(cache_metrics).route_layer(require::<SystemMonitor>()))
+        .route("/admin/users/:id/export", get(export_user_data).route_layer(require::<UsersRead>()))
+        .route("/admin/users/:id/erase", delete(erase_user).route_layer(require::<UsersManage>()))
+        .route("/admin/users/:id/revoke-tokens", post(revoke_user_tokens).route_layer(require::<UsersManage>()))
+        .route("/admin/users/:id/ban", post(ban_user).route_layer(require::<UsersBan>()))
+        .route("/admin/users/:id/unban", post(unban_user).route_layer(require::<UsersBan>()))
+        .route(
+            "/admin/users/:id/roles",
+            get(get_user_roles).put(set_user_roles).route_layer(require::<RolesManage>()),
+        )
+        .route("/admin/roles", get(list_roles).route_layer(require::<RolesManage>()))
+        .route(
+            "/admin/backups",
+            get(list_backups).post(create_backup).route_layer(require::<BackupsManage>()),
+        )
+        .route("/admin/backups/:backup_id/restore", post(restore_backup).route_layer(require::<BackupsManage>()))
         .layer(Extension(pool.clone()));
 
-
-    Router::new()
+    let router = Router::new()
         .merge(auth_routes)
+        .merge(session_routes)
         .merge(user_routes)
-        .merge(admin_routes)
+        .merge(account_routes)
+        .merge(conversation_routes)
+        .merge(share_routes)
+        .merge(admin_routes);
+
+    // Leader-side endpoints followers pull from and forward writes to
+    #[cfg(feature = "replication")]
+    let router = router
+        .route("/replication/log", get(crate::controllers::replication_controller::fetch_log))
+        .route("/replication/append", post(crate::controllers::replication_controller::append_entry));
+
+    router
         .route("/", get(default_handler))
         .fallback(handler_404)
         .layer(Extension(pool))
         .layer(Extension(broadcaster))
+        .layer(Extension(message_manager))
+        .layer(Extension(file_manager))
+        .layer(Extension(backup_service))
+        .layer(Extension(revocations))
+        .layer(Extension(verification))
+        .layer(Extension(passwords))
+        .layer(Extension(two_factor))
+        .layer(Extension(oidc))
+        .layer(Extension(login_throttle))
 }
\ No newline at end of file
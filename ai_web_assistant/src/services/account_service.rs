@@ -0,0 +1,195 @@
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::io::DuplexStream;
+use uuid::Uuid;
+
+use crate::helpers::export::{self, ExportFormat, ZipFile};
+use crate::helpers::message_manager::MessageManager;
+use crate::repository::refresh_token_repository::RefreshTokenRepository;
+use crate::repository::user_repository::UserRepository;
+use crate::responses::responses::SafeUser;
+use crate::services::revocation_service::RevocationService;
+use crate::utils::file_utils::{JsonFileManager, UserRecords};
+
+// Everything kept about an account, across Postgres, the message store and the JSON files
+pub struct AccountService {
+    pub repository: UserRepository,
+    pub message_manager: Arc<MessageManager>,
+    pub file_manager: Arc<JsonFileManager>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StoreErasure {
+    pub store: &'static str,
+    pub removed: usize,
+    // What a fresh read of the store still found after erasing
+    pub remaining: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErasureReport {
+    pub report_id: String,
+    pub user_id: i64,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+    pub complete: bool,
+    pub stores: Vec<StoreErasure>,
+    // SHA-256 of this report serialized with `digest` set to ""
+    pub digest: String,
+}
+
+impl AccountService {
+    pub fn new(repository: UserRepository, message_manager: Arc<MessageManager>, file_manager: Arc<JsonFileManager>) -> Self {
+        Self { repository, message_manager, file_manager }
+    }
+
+    // "Download my data": a zip with the account row, profile and session records,
+    // trash, share links and every conversation in the importable JSON format
+    pub async fn export_archive(&self, user_id: i64) -> Result<DuplexStream> {
+        let now = Utc::now();
+        let user_key = user_id.to_string();
+
+        let account: Option<SafeUser> = if self.repository.exists(user_id).await? {
+            Some(self.repository.find_by_id(i32::try_from(user_id)?).await?.into())
+        } else {
+            None
+        };
+        let user_data = self.message_manager.get_user_data(&user_key).await?;
+        let records = self.file_manager.user_records(user_id as u64).await?;
+
+        let mut conversations: Vec<_> = user_data.conversations.into_iter().collect();
+        conversations.sort_by_key(|(_, conversation)| conversation.created_at);
+
+        let mut files = vec![
+            json_file("account.json", &account, now)?,
+            json_file("profiles.json", &records.profiles, now)?,
+            json_file("sessions.json", &records.sessions, now)?,
+            json_file("trash.json", &user_data.trash, now)?,
+            json_file("shares.json", &user_data.shares, now)?,
+        ];
+        if let Some(retention) = &user_data.retention {
+            files.push(json_file("retention.json", retention, now)?);
+        }
+        if !records.legacy_conversations.is_empty() {
+            files.push(json_file("legacy_messages.json", &records.legacy_conversations, now)?);
+        }
+
+        let manifest = json!({
+            "user_id": user_id,
+            "generated_at": now,
+            "conversation_count": conversations.len(),
+            "files": files.iter().map(|f| f.name.clone()).collect::<Vec<_>>(),
+            "conversation_format": "conversations/*.json use the JSON export schema and can be imported again"
+        });
+        files.insert(0, json_file("manifest.json", &manifest, now)?);
+
+        let conversation_files = conversations.into_iter().map(|(conversation_id, conversation)| {
+            let content = export::render(&conversation_id, &conversation, ExportFormat::Json)?;
+            Ok(ZipFile {
+                name: format!("conversations/{}", export::file_name(&conversation_id, &conversation, ExportFormat::Json)),
+                modified: conversation.created_at,
+                content: content.into_bytes(),
+            })
+        });
+
+        Ok(export::write_zip(files.into_iter().map(Ok).chain(conversation_files)))
+    }
+
+    // Removes the user from every store, then reads each store again to confirm nothing is
+    // left. Postgres goes last so a failed run can be retried while the account still exists.
+    pub async fn erase(&self, user_id: i64, revocations: &RevocationService) -> ErasureReport {
+        let started_at = Utc::now();
+        let user_key = user_id.to_string();
+        let mut stores = Vec::new();
+
+        // Tokens first, so open sessions can't write new data while the stores are emptied
+        let erased = revocations.revoke_user(user_id, "account erased").await
+            .map(|sessions| (sessions as usize, Some("access tokens revoked and WebSocket connections closed".to_string())));
+        let remaining = RefreshTokenRepository { db: self.repository.db.clone() }
+            .active_sessions(user_id)
+            .await
+            .map(|sessions| sessions.len());
+        stores.push(store_erasure("sessions", erased, remaining));
+
+        let erased = self.message_manager.erase_user(&user_key).await.map(|erased| (
+            erased.conversations + erased.trash_items + erased.share_links,
+            Some(format!(
+                "{} conversations ({} messages), {} trash items, {} share links",
+                erased.conversations, erased.messages, erased.trash_items, erased.share_links,
+            )),
+        ));
+        let remaining = self.message_manager.has_user_data(&user_key).await.map(usize::from);
+        stores.push(store_erasure("message_store", erased, remaining));
+
+        // users.json, sessions.json and the legacy messages.json are reported separately
+        let erased = self.file_manager.erase_user(user_id as u64).await.map(|r| record_counts(&r));
+        let remaining = self.file_manager.user_records(user_id as u64).await.map(|r| record_counts(&r));
+        for (i, store) in ["users_json", "sessions_json", "legacy_messages_json"].into_iter().enumerate() {
+            stores.push(store_erasure(
+                store,
+                erased.as_ref().map(|counts| (counts[i], None)).map_err(|e| anyhow!("{}", e)),
+                remaining.as_ref().map(|counts| counts[i]).map_err(|e| anyhow!("{}", e)),
+            ));
+        }
+
+        let erased = self.repository.erase_user(user_id).await
+            .map(|rows| (rows as usize, Some("chat_sessions, messages and user_settings rows cascade".to_string())));
+        let remaining = self.repository.exists(user_id).await.map(usize::from);
+        stores.push(store_erasure("postgres_users", erased, remaining));
+
+        let mut report = ErasureReport {
+            report_id: Uuid::new_v4().to_string(),
+            user_id,
+            started_at,
+            completed_at: Utc::now(),
+            complete: stores.iter().all(|s| s.error.is_none() && s.remaining == 0),
+            stores,
+            digest: String::new(),
+        };
+        report.digest = serde_json::to_vec(&report)
+            .map(|bytes| hex::encode(Sha256::digest(&bytes)))
+            .unwrap_or_default();
+
+        println!("🧹 Erased user {} (report {}, complete: {})", user_id, report.report_id, report.complete);
+        report
+    }
+}
+
+fn json_file<T: Serialize>(name: &str, value: &T, modified: DateTime<Utc>) -> Result<ZipFile> {
+    Ok(ZipFile {
+        name: name.to_string(),
+        modified,
+        content: serde_json::to_vec_pretty(value)?,
+    })
+}
+
+fn record_counts(records: &UserRecords) -> [usize; 3] {
+    [records.profiles.len(), records.sessions.len(), records.legacy_conversations.len()]
+}
+
+fn store_erasure(store: &'static str, erased: Result<(usize, Option<String>)>, remaining: Result<usize>) -> StoreErasure {
+    let (removed, detail, erase_error) = match erased {
+        Ok((removed, detail)) => (removed, detail, None),
+        Err(e) => (0, None, Some(format!("erase failed: {}", e))),
+    };
+    let (remaining, check_error) = match remaining {
+        Ok(remaining) => (remaining, None),
+        Err(e) => (0, Some(format!("verification failed: {}", e))),
+    };
+
+    StoreErasure {
+        store,
+        removed,
+        remaining,
+        detail,
+        error: erase_error.or(check_error),
+    }
+}
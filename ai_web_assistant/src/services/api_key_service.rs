@@ -0,0 +1,186 @@
+use std::env;
+use anyhow::{anyhow, Result};
+use axum::http::Method;
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::repository::api_key_repository::{ApiKeyRecord, ApiKeyRepository};
+use crate::repository::role_repository::RoleRepository;
+use crate::services::auth_service::{hash_secret_token, new_secret_token};
+use crate::services::revocation_service::RevocationService;
+use crate::utils::jwt::Claims;
+
+// Keys look like "aiwa_<64 hex chars>", so they can't be mistaken for a JWT
+pub const API_KEY_PREFIX: &str = "aiwa_";
+
+// read: HTTP GET, write: other HTTP methods, chat: the WebSocket, admin: /admin routes
+pub const SCOPES: [&str; 4] = ["read", "write", "chat", "admin"];
+
+const DEFAULT_TTL_DAYS: i64 = 90;
+const DEFAULT_MAX_TTL_DAYS: i64 = 365;
+
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    // The only time the full key is returned
+    pub key: String,
+    #[serde(flatten)]
+    pub record: ApiKeyRecord,
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+// Scope a request needs. None for routes keys may never use: credentials, sessions,
+// key management, the account export / erasure and the user record (email change,
+// deletion) stay behind an interactive login.
+pub fn required_scope(method: &Method, path: &str) -> Option<&'static str> {
+    if path.starts_with("/auth/") || path.starts_with("/api-keys")
+        || path == "/account" || path.starts_with("/account/")
+        || path.starts_with("/users/")
+    {
+        return None;
+    }
+    if path.starts_with("/admin/") {
+        return Some("admin");
+    }
+    if method == Method::GET || method == Method::HEAD {
+        Some("read")
+    } else {
+        Some("write")
+    }
+}
+
+pub async fn create_key(
+    db: &PgPool,
+    user_id: i64,
+    is_admin: bool,
+    name: &str,
+    scopes: &[String],
+    expires_in_days: Option<i64>,
+) -> Result<CreatedApiKey> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(anyhow!("Name must be 1 to 100 characters"));
+    }
+    if scopes.is_empty() {
+        return Err(anyhow!("At least one scope is required"));
+    }
+    if let Some(unknown) = scopes.iter().find(|scope| !SCOPES.contains(&scope.as_str())) {
+        return Err(anyhow!("Unknown scope {:?}; expected one of {:?}", unknown, SCOPES));
+    }
+    if scopes.iter().any(|scope| scope == "admin") && !is_admin {
+        return Err(anyhow!("Only admins can create keys with the admin scope"));
+    }
+
+    let max_days = env::var("API_KEY_MAX_TTL_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(DEFAULT_MAX_TTL_DAYS);
+    let days = expires_in_days.unwrap_or(DEFAULT_TTL_DAYS);
+    if days < 1 || days > max_days {
+        return Err(anyhow!("expires_in_days must be between 1 and {}", max_days));
+    }
+
+    let mut scopes = scopes.to_vec();
+    scopes.sort();
+    scopes.dedup();
+
+    let key = format!("{}{}", API_KEY_PREFIX, new_secret_token());
+    let record = ApiKeyRepository { db: db.clone() }
+        .create(user_id, name, &key[..API_KEY_PREFIX.len() + 8], &hash_secret_token(&key), &scopes, Utc::now() + Duration::days(days))
+        .await?;
+
+    println!("🔑 User {} created API key {} ({})", user_id, record.id, record.name);
+    Ok(CreatedApiKey { key, record })
+}
+
+pub async fn list_keys(db: &PgPool, user_id: i64) -> Result<Vec<ApiKeyRecord>> {
+    ApiKeyRepository { db: db.clone() }.list_for_user(user_id).await
+}
+
+// False when the user has no such active key
+pub async fn revoke_key(db: &PgPool, revocations: &RevocationService, user_id: i64, key_id: &str) -> Result<bool> {
+    let id = Uuid::parse_str(key_id).map_err(|_| anyhow!("Invalid key id"))?;
+    if !(ApiKeyRepository { db: db.clone() }).revoke(user_id, id).await? {
+        return Ok(false);
+    }
+
+    revocations.revoke_api_key(key_id, "api key revoked").await?;
+    Ok(true)
+}
+
+// Claims for a request made with an API key, if the key is usable for `scope`
+pub async fn authenticate(db: &PgPool, key: &str, scope: &str) -> Result<Claims> {
+    let repo = ApiKeyRepository { db: db.clone() };
+    let owner = repo.find_active_by_hash(&hash_secret_token(key)).await?
+        .ok_or_else(|| anyhow!("Invalid API key"))?;
+
+    if !owner.scopes.iter().any(|s| s == scope) {
+        return Err(anyhow!("API key lacks the {:?} scope", scope));
+    }
+
+    if let Err(e) = repo.touch(&owner.key_id).await {
+        eprintln!("❌ Failed to record API key use: {}", e);
+    }
+
+    // Looked up on every use, so role changes apply to keys right away
+    let access = RoleRepository { db: db.clone() }.access_for_user(owner.user_id).await?;
+    let has_admin_scope = owner.scopes.iter().any(|s| s == "admin");
+    let permissions = if has_admin_scope {
+        access.permissions
+    } else {
+        // Without the admin scope a key only carries what a regular user could do
+        access.permissions.into_iter().filter(|p| p == "chat.use").collect()
+    };
+
+    Ok(Claims {
+        sub: owner.user_id,
+        email: owner.email,
+        roles: access.roles,
+        is_admin: owner.is_admin && has_admin_scope,
+        is_user: true,
+        exp: owner.expires_at.timestamp() as usize,
+        jti: format!("api_key:{}", owner.key_id),
+        iat: owner.created_at.timestamp() as usize,
+        iat_micros: owner.created_at.timestamp_micros(),
+        sid: String::new(),
+        api_key_id: Some(owner.key_id),
+        scopes: owner.scopes,
+        permissions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_and_credential_routes_need_an_interactive_login() {
+        for path in ["/auth/logout", "/api-keys", "/api-keys/abc", "/account", "/account/export"] {
+            assert_eq!(required_scope(&Method::GET, path), None, "{}", path);
+            assert_eq!(required_scope(&Method::POST, path), None, "{}", path);
+        }
+    }
+
+    #[test]
+    fn user_records_cannot_be_changed_or_erased_with_a_key() {
+        for method in [Method::PUT, Method::DELETE, Method::GET] {
+            assert_eq!(required_scope(&method, "/users/42"), None, "{}", method);
+        }
+    }
+
+    #[test]
+    fn scope_follows_method_and_admin_prefix() {
+        assert_eq!(required_scope(&Method::GET, "/conversations"), Some("read"));
+        assert_eq!(required_scope(&Method::HEAD, "/conversations"), Some("read"));
+        assert_eq!(required_scope(&Method::POST, "/conversations"), Some("write"));
+        assert_eq!(required_scope(&Method::DELETE, "/conversations/1"), Some("write"));
+        assert_eq!(required_scope(&Method::GET, "/admin/users"), Some("admin"));
+        // Only whole path segments count
+        assert_eq!(required_scope(&Method::GET, "/accounts"), Some("read"));
+    }
+}
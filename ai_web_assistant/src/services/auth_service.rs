@@ -1,13 +1,21 @@
-use rand::rngs::OsRng; 
+use rand::{rngs::OsRng, RngCore};
+use std::env;
+use std::sync::OnceLock;
+use crate::models::users::User;
 use crate::payloads::login_request::LoginRequest;
 use crate::payloads::register_request::RegisterRequest;
-use crate::responses::login_responses::LoginResponse;
+use crate::responses::login_responses::{LoginResponse, TwoFactorChallenge};
 use crate::responses::responses::SafeUser;
 use crate::{
-    repository::auth_repository::AuthenticationRepository,
-    utils::jwt::generate_token,
+    repository::{auth_repository::AuthenticationRepository, refresh_token_repository::{RefreshTokenRecord, RefreshTokenRepository}, role_repository::RoleRepository, two_factor_repository::TwoFactorRepository, user_repository::UserRepository},
+    services::login_throttle_service::{normalize_email, LoginThrottle},
+    services::two_factor_service::{pending_token_ttl, TwoFactorService, TWO_FACTOR_LOGIN},
+    utils::jwt::{access_token_ttl, decode_action_token, generate_token},
 };
 use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use password_hash::{SaltString};
 use serde::Serialize;
@@ -23,45 +31,220 @@ pub struct ErrorResponse {
     pub error: String,
 }
 
+pub enum LoginOutcome {
+    Session(LoginResponse),
+    // Password was right; the code step at /auth/login/2fa comes next
+    TwoFactorRequired(TwoFactorChallenge),
+    // Too many recent failures for this email or IP; nothing was checked
+    Throttled { retry_after_secs: i64 },
+}
+
 pub async fn register_user(
     db: &PgPool,
     req: RegisterRequest,
 ) -> Result<SafeUser> {
-    let hashed_password = hash_password(&req.password.as_deref().unwrap())?;
+    let hashed_password = hash_password(req.password.as_deref().unwrap())?;
     let repo = AuthenticationRepository { db: db.clone() };
-    let user = repo.create_user(&req.email.as_deref().unwrap(), &req.username.as_deref().unwrap(), &hashed_password).await?;
+    let user = repo.create_user(req.email.as_deref().unwrap(), req.username.as_deref().unwrap(), &hashed_password).await?;
     Ok(user)
 }
 
 pub async fn login_user(
     db: &PgPool,
+    throttle: &LoginThrottle,
+    two_factor: &TwoFactorService,
     req: LoginRequest,
-) -> Result<LoginResponse> {
+    ip: &str,
+) -> Result<LoginOutcome> {
+    let email = req.email.as_deref().unwrap();
+    let throttle_key = normalize_email(email);
+    if let Some(retry_after_secs) = throttle.retry_after(&throttle_key, ip).await? {
+        return Ok(LoginOutcome::Throttled { retry_after_secs });
+    }
+
     let repo = AuthenticationRepository { db: db.clone() };
-    let user = repo.login_user(&req.email.as_deref().unwrap()).await?;
+    let user = repo.login_user(email).await?;
 
-    let is_valid = verify_password(&req.password.as_deref().unwrap(), &user.password)?;
-    if !is_valid {
+    // Unknown emails are checked against a dummy hash, so the response takes as long and
+    // reads the same whether or not the account exists
+    let stored_hash = match &user {
+        Some(user) => user.password.as_str(),
+        None => dummy_password_hash(),
+    };
+    let is_valid = verify_password(req.password.as_deref().unwrap(), stored_hash)?;
+    let user_id = user.as_ref().map(|user| user.id);
+    let Some(user) = user.filter(|user| is_valid && user.is_active) else {
+        throttle.record_failure(&throttle_key, ip, user_id).await?;
         return Err(anyhow!("Invalid credentials"));
+    };
+
+    // The failures stay on the books until the code step succeeds too
+    if (TwoFactorRepository { db: db.clone() }).is_enabled(user.id).await? {
+        return Ok(LoginOutcome::TwoFactorRequired(TwoFactorChallenge {
+            two_factor_required: true,
+            pending_token: two_factor.start_login(user.id, &user.email).await?,
+            expires_in: pending_token_ttl().num_seconds(),
+        }));
+    }
+    throttle.record_success(&throttle_key, ip).await?;
+
+    // Every login starts a new refresh token family
+    start_session(db, &user, Uuid::new_v4()).await.map(LoginOutcome::Session)
+}
+
+// Second step for accounts with 2FA. Wrong codes count against the account and the IP like
+// wrong passwords, so the code can't be guessed faster than the password could.
+pub async fn login_two_factor(
+    throttle: &LoginThrottle,
+    two_factor: &TwoFactorService,
+    pending_token: &str,
+    code: &str,
+    ip: &str,
+) -> Result<LoginOutcome> {
+    let claims = decode_action_token(pending_token, TWO_FACTOR_LOGIN)
+        .map_err(|_| anyhow!("Login attempt expired; sign in again"))?;
+    let throttle_key = normalize_email(&claims.email);
+    if let Some(retry_after_secs) = throttle.retry_after(&throttle_key, ip).await? {
+        return Ok(LoginOutcome::Throttled { retry_after_secs });
+    }
+
+    match two_factor.complete_login(pending_token, code).await {
+        Ok(session) => {
+            throttle.record_success(&throttle_key, ip).await?;
+            Ok(LoginOutcome::Session(session))
+        }
+        Err(e) => {
+            throttle.record_failure(&throttle_key, ip, Some(claims.sub)).await?;
+            Err(e)
+        }
+    }
+}
+
+// Argon2 hash of a random password, made once (at startup); verifying against it costs the
+// same as a real check
+pub fn dummy_password_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| hash_password(&new_secret_token()).expect("Failed to hash dummy password"))
+}
+
+// Exchanges a refresh token for a new access token and a new refresh token.
+// Presenting a token that was already exchanged revokes its whole family.
+pub async fn refresh_session(db: &PgPool, refresh_token: &str) -> Result<LoginResponse> {
+    let repo = RefreshTokenRepository { db: db.clone() };
+    let record = repo.find_by_hash(&hash_secret_token(refresh_token)).await?
+        .ok_or_else(|| anyhow!("Invalid refresh token"))?;
+
+    match refresh_decision(&record, Utc::now()) {
+        RefreshDecision::Rotate => {}
+        RefreshDecision::Revoked => return Err(anyhow!("Refresh token has been revoked")),
+        RefreshDecision::Reused => return Err(reuse_detected(&repo, &record).await),
+        RefreshDecision::Expired => return Err(anyhow!("Refresh token has expired")),
+    }
+
+    let user = UserRepository { db: db.clone() }.find_by_id(i32::try_from(record.user_id)?).await
+        .map_err(|_| anyhow!("Invalid refresh token"))?;
+    if !user.is_active {
+        repo.revoke_family(record.family_id).await?;
+        return Err(anyhow!("Account is disabled"));
     }
 
-    let is_admin = user.is_admin;
+    let refresh_token = new_secret_token();
+    let expires_at = Utc::now() + refresh_token_ttl();
+    if !repo.rotate(&record, &hash_secret_token(&refresh_token), expires_at).await? {
+        // Another request exchanged the same token first
+        return Err(reuse_detected(&repo, &record).await);
+    }
+
+    session_response(db, &user, record.family_id, refresh_token).await
+}
+
+#[derive(Debug, PartialEq)]
+enum RefreshDecision {
+    Rotate,
+    Revoked,
+    // Already exchanged once, so a copy is out there: the whole family is revoked
+    Reused,
+    Expired,
+}
 
+// A revoked family stays revoked quietly; only a live, already-used token counts as reuse,
+// even once it has expired
+fn refresh_decision(record: &RefreshTokenRecord, now: DateTime<Utc>) -> RefreshDecision {
+    if record.revoked_at.is_some() {
+        RefreshDecision::Revoked
+    } else if record.used_at.is_some() {
+        RefreshDecision::Reused
+    } else if record.expires_at <= now {
+        RefreshDecision::Expired
+    } else {
+        RefreshDecision::Rotate
+    }
+}
+
+pub async fn start_session(db: &PgPool, user: &User, family_id: Uuid) -> Result<LoginResponse> {
+    let refresh_token = new_secret_token();
+    RefreshTokenRepository { db: db.clone() }
+        .create(user.id, family_id, &hash_secret_token(&refresh_token), Utc::now() + refresh_token_ttl())
+        .await?;
+
+    session_response(db, user, family_id, refresh_token).await
+}
+
+// The refresh token family doubles as the session (device) id carried in access tokens
+// Roles and permissions are read fresh for every access token
+async fn session_response(db: &PgPool, user: &User, family_id: Uuid, refresh_token: String) -> Result<LoginResponse> {
+    let access = RoleRepository { db: db.clone() }.access_for_user(user.id).await?;
     let token = generate_token(
         user.id,
         user.email.clone(),
-        is_admin,
+        &access,
+        &family_id.to_string(),
     )?;
 
     Ok(LoginResponse {
         token,
+        expires_in: access_token_ttl().num_seconds(),
+        refresh_token,
         id: user.id,
         email: user.email.clone(),
         username: user.username.clone(),
     })
 }
 
-fn hash_password(password: &str) -> Result<String> {
+async fn reuse_detected(repo: &RefreshTokenRepository, record: &RefreshTokenRecord) -> anyhow::Error {
+    match repo.revoke_family(record.family_id).await {
+        Ok(revoked) => eprintln!(
+            "🚨 Refresh token reuse for user {}: revoked {} token(s) in family {}",
+            record.user_id, revoked, record.family_id,
+        ),
+        Err(e) => eprintln!("❌ Failed to revoke refresh token family {}: {}", record.family_id, e),
+    }
+    anyhow!("Refresh token reuse detected; please log in again")
+}
+
+const DEFAULT_REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+fn refresh_token_ttl() -> Duration {
+    let days = env::var("REFRESH_TOKEN_TTL_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(DEFAULT_REFRESH_TOKEN_TTL_DAYS);
+    Duration::days(days)
+}
+
+// Refresh and password reset tokens: 256 random bits, hex encoded; only the SHA-256 is stored
+pub fn new_secret_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn hash_secret_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
 
@@ -73,7 +256,7 @@ fn hash_password(password: &str) -> Result<String> {
     Ok(password_hash)
 }
 
-fn verify_password(password: &str, hash: &str) -> Result<bool> {
+pub fn verify_password(password: &str, hash: &str) -> Result<bool> {
     let parsed_hash = PasswordHash::new(hash)
         .map_err(|e| anyhow!(e.to_string()))?;
 
@@ -82,4 +265,156 @@ fn verify_password(password: &str, hash: &str) -> Result<bool> {
         .is_ok();
 
     Ok(is_valid)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::login_throttle_service::LoginThrottleConfig;
+    use crate::repository::login_attempt_repository::{LoginAttemptRepository, ACCOUNT};
+    use crate::test_support;
+
+    const PASSWORD: &str = "correct horse";
+    const RECOVERY_CODE: &str = "abcde12345";
+
+    struct Fixture {
+        db: PgPool,
+        throttle: LoginThrottle,
+        two_factor: TwoFactorService,
+        email: String,
+        ip: String,
+    }
+
+    // A user with a password and 2FA on; one recovery code is RECOVERY_CODE
+    async fn fixture() -> Fixture {
+        let db = test_support::database().await;
+        let (user_id, email) = test_support::create_user(&db).await;
+        sqlx::query("UPDATE users SET password = $2 WHERE id = $1")
+            .bind(user_id)
+            .bind(hash_password(PASSWORD).unwrap())
+            .execute(&db)
+            .await
+            .unwrap();
+        let repo = TwoFactorRepository { db: db.clone() };
+        repo.start_enrolment(user_id, &crate::utils::totp::generate_secret()).await.unwrap();
+        repo.enable(user_id, &[hash_secret_token(RECOVERY_CODE)]).await.unwrap();
+
+        let config = LoginThrottleConfig { account_free_failures: 1, ..LoginThrottleConfig::from_env() };
+        Fixture {
+            throttle: LoginThrottle::new(db.clone(), config),
+            two_factor: TwoFactorService::new(db.clone()),
+            ip: format!("test-{}", Uuid::new_v4()),
+            db,
+            email,
+        }
+    }
+
+    impl Fixture {
+        async fn password(&self, password: &str) -> Result<LoginOutcome> {
+            let req = LoginRequest { email: Some(self.email.clone()), password: Some(password.to_string()) };
+            login_user(&self.db, &self.throttle, &self.two_factor, req, &self.ip).await
+        }
+
+        async fn pending_token(&self) -> String {
+            match self.password(PASSWORD).await.unwrap() {
+                LoginOutcome::TwoFactorRequired(challenge) => challenge.pending_token,
+                _ => panic!("expected the 2FA step"),
+            }
+        }
+
+        async fn account_failures(&self) -> i64 {
+            LoginAttemptRepository { db: self.db.clone() }
+                .failures(ACCOUNT, &normalize_email(&self.email), Utc::now() - Duration::hours(1))
+                .await
+                .unwrap()
+                .count
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn wrong_codes_are_throttled_like_wrong_passwords() {
+        let fixture = fixture().await;
+        let token = fixture.pending_token().await;
+
+        assert!(login_two_factor(&fixture.throttle, &fixture.two_factor, &token, "000000", &fixture.ip).await.is_err());
+        assert!(login_two_factor(&fixture.throttle, &fixture.two_factor, &token, "000000", &fixture.ip).await.is_err());
+        assert_eq!(fixture.account_failures().await, 2);
+
+        // Past the free failure, even the right code has to wait
+        match login_two_factor(&fixture.throttle, &fixture.two_factor, &token, RECOVERY_CODE, &fixture.ip).await {
+            Ok(LoginOutcome::Throttled { retry_after_secs }) => assert!(retry_after_secs >= 1),
+            _ => panic!("expected to be throttled"),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn only_a_completed_login_clears_the_account_failures() {
+        let fixture = fixture().await;
+        assert!(fixture.password("wrong password").await.is_err());
+        assert_eq!(fixture.account_failures().await, 1);
+
+        // The password alone isn't a completed login while 2FA is on
+        let token = fixture.pending_token().await;
+        assert_eq!(fixture.account_failures().await, 1);
+
+        match login_two_factor(&fixture.throttle, &fixture.two_factor, &token, RECOVERY_CODE, &fixture.ip).await {
+            Ok(LoginOutcome::Session(_)) => {}
+            _ => panic!("expected a session"),
+        }
+        assert_eq!(fixture.account_failures().await, 0);
+    }
+
+    fn record(used: bool, revoked: bool, expires_in: Duration) -> RefreshTokenRecord {
+        let now = Utc::now();
+        RefreshTokenRecord {
+            id: Uuid::new_v4(),
+            user_id: 1,
+            family_id: Uuid::new_v4(),
+            expires_at: now + expires_in,
+            used_at: used.then_some(now),
+            revoked_at: revoked.then_some(now),
+        }
+    }
+
+    #[test]
+    fn an_unused_live_token_is_rotated() {
+        assert_eq!(refresh_decision(&record(false, false, Duration::days(1)), Utc::now()), RefreshDecision::Rotate);
+    }
+
+    #[test]
+    fn presenting_an_exchanged_token_again_is_reuse() {
+        assert_eq!(refresh_decision(&record(true, false, Duration::days(1)), Utc::now()), RefreshDecision::Reused);
+        // An old copy is still a copy after it expires
+        assert_eq!(refresh_decision(&record(true, false, -Duration::days(1)), Utc::now()), RefreshDecision::Reused);
+    }
+
+    #[test]
+    fn a_revoked_family_is_not_revoked_again() {
+        assert_eq!(refresh_decision(&record(true, true, Duration::days(1)), Utc::now()), RefreshDecision::Revoked);
+        assert_eq!(refresh_decision(&record(false, true, Duration::days(1)), Utc::now()), RefreshDecision::Revoked);
+    }
+
+    #[test]
+    fn an_unused_token_past_its_expiry_is_expired() {
+        let token = record(false, false, Duration::days(1));
+        assert_eq!(refresh_decision(&token, token.expires_at), RefreshDecision::Expired);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn reusing_a_rotated_token_revokes_the_whole_family() {
+        let db = test_support::database().await;
+        let (user_id, _) = test_support::create_user(&db).await;
+        let user = UserRepository { db: db.clone() }.find_by_id(user_id as i32).await.unwrap();
+
+        let first = start_session(&db, &user, Uuid::new_v4()).await.unwrap();
+        let second = refresh_session(&db, &first.refresh_token).await.unwrap();
+        assert_ne!(first.refresh_token, second.refresh_token);
+
+        assert!(refresh_session(&db, &first.refresh_token).await.is_err());
+        // The legitimate holder of the newer token is logged out too
+        assert!(refresh_session(&db, &second.refresh_token).await.is_err());
+    }
+}
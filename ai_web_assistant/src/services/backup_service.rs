@@ -0,0 +1,539 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::helpers::message_manager::MessageManager;
+use crate::helpers::share::ShareSnapshot;
+use crate::models::users::UserSettings;
+use crate::repository::user_repository::UserRepository;
+use crate::utils::file_models::UserConversations;
+
+const DEFAULT_BACKUP_DIR: &str = "data/backups";
+const DEFAULT_INTERVAL_SECS: u64 = 86400;
+const DEFAULT_KEEP: usize = 7;
+
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    // None turns the schedule off; backups can still be taken by hand
+    pub interval: Option<Duration>,
+    pub keep: usize,
+}
+
+impl BackupConfig {
+    pub fn from_env() -> Self {
+        let dir = env::var("BACKUP_DIR").unwrap_or_else(|_| DEFAULT_BACKUP_DIR.to_string());
+
+        let interval_secs = env::var("BACKUP_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_INTERVAL_SECS);
+
+        let keep = env::var("BACKUP_KEEP")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|keep| *keep > 0)
+            .unwrap_or(DEFAULT_KEEP);
+
+        Self {
+            dir: PathBuf::from(dir),
+            interval: (interval_secs > 0).then(|| Duration::from_secs(interval_secs)),
+            keep,
+        }
+    }
+}
+
+// One line of a snapshot file (gzipped JSON lines)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum BackupRecord {
+    Settings { rows: Vec<UserSettings> },
+    User {
+        user_id: String,
+        data: UserConversations,
+        #[serde(default)]
+        shares: Vec<ShareSnapshot>,
+    },
+}
+
+// Written next to the snapshot once it is complete; a snapshot without one is ignored
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupManifest {
+    pub backup_id: String,
+    pub created_at: DateTime<Utc>,
+    pub file: String,
+    pub bytes: u64,
+    pub sha256: String,
+    pub users: usize,
+    pub conversations: usize,
+    pub messages: usize,
+    pub settings_rows: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub backup_id: String,
+    // Taken right before restoring, so the restore itself can be undone
+    pub safety_backup_id: String,
+    pub users_restored: usize,
+    // Users created after the backup was taken (whole-store restores only)
+    pub users_removed: usize,
+    // Accounts that no longer exist, e.g. erased since; their data is never brought back
+    pub users_skipped: Vec<String>,
+    pub settings_rows: u64,
+}
+
+pub struct BackupService {
+    config: BackupConfig,
+    repository: UserRepository,
+    message_manager: Arc<MessageManager>,
+    // Backups and restores never overlap
+    running: Mutex<()>,
+}
+
+impl BackupService {
+    pub fn new(config: BackupConfig, repository: UserRepository, message_manager: Arc<MessageManager>) -> Self {
+        Self { config, repository, message_manager, running: Mutex::new(()) }
+    }
+
+    pub async fn create(&self) -> Result<BackupManifest> {
+        let _running = self.running.lock().await;
+
+        let manifest = self.write_snapshot().await?;
+        if let Err(e) = self.rotate().await {
+            eprintln!("❌ Failed to rotate backups: {}", e);
+        }
+        Ok(manifest)
+    }
+
+    // Newest first
+    pub async fn list(&self) -> Result<Vec<BackupManifest>> {
+        let mut manifests = Vec::new();
+
+        let mut entries = match tokio::fs::read_dir(&self.config.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(manifests),
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            match tokio::fs::read(&path).await.map_err(anyhow::Error::from)
+                .and_then(|bytes| Ok(serde_json::from_slice::<BackupManifest>(&bytes)?))
+            {
+                Ok(manifest) => manifests.push(manifest),
+                Err(e) => eprintln!("❌ Skipping unreadable backup manifest {}: {}", path.display(), e),
+            }
+        }
+
+        manifests.sort_by_key(|manifest| Reverse(manifest.created_at));
+        Ok(manifests)
+    }
+
+    // Rolls the whole store, or just `user_id`, back to the given backup.
+    // The checksum is verified before anything is touched.
+    pub async fn restore(&self, backup_id: &str, user_id: Option<i64>) -> Result<RestoreReport> {
+        let _running = self.running.lock().await;
+
+        let manifest = self.manifest(backup_id).await?;
+        self.verify(&manifest).await?;
+
+        if let Some(user_id) = user_id
+            && !self.repository.exists(user_id).await?
+        {
+            return Err(anyhow!("User {} no longer exists", user_id));
+        }
+
+        let safety_backup = self.write_snapshot().await?;
+        let current_users = self.message_manager.user_ids().await?;
+
+        let (tx, mut rx) = mpsc::channel(16);
+        let path = self.config.dir.join(&manifest.file);
+        let reader = tokio::task::spawn_blocking(move || read_snapshot(&path, tx));
+
+        let target = user_id.map(|id| id.to_string());
+        let mut report = RestoreReport {
+            backup_id: manifest.backup_id.clone(),
+            safety_backup_id: safety_backup.backup_id,
+            users_restored: 0,
+            users_removed: 0,
+            users_skipped: Vec::new(),
+            settings_rows: 0,
+        };
+        let mut restored = HashSet::new();
+        let mut settings = Vec::new();
+
+        while let Some(record) = rx.recv().await {
+            match record {
+                BackupRecord::Settings { rows } => settings = rows,
+                BackupRecord::User { user_id, data, shares } => {
+                    if target.as_ref().is_some_and(|target| *target != user_id) {
+                        continue;
+                    }
+                    let exists = match user_id.parse::<i64>() {
+                        Ok(id) => self.repository.exists(id).await?,
+                        Err(_) => false,
+                    };
+                    if !exists {
+                        report.users_skipped.push(user_id);
+                        continue;
+                    }
+
+                    self.message_manager.restore_user(&user_id, Some((data, shares))).await?;
+                    restored.insert(user_id);
+                    report.users_restored += 1;
+                }
+            }
+        }
+        reader.await.map_err(|e| anyhow!("Backup reader failed: {}", e))??;
+
+        // Anyone without data in the backup had none at that point in time
+        let to_clear: Vec<String> = match &target {
+            Some(target) => vec![target.clone()],
+            None => current_users,
+        };
+        for user_id in to_clear.into_iter().filter(|id| !restored.contains(id)) {
+            self.message_manager.restore_user(&user_id, None).await?;
+            report.users_removed += 1;
+        }
+
+        settings.retain(|row| user_id.is_none_or(|id| row.user_id == id));
+        report.settings_rows = self.repository.replace_settings(user_id, &settings).await?;
+
+        println!(
+            "♻️ Restored backup {} ({} users restored, {} removed, {} skipped)",
+            report.backup_id, report.users_restored, report.users_removed, report.users_skipped.len(),
+        );
+        Ok(report)
+    }
+
+    async fn write_snapshot(&self) -> Result<BackupManifest> {
+        tokio::fs::create_dir_all(&self.config.dir).await?;
+
+        let created_at = Utc::now();
+        let backup_id = created_at.format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        let file = format!("{}.jsonl.gz", backup_id);
+        let path = self.config.dir.join(&file);
+        let tmp_path = path.with_extension("gz.tmp");
+
+        let (tx, rx) = mpsc::channel(64);
+        let writer = tokio::task::spawn_blocking({
+            let tmp_path = tmp_path.clone();
+            move || write_snapshot_file(&tmp_path, rx)
+        });
+
+        let mut manifest = BackupManifest {
+            backup_id,
+            created_at,
+            file,
+            bytes: 0,
+            sha256: String::new(),
+            users: 0,
+            conversations: 0,
+            messages: 0,
+            settings_rows: 0,
+        };
+        let collected = self.collect(&tx, &mut manifest).await;
+        drop(tx);
+        let written = writer.await.map_err(|e| anyhow!("Backup writer failed: {}", e))?;
+
+        let (bytes, sha256) = match (collected, written) {
+            (Ok(()), Ok(written)) => written,
+            (_, Err(e)) | (Err(e), _) => {
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+                return Err(e);
+            }
+        };
+        tokio::fs::rename(&tmp_path, &path).await?;
+
+        manifest.bytes = bytes;
+        manifest.sha256 = sha256;
+        let manifest_path = self.manifest_path(&manifest.backup_id)?;
+        let tmp_manifest = manifest_path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_manifest, serde_json::to_vec_pretty(&manifest)?).await?;
+        tokio::fs::rename(&tmp_manifest, &manifest_path).await?;
+
+        println!("💾 Backup {} written ({} users, {} bytes)", manifest.backup_id, manifest.users, manifest.bytes);
+        Ok(manifest)
+    }
+
+    // Each user is read under their shard lock, so every user's data is internally consistent
+    async fn collect(&self, tx: &mpsc::Sender<Vec<u8>>, manifest: &mut BackupManifest) -> Result<()> {
+        let rows = self.repository.all_settings().await?;
+        manifest.settings_rows = rows.len();
+        send(tx, &BackupRecord::Settings { rows }).await?;
+
+        for user_id in self.message_manager.user_ids().await? {
+            let (data, shares) = self.message_manager.backup_user(&user_id).await?;
+            manifest.users += 1;
+            manifest.conversations += data.conversations.len();
+            manifest.messages += data.conversations.values().map(|c| c.messages.len()).sum::<usize>();
+            send(tx, &BackupRecord::User { user_id, data, shares }).await?;
+        }
+        Ok(())
+    }
+
+    async fn rotate(&self) -> Result<()> {
+        for manifest in self.list().await?.into_iter().skip(self.config.keep) {
+            for path in [self.config.dir.join(&manifest.file), self.manifest_path(&manifest.backup_id)?] {
+                match tokio::fs::remove_file(&path).await {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            println!("🗑️ Rotated out backup {}", manifest.backup_id);
+        }
+        Ok(())
+    }
+
+    async fn manifest(&self, backup_id: &str) -> Result<BackupManifest> {
+        let bytes = tokio::fs::read(self.manifest_path(backup_id)?).await
+            .map_err(|_| anyhow!("Backup not found: {}", backup_id))?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    async fn verify(&self, manifest: &BackupManifest) -> Result<()> {
+        let path = self.config.dir.join(&manifest.file);
+        let (bytes, sha256) = tokio::task::spawn_blocking(move || hash_file(&path)).await??;
+
+        if bytes != manifest.bytes || sha256 != manifest.sha256 {
+            return Err(anyhow!("Backup {} failed its checksum; refusing to restore it", manifest.backup_id));
+        }
+        Ok(())
+    }
+
+    // Backup ids become file names
+    fn manifest_path(&self, backup_id: &str) -> Result<PathBuf> {
+        let valid = !backup_id.is_empty()
+            && !backup_id.contains("..")
+            && backup_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '.');
+        if !valid {
+            return Err(anyhow!("Invalid backup id: {:?}", backup_id));
+        }
+        Ok(self.config.dir.join(format!("{}.json", backup_id)))
+    }
+}
+
+pub fn spawn_scheduler(service: Arc<BackupService>, config: BackupConfig) {
+    let Some(every) = config.interval else {
+        println!("💾 Scheduled backups are off (BACKUP_INTERVAL_SECS=0)");
+        return;
+    };
+    println!("💾 Backup every {}s to {} (keeping {})", every.as_secs(), config.dir.display(), config.keep);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        // The first tick fires immediately; a restart loop should not churn through the rotation
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(e) = service.create().await {
+                eprintln!("❌ Scheduled backup failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn send(tx: &mpsc::Sender<Vec<u8>>, record: &BackupRecord) -> Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    tx.send(line).await.map_err(|_| anyhow!("Backup writer stopped"))
+}
+
+// Compresses the incoming lines and checksums the compressed bytes as they are written
+fn write_snapshot_file(path: &Path, mut rx: mpsc::Receiver<Vec<u8>>) -> Result<(u64, String)> {
+    let writer = HashingWriter { inner: BufWriter::new(File::create(path)?), hasher: Sha256::new(), bytes: 0 };
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+
+    while let Some(line) = rx.blocking_recv() {
+        encoder.write_all(&line)?;
+    }
+
+    let mut writer = encoder.finish()?;
+    writer.inner.flush()?;
+    writer.inner.get_ref().sync_all()?;
+    Ok((writer.bytes, hex::encode(writer.hasher.finalize())))
+}
+
+fn read_snapshot(path: &Path, tx: mpsc::Sender<BackupRecord>) -> Result<()> {
+    let reader = BufReader::new(GzDecoder::new(File::open(path)?));
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|e| anyhow!("Corrupt backup record: {}", e))?;
+        if tx.blocking_send(record).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn hash_file(path: &Path) -> Result<(u64, String)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut bytes = 0;
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        bytes += read as u64;
+    }
+    Ok((bytes, hex::encode(hasher.finalize())))
+}
+
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use crate::utils::file_models::ChatSession;
+    use sqlx::PgPool;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("aiwa-backup-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn service(db: PgPool, root: &Path) -> BackupService {
+        let config = BackupConfig { dir: root.join("backups"), interval: None, keep: 10 };
+        let manager = Arc::new(MessageManager::new(&root.join("store").to_string_lossy()));
+        BackupService::new(config, UserRepository { db }, manager)
+    }
+
+    fn session(title: &str) -> ChatSession {
+        serde_json::from_value(serde_json::json!({
+            "title": title,
+            "created_at": Utc::now(),
+            "messages": [],
+            "imported": { "source": "test", "external_id": title, "content_hash": title, "imported_at": Utc::now() },
+        })).unwrap()
+    }
+
+    async fn titles(service: &BackupService, user_id: i64) -> Vec<String> {
+        let data = service.message_manager.get_user_data(&user_id.to_string()).await.unwrap();
+        let mut titles: Vec<String> = data.conversations.into_values().map(|c| c.title).collect();
+        titles.sort();
+        titles
+    }
+
+    #[tokio::test]
+    async fn a_backup_that_fails_its_checksum_is_not_restored() {
+        let root = temp_dir();
+        let service = service(PgPool::connect_lazy("postgres://localhost:1/unused").unwrap(), &root);
+        std::fs::create_dir_all(&service.config.dir).unwrap();
+
+        let snapshot = service.config.dir.join("20240101T000000.000Z.jsonl.gz");
+        std::fs::write(&snapshot, b"original bytes").unwrap();
+        let (bytes, sha256) = hash_file(&snapshot).unwrap();
+        let manifest = BackupManifest {
+            backup_id: "20240101T000000.000Z".to_string(),
+            created_at: Utc::now(),
+            file: "20240101T000000.000Z.jsonl.gz".to_string(),
+            bytes,
+            sha256,
+            users: 0,
+            conversations: 0,
+            messages: 0,
+            settings_rows: 0,
+        };
+        std::fs::write(service.manifest_path(&manifest.backup_id).unwrap(), serde_json::to_vec(&manifest).unwrap()).unwrap();
+        std::fs::write(&snapshot, b"tampered bytes").unwrap();
+
+        let error = service.restore(&manifest.backup_id, None).await.unwrap_err();
+        assert!(error.to_string().contains("checksum"));
+        // Nothing was touched, not even a safety backup taken
+        assert_eq!(service.list().await.unwrap().len(), 1);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn a_safety_backup_is_taken_before_restoring() {
+        let db = test_support::database().await;
+        let root = temp_dir();
+        let service = service(db.clone(), &root);
+        let (user_id, _) = test_support::create_user(&db).await;
+
+        service.message_manager.import_conversations(&user_id.to_string(), vec![session("before")]).await.unwrap();
+        let backup = service.create().await.unwrap();
+        service.message_manager.import_conversations(&user_id.to_string(), vec![session("after")]).await.unwrap();
+
+        // Backup ids have millisecond resolution
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let report = service.restore(&backup.backup_id, Some(user_id)).await.unwrap();
+        assert_eq!(titles(&service, user_id).await, vec!["before"]);
+        assert_ne!(report.safety_backup_id, backup.backup_id);
+
+        // The safety backup undoes the restore
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        service.restore(&report.safety_backup_id, Some(user_id)).await.unwrap();
+        assert_eq!(titles(&service, user_id).await, vec!["after", "before"]);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn users_deleted_since_the_backup_are_not_brought_back() {
+        let db = test_support::database().await;
+        let root = temp_dir();
+        let service = service(db.clone(), &root);
+        let (kept, _) = test_support::create_user(&db).await;
+        let (deleted, _) = test_support::create_user(&db).await;
+
+        for user_id in [kept, deleted] {
+            service.message_manager.import_conversations(&user_id.to_string(), vec![session("before")]).await.unwrap();
+        }
+        let backup = service.create().await.unwrap();
+
+        service.message_manager.erase_user(&deleted.to_string()).await.unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1").bind(deleted).execute(&db).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let report = service.restore(&backup.backup_id, None).await.unwrap();
+        assert_eq!(report.users_skipped, vec![deleted.to_string()]);
+        assert_eq!(report.users_restored, 1);
+        assert_eq!(titles(&service, kept).await, vec!["before"]);
+        assert!(!service.message_manager.has_user_data(&deleted.to_string()).await.unwrap());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -65,10 +65,10 @@ impl LlmService {
             let body = response.text().await.unwrap_or_else(|_| "Failed to read error body.".to_string());
             let _ = broadcaster.send_to(
                 &client_id,
-                serde_json::to_string(&CommunicationResponse::Error {
+                CommunicationResponse::Error {
                     status: "ai_error".to_string(),
                     error: format!("Ollama API returned error {}: {}", status, body),
-                }).unwrap(),
+                },
             ).await;
             return Err(format!("Ollama API error: {}", status).into());
         }
@@ -76,7 +76,7 @@ impl LlmService {
         // Convert the streaming response into an async reader
         let byte_stream = response.bytes_stream();
         let stream_reader = StreamReader::new(
-            byte_stream.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+            byte_stream.map_err(std::io::Error::other)
         );
         let reader = TokioBufReader::new(stream_reader);
         let mut lines = reader.lines();
@@ -104,9 +104,9 @@ impl LlmService {
                 // Also stream to WebSocket
                 let _ = broadcaster.send_to(
                     &client_id,
-                    serde_json::to_string(&CommunicationResponse::StreamChunk {
+                    CommunicationResponse::StreamChunk {
                         chunk: text,
-                    }).unwrap()
+                    }
                 ).await;
             }
 
@@ -117,9 +117,9 @@ impl LlmService {
 
         let _ = broadcaster.send_to(
             &client_id,
-            serde_json::to_string(&CommunicationResponse::StreamEnd {
+            CommunicationResponse::StreamEnd {
                 status: "success".to_string(),
-            }).unwrap()
+            }
         ).await;
 
         Ok(full_response) // Return the accumulated response
@@ -0,0 +1,201 @@
+use std::env;
+use std::sync::Arc;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+
+use crate::repository::login_attempt_repository::{FailureStats, LoginAttemptRepository, ACCOUNT, IP};
+
+#[derive(Debug, Clone)]
+pub struct LoginThrottleConfig {
+    // Failures only count while they're this recent
+    pub window: Duration,
+    pub max_account_failures: i64,
+    pub max_ip_failures: i64,
+    // Failures allowed before backoff starts; each one after that doubles the wait
+    pub account_free_failures: i64,
+    pub ip_free_failures: i64,
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+    pub lockout: Duration,
+    pub retention: Duration,
+}
+
+impl LoginThrottleConfig {
+    pub fn from_env() -> Self {
+        let number = |key: &str, default: i64| {
+            env::var(key).ok().and_then(|v| v.parse::<i64>().ok()).filter(|n| *n > 0).unwrap_or(default)
+        };
+
+        Self {
+            window: Duration::minutes(number("LOGIN_FAILURE_WINDOW_MINUTES", 15)),
+            max_account_failures: number("LOGIN_MAX_FAILURES_PER_ACCOUNT", 5),
+            max_ip_failures: number("LOGIN_MAX_FAILURES_PER_IP", 20),
+            account_free_failures: number("LOGIN_ACCOUNT_FREE_FAILURES", 2),
+            ip_free_failures: number("LOGIN_IP_FREE_FAILURES", 5),
+            backoff_base: Duration::seconds(number("LOGIN_BACKOFF_BASE_SECS", 1)),
+            backoff_max: Duration::seconds(number("LOGIN_BACKOFF_MAX_SECS", 60)),
+            lockout: Duration::minutes(number("LOGIN_LOCKOUT_MINUTES", 15)),
+            retention: Duration::days(number("LOGIN_ATTEMPT_RETENTION_DAYS", 30)),
+        }
+    }
+}
+
+pub struct LoginThrottle {
+    db: PgPool,
+    config: LoginThrottleConfig,
+}
+
+impl LoginThrottle {
+    pub fn new(db: PgPool, config: LoginThrottleConfig) -> Self {
+        Self { db, config }
+    }
+
+    fn repository(&self) -> LoginAttemptRepository {
+        LoginAttemptRepository { db: self.db.clone() }
+    }
+
+    // Seconds the caller has to wait before this email may be tried from this IP, if any.
+    // Keyed by the email as typed, so unknown addresses are throttled exactly like real ones.
+    pub async fn retry_after(&self, email: &str, ip: &str) -> Result<Option<i64>> {
+        let repo = self.repository();
+        let now = Utc::now();
+
+        let mut retry_at = repo.locked_until(email, ip).await?;
+        let since = now - self.config.window;
+        let account = repo.failures(ACCOUNT, email, since).await?;
+        let ip_failures = repo.failures(IP, ip, since).await?;
+        retry_at = retry_at
+            .max(self.backoff_until(&account, self.config.account_free_failures))
+            .max(self.backoff_until(&ip_failures, self.config.ip_free_failures));
+
+        Ok(retry_at
+            .filter(|retry_at| *retry_at > now)
+            .map(|retry_at| (retry_at - now).num_seconds().max(1)))
+    }
+
+    // `user_id` is only for the audit trail; it is None when no account has this email
+    pub async fn record_failure(&self, email: &str, ip: &str, user_id: Option<i64>) -> Result<()> {
+        let repo = self.repository();
+        repo.record(email, ip, false).await?;
+
+        let since = Utc::now() - self.config.window;
+        let locked_until = Utc::now() + self.config.lockout;
+
+        let account = repo.failures(ACCOUNT, email, since).await?;
+        if account.count >= self.config.max_account_failures {
+            repo.record_lockout(ACCOUNT, email, user_id, ip, account.count, locked_until).await?;
+            println!("🔒 Locked logins for {} after {} failures (last from {})", email, account.count, ip);
+        }
+
+        let ip_failures = repo.failures(IP, ip, since).await?;
+        if ip_failures.count >= self.config.max_ip_failures {
+            repo.record_lockout(IP, ip, user_id, ip, ip_failures.count, locked_until).await?;
+            println!("🔒 Locked logins from {} after {} failures", ip, ip_failures.count);
+        }
+
+        Ok(())
+    }
+
+    // Only for a completed login (after the 2FA step, if any). The account starts over with a
+    // clean slate; the IP keeps its failures, since they may be for other accounts.
+    pub async fn record_success(&self, email: &str, ip: &str) -> Result<()> {
+        let repo = self.repository();
+        repo.clear_failures(email).await?;
+        repo.record(email, ip, true).await
+    }
+
+    pub async fn prune(&self) -> Result<u64> {
+        self.repository().prune(Utc::now() - self.config.retention).await
+    }
+
+    fn backoff_until(&self, stats: &FailureStats, free_failures: i64) -> Option<DateTime<Utc>> {
+        let extra = stats.count - free_failures;
+        if extra <= 0 {
+            return None;
+        }
+        let factor = 1i32 << (extra - 1).min(20);
+        let wait = (self.config.backoff_base * factor).min(self.config.backoff_max);
+        stats.last_failed_at.map(|last| last + wait)
+    }
+}
+
+// Emails are compared case-insensitively for throttling
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+pub fn spawn_pruner(throttle: Arc<LoginThrottle>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match throttle.prune().await {
+                Ok(0) => {}
+                Ok(count) => println!("🔒 Pruned {} old login attempt(s)", count),
+                Err(e) => eprintln!("❌ Failed to prune login attempts: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle() -> LoginThrottle {
+        let config = LoginThrottleConfig {
+            window: Duration::minutes(15),
+            max_account_failures: 5,
+            max_ip_failures: 20,
+            account_free_failures: 2,
+            ip_free_failures: 5,
+            backoff_base: Duration::seconds(1),
+            backoff_max: Duration::seconds(60),
+            lockout: Duration::minutes(15),
+            retention: Duration::days(30),
+        };
+        LoginThrottle::new(PgPool::connect_lazy("postgres://localhost:1/unused").unwrap(), config)
+    }
+
+    fn wait(throttle: &LoginThrottle, count: i64) -> Option<Duration> {
+        let last = Utc::now();
+        throttle
+            .backoff_until(&FailureStats { count, last_failed_at: Some(last) }, 2)
+            .map(|until| until - last)
+    }
+
+    #[tokio::test]
+    async fn free_failures_have_no_backoff() {
+        let throttle = throttle();
+        assert_eq!(wait(&throttle, 0), None);
+        assert_eq!(wait(&throttle, 2), None);
+    }
+
+    #[tokio::test]
+    async fn backoff_doubles_after_the_free_failures() {
+        let throttle = throttle();
+        assert_eq!(wait(&throttle, 3), Some(Duration::seconds(1)));
+        assert_eq!(wait(&throttle, 4), Some(Duration::seconds(2)));
+        assert_eq!(wait(&throttle, 5), Some(Duration::seconds(4)));
+        assert_eq!(wait(&throttle, 8), Some(Duration::seconds(32)));
+    }
+
+    #[tokio::test]
+    async fn backoff_is_capped_without_overflowing() {
+        let throttle = throttle();
+        assert_eq!(wait(&throttle, 9), Some(Duration::seconds(60)));
+        assert_eq!(wait(&throttle, 1_000), Some(Duration::seconds(60)));
+    }
+
+    #[tokio::test]
+    async fn backoff_needs_a_last_failure() {
+        let throttle = throttle();
+        assert!(throttle.backoff_until(&FailureStats { count: 10, last_failed_at: None }, 2).is_none());
+    }
+
+    #[test]
+    fn emails_are_normalized() {
+        assert_eq!(normalize_email("  Alice@Example.COM "), "alice@example.com");
+    }
+}
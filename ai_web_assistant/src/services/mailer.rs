@@ -0,0 +1,133 @@
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use serde::Serialize;
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Where account emails (verification links, ...) go. Picked with MAIL_TRANSPORT.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<()>;
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    // SMTP_TLS is "starttls" (default), "tls" or "none"
+    pub fn from_env() -> Result<Self> {
+        let host = env::var("SMTP_HOST").map_err(|_| anyhow!("SMTP_HOST must be set when MAIL_TRANSPORT=smtp"))?;
+        let tls = env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string());
+
+        let mut builder = match tls.as_str() {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?,
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host.as_str()),
+            other => return Err(anyhow!("Unknown SMTP_TLS mode {:?}", other)),
+        };
+        if let Some(port) = env::var("SMTP_PORT").ok().and_then(|v| v.parse::<u16>().ok()) {
+            builder = builder.port(port);
+        }
+        if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: mail_from().parse()?,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse()?)
+            .subject(email.subject.clone())
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())?;
+
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+// Dev and test sink: appends each email as a JSON line to a file, or only logs it
+pub struct FileMailer {
+    path: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+impl FileMailer {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { path, lock: Mutex::new(()) }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> Result<()> {
+        let Some(path) = &self.path else {
+            println!("📧 To {} | {}\n{}", email.to, email.subject, email.body);
+            return Ok(());
+        };
+
+        let _guard = self.lock.lock().await;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let mut line = serde_json::to_vec(&json!({
+            "from": mail_from(),
+            "to": email.to,
+            "subject": email.subject,
+            "body": email.body,
+            "sent_at": Utc::now(),
+        }))?;
+        line.push(b'\n');
+
+        let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+        file.write_all(&line).await?;
+        println!("📧 Wrote email to {} into {}", email.to, path.display());
+        Ok(())
+    }
+}
+
+// MAIL_TRANSPORT is "smtp", "file" (MAIL_OUTBOX, default data/outbox.jsonl) or "log" (default)
+pub fn from_env() -> Result<Arc<dyn Mailer>> {
+    let transport = env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "log".to_string());
+    let mailer: Arc<dyn Mailer> = match transport.as_str() {
+        "smtp" => Arc::new(SmtpMailer::from_env()?),
+        "file" => {
+            let path = env::var("MAIL_OUTBOX").unwrap_or_else(|_| "data/outbox.jsonl".to_string());
+            Arc::new(FileMailer::new(Some(PathBuf::from(path))))
+        }
+        "log" => Arc::new(FileMailer::new(None)),
+        other => return Err(anyhow!("Unknown MAIL_TRANSPORT {:?}", other)),
+    };
+    Ok(mailer)
+}
+
+fn mail_from() -> String {
+    env::var("MAIL_FROM").unwrap_or_else(|_| "AI Web Assistant <no-reply@localhost>".to_string())
+}
@@ -1,3 +1,14 @@
 pub mod auth_service;
 pub mod user_service;
-pub mod llm_service;
\ No newline at end of file
+pub mod llm_service;
+pub mod account_service;
+pub mod backup_service;
+pub mod revocation_service;
+pub mod mailer;
+pub mod verification_service;
+pub mod password_service;
+pub mod two_factor_service;
+pub mod api_key_service;
+pub mod oidc_service;
+pub mod login_throttle_service;
+pub mod role_service;
\ No newline at end of file
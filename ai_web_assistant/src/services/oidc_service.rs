@@ -0,0 +1,685 @@
+use std::env;
+use std::time::{Duration as StdDuration, Instant};
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Utc};
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use rand::{rngs::OsRng, RngCore};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::models::users::User;
+use crate::repository::auth_repository::AuthenticationRepository;
+use crate::repository::oidc_repository::OidcRepository;
+use crate::repository::role_repository::RoleRepository;
+use crate::repository::user_repository::UserRepository;
+use crate::responses::login_responses::LoginResponse;
+use crate::services::auth_service::{hash_password, new_secret_token, start_session};
+
+// How long a user has to finish signing in at the identity provider
+const LOGIN_STATE_TTL_MINUTES: i64 = 10;
+const DISCOVERY_TTL: StdDuration = StdDuration::from_secs(3600);
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    // Public clients rely on PKCE alone
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String,
+    // Create accounts for unknown users; otherwise only existing accounts can sign in
+    pub auto_provision: bool,
+    // Role given to provisioned accounts, on top of "user"; any role in the roles table
+    pub default_role: String,
+    // Browser flows land here with the tokens in the URL fragment; unset returns JSON
+    pub post_login_redirect: Option<String>,
+}
+
+impl OidcConfig {
+    // None unless OIDC_ISSUER and OIDC_CLIENT_ID are set
+    pub fn from_env() -> Option<Self> {
+        let issuer = env::var("OIDC_ISSUER").ok().filter(|v| !v.is_empty())?;
+        let client_id = match env::var("OIDC_CLIENT_ID").ok().filter(|v| !v.is_empty()) {
+            Some(client_id) => client_id,
+            None => {
+                eprintln!("⚠️ OIDC_ISSUER is set but OIDC_CLIENT_ID is not; single sign-on disabled");
+                return None;
+            }
+        };
+        let base_url = env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:8022".to_string());
+
+        let default_role = env::var("OIDC_DEFAULT_ROLE").unwrap_or_else(|_| "user".to_string()).trim().to_lowercase();
+
+        Some(Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret: env::var("OIDC_CLIENT_SECRET").ok().filter(|v| !v.is_empty()),
+            redirect_uri: env::var("OIDC_REDIRECT_URI")
+                .unwrap_or_else(|_| format!("{}/auth/oidc/callback", base_url.trim_end_matches('/'))),
+            scopes: env::var("OIDC_SCOPES").unwrap_or_else(|_| "openid email profile".to_string()),
+            auto_provision: env::var("OIDC_AUTO_PROVISION")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(true),
+            default_role,
+            post_login_redirect: env::var("OIDC_POST_LOGIN_REDIRECT").ok().filter(|v| !v.is_empty()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    // Some providers send "true" as a string
+    email_verified: Option<serde_json::Value>,
+    preferred_username: Option<String>,
+}
+
+impl IdTokenClaims {
+    fn verified_email(&self) -> Option<&str> {
+        let verified = match &self.email_verified {
+            Some(serde_json::Value::Bool(verified)) => *verified,
+            Some(serde_json::Value::String(verified)) => verified == "true",
+            _ => false,
+        };
+        self.email.as_deref().filter(|_| verified)
+    }
+}
+
+struct ProviderCache {
+    fetched_at: Instant,
+    discovery: Discovery,
+    jwks: JwkSet,
+}
+
+pub struct OidcService {
+    db: PgPool,
+    config: Option<OidcConfig>,
+    http: reqwest::Client,
+    cache: RwLock<Option<ProviderCache>>,
+}
+
+impl OidcService {
+    pub fn new(db: PgPool, config: Option<OidcConfig>) -> Self {
+        if let Some(config) = &config {
+            println!("🏢 Single sign-on enabled for {}", config.issuer);
+        }
+        Self {
+            db,
+            config,
+            http: reqwest::Client::new(),
+            cache: RwLock::new(None),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    pub fn post_login_redirect(&self) -> Option<&str> {
+        self.config.as_ref()?.post_login_redirect.as_deref()
+    }
+
+    fn config(&self) -> Result<&OidcConfig> {
+        self.config.as_ref().ok_or_else(|| anyhow!("Single sign-on is not configured"))
+    }
+
+    // Authorization URL to send the browser to; the state, PKCE verifier and nonce are
+    // kept server-side until the provider redirects back
+    pub async fn authorization_url(&self) -> Result<String> {
+        let config = self.config()?;
+        let discovery = self.discovery(false).await?;
+
+        let state = random_token();
+        let nonce = random_token();
+        let code_verifier = random_token();
+        let code_challenge = BASE64URL_NOPAD.encode(&Sha256::digest(code_verifier.as_bytes()));
+
+        OidcRepository { db: self.db.clone() }
+            .save_state(&state, &code_verifier, &nonce, Utc::now() + Duration::minutes(LOGIN_STATE_TTL_MINUTES))
+            .await?;
+
+        let url = reqwest::Url::parse_with_params(&discovery.authorization_endpoint, &[
+            ("response_type", "code"),
+            ("client_id", config.client_id.as_str()),
+            ("redirect_uri", config.redirect_uri.as_str()),
+            ("scope", config.scopes.as_str()),
+            ("state", state.as_str()),
+            ("nonce", nonce.as_str()),
+            ("code_challenge", code_challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ])?;
+        Ok(url.to_string())
+    }
+
+    // Redeems the code, validates the ID token and signs the matching account in
+    pub async fn complete_login(&self, code: &str, state: &str) -> Result<LoginResponse> {
+        let config = self.config()?;
+        let (code_verifier, nonce) = OidcRepository { db: self.db.clone() }
+            .take_state(state)
+            .await?
+            .ok_or_else(|| anyhow!("Login attempt expired or unknown; start again"))?;
+        let discovery = self.discovery(false).await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", config.redirect_uri.as_str()),
+            ("client_id", config.client_id.as_str()),
+            ("code_verifier", code_verifier.as_str()),
+        ];
+        if let Some(secret) = &config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+
+        let response = self.http.post(&discovery.token_endpoint).form(&form).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            eprintln!("❌ OIDC token exchange failed ({}): {}", status, body);
+            return Err(anyhow!("Identity provider rejected the login"));
+        }
+        let tokens: TokenResponse = response.json().await?;
+
+        let claims = self.validate_id_token(&tokens.id_token, &discovery.issuer).await?;
+        if claims.nonce.as_deref() != Some(nonce.as_str()) {
+            return Err(anyhow!("ID token nonce mismatch"));
+        }
+
+        let user = self.resolve_user(config, &discovery.issuer, &claims).await?;
+        if !user.is_active {
+            return Err(anyhow!("Account is disabled"));
+        }
+
+        println!("🏢 User {} signed in through {}", user.id, discovery.issuer);
+        start_session(&self.db, &user, Uuid::new_v4()).await
+    }
+
+    async fn validate_id_token(&self, id_token: &str, issuer: &str) -> Result<IdTokenClaims> {
+        let config = self.config()?;
+        let header = decode_header(id_token)?;
+
+        let key = match header.alg {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                let secret = config.client_secret.as_ref()
+                    .ok_or_else(|| anyhow!("HMAC-signed ID token but no OIDC_CLIENT_SECRET"))?;
+                DecodingKey::from_secret(secret.as_bytes())
+            }
+            _ => {
+                let kid = header.kid.as_deref().ok_or_else(|| anyhow!("ID token has no key id"))?;
+                // An unknown kid usually means the provider rotated its keys
+                let jwk = match self.cached_jwk(kid).await {
+                    Some(jwk) => jwk,
+                    None => {
+                        self.discovery(true).await?;
+                        self.cached_jwk(kid).await.ok_or_else(|| anyhow!("ID token signed with unknown key {}", kid))?
+                    }
+                };
+                DecodingKey::from_jwk(&jwk)?
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&config.client_id]);
+        validation.set_issuer(&[issuer]);
+
+        Ok(decode::<IdTokenClaims>(id_token, &key, &validation)?.claims)
+    }
+
+    // Known identity first, then an existing account with the same verified email, then a new account
+    async fn resolve_user(&self, config: &OidcConfig, issuer: &str, claims: &IdTokenClaims) -> Result<User> {
+        let identities = OidcRepository { db: self.db.clone() };
+        let users = UserRepository { db: self.db.clone() };
+
+        if let Some(user_id) = identities.find_linked_user(issuer, &claims.sub).await? {
+            return users.find_by_id(i32::try_from(user_id)?).await;
+        }
+
+        // Unverified emails are never trusted for linking; anyone can claim an address at some providers
+        let email = claims.verified_email()
+            .ok_or_else(|| anyhow!("Identity provider did not return a verified email"))?;
+
+        let user = match users.find_by_email_ignore_case(email).await? {
+            Some(user) => {
+                println!("🔗 Linked {} identity {} to user {}", issuer, claims.sub, user.id);
+                user
+            }
+            None if config.auto_provision => self.provision(config, email, claims.preferred_username.as_deref()).await?,
+            None => return Err(anyhow!("No account exists for {}", email)),
+        };
+
+        identities.link(user.id, issuer, &claims.sub, Some(email)).await?;
+        Ok(user)
+    }
+
+    async fn provision(&self, config: &OidcConfig, email: &str, preferred_username: Option<&str>) -> Result<User> {
+        let users = UserRepository { db: self.db.clone() };
+        let base = username_base(preferred_username.unwrap_or_else(|| email.split('@').next().unwrap_or("user")));
+
+        let mut username = base.clone();
+        while users.username_taken(&username).await? {
+            username = format!("{}-{}", base, &random_token()[..6]);
+        }
+
+        // Random password nobody knows; the account can still set one via forgot-password
+        let password = hash_password(&new_secret_token())?;
+        let user = AuthenticationRepository { db: self.db.clone() }
+            .create_sso_user(email, &username, &password)
+            .await?;
+        if config.default_role != "user" {
+            let roles = ["user".to_string(), config.default_role.clone()];
+            RoleRepository { db: self.db.clone() }.set_user_roles(user.id, &roles).await?;
+        }
+
+        println!("👤 Provisioned user {} ({}) from single sign-on", user.id, config.default_role);
+        Ok(user)
+    }
+
+    async fn cached_jwk(&self, kid: &str) -> Option<jsonwebtoken::jwk::Jwk> {
+        self.cache.read().await.as_ref()?.jwks.find(kid).cloned()
+    }
+
+    // Discovery document and signing keys, refetched hourly or when forced
+    async fn discovery(&self, force: bool) -> Result<Discovery> {
+        if !force
+            && let Some(cache) = self.cache.read().await.as_ref().filter(|c| c.fetched_at.elapsed() < DISCOVERY_TTL)
+        {
+            return Ok(cache.discovery.clone());
+        }
+
+        let config = self.config()?;
+        let url = format!("{}/.well-known/openid-configuration", config.issuer);
+        let discovery: Discovery = self.http.get(&url).send().await?.error_for_status()?.json().await?;
+        if discovery.issuer.trim_end_matches('/') != config.issuer {
+            return Err(anyhow!("Discovery issuer {} does not match OIDC_ISSUER", discovery.issuer));
+        }
+        let jwks: JwkSet = self.http.get(&discovery.jwks_uri).send().await?.error_for_status()?.json().await?;
+
+        *self.cache.write().await = Some(ProviderCache {
+            fetched_at: Instant::now(),
+            discovery: discovery.clone(),
+            jwks,
+        });
+        Ok(discovery)
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    BASE64URL_NOPAD.encode(&bytes)
+}
+
+// Letters, digits, '.', '_' and '-' only; 3 to 40 characters like registered usernames
+fn username_base(raw: &str) -> String {
+    let cleaned: String = raw
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        .take(40)
+        .collect();
+    if cleaned.len() < 3 { "user".to_string() } else { cleaned }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use axum::{extract::State, http::StatusCode, routing::{get, post}, Form, Json, Router};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::{json, Value};
+
+    use crate::test_support;
+
+    // Throwaway key for the mock identity provider below; it signs nothing outside these tests
+    const TEST_KEY_PEM: &str = include_str!("testdata/oidc_test_key.pem");
+    const TEST_KEY_N: &str = "sdYlzVqSPezlyeG5FWHlzT955d-fgywtE7ACOtG2LUV3ROjIWr_pQ2wqSbQrT4GDh3UNTp7G_nh0cOA6bNJ5DfYuPf6nAIwtR0S7SqkaA7qNFV-Nhsht4S6FjgSjmgvIUJaEb7FcyaPNiI71M50_B4RNPo4IF7SShkvaLUluo95aDCMVV7sCAdOoOYYPs4e_MksT6ItNZeqxH5T0NNP0dWQ-w2G9pAdXdm48Sn5aBLFSaeOK4hfQ4CWqYv4iGz2Nq_bmvGRsiCOiiwSAdeexPdGtkViY0iu4EP7IvNnS9EXVQqK8d9k3e3j1I-ZSnrYkgBZNwSUw3nbj59u8jTQrDQ";
+    const TEST_KEY_ID: &str = "test-key";
+    const CLIENT_ID: &str = "ai-web-assistant";
+    const REDIRECT_URI: &str = "http://localhost:8022/auth/oidc/callback";
+
+    // Serves discovery, JWKS and a token endpoint that checks PKCE like a real provider
+    struct MockIdp {
+        base_url: String,
+        // Issuer the discovery document claims; a real provider always claims its own URL
+        advertised_issuer: Mutex<Option<String>>,
+        // code -> (PKCE challenge it was issued for, ID token claims)
+        codes: Mutex<HashMap<String, (String, Value)>>,
+    }
+
+    async fn discovery_document(State(idp): State<Arc<MockIdp>>) -> Json<Value> {
+        let issuer = idp.advertised_issuer.lock().unwrap().clone().unwrap_or_else(|| idp.base_url.clone());
+        Json(json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", idp.base_url),
+            "token_endpoint": format!("{}/token", idp.base_url),
+            "jwks_uri": format!("{}/jwks", idp.base_url),
+        }))
+    }
+
+    async fn jwks() -> Json<Value> {
+        Json(json!({
+            "keys": [{ "kty": "RSA", "kid": TEST_KEY_ID, "alg": "RS256", "use": "sig", "n": TEST_KEY_N, "e": "AQAB" }]
+        }))
+    }
+
+    async fn token(State(idp): State<Arc<MockIdp>>, Form(form): Form<HashMap<String, String>>) -> (StatusCode, Json<Value>) {
+        let invalid_grant = (StatusCode::BAD_REQUEST, Json(json!({ "error": "invalid_grant" })));
+        let field = |name: &str| form.get(name).map(String::as_str).unwrap_or_default();
+        if field("grant_type") != "authorization_code" || field("client_id") != CLIENT_ID || field("redirect_uri") != REDIRECT_URI {
+            return invalid_grant;
+        }
+        let Some((challenge, claims)) = idp.codes.lock().unwrap().remove(field("code")) else {
+            return invalid_grant;
+        };
+        if BASE64URL_NOPAD.encode(&Sha256::digest(field("code_verifier").as_bytes())) != challenge {
+            return invalid_grant;
+        }
+
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(TEST_KEY_ID.to_string());
+        let key = EncodingKey::from_rsa_pem(TEST_KEY_PEM.as_bytes()).unwrap();
+        let id_token = encode(&header, &claims, &key).unwrap();
+        (StatusCode::OK, Json(json!({ "id_token": id_token, "access_token": "unused", "token_type": "Bearer" })))
+    }
+
+    async fn mock_idp() -> Arc<MockIdp> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let idp = Arc::new(MockIdp {
+            base_url: format!("http://{}", listener.local_addr().unwrap()),
+            advertised_issuer: Mutex::new(None),
+            codes: Mutex::new(HashMap::new()),
+        });
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery_document))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(idp.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        idp
+    }
+
+    fn config(issuer: &str) -> OidcConfig {
+        OidcConfig {
+            issuer: issuer.to_string(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: None,
+            redirect_uri: REDIRECT_URI.to_string(),
+            scopes: "openid email profile".to_string(),
+            auto_provision: true,
+            default_role: "user".to_string(),
+            post_login_redirect: None,
+        }
+    }
+
+    fn offline_service(issuer: &str) -> OidcService {
+        OidcService::new(PgPool::connect_lazy("postgres://localhost:1/unused").unwrap(), Some(config(issuer)))
+    }
+
+    struct Login {
+        state: String,
+        nonce: String,
+        challenge: String,
+    }
+
+    struct Harness {
+        idp: Arc<MockIdp>,
+        service: OidcService,
+        db: PgPool,
+    }
+
+    impl Harness {
+        async fn new(configure: impl FnOnce(&mut OidcConfig)) -> Self {
+            let idp = mock_idp().await;
+            let db = test_support::database().await;
+            let mut config = config(&idp.base_url);
+            configure(&mut config);
+            Self { service: OidcService::new(db.clone(), Some(config)), idp, db }
+        }
+
+        // What the browser carries to the provider
+        async fn start(&self) -> Login {
+            let url = reqwest::Url::parse(&self.service.authorization_url().await.unwrap()).unwrap();
+            let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+            Login {
+                state: query["state"].clone(),
+                nonce: query["nonce"].clone(),
+                challenge: query["code_challenge"].clone(),
+            }
+        }
+
+        // The provider authenticating the user and redirecting back with a code
+        fn approve(&self, login: &Login, claims: Value) -> String {
+            let code = random_token();
+            self.idp.codes.lock().unwrap().insert(code.clone(), (login.challenge.clone(), claims));
+            code
+        }
+
+        fn claims(&self, login: &Login, subject: &str, email: &str, verified: Value) -> Value {
+            let now = Utc::now().timestamp();
+            json!({
+                "iss": self.idp.base_url,
+                "aud": CLIENT_ID,
+                "sub": subject,
+                "nonce": login.nonce,
+                "email": email,
+                "email_verified": verified,
+                "preferred_username": "sso.person",
+                "iat": now,
+                "exp": now + 300,
+            })
+        }
+
+        async fn sign_in(&self, subject: &str, email: &str, verified: Value) -> Result<LoginResponse> {
+            let login = self.start().await;
+            let code = self.approve(&login, self.claims(&login, subject, email, verified));
+            self.service.complete_login(&code, &login.state).await
+        }
+
+        async fn linked_user(&self, subject: &str) -> Option<i64> {
+            OidcRepository { db: self.db.clone() }.find_linked_user(&self.idp.base_url, subject).await.unwrap()
+        }
+    }
+
+    fn unique_email() -> String {
+        format!("sso-{}@example.com", &Uuid::new_v4().simple().to_string()[..12])
+    }
+
+    fn unique_subject() -> String {
+        Uuid::new_v4().to_string()
+    }
+
+    #[tokio::test]
+    async fn discovery_loads_the_provider_endpoints_and_keys() {
+        let idp = mock_idp().await;
+        let service = offline_service(&idp.base_url);
+
+        let discovery = service.discovery(false).await.unwrap();
+        assert_eq!(discovery.token_endpoint, format!("{}/token", idp.base_url));
+        assert_eq!(discovery.authorization_endpoint, format!("{}/authorize", idp.base_url));
+        assert!(service.cached_jwk(TEST_KEY_ID).await.is_some());
+        assert!(service.cached_jwk("other").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn discovery_for_another_issuer_is_refused() {
+        let idp = mock_idp().await;
+        *idp.advertised_issuer.lock().unwrap() = Some("https://evil.example.com".to_string());
+
+        let error = offline_service(&idp.base_url).discovery(false).await.unwrap_err().to_string();
+        assert!(error.contains("does not match"), "{}", error);
+    }
+
+    #[test]
+    fn only_verified_emails_count() {
+        let claims = |verified: Value| IdTokenClaims {
+            sub: "s".into(),
+            nonce: None,
+            email: Some("a@example.com".into()),
+            email_verified: Some(verified),
+            preferred_username: None,
+        };
+        assert_eq!(claims(json!(true)).verified_email(), Some("a@example.com"));
+        assert_eq!(claims(json!("true")).verified_email(), Some("a@example.com"));
+        assert_eq!(claims(json!(false)).verified_email(), None);
+        assert_eq!(claims(json!("false")).verified_email(), None);
+        assert_eq!(claims(json!(null)).verified_email(), None);
+    }
+
+    #[test]
+    fn usernames_are_cleaned_up() {
+        assert_eq!(username_base("Jane Doe!"), "JaneDoe");
+        assert_eq!(username_base("j"), "user");
+        assert_eq!(username_base(&"x".repeat(60)).len(), 40);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn authorization_url_uses_pkce_s256_with_a_stored_verifier() {
+        let harness = Harness::new(|_| {}).await;
+        let url = reqwest::Url::parse(&harness.service.authorization_url().await.unwrap()).unwrap();
+        let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+
+        assert!(url.as_str().starts_with(&format!("{}/authorize?", harness.idp.base_url)));
+        assert_eq!(query["response_type"], "code");
+        assert_eq!(query["client_id"], CLIENT_ID);
+        assert_eq!(query["redirect_uri"], REDIRECT_URI);
+        assert_eq!(query["code_challenge_method"], "S256");
+        assert_ne!(query["state"], query["nonce"]);
+
+        let (verifier, nonce) = OidcRepository { db: harness.db.clone() }.take_state(&query["state"]).await.unwrap().unwrap();
+        assert_eq!(BASE64URL_NOPAD.encode(&Sha256::digest(verifier.as_bytes())), query["code_challenge"]);
+        assert_eq!(nonce, query["nonce"]);
+        assert!(verifier.len() >= 43, "RFC 7636 wants at least 43 characters");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn unknown_verified_email_is_provisioned_with_the_default_role() {
+        let harness = Harness::new(|config| config.default_role = "staff".to_string()).await;
+        let (subject, email) = (unique_subject(), unique_email());
+
+        let session = harness.sign_in(&subject, &email, json!(true)).await.unwrap();
+        assert_eq!(session.email, email);
+        assert!(session.username.starts_with("sso.person"));
+        assert_eq!(harness.linked_user(&subject).await, Some(session.id));
+
+        let access = RoleRepository { db: harness.db.clone() }.access_for_user(session.id).await.unwrap();
+        assert_eq!(access.roles, vec!["staff".to_string(), "user".to_string()]);
+        let user = UserRepository { db: harness.db.clone() }.find_by_id(i32::try_from(session.id).unwrap()).await.unwrap();
+        assert!(user.is_verified);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn existing_account_is_linked_by_verified_email() {
+        let harness = Harness::new(|_| {}).await;
+        let (user_id, email) = test_support::create_user(&harness.db).await;
+        let subject = unique_subject();
+
+        let session = harness.sign_in(&subject, &email.to_uppercase(), json!("true")).await.unwrap();
+        assert_eq!(session.id, user_id);
+        assert_eq!(harness.linked_user(&subject).await, Some(user_id));
+
+        // Once linked, the identity wins even if the provider's email changes
+        let session = harness.sign_in(&subject, &unique_email(), json!(true)).await.unwrap();
+        assert_eq!(session.id, user_id);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn unverified_email_is_rejected() {
+        let harness = Harness::new(|_| {}).await;
+        let (_, existing) = test_support::create_user(&harness.db).await;
+        let (subject, email) = (unique_subject(), unique_email());
+
+        for (email, verified) in [(&existing, json!(false)), (&email, json!("false")), (&email, json!(null))] {
+            let error = harness.sign_in(&subject, email, verified).await.err().expect("login should fail").to_string();
+            assert!(error.contains("verified email"), "{}", error);
+        }
+        assert_eq!(harness.linked_user(&subject).await, None);
+        assert!(UserRepository { db: harness.db.clone() }.find_by_email_ignore_case(&email).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn unknown_users_are_refused_without_auto_provisioning() {
+        let harness = Harness::new(|config| config.auto_provision = false).await;
+        let error = harness.sign_in(&unique_subject(), &unique_email(), json!(true)).await.err().expect("login should fail").to_string();
+        assert!(error.contains("No account exists"), "{}", error);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn mismatched_state_is_rejected_and_state_is_single_use() {
+        let harness = Harness::new(|_| {}).await;
+        let login = harness.start().await;
+        let claims = harness.claims(&login, &unique_subject(), &unique_email(), json!(true));
+
+        let code = harness.approve(&login, claims.clone());
+        let error = harness.service.complete_login(&code, "forged-state").await.err().expect("login should fail").to_string();
+        assert!(error.contains("expired or unknown"), "{}", error);
+
+        assert!(harness.service.complete_login(&code, &login.state).await.is_ok());
+
+        let replay = harness.approve(&login, claims);
+        let error = harness.service.complete_login(&replay, &login.state).await.err().expect("login should fail").to_string();
+        assert!(error.contains("expired or unknown"), "{}", error);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn mismatched_nonce_is_rejected() {
+        let harness = Harness::new(|_| {}).await;
+        let login = harness.start().await;
+        let mut claims = harness.claims(&login, &unique_subject(), &unique_email(), json!(true));
+        claims["nonce"] = json!("replayed-nonce");
+
+        let code = harness.approve(&login, claims);
+        let error = harness.service.complete_login(&code, &login.state).await.err().expect("login should fail").to_string();
+        assert!(error.contains("nonce"), "{}", error);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn code_issued_for_another_pkce_challenge_is_refused() {
+        let harness = Harness::new(|_| {}).await;
+        let login = harness.start().await;
+        let claims = harness.claims(&login, &unique_subject(), &unique_email(), json!(true));
+        let other = Login { challenge: BASE64URL_NOPAD.encode(&Sha256::digest(b"someone else's verifier")), ..harness.start().await };
+
+        let code = harness.approve(&other, claims);
+        let error = harness.service.complete_login(&code, &login.state).await.err().expect("login should fail").to_string();
+        assert!(error.contains("rejected the login"), "{}", error);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn id_token_for_another_client_is_refused() {
+        let harness = Harness::new(|_| {}).await;
+        let login = harness.start().await;
+        let mut claims = harness.claims(&login, &unique_subject(), &unique_email(), json!(true));
+        claims["aud"] = json!("some-other-client");
+
+        let code = harness.approve(&login, claims);
+        assert!(harness.service.complete_login(&code, &login.state).await.is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use std::env;
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::repository::email_send_repository::EmailSendRepository;
+use crate::repository::password_reset_repository::PasswordResetRepository;
+use crate::repository::user_repository::UserRepository;
+use crate::responses::login_responses::LoginResponse;
+use crate::services::auth_service::{hash_password, hash_secret_token, new_secret_token, start_session, verify_password};
+use crate::services::mailer::{Email, Mailer};
+use crate::services::revocation_service::RevocationService;
+
+const PASSWORD_RESET: &str = "password_reset";
+
+#[derive(Debug, Clone)]
+pub struct PasswordResetConfig {
+    // Page that reads ?token= and posts it to /auth/reset-password
+    pub reset_url: String,
+    pub token_ttl: Duration,
+    pub cooldown: Duration,
+    pub max_per_hour: i64,
+}
+
+impl PasswordResetConfig {
+    pub fn from_env() -> Self {
+        let number = |key: &str, default: i64| {
+            env::var(key).ok().and_then(|v| v.parse::<i64>().ok()).filter(|n| *n > 0).unwrap_or(default)
+        };
+        let base_url = env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:8022".to_string());
+
+        Self {
+            reset_url: env::var("PASSWORD_RESET_URL")
+                .unwrap_or_else(|_| format!("{}/reset-password", base_url.trim_end_matches('/'))),
+            token_ttl: Duration::minutes(number("PASSWORD_RESET_TTL_MINUTES", 30)),
+            cooldown: Duration::seconds(number("PASSWORD_RESET_COOLDOWN_SECS", 60)),
+            max_per_hour: number("PASSWORD_RESET_MAX_PER_HOUR", 5),
+        }
+    }
+}
+
+pub struct PasswordService {
+    db: PgPool,
+    mailer: Arc<dyn Mailer>,
+    revocations: Arc<RevocationService>,
+    config: PasswordResetConfig,
+}
+
+impl PasswordService {
+    pub fn new(db: PgPool, mailer: Arc<dyn Mailer>, revocations: Arc<RevocationService>, config: PasswordResetConfig) -> Self {
+        Self { db, mailer, revocations, config }
+    }
+
+    // Emails a reset link if the address belongs to an active account. Unknown addresses and
+    // rate-limited requests are dropped quietly so callers can't probe which emails exist.
+    pub async fn request_reset(&self, email: &str) -> Result<()> {
+        let Ok(user) = (UserRepository { db: self.db.clone() }).find_by_email(email).await else {
+            return Ok(());
+        };
+        if !user.is_active {
+            return Ok(());
+        }
+
+        let sends = EmailSendRepository { db: self.db.clone() };
+        let now = Utc::now();
+        let recent = sends.recent(user.id, PASSWORD_RESET, now - Duration::hours(1)).await?;
+        let cooling_down = recent.last_sent_at.is_some_and(|last| last + self.config.cooldown > now);
+        if cooling_down || recent.count >= self.config.max_per_hour {
+            println!("📧 Skipped password reset email for user {} (rate limited)", user.id);
+            return Ok(());
+        }
+
+        let token = new_secret_token();
+        PasswordResetRepository { db: self.db.clone() }
+            .create(user.id, &hash_secret_token(&token), now + self.config.token_ttl)
+            .await?;
+
+        self.mailer.send(&Email {
+            to: user.email.clone(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Someone asked to reset the password of your account.\n\nChoose a new password here:\n\n{}?token={}\n\nThe link works once and expires in {} minutes. If it wasn't you, ignore this email; your password stays the same.\n",
+                self.config.reset_url,
+                token,
+                self.config.token_ttl.num_minutes(),
+            ),
+        }).await?;
+
+        sends.record(user.id, PASSWORD_RESET).await
+    }
+
+    // Sets the new password and signs the account out everywhere
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> Result<()> {
+        let user_id = PasswordResetRepository { db: self.db.clone() }
+            .consume(&hash_secret_token(token))
+            .await?
+            .ok_or_else(|| anyhow!("Reset link is invalid, expired or already used"))?;
+
+        self.set_password(user_id, new_password, "password reset").await
+    }
+
+    // Re-checks the current password, revokes every existing session (this one included)
+    // and starts a fresh one for the caller
+    pub async fn change_password(&self, user_id: i64, current_password: &str, new_password: &str) -> Result<LoginResponse> {
+        let user = UserRepository { db: self.db.clone() }.find_by_id(i32::try_from(user_id)?).await?;
+        if !verify_password(current_password, &user.password)? {
+            return Err(anyhow!("Current password is incorrect"));
+        }
+
+        self.set_password(user_id, new_password, "password changed").await?;
+        start_session(&self.db, &user, Uuid::new_v4()).await
+    }
+
+    async fn set_password(&self, user_id: i64, new_password: &str, reason: &str) -> Result<()> {
+        UserRepository { db: self.db.clone() }
+            .update_password(user_id, &hash_password(new_password)?)
+            .await?;
+        PasswordResetRepository { db: self.db.clone() }.invalidate_for_user(user_id).await?;
+        self.revocations.revoke_user(user_id, reason).await?;
+
+        println!("🔑 Password of user {} updated ({})", user_id, reason);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mailer::FileMailer;
+    use crate::test_support;
+    use crate::utils::jwt::decode_token;
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn changing_the_password_keeps_the_new_session_and_revokes_the_old_ones() {
+        let db = test_support::database().await;
+        let (user_id, _) = test_support::create_user(&db).await;
+        UserRepository { db: db.clone() }.update_password(user_id, &hash_password("old password").unwrap()).await.unwrap();
+
+        let revocations = Arc::new(RevocationService::new(db.clone()));
+        let passwords = PasswordService::new(db.clone(), Arc::new(FileMailer::new(None)), revocations.clone(), PasswordResetConfig::from_env());
+        let user = UserRepository { db: db.clone() }.find_by_id(i32::try_from(user_id).unwrap()).await.unwrap();
+        let old_session = decode_token(&start_session(&db, &user, Uuid::new_v4()).await.unwrap().token).unwrap();
+
+        assert!(passwords.change_password(user_id, "wrong", "new password").await.is_err());
+        let session = passwords.change_password(user_id, "old password", "new password").await.unwrap();
+
+        assert!(revocations.is_revoked(&old_session));
+        assert!(!revocations.is_revoked(&decode_token(&session.token).unwrap()));
+    }
+
+    // A reset token stored the way request_reset stores it
+    async fn issue_reset(db: &PgPool, user_id: i64, ttl: Duration) -> String {
+        let token = new_secret_token();
+        PasswordResetRepository { db: db.clone() }
+            .create(user_id, &hash_secret_token(&token), Utc::now() + ttl)
+            .await
+            .unwrap();
+        token
+    }
+
+    async fn fixture() -> (PgPool, PasswordService, i64) {
+        let db = test_support::database().await;
+        let (user_id, _) = test_support::create_user(&db).await;
+        let revocations = Arc::new(RevocationService::new(db.clone()));
+        let passwords = PasswordService::new(db.clone(), Arc::new(FileMailer::new(None)), revocations, PasswordResetConfig::from_env());
+        (db, passwords, user_id)
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn a_reset_link_works_once() {
+        let (db, passwords, user_id) = fixture().await;
+        let token = issue_reset(&db, user_id, Duration::minutes(30)).await;
+
+        passwords.reset_password(&token, "first new password").await.unwrap();
+        assert!(passwords.reset_password(&token, "second new password").await.is_err());
+
+        let user = UserRepository { db }.find_by_id(i32::try_from(user_id).unwrap()).await.unwrap();
+        assert!(verify_password("first new password", &user.password).unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn an_expired_reset_link_is_rejected() {
+        let (db, passwords, user_id) = fixture().await;
+        let token = issue_reset(&db, user_id, Duration::minutes(-1)).await;
+        assert!(passwords.reset_password(&token, "new password").await.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn requesting_a_new_reset_link_invalidates_the_previous_one() {
+        let (db, passwords, user_id) = fixture().await;
+        let first = issue_reset(&db, user_id, Duration::minutes(30)).await;
+        let second = issue_reset(&db, user_id, Duration::minutes(30)).await;
+
+        assert!(passwords.reset_password(&first, "new password").await.is_err());
+        passwords.reset_password(&second, "new password").await.unwrap();
+    }
+}
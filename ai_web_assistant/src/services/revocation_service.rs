@@ -0,0 +1,295 @@
+use std::env;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, SubsecRound, Utc};
+use dashmap::DashMap;
+use sqlx::PgPool;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use crate::repository::api_key_repository::ApiKeyRepository;
+use crate::repository::refresh_token_repository::RefreshTokenRepository;
+use crate::repository::revocation_repository::{RevocationRecord, RevocationRepository};
+use crate::utils::jwt::{access_token_ttl, Claims};
+
+const DEFAULT_SYNC_INTERVAL_SECS: u64 = 5;
+
+// In-memory view of token_revocations, checked on every authenticated request and WebSocket
+// message. Revocations made here apply at once; ones made by other instances arrive with
+// the next sync from Postgres.
+pub struct RevocationService {
+    db: PgPool,
+    // jti -> when the revocation can be forgotten
+    tokens: DashMap<String, DateTime<Utc>>,
+    sessions: DashMap<String, DateTime<Utc>>,
+    // user id -> (tokens issued before this are revoked, when it can be forgotten)
+    users: DashMap<i64, (DateTime<Utc>, DateTime<Utc>)>,
+    last_synced_id: AtomicI64,
+    // Fired whenever something is revoked, so open WebSocket connections can check themselves
+    changes: broadcast::Sender<()>,
+}
+
+impl RevocationService {
+    pub fn new(db: PgPool) -> Self {
+        Self {
+            db,
+            tokens: DashMap::new(),
+            sessions: DashMap::new(),
+            users: DashMap::new(),
+            last_synced_id: AtomicI64::new(0),
+            changes: broadcast::channel(64).0,
+        }
+    }
+
+    pub fn is_revoked(&self, claims: &Claims) -> bool {
+        if !claims.jti.is_empty() && self.tokens.contains_key(&claims.jti) {
+            return true;
+        }
+        if !claims.sid.is_empty() && self.sessions.contains_key(&claims.sid) {
+            return true;
+        }
+        self.users.get(&claims.sub)
+            .is_some_and(|entry| issued_at_micros(claims) <= entry.0.timestamp_micros())
+    }
+
+    // Logout of this one token
+    pub async fn revoke_token(&self, claims: &Claims, reason: &str) -> Result<()> {
+        if claims.jti.is_empty() {
+            return Err(anyhow!("Token has no id and can only be revoked with its session or user"));
+        }
+        let expires_at = DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or_else(Utc::now);
+        self.record("token", &claims.jti, reason, expires_at).await
+    }
+
+    // Closes open WebSocket connections made with the key. HTTP requests already fail because
+    // keys are looked up on every use, so the entry only has to last until every instance synced.
+    pub async fn revoke_api_key(&self, key_id: &str, reason: &str) -> Result<()> {
+        let jti = format!("api_key:{}", key_id);
+        self.record("token", &jti, reason, Utc::now() + chrono::Duration::hours(1)).await
+    }
+
+    // Logout of one device: the refresh token family dies and every access token it issued
+    // is revoked. Returns false when the user has no such session.
+    pub async fn revoke_session(&self, user_id: i64, session_id: &str, reason: &str) -> Result<bool> {
+        let family_id = Uuid::parse_str(session_id).map_err(|_| anyhow!("Invalid session id"))?;
+        let refreshable = RefreshTokenRepository { db: self.db.clone() }
+            .revoke_user_family(user_id, family_id)
+            .await?;
+
+        // Access tokens can outlive their family's last refresh token by up to one TTL
+        self.record("session", session_id, reason, Utc::now() + access_token_ttl()).await?;
+        Ok(refreshable > 0)
+    }
+
+    // Everything issued to the user so far, API keys included, e.g. after a password change,
+    // ban or erasure.
+    // Returns how many refresh tokens were still usable.
+    pub async fn revoke_user(&self, user_id: i64, reason: &str) -> Result<u64> {
+        let refreshable = RefreshTokenRepository { db: self.db.clone() }
+            .revoke_all_for_user(user_id)
+            .await?;
+        // API keys too: one may have been created by whoever the user is locking out
+        ApiKeyRepository { db: self.db.clone() }.revoke_all_for_user(user_id).await?;
+
+        self.record("user", &user_id.to_string(), reason, Utc::now() + access_token_ttl()).await?;
+        println!("🔒 Revoked all tokens of user {} ({})", user_id, reason);
+        Ok(refreshable)
+    }
+
+    // Resolves once the token currently held by a connection is revoked
+    pub async fn wait_until_revoked(&self, claims: &std::sync::Mutex<Claims>) {
+        let mut changes = self.changes.subscribe();
+        loop {
+            if self.is_revoked(&claims.lock().unwrap()) {
+                return;
+            }
+            match changes.recv().await {
+                Ok(()) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => std::future::pending::<()>().await,
+            }
+        }
+    }
+
+    // Picks up revocations recorded since the last sync, including other instances'
+    pub async fn sync(&self) -> Result<usize> {
+        let records = RevocationRepository { db: self.db.clone() }
+            .since(self.last_synced_id.load(Ordering::SeqCst))
+            .await?;
+
+        for record in &records {
+            self.apply(record);
+        }
+        if !records.is_empty() {
+            let _ = self.changes.send(());
+        }
+        Ok(records.len())
+    }
+
+    async fn record(&self, scope: &str, subject: &str, reason: &str, expires_at: DateTime<Utc>) -> Result<()> {
+        // Our clock, not the database's: it is the one that stamps the tokens we issue next
+        let revoked_at = Utc::now().trunc_subsecs(6);
+        let record = RevocationRepository { db: self.db.clone() }
+            .insert(scope, subject, reason, revoked_at, expires_at)
+            .await?;
+
+        self.apply(&record);
+        let _ = self.changes.send(());
+        Ok(())
+    }
+
+    fn apply(&self, record: &RevocationRecord) {
+        match record.scope.as_str() {
+            "token" => {
+                self.tokens.insert(record.subject.clone(), record.expires_at);
+            }
+            "session" => {
+                self.sessions.insert(record.subject.clone(), record.expires_at);
+            }
+            "user" => {
+                if let Ok(user_id) = record.subject.parse::<i64>() {
+                    let mut entry = self.users.entry(user_id).or_insert((record.revoked_at, record.expires_at));
+                    if record.revoked_at > entry.0 {
+                        *entry = (record.revoked_at, record.expires_at);
+                    }
+                }
+            }
+            other => eprintln!("❌ Unknown token revocation scope {:?}", other),
+        }
+        self.last_synced_id.fetch_max(record.id, Ordering::SeqCst);
+    }
+
+    async fn forget_expired(&self) -> Result<u64> {
+        let now = Utc::now();
+        self.tokens.retain(|_, expires_at| *expires_at > now);
+        self.sessions.retain(|_, expires_at| *expires_at > now);
+        self.users.retain(|_, (_, expires_at)| *expires_at > now);
+
+        RevocationRepository { db: self.db.clone() }.delete_expired().await
+    }
+}
+
+// Tokens from before iat_micros existed only know their second; they count as issued at its start,
+// so a revocation still covers them
+fn issued_at_micros(claims: &Claims) -> i64 {
+    if claims.iat_micros > 0 {
+        claims.iat_micros
+    } else {
+        claims.iat as i64 * 1_000_000
+    }
+}
+
+// REVOCATION_SYNC_INTERVAL_SECS controls how quickly other instances' revocations take effect here
+pub fn spawn_sync(service: Arc<RevocationService>) {
+    let secs = env::var("REVOCATION_SYNC_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_SYNC_INTERVAL_SECS);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(secs));
+        let mut ticks: u64 = 0;
+        loop {
+            interval.tick().await;
+            if let Err(e) = service.sync().await {
+                eprintln!("❌ Failed to sync token revocations: {}", e);
+            }
+
+            // Expired revocations are cleared about once an hour
+            ticks += 1;
+            if ticks.is_multiple_of((3600 / secs).max(1)) {
+                match service.forget_expired().await {
+                    Ok(0) => {}
+                    Ok(count) => println!("🔒 Dropped {} expired token revocation(s)", count),
+                    Err(e) => eprintln!("❌ Failed to drop expired token revocations: {}", e),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(sub: i64, iat: i64) -> Claims {
+        serde_json::from_value(serde_json::json!({
+            "sub": sub, "email": "a@example.com", "roles": [], "is_admin": false,
+            "is_user": true, "exp": iat + 900, "iat": iat, "jti": "t1", "sid": "s1"
+        }))
+        .unwrap()
+    }
+
+    fn service() -> RevocationService {
+        RevocationService::new(PgPool::connect_lazy("postgres://localhost/unused").unwrap())
+    }
+
+    fn revoke_user_now(service: &RevocationService, user_id: i64) -> DateTime<Utc> {
+        let revoked_at = Utc::now().trunc_subsecs(6);
+        service.apply(&RevocationRecord {
+            id: 1,
+            scope: "user".into(),
+            subject: user_id.to_string(),
+            revoked_at,
+            expires_at: revoked_at + chrono::Duration::hours(1),
+        });
+        revoked_at
+    }
+
+    fn issue(user_id: i64) -> Claims {
+        let token = crate::utils::jwt::generate_token(user_id, "a@example.com".into(), &Default::default(), "s1").unwrap();
+        crate::utils::jwt::decode_token(&token).unwrap()
+    }
+
+    #[tokio::test]
+    async fn user_revocation_compares_microseconds() {
+        let service = service();
+        let revoked_at = revoke_user_now(&service, 7);
+        let micros = revoked_at.timestamp_micros();
+
+        let at = |iat_micros: i64| Claims { iat_micros, iat: revoked_at.timestamp() as usize, ..claims(7, 0) };
+        assert!(service.is_revoked(&at(micros - 1)));
+        assert!(service.is_revoked(&at(micros)));
+        assert!(!service.is_revoked(&at(micros + 1)));
+    }
+
+    #[tokio::test]
+    async fn token_issued_after_a_user_revocation_is_accepted() {
+        let service = service();
+        let before = issue(7);
+        revoke_user_now(&service, 7);
+        let after = issue(7);
+
+        assert!(service.is_revoked(&before));
+        assert!(!service.is_revoked(&after));
+    }
+
+    #[tokio::test]
+    async fn tokens_without_micros_count_as_issued_at_the_start_of_their_second() {
+        let service = service();
+        let revoked_at = Utc::now();
+        service.users.insert(7, (revoked_at, revoked_at + chrono::Duration::hours(1)));
+
+        let second = revoked_at.timestamp();
+        assert!(service.is_revoked(&claims(7, second - 1)));
+        assert!(service.is_revoked(&claims(7, second)));
+        assert!(!service.is_revoked(&claims(7, second + 1)));
+        assert!(!service.is_revoked(&claims(8, second)));
+    }
+
+    #[tokio::test]
+    async fn token_and_session_revocations_match_by_id() {
+        let service = service();
+        let token = claims(7, Utc::now().timestamp());
+        assert!(!service.is_revoked(&token));
+
+        service.sessions.insert("s1".into(), Utc::now());
+        assert!(service.is_revoked(&token));
+        service.sessions.clear();
+
+        service.tokens.insert("t1".into(), Utc::now());
+        assert!(service.is_revoked(&token));
+    }
+}
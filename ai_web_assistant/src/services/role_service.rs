@@ -0,0 +1,59 @@
+use anyhow::{anyhow, Result};
+use sqlx::PgPool;
+
+use crate::repository::role_repository::{RoleRecord, RoleRepository, UserAccess};
+use crate::repository::user_repository::UserRepository;
+use crate::services::revocation_service::RevocationService;
+use crate::utils::jwt::Claims;
+
+pub async fn list_roles(db: &PgPool) -> Result<Vec<RoleRecord>> {
+    RoleRepository { db: db.clone() }.list_roles().await
+}
+
+pub async fn user_access(db: &PgPool, user_id: i64) -> Result<UserAccess> {
+    if !(UserRepository { db: db.clone() }).exists(user_id).await? {
+        return Err(anyhow!("User not found"));
+    }
+    RoleRepository { db: db.clone() }.access_for_user(user_id).await
+}
+
+// Takes effect for the user's API keys at once and for their sessions at the next token refresh
+pub async fn set_roles(db: &PgPool, actor: &Claims, user_id: i64, roles: &[String]) -> Result<UserAccess> {
+    // Nobody can strip their own roles.manage and leave the system without a role admin
+    if actor.sub == user_id {
+        return Err(anyhow!("You can't change your own roles"));
+    }
+    let mut roles: Vec<String> = roles.iter().map(|role| role.trim().to_lowercase()).collect();
+    roles.sort();
+    roles.dedup();
+    if roles.is_empty() {
+        return Err(anyhow!("At least one role is required"));
+    }
+    if !(UserRepository { db: db.clone() }).exists(user_id).await? {
+        return Err(anyhow!("User not found"));
+    }
+
+    let repo = RoleRepository { db: db.clone() };
+    repo.set_user_roles(user_id, &roles).await?;
+    println!("🛂 User {} set the roles of user {} to {:?}", actor.sub, user_id, roles);
+    repo.access_for_user(user_id).await
+}
+
+// Banning deactivates the account and signs it out everywhere; unbanning only reactivates it
+pub async fn set_banned(db: &PgPool, revocations: &RevocationService, actor: &Claims, user_id: i64, banned: bool) -> Result<()> {
+    if actor.sub == user_id {
+        return Err(anyhow!("You can't ban or unban yourself"));
+    }
+    // Moderators can't lock out the people who manage their roles
+    let target = RoleRepository { db: db.clone() }.access_for_user(user_id).await?;
+    if target.permissions.iter().any(|p| p == "roles.manage") && !actor.has_permission("roles.manage") {
+        return Err(anyhow!("Only role managers can ban this account"));
+    }
+
+    UserRepository { db: db.clone() }.set_active(user_id, !banned).await?;
+    if banned {
+        revocations.revoke_user(user_id, "banned").await?;
+    }
+    println!("🛂 User {} {} user {}", actor.sub, if banned { "banned" } else { "unbanned" }, user_id);
+    Ok(())
+}
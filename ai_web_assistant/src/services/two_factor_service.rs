@@ -0,0 +1,320 @@
+use std::env;
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Utc};
+use rand::{rngs::OsRng, RngCore};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::repository::two_factor_repository::TwoFactorRepository;
+use crate::repository::user_repository::UserRepository;
+use crate::responses::login_responses::LoginResponse;
+use crate::services::auth_service::{hash_secret_token, start_session, verify_password};
+use crate::utils::jwt::{decode_action_token, generate_tracked_action_token};
+use crate::utils::totp;
+
+// Purpose of the short-lived token handed out between the password and the code step
+pub const TWO_FACTOR_LOGIN: &str = "two_factor_login";
+const RECOVERY_CODE_COUNT: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct TwoFactorLimits {
+    // Codes one pending token may be tried with before the password is needed again
+    pub max_attempts_per_login: i32,
+    // Wrong codes a user may enter across all pending logins within `failure_window`
+    pub max_failures_per_user: i64,
+    pub failure_window: Duration,
+}
+
+impl TwoFactorLimits {
+    pub fn from_env() -> Self {
+        let number = |key: &str, default: i64| {
+            env::var(key).ok().and_then(|v| v.parse::<i64>().ok()).filter(|n| *n > 0).unwrap_or(default)
+        };
+
+        Self {
+            max_attempts_per_login: i32::try_from(number("TWO_FACTOR_MAX_ATTEMPTS", 3)).unwrap_or(3),
+            max_failures_per_user: number("TWO_FACTOR_MAX_FAILURES_PER_USER", 10),
+            failure_window: Duration::minutes(number("TWO_FACTOR_FAILURE_WINDOW_MINUTES", 15)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub recovery_codes_remaining: i64,
+}
+
+pub struct TwoFactorService {
+    db: PgPool,
+    issuer: String,
+    limits: TwoFactorLimits,
+}
+
+impl TwoFactorService {
+    pub fn new(db: PgPool) -> Self {
+        Self {
+            db,
+            issuer: env::var("TOTP_ISSUER").unwrap_or_else(|_| "AI Web Assistant".to_string()),
+            limits: TwoFactorLimits::from_env(),
+        }
+    }
+
+    fn repository(&self) -> TwoFactorRepository {
+        TwoFactorRepository { db: self.db.clone() }
+    }
+
+    pub async fn status(&self, user_id: i64) -> Result<TwoFactorStatus> {
+        let repo = self.repository();
+        let enabled = repo.is_enabled(user_id).await?;
+        Ok(TwoFactorStatus {
+            enabled,
+            recovery_codes_remaining: if enabled { repo.remaining_recovery_codes(user_id).await? } else { 0 },
+        })
+    }
+
+    // New secret for the authenticator app; 2FA stays off until `confirm` sees a code from it
+    pub async fn setup(&self, user_id: i64, email: &str) -> Result<TwoFactorSetup> {
+        let secret = totp::generate_secret();
+        if !self.repository().start_enrolment(user_id, &secret).await? {
+            return Err(anyhow!("Two-factor authentication is already enabled"));
+        }
+
+        Ok(TwoFactorSetup {
+            otpauth_uri: totp::otpauth_uri(&self.issuer, email, &secret),
+            secret,
+        })
+    }
+
+    // Enables 2FA and returns the recovery codes; this is the only time they are shown
+    pub async fn confirm(&self, user_id: i64, code: &str) -> Result<Vec<String>> {
+        let record = self.repository().find(user_id).await?
+            .ok_or_else(|| anyhow!("Start two-factor setup first"))?;
+        if record.enabled_at.is_some() {
+            return Err(anyhow!("Two-factor authentication is already enabled"));
+        }
+        if !self.accept_totp(user_id, &record.secret, code).await? {
+            return Err(anyhow!("Invalid code"));
+        }
+
+        let codes = new_recovery_codes();
+        self.repository().enable(user_id, &hash_codes(&codes)).await?;
+        println!("🔐 Enabled two-factor authentication for user {}", user_id);
+        Ok(codes)
+    }
+
+    // Accepts an authenticator code or an unused recovery code
+    pub async fn verify(&self, user_id: i64, code: &str) -> Result<bool> {
+        let Some(record) = self.repository().find(user_id).await?.filter(|r| r.enabled_at.is_some()) else {
+            return Ok(false);
+        };
+        if self.accept_totp(user_id, &record.secret, code).await? {
+            return Ok(true);
+        }
+
+        let used = self.repository().use_recovery_code(user_id, &hash_secret_token(&normalize_recovery_code(code))).await?;
+        if used {
+            println!("🔐 User {} signed in with a recovery code", user_id);
+        }
+        Ok(used)
+    }
+
+    // First login step passed: hands out the pending token for /auth/login/2fa
+    pub async fn start_login(&self, user_id: i64, email: &str) -> Result<String> {
+        let jti = Uuid::new_v4();
+        let ttl = pending_token_ttl();
+        self.repository().create_pending_login(jti, user_id, Utc::now() + ttl).await?;
+        generate_tracked_action_token(user_id, email, TWO_FACTOR_LOGIN, ttl, &jti.to_string())
+    }
+
+    // Second login step: the pending token from /auth/login plus a code. Each token is good for
+    // one successful login and a few tries; too many wrong codes for the user end all of them.
+    pub async fn complete_login(&self, pending_token: &str, code: &str) -> Result<LoginResponse> {
+        let expired = || anyhow!("Login attempt expired; sign in again");
+        let claims = decode_action_token(pending_token, TWO_FACTOR_LOGIN).map_err(|_| expired())?;
+        let jti = Uuid::parse_str(&claims.jti).map_err(|_| expired())?;
+        let user = UserRepository { db: self.db.clone() }.find_by_id(i32::try_from(claims.sub)?).await
+            .map_err(|_| anyhow!("Invalid credentials"))?;
+        if !user.is_active || user.email != claims.email {
+            return Err(anyhow!("Invalid credentials"));
+        }
+
+        let repo = self.repository();
+        let since = Utc::now() - self.limits.failure_window;
+        if repo.recent_failed_attempts(user.id, since).await? >= self.limits.max_failures_per_user {
+            repo.consume_pending_logins(user.id).await?;
+            return Err(anyhow!("Too many invalid codes; sign in again later"));
+        }
+        let attempts = repo.take_attempt(jti, user.id, self.limits.max_attempts_per_login).await?
+            .ok_or_else(expired)?;
+
+        if !self.verify(user.id, code).await? {
+            if attempts >= self.limits.max_attempts_per_login {
+                return Err(anyhow!("Invalid code; sign in again"));
+            }
+            return Err(anyhow!("Invalid code"));
+        }
+        repo.complete_pending_login(jti).await?;
+        start_session(&self.db, &user, Uuid::new_v4()).await
+    }
+
+    pub async fn regenerate_recovery_codes(&self, user_id: i64, code: &str) -> Result<Vec<String>> {
+        if !self.verify(user_id, code).await? {
+            return Err(anyhow!("Invalid code"));
+        }
+
+        let codes = new_recovery_codes();
+        self.repository().replace_recovery_codes(user_id, &hash_codes(&codes)).await?;
+        Ok(codes)
+    }
+
+    // Needs the password and a current code, so a stolen session alone can't turn 2FA off
+    pub async fn disable(&self, user_id: i64, password: &str, code: &str) -> Result<()> {
+        let user = UserRepository { db: self.db.clone() }.find_by_id(i32::try_from(user_id)?).await?;
+        if !verify_password(password, &user.password)? {
+            return Err(anyhow!("Password is incorrect"));
+        }
+        if !self.verify(user_id, code).await? {
+            return Err(anyhow!("Invalid code"));
+        }
+
+        self.repository().disable(user_id).await?;
+        println!("🔓 Disabled two-factor authentication for user {}", user_id);
+        Ok(())
+    }
+
+    async fn accept_totp(&self, user_id: i64, secret: &str, code: &str) -> Result<bool> {
+        match totp::verify(secret, code, Utc::now().timestamp() as u64)? {
+            Some(step) => self.repository().claim_step(user_id, step as i64).await,
+            None => Ok(false),
+        }
+    }
+}
+
+pub fn spawn_pruner(two_factor: std::sync::Arc<TwoFactorService>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            // Kept a day past expiry so the per-user failure count still sees them
+            match two_factor.repository().prune_pending_logins(Utc::now() - Duration::days(1)).await {
+                Ok(0) => {}
+                Ok(count) => println!("🔐 Pruned {} expired pending login(s)", count),
+                Err(e) => eprintln!("❌ Failed to prune pending logins: {}", e),
+            }
+        }
+    });
+}
+
+pub fn pending_token_ttl() -> Duration {
+    let secs = env::var("TWO_FACTOR_PENDING_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(300);
+    Duration::seconds(secs)
+}
+
+// Ten codes like "3f9a1-c07be"
+fn new_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            OsRng.fill_bytes(&mut bytes);
+            let code = hex::encode(bytes);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn hash_codes(codes: &[String]) -> Vec<String> {
+    codes.iter().map(|code| hash_secret_token(&normalize_recovery_code(code))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    async fn service(max_attempts_per_login: i32, max_failures_per_user: i64) -> (TwoFactorService, i64, String, Vec<String>) {
+        let db = test_support::database().await;
+        let (user_id, email) = test_support::create_user(&db).await;
+        let service = TwoFactorService {
+            db,
+            issuer: "Test".to_string(),
+            limits: TwoFactorLimits { max_attempts_per_login, max_failures_per_user, failure_window: Duration::minutes(15) },
+        };
+        let codes = new_recovery_codes();
+        service.repository().start_enrolment(user_id, &totp::generate_secret()).await.unwrap();
+        service.repository().enable(user_id, &hash_codes(&codes)).await.unwrap();
+        (service, user_id, email, codes)
+    }
+
+    fn error(result: Result<LoginResponse>) -> String {
+        result.err().expect("login should fail").to_string()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn pending_token_logs_in_once() {
+        let (service, user_id, email, codes) = service(3, 10).await;
+        let token = service.start_login(user_id, &email).await.unwrap();
+
+        assert!(service.complete_login(&token, &codes[0]).await.is_ok());
+        assert!(error(service.complete_login(&token, &codes[1]).await).contains("expired"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn pending_token_is_used_up_by_wrong_codes() {
+        let (service, user_id, email, codes) = service(2, 10).await;
+        let token = service.start_login(user_id, &email).await.unwrap();
+
+        assert_eq!(error(service.complete_login(&token, "000000").await), "Invalid code");
+        assert!(error(service.complete_login(&token, "000000").await).contains("sign in again"));
+        // Even a correct code can't revive it
+        assert!(error(service.complete_login(&token, &codes[0]).await).contains("expired"));
+
+        let fresh = service.start_login(user_id, &email).await.unwrap();
+        assert!(service.complete_login(&fresh, &codes[0]).await.is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn wrong_codes_are_capped_per_user_across_tokens() {
+        let (service, user_id, email, codes) = service(2, 3).await;
+        for _ in 0..3 {
+            let token = service.start_login(user_id, &email).await.unwrap();
+            let _ = service.complete_login(&token, "000000").await;
+        }
+
+        let token = service.start_login(user_id, &email).await.unwrap();
+        assert!(error(service.complete_login(&token, &codes[0]).await).contains("Too many"));
+
+        // The cap also ended the open pending login
+        let jti = Uuid::parse_str(&decode_action_token(&token, TWO_FACTOR_LOGIN).unwrap().jti).unwrap();
+        assert_eq!(service.repository().take_attempt(jti, user_id, 2).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn untracked_action_tokens_are_refused() {
+        let (service, user_id, email, codes) = service(3, 10).await;
+        let token = crate::utils::jwt::generate_action_token(user_id, &email, TWO_FACTOR_LOGIN, Duration::minutes(5)).unwrap();
+        assert!(error(service.complete_login(&token, &codes[0]).await).contains("expired"));
+    }
+}
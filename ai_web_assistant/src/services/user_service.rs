@@ -23,7 +23,7 @@ impl UserService {
     pub async fn get_user_by_id(&self, user_id: i32) -> Result<Option<User>> {
         match self.repository.find_by_id(user_id).await {
             Ok(user) => Ok(Some(user)),
-            Err(e) if e.downcast_ref::<sqlx::Error>().map_or(false, |err| matches!(err, sqlx::Error::RowNotFound)) => Ok(None),
+            Err(e) if e.downcast_ref::<sqlx::Error>().is_some_and(|err| matches!(err, sqlx::Error::RowNotFound)) => Ok(None),
             Err(e) => Err(e),
         }
     }
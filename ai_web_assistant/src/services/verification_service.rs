@@ -0,0 +1,189 @@
+use std::env;
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::repository::email_send_repository::EmailSendRepository;
+use crate::repository::user_repository::UserRepository;
+use crate::services::mailer::{Email, Mailer};
+use crate::utils::jwt::{decode_action_token, generate_action_token};
+
+const VERIFY_EMAIL: &str = "verify_email";
+
+#[derive(Debug, Clone)]
+pub struct VerificationConfig {
+    // Public URL of this API; links point at {base}/auth/verify-email?token=...
+    pub base_url: String,
+    pub link_ttl: Duration,
+    pub resend_cooldown: Duration,
+    pub max_sends_per_hour: i64,
+    // When set, unverified accounts can't send prompts to the assistant
+    pub require_for_ai: bool,
+}
+
+impl VerificationConfig {
+    pub fn from_env() -> Self {
+        let number = |key: &str, default: i64| {
+            env::var(key).ok().and_then(|v| v.parse::<i64>().ok()).filter(|n| *n > 0).unwrap_or(default)
+        };
+
+        Self {
+            base_url: env::var("APP_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:8022".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            link_ttl: Duration::hours(number("EMAIL_VERIFICATION_TTL_HOURS", 24)),
+            resend_cooldown: Duration::seconds(number("EMAIL_VERIFICATION_RESEND_COOLDOWN_SECS", 60)),
+            max_sends_per_hour: number("EMAIL_VERIFICATION_MAX_PER_HOUR", 5),
+            require_for_ai: env::var("REQUIRE_VERIFIED_EMAIL_FOR_AI")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ResendOutcome {
+    Sent,
+    AlreadyVerified,
+    RateLimited { retry_after_secs: i64 },
+}
+
+pub struct VerificationService {
+    db: PgPool,
+    mailer: Arc<dyn Mailer>,
+    config: VerificationConfig,
+}
+
+impl VerificationService {
+    pub fn new(db: PgPool, mailer: Arc<dyn Mailer>, config: VerificationConfig) -> Self {
+        Self { db, mailer, config }
+    }
+
+    // Signed link bound to the current address, so changing the email invalidates it
+    pub async fn send_verification(&self, user_id: i64, email: &str) -> Result<()> {
+        let token = generate_action_token(user_id, email, VERIFY_EMAIL, self.config.link_ttl)?;
+        let link = format!("{}/auth/verify-email?token={}", self.config.base_url, token);
+
+        self.mailer.send(&Email {
+            to: email.to_string(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Welcome!\n\nConfirm your email address by opening this link:\n\n{}\n\nThe link expires in {} hours. If you didn't create an account, ignore this email.\n",
+                link,
+                self.config.link_ttl.num_hours(),
+            ),
+        }).await?;
+
+        EmailSendRepository { db: self.db.clone() }.record(user_id, VERIFY_EMAIL).await
+    }
+
+    pub async fn resend(&self, user_id: i64) -> Result<ResendOutcome> {
+        let user = UserRepository { db: self.db.clone() }.find_by_id(i32::try_from(user_id)?).await?;
+        if user.is_verified {
+            return Ok(ResendOutcome::AlreadyVerified);
+        }
+
+        let now = Utc::now();
+        let recent = EmailSendRepository { db: self.db.clone() }
+            .recent(user_id, VERIFY_EMAIL, now - Duration::hours(1))
+            .await?;
+
+        // Wait for whichever frees up last: the cooldown or a slot in the hourly budget
+        let mut retry_at = recent.last_sent_at.map(|last| last + self.config.resend_cooldown);
+        if recent.count >= self.config.max_sends_per_hour {
+            retry_at = retry_at.max(recent.first_sent_at.map(|first| first + Duration::hours(1)));
+        }
+        if let Some(retry_at) = retry_at.filter(|retry_at| *retry_at > now) {
+            return Ok(ResendOutcome::RateLimited {
+                retry_after_secs: (retry_at - now).num_seconds().max(1),
+            });
+        }
+
+        self.send_verification(user_id, &user.email).await?;
+        Ok(ResendOutcome::Sent)
+    }
+
+    // Returns the verified user's id. Following a link twice is harmless.
+    pub async fn verify(&self, token: &str) -> Result<i64> {
+        let claims = decode_action_token(token, VERIFY_EMAIL)
+            .map_err(|_| anyhow!("Verification link is invalid or has expired"))?;
+
+        let verified = UserRepository { db: self.db.clone() }
+            .mark_email_verified(claims.sub, &claims.email)
+            .await?;
+        if !verified {
+            return Err(anyhow!("Verification link is no longer valid for this account"));
+        }
+
+        println!("✅ Verified email of user {}", claims.sub);
+        Ok(claims.sub)
+    }
+
+    pub async fn may_use_ai(&self, user_id: i64) -> Result<bool> {
+        if !self.config.require_for_ai {
+            return Ok(true);
+        }
+        UserRepository { db: self.db.clone() }.is_verified(user_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mailer::FileMailer;
+    use crate::test_support;
+
+    fn service(db: PgPool) -> VerificationService {
+        VerificationService::new(db, Arc::new(FileMailer::new(None)), VerificationConfig::from_env())
+    }
+
+    fn lazy_pool() -> PgPool {
+        PgPool::connect_lazy("postgres://localhost:1/unused").unwrap()
+    }
+
+    #[tokio::test]
+    async fn expired_links_are_rejected() {
+        let token = generate_action_token(1, "a@example.com", VERIFY_EMAIL, Duration::hours(-1)).unwrap();
+        assert!(service(lazy_pool()).verify(&token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn tokens_issued_for_another_purpose_are_rejected() {
+        let token = generate_action_token(1, "a@example.com", "password_reset", Duration::hours(1)).unwrap();
+        assert!(service(lazy_pool()).verify(&token).await.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn following_a_link_twice_is_harmless() {
+        let db = test_support::database().await;
+        let (user_id, email) = test_support::create_user(&db).await;
+        let service = service(db.clone());
+        let token = generate_action_token(user_id, &email, VERIFY_EMAIL, Duration::hours(1)).unwrap();
+
+        assert_eq!(service.verify(&token).await.unwrap(), user_id);
+        assert_eq!(service.verify(&token).await.unwrap(), user_id);
+        assert!(UserRepository { db }.is_verified(user_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn changing_the_email_invalidates_links_sent_to_the_old_one() {
+        let db = test_support::database().await;
+        let (user_id, email) = test_support::create_user(&db).await;
+        let service = service(db.clone());
+        let old_link = generate_action_token(user_id, &email, VERIFY_EMAIL, Duration::hours(1)).unwrap();
+
+        let new_email = format!("new-{}", email);
+        sqlx::query("UPDATE users SET email = $2 WHERE id = $1").bind(user_id).bind(&new_email).execute(&db).await.unwrap();
+        assert!(service.verify(&old_link).await.is_err());
+        assert!(!UserRepository { db: db.clone() }.is_verified(user_id).await.unwrap());
+
+        let new_link = generate_action_token(user_id, &new_email, VERIFY_EMAIL, Duration::hours(1)).unwrap();
+        assert_eq!(service.verify(&new_link).await.unwrap(), user_id);
+    }
+}
@@ -1,19 +1,34 @@
 use utoipa::OpenApi;
-use crate::payloads::{login_request::LoginRequest, register_request::RegisterRequest};
-use crate::responses::login_responses::LoginResponse;
+use crate::payloads::{login_request::LoginRequest, logout_request::LogoutRequest, password_request::{ChangePasswordRequest, ForgotPasswordRequest, ResetPasswordRequest}, refresh_request::RefreshRequest, register_request::RegisterRequest, two_factor_request::TwoFactorLoginRequest};
+use crate::responses::login_responses::{LoginResponse, TwoFactorChallenge};
 use crate::controllers::auth_controller::ErrorResponse;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         crate::controllers::auth_controller::register_user,
-        crate::controllers::auth_controller::login_user
+        crate::controllers::auth_controller::login_user,
+        crate::controllers::auth_controller::login_two_factor,
+        crate::controllers::auth_controller::refresh_token,
+        crate::controllers::auth_controller::logout_user,
+        crate::controllers::auth_controller::verify_email,
+        crate::controllers::auth_controller::resend_verification,
+        crate::controllers::auth_controller::forgot_password,
+        crate::controllers::auth_controller::reset_password,
+        crate::controllers::auth_controller::change_password
     ),
     components(
         schemas(
             LoginRequest,
+            RefreshRequest,
+            LogoutRequest,
+            ForgotPasswordRequest,
+            ResetPasswordRequest,
+            ChangePasswordRequest,
             RegisterRequest,
             LoginResponse,
+            TwoFactorChallenge,
+            TwoFactorLoginRequest,
             ErrorResponse
         )
     ),
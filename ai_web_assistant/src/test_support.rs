@@ -0,0 +1,25 @@
+// Fixtures for tests that need Postgres. They are #[ignore]d; run them against a scratch
+// database with: TEST_DATABASE_URL=postgres://... cargo test -- --ignored
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn database() -> PgPool {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must point at a scratch database");
+    let pool = PgPool::connect(&url).await.expect("Failed to connect to TEST_DATABASE_URL");
+    sqlx::migrate!().run(&pool).await.expect("Failed to run migrations");
+    pool
+}
+
+// A fresh active account with a unique email; returns (id, email)
+pub async fn create_user(db: &PgPool) -> (i64, String) {
+    let tag = Uuid::new_v4().simple().to_string();
+    let email = format!("test-{}@example.com", &tag[..12]);
+    let id = sqlx::query_scalar("INSERT INTO users (uuid, email, username, password) VALUES ($1, $2, $3, 'x') RETURNING id")
+        .bind(&tag)
+        .bind(&email)
+        .bind(format!("test_{}", &tag[..12]))
+        .fetch_one(db)
+        .await
+        .expect("Failed to create test user");
+    (id, email)
+}
@@ -0,0 +1,20 @@
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use axum::http::HeaderMap;
+
+// Address of the caller. Behind nginx (TRUST_PROXY_HEADERS=true) that's X-Real-IP, or the
+// entry nginx appended to X-Forwarded-For; the client can forge anything before it.
+pub fn client_ip(headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+    let trust_proxy = env::var("TRUST_PROXY_HEADERS")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false);
+    if !trust_proxy {
+        return peer.ip();
+    }
+
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    header("x-real-ip")
+        .and_then(|v| v.trim().parse().ok())
+        .or_else(|| header("x-forwarded-for")?.rsplit(',').next()?.trim().parse().ok())
+        .unwrap_or_else(|| peer.ip())
+}
@@ -74,9 +74,44 @@ pub struct MessageData {
     pub users: HashMap<String, UserConversations>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct UserConversations {
     pub conversations: HashMap<String, ChatSession>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionPolicy>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trash: Vec<TrashItem>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shares: Vec<ShareLink>,
+}
+
+// A published snapshot of one of the user's conversations; the snapshot itself lives under <root>/shares
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShareLink {
+    pub share_id: String,
+    pub token: String,
+    pub conversation_id: String,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// Something the user deleted, kept until restored or purged after the grace period
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashItem {
+    pub trash_id: String,
+    pub deleted_at: DateTime<Utc>,
+    pub conversation_id: String,
+    #[serde(flatten)]
+    pub entry: TrashedEntry,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TrashedEntry {
+    Conversation { conversation: ChatSession },
+    Message { conversation_title: String, message: ChatMessage },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -84,6 +119,37 @@ pub struct ChatSession {
     pub title: String,
     pub created_at: DateTime<Utc>,
     pub messages: Vec<ChatMessage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    // Slash-separated path for nested folders, e.g. "Work/Clients"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    // Hidden from the sidebar but kept, unlike deleted conversations
+    #[serde(default)]
+    pub archived: bool,
+    // Where an imported conversation came from; used to deduplicate re-imports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imported: Option<ImportOrigin>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportOrigin {
+    pub source: String,
+    pub external_id: String,
+    pub content_hash: String,
+    pub imported_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RetentionPolicy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_last: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -97,6 +163,18 @@ pub struct ChatMessage {
     pub timestamp: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub edit_timestamp: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revisions: Vec<MessageRevision>,
+}
+
+// Immutable snapshot of a message's content; revision 1 is the original text
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageRevision {
+    pub revision: u32,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverted_from: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -131,4 +209,12 @@ pub struct ConversationMetadata {
     pub id: String,
     pub title: String,
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub archived: bool,
 }
\ No newline at end of file
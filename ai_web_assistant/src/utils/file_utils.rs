@@ -11,6 +11,24 @@ use anyhow::{Result, anyhow};
 use crate::payloads::communication_response::ConversationSummary;
 use crate::utils::file_models::ConversationMetadata;
 
+const USERS_FILE: &str = "users.json";
+const SESSIONS_FILE: &str = "sessions.json";
+// messages.json is renamed once the message store has migrated it, but the data is still there
+const LEGACY_MESSAGE_FILES: [&str; 2] = ["messages.json", "messages.json.migrated"];
+
+#[derive(Debug, Default, Serialize)]
+pub struct UserRecords {
+    pub profiles: Vec<Value>,
+    pub sessions: Vec<Value>,
+    pub legacy_conversations: Vec<Value>,
+}
+
+impl UserRecords {
+    pub fn count(&self) -> usize {
+        self.profiles.len() + self.sessions.len() + self.legacy_conversations.len()
+    }
+}
+
 pub struct JsonFileManager {
     users_file: Mutex<()>,
     sessions_file: Mutex<()>,
@@ -67,9 +85,7 @@ impl JsonFileManager {
         
         let initial_json = if file_path.ends_with("users.json") {
             serde_json::json!({ "users": [] })
-        } else if file_path.ends_with("sessions.json") {
-            serde_json::json!({ "sessions": [] })
-        } else if file_path.ends_with("messages.json") {
+        } else if file_path.ends_with("sessions.json") || file_path.ends_with("messages.json") {
             serde_json::json!({ "sessions": [] })
         } else {
             return Err(anyhow!("Unknown file type"));
@@ -120,40 +136,39 @@ impl JsonFileManager {
     fn append_to_messages<T: Serialize>(&self, json_value: &mut serde_json::Value, data: T) -> Result<()> {
         let data_value = serde_json::to_value(data)?;
         
-        if let Some(users) = json_value.get_mut("users").and_then(|u| u.as_object_mut()) {
-            if let Some((user_id, session_id, messages)) = Self::extract_user_session_data(&data_value)? {
-                // Initialize user if not exists
-                if !users.contains_key(&user_id) {
-                    users.insert(user_id.clone(), serde_json::json!({
-                        "conversations": {}
+        if let Some(users) = json_value.get_mut("users").and_then(|u| u.as_object_mut())
+            && let Some((user_id, session_id, messages)) = Self::extract_user_session_data(&data_value)?
+        {
+            // Initialize user if not exists
+            if !users.contains_key(&user_id) {
+                users.insert(user_id.clone(), serde_json::json!({
+                    "conversations": {}
+                }));
+            }
+
+            if let Some(user_entry) = users.get_mut(&user_id)
+                && let Some(conversations) = user_entry.get_mut("conversations").and_then(|c| c.as_object_mut())
+            {
+                // Initialize session if not exists
+                if !conversations.contains_key(&session_id) {
+                    conversations.insert(session_id.clone(), serde_json::json!({
+                        "title": "New Chat",
+                        "created_at": Utc::now().to_rfc3339(),
+                        "messages": []
                     }));
                 }
-                
-                if let Some(user_entry) = users.get_mut(&user_id) {
-                    if let Some(conversations) = user_entry.get_mut("conversations").and_then(|c| c.as_object_mut()) {
-                        // Initialize session if not exists
-                        if !conversations.contains_key(&session_id) {
-                            conversations.insert(session_id.clone(), serde_json::json!({
-                                "title": "New Chat",
-                                "created_at": Utc::now().to_rfc3339(),
-                                "messages": []
-                            }));
-                        }
-                        
-                        if let Some(session_entry) = conversations.get_mut(&session_id) {
-                            if let Some(session_messages) = session_entry.get_mut("messages").and_then(|m| m.as_array_mut()) {
-                                // Add all messages to the session - FIXED ITERATION
-                                if let Some(messages_array) = messages.as_array() {
-                                    for message in messages_array.iter() {
-                                        session_messages.push(message.clone());
-                                    }
-                                    
-                                    // Update title if this is the first user message
-                                    Self::update_chat_title(session_entry, messages_array)?;
-                                }
-                            }
-                        }
+
+                if let Some(session_entry) = conversations.get_mut(&session_id)
+                    && let Some(session_messages) = session_entry.get_mut("messages").and_then(|m| m.as_array_mut())
+                    && let Some(messages_array) = messages.as_array()
+                {
+                    // Add all messages to the session - FIXED ITERATION
+                    for message in messages_array.iter() {
+                        session_messages.push(message.clone());
                     }
+
+                    // Update title if this is the first user message
+                    Self::update_chat_title(session_entry, messages_array)?;
                 }
             }
         }
@@ -184,25 +199,21 @@ impl JsonFileManager {
     }
 
     fn update_chat_title(session_entry: &mut serde_json::Value, messages: &[serde_json::Value]) -> Result<()> {
-        if let Some(session_obj) = session_entry.as_object_mut() {
-            if let Some(title) = session_obj.get("title").and_then(|t| t.as_str()) {
-                if title == "New Chat" {
-                    // Find first user message to set as title
-                    for message in messages {
-                        if let Some(role) = message.get("role").and_then(|r| r.as_str()) {
-                            if role == "user" {
-                                if let Some(content) = message.get("content").and_then(|c| c.as_str()) {
-                                    let title = if content.len() > 50 {
-                                        format!("{}...", &content[..50])
-                                    } else {
-                                        content.to_string()
-                                    };
-                                    session_obj.insert("title".to_string(), serde_json::Value::String(title));
-                                    break;
-                                }
-                            }
-                        }
-                    }
+        if let Some(session_obj) = session_entry.as_object_mut()
+            && session_obj.get("title").and_then(|t| t.as_str()) == Some("New Chat")
+        {
+            // Find first user message to set as title
+            for message in messages {
+                if message.get("role").and_then(|r| r.as_str()) == Some("user")
+                    && let Some(content) = message.get("content").and_then(|c| c.as_str())
+                {
+                    let title = if content.len() > 50 {
+                        format!("{}...", &content[..50])
+                    } else {
+                        content.to_string()
+                    };
+                    session_obj.insert("title".to_string(), serde_json::Value::String(title));
+                    break;
                 }
             }
         }
@@ -223,35 +234,112 @@ impl JsonFileManager {
 
         let mut conversations = Vec::new();
 
-        if let Some(users) = json_value.get("users").and_then(|u| u.as_object()) {
-            if let Some(user_entry) = users.get(&user_id.to_string()) {
-                if let Some(conversations_obj) = user_entry.get("conversations").and_then(|c| c.as_object()) {
-                    for (session_id, conv_data) in conversations_obj.iter() {
-                        let title = conv_data.get("title")
-                            .and_then(|t| t.as_str())
-                            .unwrap_or("Untitled")
-                            .to_string();
-
-                        let created_at = conv_data.get("created_at")
-                            .and_then(|ts| ts.as_str())
-                            .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
-                            .map(|dt| dt.with_timezone(&Utc))
-                            .unwrap_or_else(|| Utc::now());
-
-                        conversations.push(ConversationSummary {
-                            conversation_id: session_id.clone(),
-                            title,
-                            created_at,
-                        });
-                    }
-                }
+        if let Some(users) = json_value.get("users").and_then(|u| u.as_object())
+            && let Some(user_entry) = users.get(&user_id.to_string())
+            && let Some(conversations_obj) = user_entry.get("conversations").and_then(|c| c.as_object())
+        {
+            for (session_id, conv_data) in conversations_obj.iter() {
+                let title = conv_data.get("title")
+                    .and_then(|t| t.as_str())
+                    .unwrap_or("Untitled")
+                    .to_string();
+
+                let created_at = conv_data.get("created_at")
+                    .and_then(|ts| ts.as_str())
+                    .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .unwrap_or_else(Utc::now);
+
+                conversations.push(ConversationSummary {
+                    conversation_id: session_id.clone(),
+                    title,
+                    created_at,
+                    pinned: false,
+                    tags: Vec::new(),
+                    folder: None,
+                });
             }
         }
 
         Ok(conversations)
     }
 
-    
+    // Everything users.json, sessions.json and the legacy messages.json hold about one user
+    pub async fn user_records(&self, user_id: u64) -> Result<UserRecords> {
+        let mut records = UserRecords::default();
+
+        {
+            let _lock = self.users_file.lock().await;
+            if let Some(mut json_value) = self.read_if_exists(USERS_FILE).await? {
+                records.profiles = take_profiles(&mut json_value, user_id);
+            }
+        }
+        {
+            let _lock = self.sessions_file.lock().await;
+            if let Some(mut json_value) = self.read_if_exists(SESSIONS_FILE).await? {
+                records.sessions = take_sessions(&mut json_value, &user_id.to_string());
+            }
+        }
+        {
+            let _lock = self.messages_file.lock().await;
+            for file_path in LEGACY_MESSAGE_FILES {
+                if let Some(mut json_value) = self.read_if_exists(file_path).await? {
+                    records.legacy_conversations.extend(take_legacy_user(&mut json_value, &user_id.to_string()));
+                }
+            }
+        }
+
+        Ok(records)
+    }
+
+    // Removes the user from every JSON file and returns what was removed
+    pub async fn erase_user(&self, user_id: u64) -> Result<UserRecords> {
+        let mut records = UserRecords::default();
+
+        {
+            let _lock = self.users_file.lock().await;
+            if let Some(mut json_value) = self.read_if_exists(USERS_FILE).await? {
+                records.profiles = take_profiles(&mut json_value, user_id);
+                if !records.profiles.is_empty() {
+                    self.write_file(USERS_FILE, &json_value).await?;
+                }
+            }
+        }
+        {
+            let _lock = self.sessions_file.lock().await;
+            if let Some(mut json_value) = self.read_if_exists(SESSIONS_FILE).await? {
+                records.sessions = take_sessions(&mut json_value, &user_id.to_string());
+                if !records.sessions.is_empty() {
+                    self.write_file(SESSIONS_FILE, &json_value).await?;
+                }
+            }
+        }
+        {
+            let _lock = self.messages_file.lock().await;
+            for file_path in LEGACY_MESSAGE_FILES {
+                if let Some(mut json_value) = self.read_if_exists(file_path).await?
+                    && let Some(conversations) = take_legacy_user(&mut json_value, &user_id.to_string())
+                {
+                    records.legacy_conversations.push(conversations);
+                    self.write_file(file_path, &json_value).await?;
+                }
+            }
+        }
+
+        Ok(records)
+    }
+
+    async fn read_if_exists(&self, file_path: &str) -> Result<Option<Value>> {
+        match tokio::fs::read(file_path).await {
+            Ok(bytes) if bytes.is_empty() => Ok(None),
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| anyhow!("Failed to parse {}: {}", file_path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     fn extract_user_session_data(data_value: &serde_json::Value) -> Result<Option<(String, String, serde_json::Value)>> {
         // This is a simplified version - you'll need to adapt based on your actual data structure
         // For now, return None as placeholder
@@ -259,4 +347,51 @@ impl JsonFileManager {
     }
 
     
-}
\ No newline at end of file
+}
+
+// users.json: {"users": [UserData, ...]}, one record per login, matched on basic_info.id "usr_<id>"
+fn take_profiles(json_value: &mut Value, user_id: u64) -> Vec<Value> {
+    let profile_id = format!("usr_{}", user_id);
+    let Some(users) = json_value.get_mut("users").and_then(|u| u.as_array_mut()) else {
+        return Vec::new();
+    };
+
+    let (taken, kept): (Vec<Value>, Vec<Value>) = users.drain(..).partition(|user| {
+        user.pointer("/basic_info/id").and_then(Value::as_str) == Some(profile_id.as_str())
+    });
+    *users = kept;
+    taken
+}
+
+// sessions.json nests session lists under the user id at varying depths, so every
+// object is searched for that key; objects left empty are dropped from their arrays
+fn take_sessions(json_value: &mut Value, user_key: &str) -> Vec<Value> {
+    let mut taken = Vec::new();
+    match json_value {
+        Value::Object(map) => {
+            if let Some(Value::Array(sessions)) = map.remove(user_key) {
+                taken.extend(sessions);
+            }
+            for value in map.values_mut() {
+                taken.extend(take_sessions(value, user_key));
+            }
+        }
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                taken.extend(take_sessions(item, user_key));
+            }
+            if !taken.is_empty() {
+                items.retain(|item| !matches!(item, Value::Object(map) if map.is_empty()));
+            }
+        }
+        _ => {}
+    }
+    taken
+}
+
+// messages.json: {"users": {"<id>": {"conversations": {...}}}}
+fn take_legacy_user(json_value: &mut Value, user_key: &str) -> Option<Value> {
+    json_value.get_mut("users")
+        .and_then(|u| u.as_object_mut())
+        .and_then(|users| users.remove(user_key))
+}
@@ -4,6 +4,9 @@ use serde::{Deserialize, Serialize};
 use anyhow::{Result, anyhow};
 use dotenv::dotenv;
 use std::env;
+use uuid::Uuid;
+
+use crate::repository::role_repository::UserAccess;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
@@ -13,6 +16,31 @@ pub struct Claims {
     pub is_admin: bool,
     pub is_user: bool,
     pub exp: usize,
+    // Unique per token, so it can be revoked on its own
+    #[serde(default)]
+    pub jti: String,
+    #[serde(default)]
+    pub iat: usize,
+    // iat in microseconds, so a revocation and a login in the same second can be told apart
+    #[serde(default)]
+    pub iat_micros: i64,
+    // Login session (refresh token family); one per device
+    #[serde(default)]
+    pub sid: String,
+    // Set only when authenticated with a personal API key, whose scopes then limit access
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+    // From the user's roles when the token was issued
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+impl Claims {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
 }
 
 pub fn get_secret() -> Result<Vec<u8>> {
@@ -22,20 +50,40 @@ pub fn get_secret() -> Result<Vec<u8>> {
         .map_err(|_| anyhow!("JWT_SECRET must be set in .env"))
 }
 
-pub fn generate_token(user_id: i64, email: String, is_admin: bool) -> Result<String> {
+const DEFAULT_ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+
+// Access tokens are short-lived; sessions are extended with refresh tokens
+pub fn access_token_ttl() -> Duration {
+    let minutes = env::var("JWT_ACCESS_TTL_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|minutes| *minutes > 0)
+        .unwrap_or(DEFAULT_ACCESS_TOKEN_TTL_MINUTES);
+    Duration::minutes(minutes)
+}
+
+pub fn generate_token(user_id: i64, email: String, access: &UserAccess, session_id: &str) -> Result<String> {
     let secret = get_secret()?;
-    let expiration = Utc::now()
-        .checked_add_signed(Duration::hours(24)) 
+    let now = Utc::now();
+    let expiration = now
+        .checked_add_signed(access_token_ttl())
         .ok_or_else(|| anyhow!("Invalid expiration time"))?
         .timestamp() as usize;
 
     let claims = Claims {
         sub: user_id,
         email,
-        roles: vec!["USER".to_string()],
+        roles: access.roles.clone(),
         is_user: true,
-        is_admin,
+        is_admin: access.roles.iter().any(|role| role == "admin"),
         exp: expiration,
+        jti: Uuid::new_v4().to_string(),
+        iat: now.timestamp() as usize,
+        iat_micros: now.timestamp_micros(),
+        sid: session_id.to_string(),
+        api_key_id: None,
+        scopes: Vec::new(),
+        permissions: access.permissions.clone(),
     };
 
     let token = encode(
@@ -58,4 +106,54 @@ pub fn decode_token(token: &str) -> Result<Claims> {
     )?;
     
     Ok(token_data.claims)
-}
\ No newline at end of file
+}
+// Single-purpose tokens sent by email (e.g. "verify_email"). They don't decode as access
+// tokens and are bound to the address they were sent to.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActionClaims {
+    pub sub: i64,
+    pub email: String,
+    pub purpose: String,
+    pub exp: usize,
+    // Set when the server tracks the token, so it can be used up before it expires
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub jti: String,
+}
+
+pub fn generate_action_token(user_id: i64, email: &str, purpose: &str, ttl: Duration) -> Result<String> {
+    generate_tracked_action_token(user_id, email, purpose, ttl, "")
+}
+
+pub fn generate_tracked_action_token(user_id: i64, email: &str, purpose: &str, ttl: Duration, jti: &str) -> Result<String> {
+    let secret = get_secret()?;
+    let expiration = Utc::now()
+        .checked_add_signed(ttl)
+        .ok_or_else(|| anyhow!("Invalid expiration time"))?
+        .timestamp() as usize;
+
+    let claims = ActionClaims {
+        sub: user_id,
+        email: email.to_string(),
+        purpose: purpose.to_string(),
+        exp: expiration,
+        jti: jti.to_string(),
+    };
+
+    Ok(encode(&Header::default(), &claims, &EncodingKey::from_secret(&secret))?)
+}
+
+pub fn decode_action_token(token: &str, purpose: &str) -> Result<ActionClaims> {
+    let secret = get_secret()?;
+
+    let claims = decode::<ActionClaims>(
+        token,
+        &DecodingKey::from_secret(&secret),
+        &Validation::new(Algorithm::HS256),
+    )?
+    .claims;
+
+    if claims.purpose != purpose {
+        return Err(anyhow!("Token is not valid for this action"));
+    }
+    Ok(claims)
+}
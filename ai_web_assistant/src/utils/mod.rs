@@ -1,3 +1,5 @@
 pub mod jwt;
 pub mod file_utils;
 pub mod file_models;
+pub mod totp;
+pub mod client_ip;
\ No newline at end of file
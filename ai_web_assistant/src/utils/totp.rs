@@ -0,0 +1,115 @@
+use anyhow::{anyhow, Result};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha1::Sha1;
+use subtle::ConstantTimeEq;
+
+// RFC 6238 with the parameters every authenticator app supports: SHA-1, 6 digits, 30 seconds
+const DIGITS: u32 = 6;
+const PERIOD_SECS: u64 = 30;
+// Codes from one step before or after are accepted to allow for clock drift
+const DRIFT_STEPS: u64 = 1;
+
+// 160 random bits, base32 encoded as authenticator apps expect
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        url_encode(issuer),
+        url_encode(account),
+        secret,
+        url_encode(issuer),
+        DIGITS,
+        PERIOD_SECS,
+    )
+}
+
+// Returns the time step the code belongs to, so callers can refuse to accept it twice
+pub fn verify(secret: &str, code: &str, unix_time: u64) -> Result<Option<u64>> {
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+    let key = BASE32_NOPAD
+        .decode(secret.as_bytes())
+        .map_err(|e| anyhow!("Invalid TOTP secret: {}", e))?;
+
+    let current = unix_time / PERIOD_SECS;
+    for step in current.saturating_sub(DRIFT_STEPS)..=current + DRIFT_STEPS {
+        if bool::from(code_at(&key, step)?.as_bytes().ct_eq(code.as_bytes())) {
+            return Ok(Some(step));
+        }
+    }
+    Ok(None)
+}
+
+fn code_at(key: &[u8], step: u64) -> Result<String> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).map_err(|e| anyhow!(e.to_string()))?;
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation (RFC 4226 section 5.3)
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+    Ok(format!("{:0width$}", binary % 10u32.pow(DIGITS), width = DIGITS as usize))
+}
+
+fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, SHA-1 key "12345678901234567890"; 6-digit codes are the last six digits
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn accepts_the_rfc_test_vectors() {
+        for (time, code) in [(59, "287082"), (1_111_111_109, "081804"), (1_234_567_890, "005924"), (2_000_000_000, "279037")] {
+            assert_eq!(verify(RFC_SECRET, code, time).unwrap(), Some(time / PERIOD_SECS), "t={}", time);
+        }
+    }
+
+    #[test]
+    fn allows_one_step_of_drift_either_way() {
+        let time = 1_111_111_109;
+        assert!(verify(RFC_SECRET, "081804", time - PERIOD_SECS).unwrap().is_some());
+        assert!(verify(RFC_SECRET, "081804", time + PERIOD_SECS).unwrap().is_some());
+        assert_eq!(verify(RFC_SECRET, "081804", time + 2 * PERIOD_SECS).unwrap(), None);
+        assert_eq!(verify(RFC_SECRET, "081804", time - 2 * PERIOD_SECS).unwrap(), None);
+    }
+
+    #[test]
+    fn tolerates_spaces_but_not_other_shapes() {
+        assert!(verify(RFC_SECRET, " 081 804 ", 1_111_111_109).unwrap().is_some());
+        for code in ["", "81804", "0818040", "08180a", "081-804"] {
+            assert_eq!(verify(RFC_SECRET, code, 1_111_111_109).unwrap(), None, "{:?}", code);
+        }
+    }
+
+    #[test]
+    fn rejects_a_malformed_secret() {
+        assert!(verify("not base32!", "123456", 0).is_err());
+    }
+
+    #[test]
+    fn generated_secrets_decode_to_160_bits() {
+        let secret = generate_secret();
+        assert_eq!(BASE32_NOPAD.decode(secret.as_bytes()).unwrap().len(), 20);
+        assert!(otpauth_uri("AI Web Assistant", "a@b.c", &secret).starts_with("otpauth://totp/AI%20Web%20Assistant:a%40b.c?secret="));
+    }
+}
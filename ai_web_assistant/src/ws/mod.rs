@@ -1,4 +1,5 @@
 pub mod ws_server;
 pub mod ws_channel;
 pub mod ws_handler;
-pub mod ws_auth;
\ No newline at end of file
+pub mod ws_auth;
+pub mod ws_protocol;
\ No newline at end of file
@@ -1,6 +1,10 @@
 use axum::extract::FromRequestParts;
 use axum::http::{request::Parts, StatusCode};
 use async_trait::async_trait;
+use std::sync::Arc;
+use sqlx::PgPool;
+use crate::services::api_key_service;
+use crate::services::revocation_service::RevocationService;
 use crate::utils::jwt::{decode_token, Claims};
 use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
 use tokio_tungstenite::tungstenite::Message;
@@ -37,7 +41,10 @@ where
             None => return Err((StatusCode::BAD_REQUEST, "Missing token in query".into())),
         };
 
-        Self::validate_token(&token)
+        match (parts.extensions.get::<Arc<RevocationService>>(), parts.extensions.get::<PgPool>()) {
+            (Some(revocations), Some(db)) => Self::authorize(&token, revocations, db).await,
+            _ => Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal auth error".into())),
+        }
     }
 }
 
@@ -66,7 +73,23 @@ impl WsAuth {
         }
     }
 
-    pub async fn from_first_message(msg: &Message) -> Result<Self, (StatusCode, String)> {
+    // Signature and expiry (or an API key with the "chat" scope), then the revocation store
+    pub async fn authorize(token: &str, revocations: &RevocationService, db: &PgPool) -> Result<Self, (StatusCode, String)> {
+        let auth = if api_key_service::is_api_key(token) {
+            api_key_service::authenticate(db, token, "chat")
+                .await
+                .map(WsAuth)
+                .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?
+        } else {
+            Self::validate_token(token)?
+        };
+        if revocations.is_revoked(&auth.0) {
+            return Err((StatusCode::UNAUTHORIZED, "Token has been revoked".into()));
+        }
+        Ok(auth)
+    }
+
+    pub async fn from_first_message(msg: &Message, revocations: &RevocationService, db: &PgPool) -> Result<Self, (StatusCode, String)> {
         match msg {
             Message::Text(text) => {
                 let json = serde_json::from_str::<serde_json::Value>(text)
@@ -75,7 +98,7 @@ impl WsAuth {
                     .and_then(|v| v.as_str())
                     .ok_or((StatusCode::BAD_REQUEST, "Missing token field".into()))?;
 
-                Self::validate_token(token)
+                Self::authorize(token, revocations, db).await
             }
             _ => Err((StatusCode::BAD_REQUEST, "Expected text message".into())),
         }
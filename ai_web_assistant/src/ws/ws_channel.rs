@@ -3,9 +3,31 @@ use std::collections::HashMap;
 use uuid::Uuid;
 use tokio::sync::{Mutex, mpsc::{UnboundedSender}};
 
+use crate::payloads::communication_response::CommunicationResponse;
+
+// What gets queued for a client. Typed responses stay typed until the connection's
+// WireProtocol frames them, so binary clients never pay for a JSON round trip.
+#[derive(Debug, Clone)]
+pub enum Outgoing {
+    Text(String),
+    Response(CommunicationResponse),
+}
+
+impl From<String> for Outgoing {
+    fn from(text: String) -> Self {
+        Outgoing::Text(text)
+    }
+}
+
+impl From<CommunicationResponse> for Outgoing {
+    fn from(response: CommunicationResponse) -> Self {
+        Outgoing::Response(response)
+    }
+}
+
 #[derive(Clone)]
 pub struct WsBroadcaster {
-    clients: Arc<Mutex<HashMap<Uuid, UnboundedSender<String>>>>,
+    clients: Arc<Mutex<HashMap<Uuid, UnboundedSender<Outgoing>>>>,
 }
 
 impl WsBroadcaster {
@@ -15,7 +37,7 @@ impl WsBroadcaster {
         }
     }
 
-    pub async fn add_client(&self, client_id: Uuid, tx: UnboundedSender<String>) {
+    pub async fn add_client(&self, client_id: Uuid, tx: UnboundedSender<Outgoing>) {
         self.clients.lock().await.insert(client_id, tx);
     }
 
@@ -25,8 +47,9 @@ impl WsBroadcaster {
 
     #[allow(dead_code)]
     // Broadcast to all clients
-    pub async fn broadcast(&self, message: String) {
-        let clients: Vec<(Uuid, UnboundedSender<String>)> = {
+    pub async fn broadcast(&self, message: impl Into<Outgoing>) {
+        let message = message.into();
+        let clients: Vec<(Uuid, UnboundedSender<Outgoing>)> = {
             let clients_guard = self.clients.lock().await;
             clients_guard.iter().map(|(id, tx)| (*id, tx.clone())).collect()
         };
@@ -45,8 +68,9 @@ impl WsBroadcaster {
     }
 
     #[allow(dead_code)]
-    pub async fn broadcast_except(&self, sender_id: &Uuid, message: String) {
-        let clients: Vec<(Uuid, UnboundedSender<String>)> = {
+    pub async fn broadcast_except(&self, sender_id: &Uuid, message: impl Into<Outgoing>) {
+        let message = message.into();
+        let clients: Vec<(Uuid, UnboundedSender<Outgoing>)> = {
             let clients_guard = self.clients.lock().await;
             clients_guard.iter()
                 .filter(|(id, _)| *id != sender_id)
@@ -61,10 +85,10 @@ impl WsBroadcaster {
 
 
     // Send to specific client
-    pub async fn send_to(&self, client_id: &Uuid, message: String) -> bool {
+    pub async fn send_to(&self, client_id: &Uuid, message: impl Into<Outgoing>) -> bool {
         let clients = self.clients.lock().await;
         if let Some(tx) = clients.get(client_id) {
-            tx.send(message).is_ok()
+            tx.send(message.into()).is_ok()
         } else {
             false
         }
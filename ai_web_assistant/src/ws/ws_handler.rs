@@ -4,15 +4,20 @@ use std::sync::Arc;
 use futures_util::{SinkExt, StreamExt};
 use serde::Serialize;
 use serde_json::{json};
-use tokio::sync::mpsc;
-use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame, Message};
 use uuid::Uuid;
 use chrono::Utc;
 
+#[cfg(feature = "ttl")]
+use crate::{helpers::retention::{self, RetentionConfig}, utils::file_models::RetentionPolicy};
+use crate::middleware::ownership;
+use crate::repository::two_factor_repository::TwoFactorRepository;
 use crate::{
-    helpers::message_manager::MessageManager, payloads::{communication_request::CommunicationRequest, communication_response::{CommunicationResponse, ConversationSummary}, connection_request::ConnectionRequest}, services::{llm_service::LlmService, user_service::UserService}, utils::{file_models::{AuthSession, BasicInfo, ChatMessage, ChatSession, ContentPreferences, PasswordInfo, PremiumMembership, SecurityInfo, SessionInfo, SessionRecord, SubscriptionInfo, TwoFactorAuth, UserData, UserSessions, UsersWrapper}, file_utils::JsonFileManager, jwt::Claims}, ws::{ws_auth::WsAuth, ws_channel::WsBroadcaster}
+    helpers::{export, import, message_manager::MessageManager, organize::{OrganizeChange, SidebarFilter}, pagination::{self, Order, Page}, share, trash::{self, TrashConfig}}, payloads::{communication_request::CommunicationRequest, communication_response::{CommunicationResponse, ConversationSummary}, connection_request::ConnectionRequest}, services::{llm_service::LlmService, revocation_service::RevocationService, user_service::UserService, verification_service::VerificationService}, utils::{file_models::{AuthSession, BasicInfo, ChatMessage, ContentPreferences, ConversationMetadata, PasswordInfo, PremiumMembership, SecurityInfo, SessionInfo, SessionRecord, SubscriptionInfo, TwoFactorAuth, UserData, UserSessions, UsersWrapper}, file_utils::JsonFileManager, jwt::Claims}, ws::{ws_auth::WsAuth, ws_channel::WsBroadcaster, ws_protocol::WireProtocol}
 };
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_ws_connection(
     ws_stream: tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
     client_id: Uuid,
@@ -22,15 +27,17 @@ pub async fn handle_ws_connection(
     llm_service: Arc<LlmService>,
     file_manager: Arc<JsonFileManager>,
     message_manager: Arc<MessageManager>,
+    revocations: Arc<RevocationService>,
+    verification: Arc<VerificationService>,
 ) {
     let (mut ws_sender, mut ws_receiver) = ws_stream.split();
 
     // AUTHENTICATION PHASE 
-    let (user_id, claims) = match ws_receiver.next().await {
+    let (user_id, claims, protocol) = match ws_receiver.next().await {
         Some(Ok(first_msg)) => {
-            match WsAuth::from_first_message(&first_msg).await {
+            match WsAuth::from_first_message(&first_msg, &revocations, &user_service.repository.db).await {
                 Ok(WsAuth(claims)) => {
-                    (claims.sub as u64, claims)
+                    (claims.sub as u64, claims, WireProtocol::negotiate(&first_msg))
                 }
                 Err((code, msg)) => {
                     let _ = ws_sender.send(Message::Text(
@@ -87,13 +94,13 @@ pub async fn handle_ws_connection(
     // Check if user already has a session
     if let Some(wrapper) = auth_session.auth_session.first_mut() {
         for user_sessions in &mut wrapper.users {
-            if let Some(sessions) = user_sessions.sessions.get_mut(&user_id) {
-                if let Some(existing_session) = sessions.first() {
-                    // Use existing session ID
-                    session_id = existing_session.session_id.clone();
-                    session_exists = true;
-                    break;
-                }
+            if let Some(sessions) = user_sessions.sessions.get_mut(&user_id)
+                && let Some(existing_session) = sessions.first()
+            {
+                // Use existing session ID
+                session_id = existing_session.session_id.clone();
+                session_exists = true;
+                break;
             }
         }
     }
@@ -123,6 +130,12 @@ pub async fn handle_ws_connection(
         }
     }
 
+    // The profile mirrors the real 2FA state kept in Postgres
+    let two_factor_enabled = TwoFactorRepository { db: user_service.repository.db.clone() }
+        .is_enabled(claims.sub)
+        .await
+        .unwrap_or(false);
+
     // 1. SAVE USER DATA FIRST
     let user_data = UserData {
         session_id: session_id.clone(),
@@ -145,8 +158,8 @@ pub async fn handle_ws_connection(
                 requires_reset: false,
             },
             two_factor_auth: TwoFactorAuth {
-                enabled: true,
-                method: "authenticator_app".to_string(),
+                enabled: two_factor_enabled,
+                method: if two_factor_enabled { "authenticator_app" } else { "none" }.to_string(),
             },
         },
         subscriptions: SubscriptionInfo {
@@ -164,37 +177,52 @@ pub async fn handle_ws_connection(
     // 2. SAVE SESSION DATA SECOND
     self::save_with_retry(&file_manager, "sessions.json", auth_session, 3).await;
 
-    // 3. SAVE MESSAGE DATA LAST
-    let chat_session = ChatSession {
-        title: "New Chat Session".to_string(),
-        created_at: now,
-        messages: Vec::new(),
-    };
-    self::save_with_retry(&file_manager, "messages.json", chat_session, 3).await;
-
     // Register client
     let (tx, mut rx) = mpsc::unbounded_channel();
     broadcaster.add_client(client_id, tx).await;
+    message_manager.user_connected(&user_id.to_string());
 
     // Send session info
-    let _ = ws_sender.send(Message::Text(
-        serde_json::to_string(&CommunicationResponse::SessionCreated {
+    let _ = ws_sender.send(protocol.frame(
+        CommunicationResponse::SessionCreated {
             status: "session_created".to_string(),
             session_id: session_id.clone(),
             user_id,
-        }).unwrap().into()
+        }
     )).await;
 
+    // The token the connection is authenticated with; "reauthenticate" swaps it
+    let current_claims = Arc::new(std::sync::Mutex::new(claims.clone()));
+
     // MAIN MESSAGE PROCESSING LOOP
-    let process_task = tokio::spawn({
+    let mut process_task = tokio::spawn({
         let broadcaster = broadcaster.clone();
         let llm_service = llm_service.clone();
         let file_manager = file_manager.clone();
-        let client_id_for_task = client_id.clone();
+        let message_manager = message_manager.clone();
+        let client_id_for_task = client_id;
         let session_id_clone = session_id.clone();
+        let revocations = revocations.clone();
+        let verification = verification.clone();
+        let current_claims = current_claims.clone();
+        let db = user_service.repository.db.clone();
 
         async move {
             while let Some(Ok(msg)) = ws_receiver.next().await {
+                let msg = match WireProtocol::normalize_incoming(msg) {
+                    Ok(msg) => msg,
+                    Err(error) => {
+                        let _ = broadcaster.send_to(
+                            &client_id_for_task,
+                            CommunicationResponse::Error {
+                                status: "invalid_message".to_string(),
+                                error,
+                            }
+                        ).await;
+                        continue;
+                    }
+                };
+
                 match msg {
                     Message::Text(text) => {
                         // First, try parsing as a ConnectionRequest
@@ -205,9 +233,9 @@ pub async fn handle_ws_connection(
                                         if req_session_id == session_id_clone {
                                             let _ = broadcaster.send_to(
                                                 &client_id_for_task,
-                                                serde_json::to_string(&CommunicationResponse::Disconnected {
+                                                CommunicationResponse::Disconnected {
                                                     status: "disconnected".to_string(),
-                                                }).unwrap()
+                                                }
                                             ).await;
                                             break;
                                         }
@@ -215,20 +243,99 @@ pub async fn handle_ws_connection(
                                     ConnectionRequest::StartConnection { .. } => {
                                         let _ = broadcaster.send_to(
                                             &client_id_for_task,
-                                            serde_json::to_string(&CommunicationResponse::Error {
+                                            CommunicationResponse::Error {
                                                 status: "invalid_request".to_string(),
                                                 error: "Already connected".to_string(),
-                                            }).unwrap()
+                                            }
                                         ).await;
                                     }
+                                    ConnectionRequest::Reauthenticate { token } => {
+                                        let response = match WsAuth::authorize(&token, &revocations, &db).await {
+                                            Ok(WsAuth(fresh)) if fresh.sub as u64 == user_id => {
+                                                let expires_at = fresh.exp;
+                                                *current_claims.lock().unwrap() = fresh;
+                                                json!({
+                                                    "type": "reauthenticated",
+                                                    "status": "ok",
+                                                    "expires_at": expires_at
+                                                })
+                                            }
+                                            Ok(_) => json!({
+                                                "type": "reauthenticated",
+                                                "status": "error",
+                                                "error": "Token belongs to a different user",
+                                                "code": 403
+                                            }),
+                                            Err((code, msg)) => json!({
+                                                "type": "reauthenticated",
+                                                "status": "error",
+                                                "error": msg,
+                                                "code": code.as_u16()
+                                            }),
+                                        };
+                                        let _ = broadcaster.send_to(&client_id_for_task, response.to_string()).await;
+                                    }
                                 }
                             }
                             Err(_) => {
+                                // Requests are refused once the access token expires, until the client
+                                // refreshes it and sends "reauthenticate"; the connection stays open
+                                let token_expires_at = current_claims.lock().unwrap().exp;
+                                if Utc::now().timestamp() as usize >= token_expires_at {
+                                    let _ = broadcaster.send_to(
+                                        &client_id_for_task,
+                                        json!({
+                                            "type": "error",
+                                            "status": "token_expired",
+                                            "error": "Access token expired; send a reauthenticate message with a fresh token",
+                                            "code": 401
+                                        }).to_string()
+                                    ).await;
+                                    continue;
+                                }
+
                                 // If not a ConnectionRequest, try parsing as CommunicationRequest
                                 match serde_json::from_str::<CommunicationRequest>(&text) {
                                     Ok(comm_req) => {
+                                        // Checked against the latest token, so a reauthenticate picks up role changes
+                                        let denied = forbidden_message(&current_claims.lock().unwrap(), &comm_req);
+                                        if let Some(error) = denied {
+                                            let _ = broadcaster.send_to(&client_id_for_task, error).await;
+                                            continue;
+                                        }
+
                                         match comm_req {
                                             CommunicationRequest::AIRequest { prompt, session_id } => {
+                                                // REQUIRE_VERIFIED_EMAIL_FOR_AI keeps unverified accounts away from the model
+                                                match verification.may_use_ai(claims.sub).await {
+                                                    Ok(true) => {}
+                                                    Ok(false) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            json!({
+                                                                "type": "error",
+                                                                "status": "email_not_verified",
+                                                                "error": "Verify your email address to use the assistant",
+                                                                "code": 403
+                                                            }).to_string()
+                                                        ).await;
+                                                        continue;
+                                                    }
+                                                    Err(e) => {
+                                                        eprintln!("❌ Failed to check email verification for user {}: {}", claims.sub, e);
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            json!({
+                                                                "type": "error",
+                                                                "status": "internal_error",
+                                                                "error": "Could not check email verification",
+                                                                "code": 500
+                                                            }).to_string()
+                                                        ).await;
+                                                        continue;
+                                                    }
+                                                }
+
                                                 // Generate unique ID for user message
                                                 let user_message_id = format!("msg_{}", Uuid::new_v4());
                                                 let timestamp = Utc::now();
@@ -242,16 +349,17 @@ pub async fn handle_ws_connection(
                                                     edited: false,
                                                     timestamp,
                                                     edit_timestamp: None,
+                                                    revisions: Vec::new(),
                                                 };
 
                                                 // Send back message_created response
                                                 let _ = broadcaster.send_to(
                                                     &client_id_for_task,
-                                                    serde_json::to_string(&CommunicationResponse::MessageCreated {
+                                                    CommunicationResponse::MessageCreated {
                                                         status: "message_created".to_string(),
                                                         message_id: user_message_id.clone(),
                                                         message: user_message.clone(),
-                                                    }).unwrap()
+                                                    }
                                                 ).await;
 
                                                 // Convert user_id to string
@@ -270,13 +378,13 @@ pub async fn handle_ws_connection(
                                                 let llm_service_clone = llm_service.clone();
                                                 let broadcaster_clone = broadcaster.clone();
                                                 let message_manager_clone = message_manager.clone();
-                                                let client_id_clone = client_id_for_task.clone();
-                                                let user_id_str_clone = user_id_str.clone();
+                                                let client_id_clone = client_id_for_task;
+                                                let user_id_str_clone = user_id_str;
                                                 let user_message_id_clone = user_message_id.clone();
 
                                                 tokio::spawn(async move {
                                                     if let Ok(response) = llm_service_clone
-                                                        .run_prompt(&prompt, broadcaster_clone.clone(), client_id_clone.clone())
+                                                        .run_prompt(&prompt, broadcaster_clone.clone(), client_id_clone)
                                                         .await
                                                     {
                                                         let ai_message = ChatMessage {
@@ -288,6 +396,7 @@ pub async fn handle_ws_connection(
                                                             edited: false,
                                                             timestamp: Utc::now(),
                                                             edit_timestamp: None,
+                                                            revisions: Vec::new(),
                                                         };
 
                                                         // Save AI message in the same session
@@ -302,40 +411,48 @@ pub async fn handle_ws_connection(
                                                 });
                                             }
 
-                                            CommunicationRequest::FetchSidebarHistory { user_id } => {
-                                                match message_manager.get_all_conversation_titles(user_id).await {
-                                                    Ok(conversations) => {
+                                            CommunicationRequest::FetchSidebarHistory { user_id, tag, folder, page } => {
+                                                let filter = SidebarFilter { tag, folder };
+                                                let first_page = page.before.is_none() && page.after.is_none();
+
+                                                // Pinned conversations are listed on their own, ahead of the paged list
+                                                let result = message_manager.get_all_conversation_titles(user_id).await
+                                                    .and_then(|conversations| {
+                                                        let (pinned, rest): (Vec<_>, Vec<_>) = conversations.into_iter()
+                                                            .filter(|conv| filter.matches(conv))
+                                                            .partition(|conv| conv.pinned);
+                                                        let page = pagination::paginate_or_all(
+                                                            rest, &page, Order::NewestFirst,
+                                                            |conv| (conv.created_at, conv.id.clone()),
+                                                        )?;
+                                                        Ok((pinned, page))
+                                                    });
+                                                match result {
+                                                    Ok((pinned, Page { items: conversations, has_more })) => {
                                                         // Map to clean JSON response
                                                         let response = conversations.into_iter()
-                                                            .map(|conv| {
-                                                                json!({
-                                                                    "id": conv.id,
-                                                                    "title": conv.title,
-                                                                    "created_at": conv.created_at.to_rfc3339()
-                                                                })
-                                                            })
+                                                            .map(sidebar_entry)
                                                             .collect::<Vec<_>>();
-                                                        
+                                                        let pinned = if first_page {
+                                                            pinned.into_iter().map(sidebar_entry).collect::<Vec<_>>()
+                                                        } else {
+                                                            Vec::new()
+                                                        };
+
                                                         let _ = broadcaster.send_to(
                                                             &client_id_for_task,
                                                             serde_json::to_string(&json!({
                                                                 "type": "sidebar_history",
                                                                 "status": "ok",
-                                                                "conversations": response
+                                                                "pinned": pinned,
+                                                                "conversations": response,
+                                                                "has_more": has_more
                                                             })).unwrap()
                                                         ).await;
                                                     }
                                                     Err(e) => {
                                                         eprintln!("Failed to fetch sidebar history for user {}: {}", user_id, e);
                                                         
-                                                        // Add debug info - check if file exists and its size
-                                                        if let Ok(metadata) = tokio::fs::metadata("messages.json").await {
-                                                            eprintln!("messages.json metadata: size={}, modified={:?}", 
-                                                                metadata.len(), metadata.modified());
-                                                        } else {
-                                                            eprintln!("messages.json does not exist");
-                                                        }
-                                                        
                                                         let _ = broadcaster.send_to(
                                                             &client_id_for_task,
                                                             serde_json::to_string(&json!({
@@ -348,26 +465,32 @@ pub async fn handle_ws_connection(
                                                 }
                                             }
                                             
-                                            CommunicationRequest::FetchConversation { conversation_id } => {
+                                            CommunicationRequest::FetchConversation { conversation_id, page } => {
                                                 let user_id_str = user_id.to_string();
-                                                match message_manager.get_user_messages(&user_id_str, Some(&conversation_id)).await {
-                                                    Ok(messages) => {
+                                                let result = message_manager.get_user_messages(&user_id_str, Some(&conversation_id)).await
+                                                    .and_then(|messages| pagination::paginate_or_all(
+                                                        messages, &page, Order::OldestFirst,
+                                                        |message| (message.timestamp, message.message_id.clone()),
+                                                    ));
+                                                match result {
+                                                    Ok(Page { items: messages, has_more }) => {
                                                         let _ = broadcaster.send_to(
                                                             &client_id_for_task,
-                                                            serde_json::to_string(&CommunicationResponse::ConversationHistory {
+                                                            CommunicationResponse::ConversationHistory {
                                                                 status: "ok".to_string(),
                                                                 conversation_id,
                                                                 messages,
-                                                            }).unwrap()
+                                                                has_more,
+                                                            }
                                                         ).await;
                                                     }
                                                     Err(e) => {
                                                         let _ = broadcaster.send_to(
                                                             &client_id_for_task,
-                                                            serde_json::to_string(&CommunicationResponse::Error {
+                                                            CommunicationResponse::Error {
                                                                 status: "error".to_string(),
                                                                 error: "Failed to fetch conversation".to_string(),
-                                                            }).unwrap()
+                                                            }
                                                         ).await;
                                                     }
                                                 }
@@ -386,11 +509,11 @@ pub async fn handle_ws_connection(
                                                 let mut existing_session_id: Option<String> = None;
                                                 if let Some(wrapper) = auth_session.auth_session.first_mut() {
                                                     for user_sessions in &mut wrapper.users {
-                                                        if let Some(sessions) = user_sessions.sessions.get(&user_id) {
-                                                            if let Some(existing) = sessions.first() {
-                                                                existing_session_id = Some(existing.session_id.clone());
-                                                                break;
-                                                            }
+                                                        if let Some(sessions) = user_sessions.sessions.get(&user_id)
+                                                            && let Some(existing) = sessions.first()
+                                                        {
+                                                            existing_session_id = Some(existing.session_id.clone());
+                                                            break;
                                                         }
                                                     }
                                                 }
@@ -399,11 +522,11 @@ pub async fn handle_ws_connection(
                                                     // Respond with existing session
                                                     let _ = broadcaster.send_to(
                                                         &client_id_for_task,
-                                                        serde_json::to_string(&CommunicationResponse::SessionCreated {
+                                                        CommunicationResponse::SessionCreated {
                                                             status: "ok".to_string(),
                                                             session_id,
                                                             user_id,
-                                                        }).unwrap()
+                                                        }
                                                     ).await;
                                                 } else {
                                                     // User not found → create new session entry
@@ -430,10 +553,10 @@ pub async fn handle_ws_connection(
                                                         eprintln!("Failed to save new session: {}", e);
                                                         let _ = broadcaster.send_to(
                                                             &client_id_for_task,
-                                                            serde_json::to_string(&CommunicationResponse::Error {
+                                                            CommunicationResponse::Error {
                                                                 status: "error".to_string(),
                                                                 error: "Failed to create session".to_string(),
-                                                            }).unwrap()
+                                                            }
                                                         ).await;
                                                         break;
                                                     }
@@ -441,11 +564,11 @@ pub async fn handle_ws_connection(
                                                     // Respond with new session
                                                     let _ = broadcaster.send_to(
                                                         &client_id_for_task,
-                                                        serde_json::to_string(&CommunicationResponse::SessionCreated {
+                                                        CommunicationResponse::SessionCreated {
                                                             status: "ok".to_string(),
                                                             session_id: new_session_id.to_string(),
                                                             user_id,
-                                                        }).unwrap()
+                                                        }
                                                     ).await;
                                                 }
                                             }
@@ -518,7 +641,8 @@ pub async fn handle_ws_connection(
                                                                 "deleted_type": delete_result.deleted_type,
                                                                 "target_id": delete_result.target_id,
                                                                 "title": delete_result.title,
-                                                                "message": delete_result.message
+                                                                "message": delete_result.message,
+                                                                "trash_id": delete_result.trash_id
                                                             })).unwrap()
                                                         ).await;
                                                     }
@@ -536,17 +660,28 @@ pub async fn handle_ws_connection(
                                                 }
                                             }
 
-                                            CommunicationRequest::FetchAllMessages => {
+                                            CommunicationRequest::FetchAllMessages { page } => {
                                                 let user_id_str = user_id.to_string();
-                                                match message_manager.get_all_user_messages(&user_id_str).await {
+                                                let result = match message_manager.get_all_user_messages(&user_id_str).await {
                                                     Ok(messages) => {
+                                                        let total_count = messages.len();
+                                                        pagination::paginate(
+                                                            messages, &page, Order::OldestFirst,
+                                                            |message| (message.timestamp, message.message_id.clone()),
+                                                        ).map(|page| (page, total_count)).map_err(|e| e.to_string())
+                                                    }
+                                                    Err(e) => Err(e.to_string()),
+                                                };
+                                                match result {
+                                                    Ok((Page { items: messages, has_more }, total_count)) => {
                                                         let _ = broadcaster.send_to(
                                                             &client_id_for_task,
                                                             serde_json::to_string(&json!({
                                                                 "type": "all_messages",
                                                                 "status": "ok",
                                                                 "messages": messages,
-                                                                "total_count": messages.len()
+                                                                "total_count": total_count,
+                                                                "has_more": has_more
                                                             })).unwrap()
                                                         ).await;
                                                     }
@@ -563,15 +698,606 @@ pub async fn handle_ws_connection(
                                                     }
                                                 }
                                             }
+
+                                            CommunicationRequest::SearchMessages { query, include_archived, page } => {
+                                                let user_id_str = user_id.to_string();
+                                                let result = message_manager.search_messages(&user_id_str, &query, include_archived).await
+                                                    .and_then(|results| pagination::paginate(
+                                                        results, &page, Order::NewestFirst,
+                                                        |result| (result.message.timestamp, result.message.message_id.clone()),
+                                                    ));
+                                                match result {
+                                                    Ok(Page { items: results, has_more }) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            CommunicationResponse::SearchResults {
+                                                                status: "ok".to_string(),
+                                                                query,
+                                                                results,
+                                                                has_more,
+                                                            }
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            CommunicationResponse::Error {
+                                                                status: "error".to_string(),
+                                                                error: format!("Failed to search messages: {}", e),
+                                                            }
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            CommunicationRequest::PinConversation { conversation_id, pinned } => {
+                                                let user_id_str = user_id.to_string();
+                                                let result = message_manager.organize_conversation(&user_id_str, &conversation_id, OrganizeChange::Pin(pinned)).await;
+                                                send_organized(&broadcaster, &client_id_for_task, result).await;
+                                            }
+
+                                            CommunicationRequest::SetConversationTags { conversation_id, tags } => {
+                                                let user_id_str = user_id.to_string();
+                                                let result = message_manager.organize_conversation(&user_id_str, &conversation_id, OrganizeChange::Tags(tags)).await;
+                                                send_organized(&broadcaster, &client_id_for_task, result).await;
+                                            }
+
+                                            CommunicationRequest::MoveToFolder { conversation_id, folder } => {
+                                                let user_id_str = user_id.to_string();
+                                                let result = message_manager.organize_conversation(&user_id_str, &conversation_id, OrganizeChange::Folder(folder)).await;
+                                                send_organized(&broadcaster, &client_id_for_task, result).await;
+                                            }
+
+                                            CommunicationRequest::ArchiveConversation { conversation_id } => {
+                                                let user_id_str = user_id.to_string();
+                                                let result = message_manager.organize_conversation(&user_id_str, &conversation_id, OrganizeChange::Archive(true)).await;
+                                                send_organized(&broadcaster, &client_id_for_task, result).await;
+                                            }
+
+                                            CommunicationRequest::UnarchiveConversation { conversation_id } => {
+                                                let user_id_str = user_id.to_string();
+                                                let result = message_manager.organize_conversation(&user_id_str, &conversation_id, OrganizeChange::Archive(false)).await;
+                                                send_organized(&broadcaster, &client_id_for_task, result).await;
+                                            }
+
+                                            CommunicationRequest::FetchArchivedConversations { page } => {
+                                                let result = message_manager.get_archived_conversation_titles(user_id).await
+                                                    .and_then(|conversations| pagination::paginate(
+                                                        conversations, &page, Order::NewestFirst,
+                                                        |conv| (conv.created_at, conv.id.clone()),
+                                                    ));
+                                                match result {
+                                                    Ok(Page { items: conversations, has_more }) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "archived_conversations",
+                                                                "status": "ok",
+                                                                "conversations": conversations.into_iter().map(sidebar_entry).collect::<Vec<_>>(),
+                                                                "has_more": has_more
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "archived_conversations",
+                                                                "status": "error",
+                                                                "error": format!("Failed to fetch archived conversations: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            CommunicationRequest::RenameFolder { from, to } => {
+                                                let user_id_str = user_id.to_string();
+                                                match message_manager.rename_folder(&user_id_str, &from, &to).await {
+                                                    Ok(moved_count) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "folder_renamed",
+                                                                "status": "ok",
+                                                                "from": from,
+                                                                "to": to,
+                                                                "moved_count": moved_count
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "folder_renamed",
+                                                                "status": "error",
+                                                                "error": format!("Failed to rename folder: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            CommunicationRequest::ListFolders => {
+                                                let user_id_str = user_id.to_string();
+                                                match message_manager.get_organization(&user_id_str).await {
+                                                    Ok(organization) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "folders",
+                                                                "status": "ok",
+                                                                "folders": organization.folders,
+                                                                "tags": organization.tags,
+                                                                "pinned_count": organization.pinned,
+                                                                "archived_count": organization.archived
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "folders",
+                                                                "status": "error",
+                                                                "error": format!("Failed to list folders: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            CommunicationRequest::ExportConversation { conversation_id, format } => {
+                                                let user_id_str = user_id.to_string();
+                                                let result = match &conversation_id {
+                                                    Some(id) => message_manager.get_conversation(&user_id_str, id).await.and_then(|conversation| {
+                                                        conversation
+                                                            .map(|conversation| vec![(id.clone(), conversation)])
+                                                            .ok_or_else(|| anyhow::anyhow!("Conversation not found: {}", id))
+                                                    }),
+                                                    None => message_manager.get_all_conversations(&user_id_str).await,
+                                                };
+
+                                                match result {
+                                                    Ok(conversations) => {
+                                                        let total = conversations.len();
+                                                        for (id, conversation) in conversations {
+                                                            let response = match export::render(&id, &conversation, format) {
+                                                                Ok(content) => json!({
+                                                                    "type": "conversation_export",
+                                                                    "status": "ok",
+                                                                    "conversation_id": id,
+                                                                    "format": format,
+                                                                    "file_name": export::file_name(&id, &conversation, format),
+                                                                    "content_type": format.content_type(),
+                                                                    "content": content
+                                                                }),
+                                                                Err(e) => json!({
+                                                                    "type": "conversation_export",
+                                                                    "status": "error",
+                                                                    "conversation_id": id,
+                                                                    "error": format!("Failed to export conversation: {}", e)
+                                                                }),
+                                                            };
+                                                            let _ = broadcaster.send_to(&client_id_for_task, serde_json::to_string(&response).unwrap()).await;
+                                                        }
+
+                                                        if conversation_id.is_none() {
+                                                            let _ = broadcaster.send_to(
+                                                                &client_id_for_task,
+                                                                serde_json::to_string(&json!({
+                                                                    "type": "export_complete",
+                                                                    "status": "ok",
+                                                                    "count": total
+                                                                })).unwrap()
+                                                            ).await;
+                                                        }
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "conversation_export",
+                                                                "status": "error",
+                                                                "error": format!("Failed to export: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            CommunicationRequest::ImportConversations { format, content } => {
+                                                let user_id_str = user_id.to_string();
+                                                let result = if content.len() > import::max_import_bytes() {
+                                                    Err(anyhow::anyhow!("Import is larger than {} bytes", import::max_import_bytes()))
+                                                } else {
+                                                    match tokio::task::spawn_blocking(move || import::parse(&content, format)).await {
+                                                        Ok(Ok(sessions)) => message_manager.import_conversations(&user_id_str, sessions).await,
+                                                        Ok(Err(e)) => Err(e),
+                                                        Err(e) => Err(e.into()),
+                                                    }
+                                                };
+
+                                                let response = match result {
+                                                    Ok(report) => json!({
+                                                        "type": "import_result",
+                                                        "status": "ok",
+                                                        "report": report
+                                                    }),
+                                                    Err(e) => json!({
+                                                        "type": "import_result",
+                                                        "status": "error",
+                                                        "error": format!("Failed to import conversations: {}", e)
+                                                    }),
+                                                };
+                                                let _ = broadcaster.send_to(&client_id_for_task, serde_json::to_string(&response).unwrap()).await;
+                                            }
+
+                                            CommunicationRequest::CreateShareLink { conversation_id, expires_in_hours } => {
+                                                let user_id_str = user_id.to_string();
+                                                let expires_in = expires_in_hours.map(|hours| chrono::Duration::hours(hours.into()));
+                                                let response = match message_manager.create_share(&user_id_str, &conversation_id, expires_in).await {
+                                                    Ok(link) => json!({
+                                                        "type": "share_link_created",
+                                                        "status": "ok",
+                                                        "share": share::view(link, Utc::now())
+                                                    }),
+                                                    Err(e) => json!({
+                                                        "type": "share_link_created",
+                                                        "status": "error",
+                                                        "error": format!("Failed to share conversation: {}", e)
+                                                    }),
+                                                };
+                                                let _ = broadcaster.send_to(&client_id_for_task, serde_json::to_string(&response).unwrap()).await;
+                                            }
+
+                                            CommunicationRequest::ListShareLinks => {
+                                                let user_id_str = user_id.to_string();
+                                                let response = match message_manager.list_shares(&user_id_str).await {
+                                                    Ok(links) => {
+                                                        let now = Utc::now();
+                                                        json!({
+                                                            "type": "share_links",
+                                                            "status": "ok",
+                                                            "shares": links.into_iter().map(|link| share::view(link, now)).collect::<Vec<_>>()
+                                                        })
+                                                    }
+                                                    Err(e) => json!({
+                                                        "type": "share_links",
+                                                        "status": "error",
+                                                        "error": format!("Failed to list share links: {}", e)
+                                                    }),
+                                                };
+                                                let _ = broadcaster.send_to(&client_id_for_task, serde_json::to_string(&response).unwrap()).await;
+                                            }
+
+                                            CommunicationRequest::RevokeShareLink { share_id } => {
+                                                let user_id_str = user_id.to_string();
+                                                let response = match message_manager.revoke_share(&user_id_str, &share_id).await {
+                                                    Ok(true) => json!({
+                                                        "type": "share_link_revoked",
+                                                        "status": "ok",
+                                                        "share_id": share_id
+                                                    }),
+                                                    Ok(false) => json!({
+                                                        "type": "share_link_revoked",
+                                                        "status": "error",
+                                                        "error": format!("Share link not found: {}", share_id)
+                                                    }),
+                                                    Err(e) => json!({
+                                                        "type": "share_link_revoked",
+                                                        "status": "error",
+                                                        "error": format!("Failed to revoke share link: {}", e)
+                                                    }),
+                                                };
+                                                let _ = broadcaster.send_to(&client_id_for_task, serde_json::to_string(&response).unwrap()).await;
+                                            }
+
+                                            CommunicationRequest::ForkSharedConversation { token } => {
+                                                let user_id_str = user_id.to_string();
+                                                let response = match message_manager.fork_shared(&token, &user_id_str).await {
+                                                    Ok(conversation) => json!({
+                                                        "type": "conversation_forked",
+                                                        "status": "ok",
+                                                        "conversation": sidebar_entry(conversation)
+                                                    }),
+                                                    Err(e) => json!({
+                                                        "type": "conversation_forked",
+                                                        "status": "error",
+                                                        "error": format!("Failed to fork conversation: {}", e)
+                                                    }),
+                                                };
+                                                let _ = broadcaster.send_to(&client_id_for_task, serde_json::to_string(&response).unwrap()).await;
+                                            }
+
+                                            CommunicationRequest::ListMessageRevisions { message_id } => {
+                                                let user_id_str = user_id.to_string();
+                                                match message_manager.list_message_revisions(&user_id_str, &message_id).await {
+                                                    Ok(revisions) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "message_revisions",
+                                                                "status": "ok",
+                                                                "message_id": message_id,
+                                                                "revisions": revisions
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "message_revisions",
+                                                                "status": "error",
+                                                                "message_id": message_id,
+                                                                "error": format!("Failed to list revisions: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            CommunicationRequest::DiffMessageRevisions { message_id, from_revision, to_revision } => {
+                                                let user_id_str = user_id.to_string();
+                                                match message_manager.diff_message_revisions(&user_id_str, &message_id, from_revision, to_revision).await {
+                                                    Ok(changes) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "message_revision_diff",
+                                                                "status": "ok",
+                                                                "message_id": message_id,
+                                                                "from_revision": from_revision,
+                                                                "to_revision": to_revision,
+                                                                "changes": changes
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "message_revision_diff",
+                                                                "status": "error",
+                                                                "message_id": message_id,
+                                                                "error": format!("Failed to diff revisions: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            CommunicationRequest::RevertMessage { message_id, revision } => {
+                                                let user_id_str = user_id.to_string();
+                                                match message_manager.revert_message(&user_id_str, &message_id, revision).await {
+                                                    Ok((message, new_revision)) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "message_reverted",
+                                                                "status": "ok",
+                                                                "message_id": message_id,
+                                                                "revision": new_revision,
+                                                                "reverted_from": revision,
+                                                                "content": message.content
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "message_reverted",
+                                                                "status": "error",
+                                                                "message_id": message_id,
+                                                                "error": format!("Failed to revert message: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            CommunicationRequest::ListTrash => {
+                                                let user_id_str = user_id.to_string();
+                                                match message_manager.list_trash(&user_id_str).await {
+                                                    Ok(items) => {
+                                                        let grace_period = TrashConfig::from_env().grace_period;
+                                                        let items = items.iter()
+                                                            .map(|item| trash::summarize(item, grace_period))
+                                                            .collect::<Vec<_>>();
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "trash",
+                                                                "status": "ok",
+                                                                "items": items
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "trash",
+                                                                "status": "error",
+                                                                "error": format!("Failed to list trash: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            CommunicationRequest::Restore { trash_id } => {
+                                                let user_id_str = user_id.to_string();
+                                                match message_manager.restore_from_trash(&user_id_str, &trash_id).await {
+                                                    Ok(item) => {
+                                                        let restored = trash::summarize(&item, TrashConfig::from_env().grace_period);
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "restored",
+                                                                "status": "ok",
+                                                                "trash_id": restored.trash_id,
+                                                                "kind": restored.kind,
+                                                                "conversation_id": restored.conversation_id,
+                                                                "title": restored.title
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "restored",
+                                                                "status": "error",
+                                                                "trash_id": trash_id,
+                                                                "error": format!("Failed to restore: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            CommunicationRequest::EmptyTrash => {
+                                                let user_id_str = user_id.to_string();
+                                                match message_manager.empty_trash(&user_id_str).await {
+                                                    Ok(purged_count) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "trash_emptied",
+                                                                "status": "ok",
+                                                                "purged_count": purged_count
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "trash_emptied",
+                                                                "status": "error",
+                                                                "error": format!("Failed to empty trash: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            #[cfg(feature = "ttl")]
+                                            CommunicationRequest::SetConversationTtl { conversation_id, ttl_seconds } => {
+                                                let user_id_str = user_id.to_string();
+                                                match message_manager.set_conversation_ttl(&user_id_str, &conversation_id, ttl_seconds).await {
+                                                    Ok(expires_at) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "conversation_ttl_updated",
+                                                                "status": "ok",
+                                                                "conversation_id": conversation_id,
+                                                                "expires_at": expires_at.map(|at| at.to_rfc3339())
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "conversation_ttl_updated",
+                                                                "status": "error",
+                                                                "error": format!("Failed to set conversation TTL: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            // Keeping zero conversations would purge the whole history on the next sweep
+                                            #[cfg(feature = "ttl")]
+                                            CommunicationRequest::SetRetentionPolicy { keep_last: Some(0), .. } => {
+                                                let _ = broadcaster.send_to(
+                                                    &client_id_for_task,
+                                                    serde_json::to_string(&json!({
+                                                        "type": "retention_policy",
+                                                        "status": "error",
+                                                        "error": "keep_last must be at least 1"
+                                                    })).unwrap()
+                                                ).await;
+                                            }
+
+                                            #[cfg(feature = "ttl")]
+                                            CommunicationRequest::SetRetentionPolicy { max_age_days, keep_last } => {
+                                                let user_id_str = user_id.to_string();
+                                                let policy = if max_age_days.is_none() && keep_last.is_none() {
+                                                    None
+                                                } else {
+                                                    Some(RetentionPolicy { max_age_days, keep_last })
+                                                };
+                                                match message_manager.set_retention_policy(&user_id_str, policy.clone()).await {
+                                                    Ok(()) => {
+                                                        let deployment = RetentionConfig::from_env().default_policy;
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "retention_policy",
+                                                                "status": "ok",
+                                                                "user_policy": policy,
+                                                                "effective_policy": retention::effective_policy(&deployment, policy.as_ref())
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "retention_policy",
+                                                                "status": "error",
+                                                                "error": format!("Failed to set retention policy: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
+
+                                            #[cfg(feature = "ttl")]
+                                            CommunicationRequest::FetchRetentionPolicy => {
+                                                let user_id_str = user_id.to_string();
+                                                match message_manager.get_retention_policy(&user_id_str).await {
+                                                    Ok(policy) => {
+                                                        let deployment = RetentionConfig::from_env().default_policy;
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "retention_policy",
+                                                                "status": "ok",
+                                                                "user_policy": policy,
+                                                                "effective_policy": retention::effective_policy(&deployment, policy.as_ref())
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                    Err(e) => {
+                                                        let _ = broadcaster.send_to(
+                                                            &client_id_for_task,
+                                                            serde_json::to_string(&json!({
+                                                                "type": "retention_policy",
+                                                                "status": "error",
+                                                                "error": format!("Failed to fetch retention policy: {}", e)
+                                                            })).unwrap()
+                                                        ).await;
+                                                    }
+                                                }
+                                            }
                                         }
                                     }
                                     Err(_) => {
                                         let _ = broadcaster.send_to(
                                             &client_id_for_task,
-                                            serde_json::to_string(&CommunicationResponse::Error {
+                                            CommunicationResponse::Error {
                                                 status: "invalid_request".to_string(),
                                                 error: "Unknown request type".to_string(),
-                                            }).unwrap()
+                                            }
                                         ).await;
                                     }
                                 }
@@ -582,10 +1308,10 @@ pub async fn handle_ws_connection(
                     _ => {
                         let _ = broadcaster.send_to(
                             &client_id_for_task,
-                            serde_json::to_string(&CommunicationResponse::Error {
+                            CommunicationResponse::Error {
                                 status: "invalid_message".to_string(),
                                 error: "Only text messages are supported".to_string(),
-                            }).unwrap()
+                            }
                         ).await;
                     }
                 }
@@ -593,23 +1319,121 @@ pub async fn handle_ws_connection(
         }
     });
 
-    // Message sending task
-    let send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            if ws_sender.send(Message::Text(msg.into())).await.is_err() {
-                break;
+    // Message sending task; a revocation makes it send a close frame and stop
+    let (close_tx, mut close_rx) = oneshot::channel::<String>();
+    let mut send_task = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(msg) => {
+                        if ws_sender.send(protocol.frame(msg)).await.is_err() {
+                            break;
+                        }
+                    }
+                    None => break,
+                },
+                Ok(reason) = &mut close_rx => {
+                    let _ = ws_sender.send(protocol.frame(json!({
+                        "type": "error",
+                        "status": "token_revoked",
+                        "error": reason.clone(),
+                        "code": 401
+                    }).to_string())).await;
+                    let _ = ws_sender.send(Message::Close(Some(CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: reason.into(),
+                    }))).await;
+                    break;
+                }
             }
         }
     });
 
+    // Closes the connection as soon as its current token is revoked (logout, password change, ban)
+    let mut revocation_task = tokio::spawn({
+        let revocations = revocations.clone();
+        let current_claims = current_claims.clone();
+        async move {
+            revocations.wait_until_revoked(&current_claims).await;
+            let _ = close_tx.send("Token has been revoked".to_string());
+        }
+    });
+
     tokio::select! {
-        _ = process_task => (),
-        _ = send_task => (),
+        _ = &mut process_task => (),
+        _ = &mut send_task => (),
+        _ = &mut revocation_task => {
+            println!("[{}] Token revoked, closing connection", client_id);
+            let _ = (&mut send_task).await;
+        }
+    }
+    process_task.abort();
+    send_task.abort();
+    revocation_task.abort();
+    broadcaster.remove_client(&client_id).await;
+
+    if let Err(e) = message_manager.user_disconnected(&user_id.to_string()).await {
+        eprintln!("[{}] Failed to flush cached messages for user {}: {}", client_id, user_id, e);
     }
 
     println!("[{}] Connection closed", client_id);
 }
 
+fn sidebar_entry(conv: ConversationMetadata) -> serde_json::Value {
+    json!({
+        "id": conv.id,
+        "title": conv.title,
+        "created_at": conv.created_at.to_rfc3339(),
+        "pinned": conv.pinned,
+        "tags": conv.tags,
+        "folder": conv.folder,
+        "archived": conv.archived
+    })
+}
+
+// Error sent back instead of handling a message the connection's token may not send
+fn forbidden_message(claims: &Claims, request: &CommunicationRequest) -> Option<String> {
+    if let Some(permission) = request.required_permission()
+        && !claims.has_permission(permission)
+    {
+        return Some(json!({
+            "type": "error",
+            "status": "forbidden",
+            "error": format!("Access denied: requires {}", permission),
+            "permission": permission,
+            "code": 403
+        }).to_string());
+    }
+    if let Some((target, permission)) = request.target_user()
+        && !ownership::may_act_for(claims, target, permission)
+    {
+        return Some(json!({
+            "type": "error",
+            "status": "forbidden",
+            "error": format!("Access denied: requires ownership or {}", permission),
+            "permission": permission,
+            "code": 403
+        }).to_string());
+    }
+    None
+}
+
+async fn send_organized(broadcaster: &WsBroadcaster, client_id: &Uuid, result: anyhow::Result<ConversationMetadata>) {
+    let response = match result {
+        Ok(conv) => json!({
+            "type": "conversation_organized",
+            "status": "ok",
+            "conversation": sidebar_entry(conv)
+        }),
+        Err(e) => json!({
+            "type": "conversation_organized",
+            "status": "error",
+            "error": format!("Failed to update conversation: {}", e)
+        }),
+    };
+    let _ = broadcaster.send_to(client_id, serde_json::to_string(&response).unwrap()).await;
+}
+
 async fn save_with_retry<T: Serialize + Send + Sync>(
     file_manager: &Arc<JsonFileManager>,
     file_path: &str,
@@ -630,3 +1454,58 @@ async fn save_with_retry<T: Serialize + Send + Sync>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(sub: i64, permissions: &[&str]) -> Claims {
+        serde_json::from_value(json!({
+            "sub": sub, "email": "a@example.com", "roles": ["user"], "is_admin": false,
+            "is_user": true, "exp": 0, "permissions": permissions
+        }))
+        .unwrap()
+    }
+
+    fn request(value: serde_json::Value) -> CommunicationRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn another_users_sidebar_is_rejected() {
+        let fetch = request(json!({"type": "fetch_sidebar_history", "user_id": 2}));
+
+        let error = forbidden_message(&claims(1, &["chat.use"]), &fetch).expect("should be rejected");
+        let error: serde_json::Value = serde_json::from_str(&error).unwrap();
+        assert_eq!(error["status"], "forbidden");
+        assert_eq!(error["permission"], "users.read");
+        assert_eq!(error["code"], 403);
+
+        assert!(forbidden_message(&claims(2, &["chat.use"]), &fetch).is_none());
+        assert!(forbidden_message(&claims(1, &["chat.use", "users.read"]), &fetch).is_none());
+    }
+
+    #[test]
+    fn starting_a_session_for_another_user_is_rejected() {
+        let start = request(json!({"type": "start_new_session", "user_id": 2}));
+
+        let error = forbidden_message(&claims(1, &["chat.use", "users.read"]), &start).expect("should be rejected");
+        assert!(error.contains("users.manage"), "{}", error);
+
+        assert!(forbidden_message(&claims(2, &["chat.use"]), &start).is_none());
+        assert!(forbidden_message(&claims(1, &["chat.use", "users.manage"]), &start).is_none());
+    }
+
+    #[test]
+    fn out_of_range_user_ids_are_nobodys() {
+        let fetch = request(json!({"type": "fetch_sidebar_history", "user_id": u64::MAX}));
+        assert!(forbidden_message(&claims(1, &["chat.use"]), &fetch).is_some());
+    }
+
+    #[test]
+    fn state_changes_need_chat_use_even_for_your_own_account() {
+        let start = request(json!({"type": "start_new_session", "user_id": 1}));
+        let error = forbidden_message(&claims(1, &[]), &start).expect("should be rejected");
+        assert!(error.contains("chat.use"), "{}", error);
+    }
+}
@@ -0,0 +1,89 @@
+// ws_protocol.rs
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+#[cfg(feature = "binary-protocol")]
+use crate::payloads::binary_protocol;
+use crate::ws::ws_channel::Outgoing;
+
+// Framing chosen by the client in its start_connection message. JSON clients and
+// binary clients can be connected to the same server at the same time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WireProtocol {
+    Json,
+    #[cfg(feature = "binary-protocol")]
+    Binary,
+}
+
+impl WireProtocol {
+    pub fn negotiate(first_msg: &Message) -> Self {
+        #[cfg(feature = "binary-protocol")]
+        if let Message::Text(text) = first_msg {
+            let requested = serde_json::from_str::<serde_json::Value>(text)
+                .ok()
+                .and_then(|json| json.get("protocol").and_then(|p| p.as_str()).map(str::to_string));
+
+            if requested.as_deref() == Some(binary_protocol::PROTOCOL_NAME) {
+                return WireProtocol::Binary;
+            }
+        }
+
+        WireProtocol::Json
+    }
+
+    // JSON clients get text; binary clients get typed responses under their compact tag
+    // and ad-hoc JSON payloads wrapped as-is
+    pub fn frame(&self, message: impl Into<Outgoing>) -> Message {
+        match (self, message.into()) {
+            (WireProtocol::Json, Outgoing::Text(text)) => Message::Text(text.into()),
+            (WireProtocol::Json, Outgoing::Response(response)) => {
+                Message::Text(serde_json::to_string(&response).unwrap_or_default().into())
+            }
+            #[cfg(feature = "binary-protocol")]
+            (WireProtocol::Binary, Outgoing::Text(text)) => Message::Binary(binary_protocol::json_frame(&text).into()),
+            #[cfg(feature = "binary-protocol")]
+            (WireProtocol::Binary, Outgoing::Response(response)) => {
+                Message::Binary(binary_protocol::encode_outgoing(&response).into())
+            }
+        }
+    }
+
+    // Binary frames are accepted from any client and lowered to the JSON request text
+    pub fn normalize_incoming(msg: Message) -> Result<Message, String> {
+        match msg {
+            #[cfg(feature = "binary-protocol")]
+            Message::Binary(frame) => binary_protocol::client_frame_to_json(&frame)
+                .map(|text| Message::Text(text.into()))
+                .map_err(|e| format!("Invalid binary frame: {}", e)),
+            other => Ok(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payloads::communication_response::CommunicationResponse;
+
+    fn chunk() -> CommunicationResponse {
+        CommunicationResponse::StreamChunk { chunk: "tok".into() }
+    }
+
+    #[test]
+    fn json_clients_get_text_for_typed_and_ad_hoc_messages() {
+        let Message::Text(text) = WireProtocol::Json.frame(chunk()) else { panic!("expected text") };
+        assert_eq!(text.as_str(), r#"{"type":"stream_chunk","chunk":"tok"}"#);
+
+        let Message::Text(text) = WireProtocol::Json.frame(r#"{"type":"pong"}"#.to_string()) else { panic!("expected text") };
+        assert_eq!(text.as_str(), r#"{"type":"pong"}"#);
+    }
+
+    #[cfg(feature = "binary-protocol")]
+    #[test]
+    fn binary_clients_get_typed_responses_under_their_tag() {
+        let Message::Binary(frame) = WireProtocol::Binary.frame(chunk()) else { panic!("expected binary") };
+        assert_eq!(frame[..2], [binary_protocol::PROTOCOL_VERSION, binary_protocol::tags::STREAM_CHUNK]);
+
+        let Message::Binary(frame) = WireProtocol::Binary.frame(r#"{"type":"pong"}"#.to_string()) else { panic!("expected binary") };
+        assert_eq!(frame[..], binary_protocol::json_frame(r#"{"type":"pong"}"#)[..]);
+    }
+}
@@ -6,16 +6,21 @@ use uuid::Uuid;
 
 use crate::{
     helpers::message_manager::MessageManager, // Remove "self," since you're not using it
-    services::{llm_service::LlmService, user_service::UserService}, 
+    services::{llm_service::LlmService, revocation_service::RevocationService, user_service::UserService, verification_service::VerificationService}, 
     utils::file_utils::JsonFileManager, 
     ws::{ws_channel::WsBroadcaster, ws_handler::handle_ws_connection}
 };
 
+#[allow(clippy::too_many_arguments)]
 pub async fn start_ws_server(
     addr: &str,
     broadcaster: Arc<WsBroadcaster>,
     user_service: Arc<UserService>,
     llm_service: Arc<LlmService>,
+    message_manager: Arc<MessageManager>,
+    file_manager: Arc<JsonFileManager>,
+    revocations: Arc<RevocationService>,
+    verification: Arc<VerificationService>,
 ) {
     let listener = TcpListener::bind(addr)
         .await
@@ -23,9 +28,6 @@ pub async fn start_ws_server(
 
     println!("🔌 WebSocket server running at ws://{}", addr);
     
-    let file_manager = Arc::new(JsonFileManager::new());
-    let message_manager = Arc::new(MessageManager::new("messages.json")); 
-    
     while let Ok((stream, _)) = listener.accept().await {
         let peer = stream
             .peer_addr()
@@ -36,6 +38,8 @@ pub async fn start_ws_server(
         let user_service = user_service.clone(); 
         let file_manager = file_manager.clone();
         let message_manager = message_manager.clone(); 
+        let revocations = revocations.clone();
+        let verification = verification.clone();
 
         tokio::spawn(async move {
             handle_connection(
@@ -45,12 +49,15 @@ pub async fn start_ws_server(
                 user_service, 
                 llm_service, 
                 file_manager, 
-                message_manager
+                message_manager,
+                revocations,
+                verification
             ).await;
         });
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_connection(
     stream: tokio::net::TcpStream,
     peer: SocketAddr,
@@ -59,6 +66,8 @@ async fn handle_connection(
     llm_service: Arc<LlmService>,
     file_manager: Arc<JsonFileManager>,
     message_manager: Arc<MessageManager>,
+    revocations: Arc<RevocationService>,
+    verification: Arc<VerificationService>,
 ) {
     if let Ok(ws_stream) = accept_async(stream).await {
         let client_id = Uuid::new_v4();
@@ -76,7 +85,9 @@ async fn handle_connection(
             user_service, 
             llm_service, 
             file_manager, 
-            message_manager
+            message_manager,
+            revocations,
+            verification
         ).await;
     }
 }
\ No newline at end of file